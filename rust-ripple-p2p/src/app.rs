@@ -1,10 +1,12 @@
 #![allow(unused_imports)]
 use std::collections::HashMap;
+use std::iter;
 use std::net::{SocketAddr, Ipv4Addr, IpAddr};
 use std::sync::Arc;
 use std::sync::mpsc::{Receiver, Sender};
-use tokio::sync::mpsc::{Receiver as TokioReceiver};
+use tokio::sync::mpsc::{Receiver as TokioReceiver, Sender as TokioSender};
 use std::thread;
+use std::thread::JoinHandle;
 use genevo::operator::prelude::{MaximizeSelector, MultiPointCrossBreeder, RouletteWheelSelector};
 
 use log::*;
@@ -14,25 +16,37 @@ use websocket::Message;
 use super::{EmptyResult};
 use crate::client::{AccountInfo, Client, Payment, Transaction};
 use crate::collector::{Collector, RippleMessage};
-use crate::container_manager::NodeKeys;
+use crate::container_manager::{Cluster, NodeKeys};
 use crate::failure_writer::{ConsensusPropertyTypes, FailureWriter};
+use crate::fuzzing::run_fuzzing;
 use crate::ga::crossover::NoCrossoverOperator;
 use crate::ga::encoding::delay_encoding::{DelayMapPhenotype, DelayGenotype};
 use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype};
+use crate::ga::encoding::hybrid_encoding::{HybridGenotype, HybridMapPhenotype};
 use crate::ga::fitness::ExtendedFitness;
 use crate::ga::genetic_algorithm;
 use crate::ga::genetic_algorithm::{run_default_mu_lambda_priorities};
-use crate::ga::parameters::{default_mu_lambda_delays, default_mu_lambda_priorities, Parameter};
+use crate::ga::local_search::{DelayNeighbourhood, LocalSearchStrategy, Neighbourhood, PriorityNeighbourhood, run_local_search};
+use crate::ga::parameters::{default_mu_lambda_delays, default_mu_lambda_priorities, GaConfiguration, Parameter};
 use crate::ga::population_builder::{build_delays_population, build_priorities_population};
 use crate::ga::encoding::priority_encoding::{PriorityGenotype, PriorityMapPhenotype};
 use crate::locality::{run_locality_experiment_delays, run_locality_experiment_priorities};
 use crate::peer_connection::PeerConnection;
 use crate::scaling::{run_priority_scaling_experiment, run_scaling_experiment};
-use crate::scheduler::{Event, P2PConnections, PeerChannel, Scheduler};
-use crate::node_state::{MutexNodeStates, NodeState, NodeStates};
-use crate::{CONFIG, Configuration, NUM_NODES};
+use crate::schedule_file::{run_schedule_files, ScheduleKind};
+use crate::scheduler::{Event, Frame, P2PConnections, PeerChannel, Scheduler};
+use crate::node_state::{MutexNodeStates, NodeState};
+use crate::{CONFIG, Configuration, NUM_NODES, PEER_GRAPH};
 use crate::scheduler::delay_scheduler::DelayScheduler;
+use crate::scheduler::hybrid_scheduler::HybridScheduler;
+use crate::scheduler::interactive_scheduler::{InteractiveScheduler, run_interactive};
 use crate::scheduler::priority_scheduler::PriorityScheduler;
+use crate::scheduler::systematic_scheduler::SystematicScheduler;
+use crate::scheduler::pct_scheduler::PctScheduler;
+use crate::pct::run_pct;
+use crate::scheduler::online_scheduler::OnlineScheduler;
+use crate::online_learning::run_online_learning;
+use crate::systematic::{forward_violations, run_systematic};
 use crate::trace_comparisons::{run_fitness_comparison, run_no_delays, run_predetermined_delays, run_delay_trace_graph_creation, run_priority_trace_graph_creation, run_predetermined_priorities, run_random_priorities, run_random_delays};
 
 const _NODE_PRIVATE_KEY: &str = "e55dc8f3741ac9668dbe858409e5d64f5ce88380f7228eccfe82b92b2c7848ba";
@@ -53,12 +67,21 @@ const _AMOUNT: u32 = 2u32.pow(31);
 
 pub struct App {
     peers: u16,
-    node_keys: Vec<NodeKeys>
+    /// The node keys of every cluster, cluster i uses the ports and docker network of `Cluster::new(i)`
+    cluster_node_keys: Vec<Vec<NodeKeys>>,
+}
+
+/// Channels of a single cluster that are connected to the peers after the scheduler has been started
+struct ClusterPeers {
+    cluster: Cluster,
+    node_keys: Vec<NodeKeys>,
+    peer_senders: HashMap<usize, HashMap<usize, TokioSender<Event>>>,
+    peer_receivers: HashMap<usize, HashMap<usize, TokioReceiver<Frame>>>,
 }
 
 impl App {
-    pub fn new(peers: u16, node_keys: Vec<NodeKeys>) -> Self {
-        App { peers, node_keys }
+    pub fn new(peers: u16, cluster_node_keys: Vec<Vec<NodeKeys>>) -> Self {
+        App { peers, cluster_node_keys }
     }
 
     /// Start proxy
@@ -67,96 +90,74 @@ impl App {
     /// Every p2p connection has two senders and receivers for relaying messages to and from the scheduler
     /// Every message gets relayed by the scheduler
     /// A separate thread is created for each node which handles websocket client requests
+    /// Every cluster gets its own collector, node states and scheduler, only the mu lambda GAs use more than one cluster
     pub async fn start<F: ExtendedFitness>(&self, scheduler_type: SchedulerType) -> EmptyResult {
         let mut tokio_tasks = vec![];
         let mut threads = vec![];
-        let (collector_tx, collector_rx) = std::sync::mpsc::channel();
-        let (subscription_tx, subscription_rx) = std::sync::mpsc::channel();
-        let (server_state_tx, server_state_rx) = std::sync::mpsc::channel();
-        let (test_harness_tx, test_harness_rx) = std::sync::mpsc::channel();
-        let (account_info_tx, account_info_rx) = std::sync::mpsc::channel();
-        let (balance_sender, balance_receiver) = std::sync::mpsc::channel();
-        let (failure_sender, failure_receiver) = std::sync::mpsc::channel();
-        let peer = self.peers.clone();
-
-        let mut node_state_vec = vec![NodeState::new(0); peer as usize];
-        for i in 0..peer { node_state_vec[i as usize].peer = i as usize }
-        let node_states = NodeStates::new(node_state_vec);
-        let mutex_node_states = Arc::new(MutexNodeStates::new(node_states));
-        let mutex_node_states_clone = mutex_node_states.clone();
-
-        // Start the collector which writes output to files and collects information on nodes
-        let collector_task = thread::spawn(move || {
-            Collector::new(peer, subscription_rx, mutex_node_states_clone).start(collector_rx, server_state_rx);
-        });
-        threads.push(collector_task);
-
-        let failure_mutex_node_states = mutex_node_states.clone();
-        FailureWriter::start_failure_writer(failure_receiver, failure_mutex_node_states);
-
-        // Create a client for each peer, which subscribes (among others) to certain streams
-        let mut clients = vec![];
-        for i in 0..self.peers {
-            clients.push(Client::new(i, format!("ws://127.0.0.1:{}", 6005+i).as_str(), subscription_tx.clone(), server_state_tx.clone(), test_harness_tx.clone(), account_info_tx.clone(), balance_sender.clone()));
+        let mut cluster_data = vec![];
+        let mut cluster_ga_receivers = vec![];
+        let mut cluster_peers = vec![];
+        for (id, node_keys) in self.cluster_node_keys.iter().enumerate() {
+            let (scheduler_data, scheduler_ga_receiver, peers) = self.create_cluster::<F>(Cluster::new(id), node_keys.clone(), &mut threads);
+            cluster_data.push(scheduler_data);
+            cluster_ga_receivers.push(scheduler_ga_receiver);
+            cluster_peers.push(peers);
         }
-        let client_senders = clients.iter().map(|client| client.sender_channel.clone()).collect_vec();
-
-        // Start p2p connections
-        let addrs = self.get_addrs(self.peers);
-        let mut peer_senders = HashMap::new();
-        let mut peer_receivers = HashMap::new();
-        let mut scheduler_peer_channels = HashMap::new();
-        let (scheduler_sender, scheduler_receiver) = tokio::sync::mpsc::channel(1000);
-        let (scheduler_ga_sender, scheduler_ga_receiver) = std::sync::mpsc::channel::<F>();
-
-        // For every combination (exclusive) of peers, create the necessary senders and receivers
-        for pair in (0..peer).into_iter().combinations(2).into_iter() {
-            let i = pair[0] as usize;
-            let j = pair[1] as usize;
-            let tx_peer_i = scheduler_sender.clone();
-            let tx_peer_j = scheduler_sender.clone();
-            let (tx_scheduler_i, rx_peer_i) = tokio::sync::mpsc::channel(32);
-            let (tx_scheduler_j, rx_peer_j) = tokio::sync::mpsc::channel(32);
-            peer_senders.entry(i).or_insert(HashMap::new()).insert(j, tx_peer_i);
-            peer_senders.entry(j).or_insert(HashMap::new()).insert(i, tx_peer_j);
-            peer_receivers.entry(i).or_insert(HashMap::new()).insert(j, rx_peer_i);
-            peer_receivers.entry(j).or_insert(HashMap::new()).insert(i, rx_peer_j);
-            scheduler_peer_channels.entry(i).or_insert(HashMap::new()).insert(j, PeerChannel::new(tx_scheduler_i));
-            scheduler_peer_channels.entry(j).or_insert(HashMap::new()).insert(i, PeerChannel::new(tx_scheduler_j));
-        }
-
-        let scheduler_data = SchedulerData::<F>::new(
-            scheduler_peer_channels,
-            collector_tx,
-            mutex_node_states,
-            self.node_keys.clone(),
-            failure_sender,
-            scheduler_receiver,
-            scheduler_ga_sender,
-            client_senders,
-            test_harness_rx,
-            account_info_rx,
-            balance_receiver
-        );
+        // Single network schedulers use the first cluster
+        let scheduler_data = cluster_data.remove(0);
+        let scheduler_ga_receiver = cluster_ga_receivers.remove(0);
 
         // Start GA and scheduler
         match scheduler_type {
             SchedulerType::Priority => {
                 Self::start_default_mu_lambda_priorities::<F>(
-                    4,
-                    4,
-                    scheduler_data,
-                    scheduler_ga_receiver,
+                    CONFIG.ga.clone(),
+                    iter::once(scheduler_data).chain(cluster_data).collect(),
+                    iter::once(scheduler_ga_receiver).chain(cluster_ga_receivers).collect(),
                 );
             }
             SchedulerType::Delay => {
                 Self::start_default_mu_lambda_delays::<F>(
-                    4,
-                    4,
-                    scheduler_data,
-                    scheduler_ga_receiver,
+                    CONFIG.ga.clone(),
+                    iter::once(scheduler_data).chain(cluster_data).collect(),
+                    iter::once(scheduler_ga_receiver).chain(cluster_ga_receivers).collect(),
                 );
             }
+            SchedulerType::Hybrid => {
+                Self::start_default_mu_lambda_hybrid::<F>(
+                    CONFIG.ga.clone(),
+                    iter::once(scheduler_data).chain(cluster_data).collect(),
+                    iter::once(scheduler_ga_receiver).chain(cluster_ga_receivers).collect(),
+                );
+            }
+            SchedulerType::DelayHillClimbing | SchedulerType::DelaySimulatedAnnealing | SchedulerType::DelayOnePlusOne => {
+                Self::start_local_search::<DelayScheduler, DelayGenotype, DelayMapPhenotype, F, DelayNeighbourhood>(
+                    scheduler_type.local_search_strategy().unwrap(),
+                    DelayNeighbourhood::new(&CONFIG.ga),
+                    iter::once(scheduler_data).chain(cluster_data).collect(),
+                    iter::once(scheduler_ga_receiver).chain(cluster_ga_receivers).collect(),
+                );
+            }
+            SchedulerType::PriorityHillClimbing | SchedulerType::PrioritySimulatedAnnealing | SchedulerType::PriorityOnePlusOne => {
+                Self::start_local_search::<PriorityScheduler, PriorityGenotype, PriorityMapPhenotype, F, PriorityNeighbourhood>(
+                    scheduler_type.local_search_strategy().unwrap(),
+                    PriorityNeighbourhood::new(&CONFIG.ga),
+                    iter::once(scheduler_data).chain(cluster_data).collect(),
+                    iter::once(scheduler_ga_receiver).chain(cluster_ga_receivers).collect(),
+                );
+            }
+            SchedulerType::DelayFuzzing => {
+                let mutex_node_states_clone_2 = scheduler_data.mutex_node_states.clone();
+                let ga_scheduler_sender = Self::start_scheduler::<DelayScheduler, DelayGenotype, DelayMapPhenotype, F>(scheduler_data);
+                let neighbourhood = DelayNeighbourhood::new(&CONFIG.ga);
+                threads.push(thread::spawn(move || run_fuzzing(CONFIG.fuzzing.clone(), neighbourhood, ga_scheduler_sender, scheduler_ga_receiver, mutex_node_states_clone_2, CONFIG.search_budget)));
+            }
+            SchedulerType::PriorityFuzzing => {
+                let mutex_node_states_clone_2 = scheduler_data.mutex_node_states.clone();
+                let ga_scheduler_sender = Self::start_scheduler::<PriorityScheduler, PriorityGenotype, PriorityMapPhenotype, F>(scheduler_data);
+                let neighbourhood = PriorityNeighbourhood::new(&CONFIG.ga);
+                threads.push(thread::spawn(move || run_fuzzing(CONFIG.fuzzing.clone(), neighbourhood, ga_scheduler_sender, scheduler_ga_receiver, mutex_node_states_clone_2, CONFIG.search_budget)));
+            }
             SchedulerType::RandomPriority => {
                 let ga_scheduler_sender = Self::start_scheduler::<PriorityScheduler, PriorityGenotype, PriorityMapPhenotype, F>(scheduler_data);
                 threads.push(thread::spawn(|| run_random_priorities(ga_scheduler_sender, scheduler_ga_receiver, CONFIG.search_budget)));
@@ -187,6 +188,49 @@ impl App {
                 let ga_scheduler_sender = Self::start_scheduler::<PriorityScheduler, PriorityGenotype, PriorityMapPhenotype, F>(scheduler_data);
                 threads.push(thread::spawn(|| run_predetermined_priorities(ga_scheduler_sender, scheduler_ga_receiver, 100)));
             }
+            SchedulerType::ScheduleFiles => {
+                let configuration = CONFIG.schedule_files.clone();
+                // The files have been checked when validating the configuration
+                let (kind, schedule_files) = configuration.load(*NUM_NODES).expect("Loading schedule files failed");
+                match kind {
+                    ScheduleKind::Delay => {
+                        let schedules = schedule_files.iter()
+                            .map(|schedule_file| (schedule_file.name.clone(), DelayMapPhenotype::from_genes(&schedule_file.delays(&PEER_GRAPH, &CONFIG.ga.delay_encoding).unwrap())))
+                            .collect_vec();
+                        let ga_scheduler_sender = Self::start_scheduler::<DelayScheduler, DelayGenotype, DelayMapPhenotype, F>(scheduler_data);
+                        threads.push(thread::spawn(move || run_schedule_files(schedules, configuration.repetitions, ga_scheduler_sender, scheduler_ga_receiver)));
+                    }
+                    ScheduleKind::Priority => {
+                        let schedules = schedule_files.iter()
+                            .map(|schedule_file| (schedule_file.name.clone(), PriorityMapPhenotype::from_genes(&schedule_file.priorities(&PEER_GRAPH))))
+                            .collect_vec();
+                        let ga_scheduler_sender = Self::start_scheduler::<PriorityScheduler, PriorityGenotype, PriorityMapPhenotype, F>(scheduler_data);
+                        threads.push(thread::spawn(move || run_schedule_files(schedules, configuration.repetitions, ga_scheduler_sender, scheduler_ga_receiver)));
+                    }
+                }
+            }
+            SchedulerType::Interactive => {
+                let ga_scheduler_sender = Self::start_scheduler::<InteractiveScheduler, PriorityGenotype, PriorityMapPhenotype, F>(scheduler_data);
+                threads.push(thread::spawn(|| run_interactive(ga_scheduler_sender, scheduler_ga_receiver, CONFIG.search_budget)));
+            }
+            SchedulerType::Systematic => {
+                // The explorer reports the violations of every execution, the failure writer still gets all of them
+                let mut scheduler_data = scheduler_data;
+                let (violation_sender, violation_receiver) = std::sync::mpsc::channel();
+                let (report_sender, report_receiver) = std::sync::mpsc::channel();
+                let failure_sender = std::mem::replace(&mut scheduler_data.failure_sender, violation_sender);
+                threads.push(thread::spawn(move || forward_violations(violation_receiver, failure_sender, report_sender)));
+                let ga_scheduler_sender = Self::spawn_scheduler::<SystematicScheduler, F>(scheduler_data);
+                threads.push(thread::spawn(move || run_systematic(CONFIG.systematic.clone(), ga_scheduler_sender, scheduler_ga_receiver, report_receiver)));
+            }
+            SchedulerType::Pct => {
+                let ga_scheduler_sender = Self::spawn_scheduler::<PctScheduler, F>(scheduler_data);
+                threads.push(thread::spawn(|| run_pct(CONFIG.pct.clone(), *NUM_NODES, ga_scheduler_sender, scheduler_ga_receiver, CONFIG.search_budget)));
+            }
+            SchedulerType::OnlineLearning => {
+                let ga_scheduler_sender = Self::spawn_scheduler::<OnlineScheduler, F>(scheduler_data);
+                threads.push(thread::spawn(|| run_online_learning(CONFIG.online_learning.clone(), ga_scheduler_sender, scheduler_ga_receiver, CONFIG.search_budget)));
+            }
             SchedulerType::DelayLocalityExperiment => {
                 let mutex_node_states_clone_2= scheduler_data.mutex_node_states.clone();
                 let ga_scheduler_sender = Self::start_scheduler::<DelayScheduler, DelayGenotype, DelayMapPhenotype, F>(scheduler_data);
@@ -208,26 +252,113 @@ impl App {
             }
         }
 
-        // For every combination (exclusive) of peers, create connections between the peers and scheduler
-        for pair in (0..peer).into_iter().combinations(2).into_iter() {
-            let i = pair[0] as usize;
-            let j = pair[1] as usize;
-            let peer_receiver_i = peer_receivers.get_mut(&i).unwrap().remove(&j).unwrap();
-            let peer_sender_i = peer_senders.get_mut(&i).unwrap().remove(&j).unwrap();
-            let peer_receiver_j = peer_receivers.get_mut(&j).unwrap().remove(&i).unwrap();
-            let peer_sender_j = peer_senders.get_mut(&j).unwrap().remove(&i).unwrap();
+        for peers in cluster_peers {
+            tokio_tasks.extend(self.connect_cluster(peers).await);
+        }
+
+        for tokio_task in tokio_tasks {
+            match tokio_task.await {
+                Ok(_) => error!("A tokio task finished with ok"),
+                Err(err) => error!("A tokio task finished with an error: {:?}", err)
+            }
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        Ok(())
+    }
+
+    /// Create the collector, failure writer, clients and scheduler channels of a single cluster
+    fn create_cluster<F: ExtendedFitness>(&self, cluster: Cluster, node_keys: Vec<NodeKeys>, threads: &mut Vec<JoinHandle<()>>) -> (SchedulerData<F>, Receiver<F>, ClusterPeers) {
+        let (collector_tx, collector_rx) = std::sync::mpsc::channel();
+        let (subscription_tx, subscription_rx) = std::sync::mpsc::channel();
+        let (server_state_tx, server_state_rx) = std::sync::mpsc::channel();
+        let (test_harness_tx, test_harness_rx) = std::sync::mpsc::channel();
+        let (account_info_tx, account_info_rx) = std::sync::mpsc::channel();
+        let (balance_sender, balance_receiver) = std::sync::mpsc::channel();
+        let (failure_sender, failure_receiver) = std::sync::mpsc::channel();
+        let peer = self.peers.clone();
+
+        let mut node_state_vec = vec![NodeState::new(0); peer as usize];
+        for i in 0..peer { node_state_vec[i as usize].peer = i as usize }
+        let mutex_node_states = Arc::new(MutexNodeStates::new(node_state_vec));
+        let mutex_node_states_clone = mutex_node_states.clone();
+
+        // Start the collector which writes output to files and collects information on nodes
+        let log_folder = cluster.log_folder();
+        let collector_task = thread::spawn(move || {
+            Collector::new(peer, subscription_rx, mutex_node_states_clone, log_folder).start(collector_rx, server_state_rx);
+        });
+        threads.push(collector_task);
+
+        let failure_mutex_node_states = mutex_node_states.clone();
+        FailureWriter::start_failure_writer(failure_receiver, failure_mutex_node_states, cluster);
+
+        // Create a client for each peer, which subscribes (among others) to certain streams
+        let mut clients = vec![];
+        for i in 0..self.peers {
+            clients.push(Client::new(i, format!("ws://127.0.0.1:{}", cluster.ws_port(i as usize)).as_str(), subscription_tx.clone(), server_state_tx.clone(), test_harness_tx.clone(), account_info_tx.clone(), balance_sender.clone()));
+        }
+        let client_senders = clients.iter().map(|client| client.sender_channel.clone()).collect_vec();
+
+        let mut peer_senders = HashMap::new();
+        let mut peer_receivers = HashMap::new();
+        let mut scheduler_peer_channels = HashMap::new();
+        let (scheduler_sender, scheduler_receiver) = tokio::sync::mpsc::channel(1000);
+        let (scheduler_ga_sender, scheduler_ga_receiver) = std::sync::mpsc::channel::<F>();
+
+        // For every edge of the peer graph, create the necessary senders and receivers
+        for &(i, j) in PEER_GRAPH.edges() {
+            let tx_peer_i = scheduler_sender.clone();
+            let tx_peer_j = scheduler_sender.clone();
+            let (tx_scheduler_i, rx_peer_i) = tokio::sync::mpsc::channel(32);
+            let (tx_scheduler_j, rx_peer_j) = tokio::sync::mpsc::channel(32);
+            peer_senders.entry(i).or_insert(HashMap::new()).insert(j, tx_peer_i);
+            peer_senders.entry(j).or_insert(HashMap::new()).insert(i, tx_peer_j);
+            peer_receivers.entry(i).or_insert(HashMap::new()).insert(j, rx_peer_i);
+            peer_receivers.entry(j).or_insert(HashMap::new()).insert(i, rx_peer_j);
+            scheduler_peer_channels.entry(i).or_insert(HashMap::new()).insert(j, PeerChannel::new(tx_scheduler_i));
+            scheduler_peer_channels.entry(j).or_insert(HashMap::new()).insert(i, PeerChannel::new(tx_scheduler_j));
+        }
+
+        let scheduler_data = SchedulerData::<F>::new(
+            scheduler_peer_channels,
+            collector_tx,
+            mutex_node_states,
+            node_keys.clone(),
+            failure_sender,
+            scheduler_receiver,
+            scheduler_ga_sender,
+            client_senders,
+            test_harness_rx,
+            account_info_rx,
+            balance_receiver
+        );
+        (scheduler_data, scheduler_ga_receiver, ClusterPeers { cluster, node_keys, peer_senders, peer_receivers })
+    }
+
+    /// For every edge of the peer graph, create connections between the peers and scheduler
+    async fn connect_cluster(&self, mut cluster_peers: ClusterPeers) -> Vec<tokio::task::JoinHandle<()>> {
+        let mut tokio_tasks = vec![];
+        let addrs = self.get_addrs(&cluster_peers.cluster, self.peers);
+        for &(i, j) in PEER_GRAPH.edges() {
+            let peer_receiver_i = cluster_peers.peer_receivers.get_mut(&i).unwrap().remove(&j).unwrap();
+            let peer_sender_i = cluster_peers.peer_senders.get_mut(&i).unwrap().remove(&j).unwrap();
+            let peer_receiver_j = cluster_peers.peer_receivers.get_mut(&j).unwrap().remove(&i).unwrap();
+            let peer_sender_j = cluster_peers.peer_senders.get_mut(&j).unwrap().remove(&i).unwrap();
 
             let name = format!("ripple{}, ripple{}", i+1, j+1);
             let address_i = addrs[i].clone();
             let address_j = addrs[j].clone();
+            let node_keys = &cluster_peers.node_keys;
             let peer = PeerConnection::new(
                 &name,
                 address_i,
                 address_j,
-                self.node_keys[i].validation_seed.clone(),
-                self.node_keys[j].validation_seed.clone(),
-                self.node_keys[i].validation_public_key.clone(),
-                self.node_keys[j].validation_public_key.clone()
+                node_keys[i].validation_seed.clone(),
+                node_keys[j].validation_seed.clone(),
+                node_keys[i].validation_public_key.clone(),
+                node_keys[j].validation_public_key.clone()
             );
             let (thread1, thread2) = peer.connect(
                 i,
@@ -240,59 +371,98 @@ impl App {
             tokio_tasks.push(thread1);
             tokio_tasks.push(thread2);
         }
-
-        for tokio_task in tokio_tasks {
-            match tokio_task.await {
-                Ok(_) => error!("A tokio task finished with ok"),
-                Err(err) => error!("A tokio task finished with an error: {:?}", err)
-            }
-        }
-        for thread in threads {
-            thread.join().unwrap();
-        }
-        Ok(())
+        tokio_tasks
     }
 
-    fn get_addrs(&self, peers: u16) -> Vec<SocketAddr> {
-        let nodes = (0..peers).map(|x| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127,0,0,1)), 51235 + x)).collect();
+    fn get_addrs(&self, cluster: &Cluster, peers: u16) -> Vec<SocketAddr> {
+        let nodes = (0..peers).map(|x| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127,0,0,1)), cluster.peer_port(x as usize))).collect();
         debug!("{:?}", nodes);
         nodes
     }
 
-    /// Start the mu lambda GA and delay scheduler
+    /// Start the mu lambda GA with the configured parameters and a delay scheduler per cluster
     #[allow(unused)]
     fn start_default_mu_lambda_delays<F: ExtendedFitness>(
-        mu: usize,
-        lambda: usize,
-        scheduler_data: SchedulerData<F>,
-        scheduler_ga_receiver: Receiver<F>,
+        ga_configuration: GaConfiguration,
+        cluster_data: Vec<SchedulerData<F>>,
+        scheduler_ga_receivers: Vec<Receiver<F>>,
     )
     {
-        // Start the scheduler
-        let ga_scheduler_sender = Self::start_scheduler::<DelayScheduler, DelayGenotype, DelayMapPhenotype, F>(scheduler_data);
+        // Start the schedulers
+        let ga_scheduler_senders = cluster_data.into_iter()
+            .map(|scheduler_data| Self::start_scheduler::<DelayScheduler, DelayGenotype, DelayMapPhenotype, F>(scheduler_data))
+            .collect_vec();
         // Start the GA
-        thread::spawn(move || genetic_algorithm::run_default_mu_lambda_delays(mu, lambda, ga_scheduler_sender, scheduler_ga_receiver));
+        thread::spawn(move || genetic_algorithm::run_default_mu_lambda_delays(ga_configuration, ga_scheduler_senders, scheduler_ga_receivers));
     }
 
-    /// Start the mu lambda GA and priority scheduler
+    /// Start the mu lambda GA with the configured parameters and a priority scheduler per cluster
     #[allow(unused)]
     fn start_default_mu_lambda_priorities<F: ExtendedFitness>(
-        mu: usize,
-        lambda: usize,
-        scheduler_data: SchedulerData<F>,
-        scheduler_ga_receiver: Receiver<F>,
+        ga_configuration: GaConfiguration,
+        cluster_data: Vec<SchedulerData<F>>,
+        scheduler_ga_receivers: Vec<Receiver<F>>,
     )
     {
-        // Start the scheduler
-        let ga_scheduler_sender = Self::start_scheduler::<PriorityScheduler, PriorityGenotype, PriorityMapPhenotype, F>(scheduler_data);
+        // Start the schedulers
+        let ga_scheduler_senders = cluster_data.into_iter()
+            .map(|scheduler_data| Self::start_scheduler::<PriorityScheduler, PriorityGenotype, PriorityMapPhenotype, F>(scheduler_data))
+            .collect_vec();
         // Start the GA
-        thread::spawn(move || genetic_algorithm::run_default_mu_lambda_priorities(mu, lambda, ga_scheduler_sender, scheduler_ga_receiver));
+        thread::spawn(move || genetic_algorithm::run_default_mu_lambda_priorities(ga_configuration, ga_scheduler_senders, scheduler_ga_receivers));
+    }
+
+    /// Start the mu lambda GA with the configured parameters and a hybrid delay and priority scheduler per cluster
+    fn start_default_mu_lambda_hybrid<F: ExtendedFitness>(
+        ga_configuration: GaConfiguration,
+        cluster_data: Vec<SchedulerData<F>>,
+        scheduler_ga_receivers: Vec<Receiver<F>>,
+    )
+    {
+        // Start the schedulers
+        let ga_scheduler_senders = cluster_data.into_iter()
+            .map(|scheduler_data| Self::start_scheduler::<HybridScheduler, HybridGenotype, HybridMapPhenotype, F>(scheduler_data))
+            .collect_vec();
+        // Start the GA
+        thread::spawn(move || genetic_algorithm::run_default_mu_lambda_hybrid(ga_configuration, ga_scheduler_senders, scheduler_ga_receivers));
+    }
+
+    /// Start a local search strategy with a scheduler per cluster
+    fn start_local_search<S, G, P, F, N>(
+        strategy: LocalSearchStrategy,
+        neighbourhood: N,
+        cluster_data: Vec<SchedulerData<F>>,
+        scheduler_ga_receivers: Vec<Receiver<F>>,
+    )
+        where S: Scheduler<IndividualPhenotype = P> + Send + 'static, G: ExtendedGenotype + serde::Serialize + 'static, P: ExtendedPhenotype<G> + 'static, F: ExtendedFitness, N: Neighbourhood<G> + 'static
+    {
+        // Start the schedulers
+        let ga_scheduler_senders = cluster_data.into_iter()
+            .map(|scheduler_data| Self::start_scheduler::<S, G, P, F>(scheduler_data))
+            .collect_vec();
+        // Start the search
+        thread::spawn(move || run_local_search::<F, G, P, N>(
+            strategy,
+            CONFIG.local_search.clone(),
+            CONFIG.reevaluation.clone(),
+            neighbourhood,
+            ga_scheduler_senders,
+            scheduler_ga_receivers,
+            CONFIG.search_budget,
+        ));
     }
 
     fn start_scheduler<S: Scheduler<IndividualPhenotype = P> + Send + 'static, G: ExtendedGenotype, P: ExtendedPhenotype<G> + 'static, F: ExtendedFitness>(
         scheduler_data: SchedulerData<F>,
     ) -> Sender<P> {
-        let (ga_scheduler_sender, ga_scheduler_receiver): (Sender<P>, Receiver<P>) = std::sync::mpsc::channel();
+        Self::spawn_scheduler::<S, F>(scheduler_data)
+    }
+
+    /// Start a scheduler whose individuals are not encoded as genotypes
+    fn spawn_scheduler<S: Scheduler + Send + 'static, F: ExtendedFitness>(
+        scheduler_data: SchedulerData<F>,
+    ) -> Sender<S::IndividualPhenotype> {
+        let (ga_scheduler_sender, ga_scheduler_receiver) = std::sync::mpsc::channel();
         let scheduler = S::new(scheduler_data.collector_tx, scheduler_data.mutex_node_states, scheduler_data.node_keys, scheduler_data.failure_sender);
         thread::spawn(move || scheduler.start(
             scheduler_data.scheduler_receiver,
@@ -355,6 +525,7 @@ impl<F: ExtendedFitness> SchedulerData<F> {
 pub enum SchedulerType {
     Priority,
     Delay,
+    Hybrid,
     RandomPriority,
     RandomDelay,
     DelayTraceGraph,
@@ -362,8 +533,40 @@ pub enum SchedulerType {
     FitnessComparison,
     PredeterminedDelay,
     PredeterminedPriority,
+    ScheduleFiles,
+    Interactive,
+    Systematic,
+    Pct,
+    OnlineLearning,
     DelayLocalityExperiment,
     PriorityLocalityExperiment,
     ScalingExperiment,
+    DelayHillClimbing,
+    PriorityHillClimbing,
+    DelaySimulatedAnnealing,
+    PrioritySimulatedAnnealing,
+    DelayOnePlusOne,
+    PriorityOnePlusOne,
+    DelayFuzzing,
+    PriorityFuzzing,
     None,
-}
\ No newline at end of file
+}
+
+impl SchedulerType {
+    /// Whether the scheduler type can evaluate individuals on multiple clusters in parallel
+    /// Simulated annealing and the (1+1)-EA test a single candidate per step and every step depends on the outcome of the last one,
+    /// so they would leave every cluster but one idle
+    pub fn supports_clusters(&self) -> bool {
+        matches!(self, SchedulerType::Priority | SchedulerType::Delay | SchedulerType::Hybrid | SchedulerType::DelayHillClimbing | SchedulerType::PriorityHillClimbing)
+    }
+
+    /// The local search strategy of the scheduler type, if it is one
+    pub fn local_search_strategy(&self) -> Option<LocalSearchStrategy> {
+        match self {
+            SchedulerType::DelayHillClimbing | SchedulerType::PriorityHillClimbing => Some(LocalSearchStrategy::HillClimbing),
+            SchedulerType::DelaySimulatedAnnealing | SchedulerType::PrioritySimulatedAnnealing => Some(LocalSearchStrategy::SimulatedAnnealing),
+            SchedulerType::DelayOnePlusOne | SchedulerType::PriorityOnePlusOne => Some(LocalSearchStrategy::OnePlusOne),
+            _ => None,
+        }
+    }
+}
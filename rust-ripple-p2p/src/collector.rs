@@ -13,7 +13,6 @@ use chrono::{DateTime, Duration, MAX_DATETIME, Utc};
 use itertools::Itertools;
 use log::error;
 use serde_with::{serde_as, DurationSecondsWithFrac};
-use crate::LOG_FOLDER;
 use crate::message_handler::RippleMessageObject::TMProposeSet;
 use crate::node_state::{ConsensusPhase, MutexNodeStates};
 use crate::protos::ripple::{TMTransaction};
@@ -28,6 +27,7 @@ pub struct Collector {
     subscription_receiver: Receiver<PeerSubscriptionObject>,
     subscription_files: Vec<BufWriter<File>>,
     node_states: Arc<MutexNodeStates>,
+    log_folder: String,
 }
 
 impl Collector {
@@ -35,10 +35,11 @@ impl Collector {
         number_of_nodes: u16,
         subscription_receiver: Receiver<PeerSubscriptionObject>,
         node_states: Arc<MutexNodeStates>,
+        log_folder: String,
     ) -> Self {
         let mut subscription_files = vec![];
         for peer in 0..number_of_nodes {
-            let mut subscription_file = BufWriter::new(File::create(Path::new(format!("{}/subscription_{}.json", *LOG_FOLDER, peer).as_str())).expect("Opening subscription file failed"));
+            let mut subscription_file = BufWriter::new(File::create(Path::new(format!("{}/subscription_{}.json", log_folder, peer).as_str())).expect("Opening subscription file failed"));
             subscription_file.write_all(String::from("[\n").as_bytes()).unwrap();
             subscription_files.push(subscription_file);
         }
@@ -46,13 +47,15 @@ impl Collector {
             subscription_receiver,
             subscription_files,
             node_states,
+            log_folder,
         }
     }
 
     pub fn start(&mut self, ripple_message_receiver: Receiver<Box<RippleMessage>>, server_state_receiver: Receiver<PeerServerStateObject>) {
         let node_states_clone = self.node_states.clone();
         let node_state_clone_2 = self.node_states.clone();
-        thread::spawn(move || Self::execution_writer(ripple_message_receiver, node_state_clone_2));
+        let log_folder = self.log_folder.clone();
+        thread::spawn(move || Self::execution_writer(ripple_message_receiver, node_state_clone_2, log_folder));
         thread::spawn(move || Self::server_state_handler(server_state_receiver, node_states_clone));
         loop {
             // Handle subscription streams in a central place, TODO: refactor to own associated method.
@@ -70,7 +73,7 @@ impl Collector {
                         // Use new consensus phase to determine the current round of consensus of the node
                         let new_consensus_phase = Self::parse_consensus_change(consensus_change.clone());
                         self.node_states.set_consensus_phase(subscription_object.peer as usize, new_consensus_phase);
-                        // println!("{:?}", self.node_states.node_states.lock().node_states[subscription_object.peer as usize]);
+                        // println!("{:?}", *self.node_states.node(subscription_object.peer as usize));
                         self.write_to_subscription_file(subscription_object.peer, json!({"ConsensusChange": consensus_change}).to_string());
                     }
                     SubscriptionObject::Transaction(transaction_subscription) => {
@@ -99,8 +102,8 @@ impl Collector {
         }
     }
 
-    fn execution_writer(ripple_message_receiver: Receiver<Box<RippleMessage>>, node_states: Arc<MutexNodeStates>) {
-        let mut execution_file = File::create(Path::new(format!("{}/execution.txt", *LOG_FOLDER).as_str())).expect("Opening execution file failed");
+    fn execution_writer(ripple_message_receiver: Receiver<Box<RippleMessage>>, node_states: Arc<MutexNodeStates>, log_folder: String) {
+        let mut execution_file = File::create(Path::new(format!("{}/execution.txt", log_folder).as_str())).expect("Opening execution file failed");
         // let mut execution_writer = BufWriter::new(execution_file);
         loop {
             // Write all messages sent by the scheduler to peers to "execution.txt". After delay!
@@ -13,8 +13,7 @@ impl ConsensusProperties {
     /// I1 Check whether a proposal has already declared consensus on a transaction set for one ledger
     pub fn check_proposal_integrity_property(node_states: &Arc<MutexNodeStates>, status_change: &TMStatusChange, sender: usize) -> Vec<ConsensusPropertyTypes> {
         if status_change.has_newEvent() && status_change.get_newEvent() == NodeEvent::neACCEPTED_LEDGER {
-            let mut node_states_vec = node_states.node_states.lock();
-            let already_present = node_states_vec.add_consensus_constructed_ledger(status_change.clone(), sender);
+            let already_present = node_states.add_consensus_constructed_ledger(status_change.clone(), sender);
             if let Some(earlier_status_change) = already_present {
                 if earlier_status_change != *status_change {
                     error!("(I1) Node has declared consensus on two transaction sets for the same ledger sequence\nOld: {:?}\nNew: {:?}", earlier_status_change, status_change);
@@ -27,8 +26,7 @@ impl ConsensusProperties {
 
     /// I2 Check whether a node has already issued a validation for a ledger
     pub fn check_validation_integrity_property(node_states: &Arc<MutexNodeStates>, validation: ParsedValidation, sender: usize) -> Vec<ConsensusPropertyTypes> {
-        let mut node_states_vec = node_states.node_states.lock();
-        let already_present = node_states_vec.add_sent_validation(validation.clone(), sender);
+        let already_present = node_states.add_sent_validation(validation.clone(), sender);
         if let Some(earlier_validation) = already_present {
             if earlier_validation != validation {
                 error!("(I2) Node validated twice for one ledger sequence\nOld: {:?}\nNew: {:?}", earlier_validation, validation);
@@ -44,7 +42,7 @@ impl ConsensusProperties {
     /// A2 Check whether two nodes validated two different ledgers
     pub fn check_agreement_properties(node_states: &Arc<MutexNodeStates>) -> Vec<ConsensusPropertyTypes> {
         let mut consensus_properties_violated = vec![];
-        let node_states_vec = &node_states.node_states.lock().node_states;
+        let node_states_vec = node_states.snapshot();
         let seqs = node_states_vec.iter()
             .map(|node| node.validated_ledgers.keys().map(|key| *key).collect::<HashSet<usize>>())
             .flatten()
@@ -73,7 +71,7 @@ impl ConsensusProperties {
     /// V1 Check whether the transaction sets on which the nodes declared consensus are actually in the proposed transaction sets
     /// V2 Check whether the transaction sets (consensus_hash) in nodes' validation messages are actually in the proposed transaction sets
     pub fn check_validity_properties(node_states: &Arc<MutexNodeStates>) -> Vec<ConsensusPropertyTypes> {
-        let node_states_vec = &node_states.node_states.lock().node_states;
+        let node_states_vec = node_states.snapshot();
         let seqs = node_states_vec.iter().map(|node| node.proposed_tx_sets.keys().map(|key| *key).collect::<HashSet<usize>>()).flatten().collect::<HashSet<usize>>();
         let mut consensus_properties_violated = vec![];
         for seq in seqs {
@@ -115,7 +113,7 @@ mod consensus_properties_tests {
     use crate::consensus_properties::ConsensusProperties;
     use crate::failure_writer::ConsensusPropertyTypes;
     use crate::message_handler::ParsedValidation;
-    use crate::node_state::{MutexNodeStates, NodeState, NodeStates};
+    use crate::node_state::{MutexNodeStates, NodeState};
     use crate::protos::ripple::{NodeEvent, TMStatusChange};
 
     #[test]
@@ -126,24 +124,24 @@ mod consensus_properties_tests {
         node_states_vec[0].validated_ledgers.insert(0, ValidatedLedger::default());
         node_states_vec[1].validated_ledgers.insert(0, ValidatedLedger::default());
         node_states_vec[2].validated_ledgers.insert(1, ValidatedLedger::default());
-        let node_states = Arc::new(MutexNodeStates::new(NodeStates::new(node_states_vec)));
+        let node_states = Arc::new(MutexNodeStates::new(node_states_vec));
         assert_eq!(ConsensusProperties::check_agreement_properties(&node_states), vec![]);
         let mut different_validation = ValidatedLedger::default();
         different_validation.ledger_hash = "Different ledger hash".to_string();
-        node_states.node_states.lock().node_states[1].validated_ledgers.insert(1, different_validation);
+        node_states.node(1).validated_ledgers.insert(1, different_validation);
         assert_eq!(ConsensusProperties::check_agreement_properties(&node_states), vec![ConsensusPropertyTypes::Agreement2]);
-        node_states.node_states.lock().node_states[1].validated_ledgers.insert(1, ValidatedLedger::default());
+        node_states.node(1).validated_ledgers.insert(1, ValidatedLedger::default());
         assert_eq!(ConsensusProperties::check_agreement_properties(&node_states), vec![]);
         let mut different_status_change = TMStatusChange::default();
         different_status_change.set_ledgerHash(vec![1, 2, 3]);
-        node_states.node_states.lock().node_states[2].consensus_constructed_ledgers.insert(0, different_status_change);
+        node_states.node(2).consensus_constructed_ledgers.insert(0, different_status_change);
         assert_eq!(ConsensusProperties::check_agreement_properties(&node_states), vec![ConsensusPropertyTypes::Agreement1]);
     }
 
     #[test]
     fn test_check_proposal_integrity_properties() {
         let node_states_vec = setup_node_states(3);
-        let node_states = Arc::new(MutexNodeStates::new(NodeStates::new(node_states_vec)));
+        let node_states = Arc::new(MutexNodeStates::new(node_states_vec));
         let mut status_change_1 = TMStatusChange::default();
         status_change_1.set_newEvent(NodeEvent::neACCEPTED_LEDGER);
         status_change_1.set_ledgerSeq(0);
@@ -161,7 +159,7 @@ mod consensus_properties_tests {
     #[test]
     fn test_check_validation_integrity_properties() {
         let node_states_vec = setup_node_states(3);
-        let node_states = Arc::new(MutexNodeStates::new(NodeStates::new(node_states_vec)));
+        let node_states = Arc::new(MutexNodeStates::new(node_states_vec));
         let mut validation_1 = ParsedValidation::default();
         validation_1.ledger_sequence = 0;
         validation_1.hash = "hash1".to_string();
@@ -177,19 +175,19 @@ mod consensus_properties_tests {
     #[test]
     fn test_check_validity_properties() {
         let node_states_vec = setup_node_states(3);
-        let node_states = Arc::new(MutexNodeStates::new(NodeStates::new(node_states_vec)));
+        let node_states = Arc::new(MutexNodeStates::new(node_states_vec));
         let proposal_1 = [1];
-        node_states.node_states.lock().add_proposed_tx_set(&proposal_1, 1);
+        node_states.add_proposed_tx_set(&proposal_1, 1);
         let mut validation_1 = ParsedValidation::default();
         validation_1.ledger_sequence = 3;
         validation_1.consensus_hash = "01".to_string();
-        node_states.node_states.lock().add_sent_validation(validation_1, 1);
+        node_states.add_sent_validation(validation_1, 1);
         // No violations
         ConsensusProperties::check_validity_properties(&node_states);
         let mut validation_1 = ParsedValidation::default();
         validation_1.ledger_sequence = 3;
         validation_1.consensus_hash = "02".to_string();
-        node_states.node_states.lock().add_sent_validation(validation_1, 0);
+        node_states.add_sent_validation(validation_1, 0);
         // Violations
         ConsensusProperties::check_validity_properties(&node_states);
     }
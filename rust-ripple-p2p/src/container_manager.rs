@@ -11,22 +11,96 @@ use rayon::prelude::*;
 use serde::{Deserialize};
 use crate::{CONFIG, LOG_FOLDER, NUM_NODES};
 
+/// Port distance between two clusters, a cluster can have at most this many nodes
+pub const CLUSTER_PORT_OFFSET: usize = 100;
+
+/// An isolated validator network with its own containers, docker network, config folders and port range.
+/// Cluster 0 keeps the names and ports of a single network run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cluster {
+    pub id: usize,
+}
+
+impl Cluster {
+    pub fn new(id: usize) -> Self {
+        Self { id }
+    }
+
+    pub fn validator_name(&self, node: usize) -> String {
+        match self.id {
+            0 => format!("validator_{}", node),
+            id => format!("cluster_{}_validator_{}", id, node),
+        }
+    }
+
+    pub fn network_name(&self) -> String {
+        match self.id {
+            0 => "ripple-net".to_string(),
+            id => format!("ripple-net-{}", id),
+        }
+    }
+
+    pub fn ws_port(&self, node: usize) -> u16 {
+        (6005 + self.id * CLUSTER_PORT_OFFSET + node) as u16
+    }
+
+    pub fn peer_port(&self, node: usize) -> u16 {
+        (51235 + self.id * CLUSTER_PORT_OFFSET + node) as u16
+    }
+
+    /// Folder for the collector and failure files of this cluster
+    pub fn log_folder(&self) -> String {
+        let folder = match self.id {
+            0 => LOG_FOLDER.clone(),
+            id => format!("{}/cluster_{}", *LOG_FOLDER, id),
+        };
+        if let Err(err) = create_dir_all(&folder) {
+            error!("Could not create cluster log folder, err: {}", err);
+        }
+        folder
+    }
+}
+
 #[allow(unused)]
 pub fn start_docker_containers(peers: usize, unls: Vec<Vec<usize>>, image_name: &str) -> Vec<NodeKeys> {
+    start_docker_clusters(1, peers, unls, image_name).remove(0)
+}
+
+/// Start num_clusters independent networks with the same unl configuration
+pub fn start_docker_clusters(num_clusters: usize, peers: usize, unls: Vec<Vec<usize>>, image_name: &str) -> Vec<Vec<NodeKeys>> {
     remove_containers("validator");
+    let node_keys = (0..num_clusters)
+        .map(|id| start_cluster_containers(&Cluster::new(id), peers, unls.clone(), image_name))
+        .collect();
+    thread::sleep(Duration::from_secs(3));
+    node_keys
+}
+
+fn start_cluster_containers(cluster: &Cluster, peers: usize, unls: Vec<Vec<usize>>, image_name: &str) -> Vec<NodeKeys> {
+    create_network(&cluster.network_name());
     let node_keys = get_node_keys(peers, image_name);
-    create_configs(peers, &node_keys);
-    configure_unls(unls, &node_keys);
+    create_configs(cluster, peers, &node_keys);
+    configure_unls(cluster, unls, &node_keys);
     let folders = if CONFIG.create_ripple_log_folders {
-        Some(create_log_folders(peers))
+        Some(create_log_folders(cluster, peers))
     } else {
         None
     };
-    run_nodes(peers, image_name, folders);
-    thread::sleep(Duration::from_secs(3));
+    run_nodes(cluster, peers, image_name, folders);
     node_keys
 }
 
+/// Create the docker network if it does not exist yet
+fn create_network(name: &str) {
+    let existing = Command::new("docker")
+        .args(["network", "ls", "--quiet", "--filter", &format!("name=^{}$", name)])
+        .output().unwrap().stdout;
+    if existing.is_empty() {
+        Command::new("docker").args(["network", "create", name]).output().unwrap();
+        debug!("created docker network {}", name);
+    }
+}
+
 pub fn remove_containers(name: &str) {
     let leftovers = Command::new("docker").arg("ps")
         .args(["--all", "--quiet"])
@@ -66,7 +140,7 @@ pub fn start_key_generator(image_name: &str) {
     if already_running.len() == 0 {
         debug!("trying to start key generator");
         remove_containers("key_generator");
-        start_node_with_options("key_generator", image_name, 0, false, None);
+        start_node_with_options("key_generator", image_name, None, None);
         thread::sleep(Duration::from_secs(2));
     }
 }
@@ -83,10 +157,10 @@ fn acquire_keys() -> NodeKeys {
 }
 
 
-pub fn create_configs(peers: usize, keys: &Vec<NodeKeys>) {
+pub fn create_configs(cluster: &Cluster, peers: usize, keys: &Vec<NodeKeys>) {
     let base = read_to_string(Path::new("../config/rippled.cfg")).unwrap();
     (0..peers).into_par_iter().for_each(|i| {
-        let path = format!("../config/validator_{}", i);
+        let path = format!("../config/{}", cluster.validator_name(i));
         fs::create_dir_all(&path).unwrap();
         fs::copy("../config/ledger.json", format!("{}/ledger.json", path)).unwrap();
         let config = base.replace("{validation_seed}", &keys[i].validation_seed);
@@ -95,9 +169,9 @@ pub fn create_configs(peers: usize, keys: &Vec<NodeKeys>) {
     });
 }
 
-pub fn configure_unls(unls: Vec<Vec<usize>>, keys: &Vec<NodeKeys>) {
+pub fn configure_unls(cluster: &Cluster, unls: Vec<Vec<usize>>, keys: &Vec<NodeKeys>) {
     (0..unls.len()).into_par_iter().for_each(|i| {
-        let path = format!("../config/validator_{}/validators.txt", i);
+        let path = format!("../config/{}/validators.txt", cluster.validator_name(i));
         let mut validators = "[validators]\n".to_owned();
         for node in 0..unls.len() {
             if i != node && unls[i].contains(&node) {
@@ -110,10 +184,10 @@ pub fn configure_unls(unls: Vec<Vec<usize>>, keys: &Vec<NodeKeys>) {
     });
 }
 
-pub fn create_log_folders(peers: usize) -> Vec<String> {
+pub fn create_log_folders(cluster: &Cluster, peers: usize) -> Vec<String> {
     let mut folders = vec![];
     for i in 0..peers {
-        let folder_name = format!("{}/validator_{}", *LOG_FOLDER, i);
+        let folder_name = format!("{}/validator_{}", cluster.log_folder(), i);
         println!("{}", folder_name);
         match create_dir_all(&folder_name) {
             Ok(_) => folders.push(folder_name),
@@ -124,21 +198,22 @@ pub fn create_log_folders(peers: usize) -> Vec<String> {
 }
 
 #[allow(unused)]
-fn run_nodes(peers: usize, image_name: &str, log_folders: Option<Vec<String>>) {
+fn run_nodes(cluster: &Cluster, peers: usize, image_name: &str, log_folders: Option<Vec<String>>) {
     if let Some(log_folders) = log_folders {
-        (0..peers).into_par_iter().for_each(|i| start_node(i, image_name, Some(&log_folders[i])));
+        (0..peers).into_par_iter().for_each(|i| start_node(cluster, i, image_name, Some(&log_folders[i])));
     } else {
-        (0..peers).into_par_iter().for_each(|i| start_node(i, image_name, None));
+        (0..peers).into_par_iter().for_each(|i| start_node(cluster, i, image_name, None));
     }
 }
 
 #[allow(unused)]
-fn start_node(id: usize, image_name: &str, log_folder: Option<&str>) {
-    start_node_with_options(&format!("validator_{}", id), image_name, id, true, log_folder);
+fn start_node(cluster: &Cluster, id: usize, image_name: &str, log_folder: Option<&str>) {
+    start_node_with_options(&cluster.validator_name(id), image_name, Some((cluster, id)), log_folder);
 }
 
-fn start_node_with_options(name: &str, image_name: &str, offset: usize, expose_to_network: bool, log_folder: Option<&str>) {
-    debug!("Starting node: {}, expose_to_network: {}, log_folder: {:?}", name, expose_to_network, log_folder);
+/// Start a container, if a cluster and node index are given, the node is exposed on the network and ports of the cluster
+fn start_node_with_options(name: &str, image_name: &str, network_node: Option<(&Cluster, usize)>, log_folder: Option<&str>) {
+    debug!("Starting node: {}, network_node: {:?}, log_folder: {:?}", name, network_node, log_folder);
     let mut command = Command::new("docker");
     let mut command = command
         .arg("run")
@@ -147,11 +222,11 @@ fn start_node_with_options(name: &str, image_name: &str, offset: usize, expose_t
     if let Some(folder) = log_folder {
         command = command.args(["--mount", &format!("type=bind,source={},target=/var/log/rippled", folder)]);
     }
-    if expose_to_network {
+    if let Some((cluster, node)) = network_node {
         command = command
-            .args(["--net", "ripple-net"])
-            .args(["-p", &format!("{}:6005", 6005 + offset)])
-            .args(["-p", &format!("{}:51235", 51235 + offset)])
+            .args(["--net", &cluster.network_name()])
+            .args(["-p", &format!("{}:6005", cluster.ws_port(node))])
+            .args(["-p", &format!("{}:51235", cluster.peer_port(node))])
     }
     command.arg(&format!("mvanmeerten/{}", image_name)).output().unwrap();
     debug!("started {}", name);
@@ -170,11 +245,11 @@ pub fn create_account() -> AccountKeys {
 }
 
 /// Check the logs of the validator to detect old proposal overwrite
-pub fn check_logs_for_b1(test_case_duration: chrono::Duration) -> bool {
+pub fn check_logs_for_b1(test_case_duration: chrono::Duration, cluster: &Cluster) -> bool {
     (0..*NUM_NODES).into_par_iter().map(|i| {
         let output = Command::new("docker").arg("logs")
             .args(["--since", &format!("{}s",test_case_duration.num_seconds())])
-            .arg(format!("validator_{}", i)).output();
+            .arg(cluster.validator_name(i)).output();
         match output {
             Ok(output) => {
                 let logs_string = std::str::from_utf8(&output.stdout).expect("parse utf8 error getting logs for b1 bug check");
@@ -200,14 +275,28 @@ pub struct AccountKeysResult {
 #[cfg(test)]
 mod container_tests {
     use chrono::{Duration, Utc};
-    use crate::container_manager::check_logs_for_b1;
+    use crate::container_manager::{check_logs_for_b1, Cluster};
     use crate::NUM_NODES;
 
     #[test]
     fn test_logs_for_b1() {
         let _num_nodes = *NUM_NODES;
         let now = Utc::now();
-        dbg!(check_logs_for_b1(Duration::seconds(30)));
+        dbg!(check_logs_for_b1(Duration::seconds(30), &Cluster::new(0)));
         dbg!(Utc::now() - now);
     }
+
+    #[test]
+    fn test_cluster_isolation() {
+        let first = Cluster::new(0);
+        let second = Cluster::new(2);
+        assert_eq!(first.validator_name(3), "validator_3");
+        assert_eq!(first.network_name(), "ripple-net");
+        assert_eq!(first.ws_port(1), 6006);
+        assert_eq!(first.peer_port(1), 51236);
+        assert_eq!(second.validator_name(3), "cluster_2_validator_3");
+        assert_eq!(second.network_name(), "ripple-net-2");
+        assert_eq!(second.ws_port(1), 6206);
+        assert_eq!(second.peer_port(1), 51436);
+    }
 }
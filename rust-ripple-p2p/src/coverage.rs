@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+use crate::collector::RippleMessage;
+use crate::message_handler::RippleMessageObject;
+use crate::node_state::{ConsensusPhase, NodeState};
+
+/// Propose sequence numbers above this value are counted as this value
+pub const MAX_COVERED_PROPOSE_SEQ: u32 = 6;
+/// Round offsets above this value are counted as this value
+pub const MAX_COVERED_ROUND_OFFSET: u32 = 3;
+/// Received validation counts above this value are counted as this value
+pub const MAX_COVERED_VALIDATIONS: u32 = 10;
+
+const BOW_OUT_PROPOSE_SEQ: u32 = 4294967295;
+
+/// Abstract consensus state of a single node, the unit of coverage
+/// round_offset: The number of rounds the node is ahead of the slowest node
+/// received_validations: The number of validations the node received in its current round
+#[derive(Clone, Debug, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct AbstractConsensusState {
+    pub consensus_phase: ConsensusPhase,
+    pub propose_seq: u32,
+    pub bowed_out: bool,
+    pub round_offset: u32,
+    pub received_validations: u32,
+}
+
+/// Per round information of a node that is not kept in its node state
+#[derive(Clone, Debug, Default)]
+struct NodeCoverageState {
+    propose_seq: u32,
+    bowed_out: bool,
+    received_validations: u32,
+}
+
+/// Tracks the abstract consensus states of all nodes and the set of states that have been reached
+#[derive(Clone, Debug)]
+pub struct CoverageTracker {
+    nodes: Vec<NodeCoverageState>,
+    coverage: HashSet<AbstractConsensusState>,
+}
+
+impl CoverageTracker {
+    pub fn new(number_of_nodes: usize) -> Self {
+        Self { nodes: vec![NodeCoverageState::default(); number_of_nodes], coverage: HashSet::new() }
+    }
+
+    /// Update the per round information of the sender or receiver of a delivered message
+    /// Returns the node whose abstract state changed, if any
+    pub fn observe_message(&mut self, ripple_message: &RippleMessage) -> Option<usize> {
+        match &ripple_message.message {
+            RippleMessageObject::TMProposeSet(proposal) => {
+                let sender = ripple_message.sender_index();
+                if proposal.get_proposeSeq() == BOW_OUT_PROPOSE_SEQ {
+                    self.nodes[sender].bowed_out = true;
+                } else {
+                    self.nodes[sender].propose_seq = self.nodes[sender].propose_seq.max(proposal.get_proposeSeq());
+                }
+                Some(sender)
+            }
+            RippleMessageObject::TMValidation(_) => {
+                let receiver = ripple_message.receiver_index();
+                self.nodes[receiver].received_validations += 1;
+                Some(receiver)
+            }
+            _ => None,
+        }
+    }
+
+    /// A node moved to a new round, the per round information starts over
+    pub fn new_round(&mut self, peer: usize) {
+        self.nodes[peer] = NodeCoverageState::default();
+    }
+
+    /// Add the current abstract state of a node to the coverage
+    pub fn record(&mut self, node_state: &NodeState, min_round: u32) {
+        let state = self.abstract_state(node_state, min_round);
+        self.coverage.insert(state);
+    }
+
+    pub fn abstract_state(&self, node_state: &NodeState, min_round: u32) -> AbstractConsensusState {
+        let node = &self.nodes[node_state.peer];
+        AbstractConsensusState {
+            consensus_phase: node_state.consensus_phase.clone(),
+            propose_seq: node.propose_seq.min(MAX_COVERED_PROPOSE_SEQ),
+            bowed_out: node.bowed_out,
+            round_offset: node_state.current_consensus_round.saturating_sub(min_round).min(MAX_COVERED_ROUND_OFFSET),
+            received_validations: node.received_validations.min(MAX_COVERED_VALIDATIONS),
+        }
+    }
+
+    pub fn coverage(&self) -> &HashSet<AbstractConsensusState> {
+        &self.coverage
+    }
+
+    /// Start collecting coverage for a new test run
+    pub fn clear_coverage(&mut self) {
+        self.coverage.clear();
+    }
+}
+
+#[cfg(test)]
+mod coverage_tests {
+    use crate::coverage::{CoverageTracker, MAX_COVERED_ROUND_OFFSET};
+    use crate::node_state::{ConsensusPhase, NodeState};
+
+    #[test]
+    fn test_record_abstract_states() {
+        let mut tracker = CoverageTracker::new(2);
+        let mut node_state = NodeState::new(1);
+        tracker.record(&node_state, 3);
+        tracker.record(&node_state, 3);
+        assert_eq!(tracker.coverage().len(), 1);
+
+        node_state.consensus_phase = ConsensusPhase::Establish;
+        node_state.current_consensus_round = 10;
+        tracker.record(&node_state, 3);
+        assert_eq!(tracker.coverage().len(), 2);
+        let state = tracker.abstract_state(&node_state, 3);
+        assert_eq!(state.round_offset, MAX_COVERED_ROUND_OFFSET);
+        assert_eq!(state.consensus_phase, ConsensusPhase::Establish);
+
+        tracker.new_round(1);
+        tracker.clear_coverage();
+        assert!(tracker.coverage().is_empty());
+    }
+}
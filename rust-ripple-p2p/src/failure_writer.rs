@@ -10,8 +10,8 @@ use petgraph::Graph;
 use crate::client::{Transaction, ValidatedLedger};
 use crate::collector::RippleMessage;
 use crate::node_state::{DependencyEvent, MutexNodeStates};
-use crate::{CONFIG, LOG_FOLDER};
-use crate::container_manager::check_logs_for_b1;
+use crate::CONFIG;
+use crate::container_manager::{check_logs_for_b1, Cluster};
 use crate::test_harness::TransactionResultCode;
 
 /// Struct responsible for writing state to failure file in case of consensus property violation
@@ -24,13 +24,14 @@ pub struct FailureWriter {
 
 impl FailureWriter {
     pub fn start_failure_writer(failure_receiver: Receiver<Vec<ConsensusPropertyTypes>>,
-                                node_states: Arc<MutexNodeStates>)
+                                node_states: Arc<MutexNodeStates>,
+                                cluster: Cluster)
     {
         let mut failure_writer = Self {
             failure_receiver,
             failure_writer: BufWriter::new(
                 File::create(
-                    Path::new(format!("{}/failure_file.txt", *LOG_FOLDER).as_str()))
+                    Path::new(format!("{}/failure_file.txt", cluster.log_folder()).as_str()))
                     .expect("Opening failure file failed")
             ),
             node_states,
@@ -51,11 +52,9 @@ impl FailureWriter {
                                         continue;
                                     }
                                 } else if target_consensus_property == &ConsensusPropertyTypes::Agreement1 {
-                                    let test_start_time = {
-                                        failure_writer.node_states.node_states.lock().test_start_time.clone()
-                                    };
+                                    let test_start_time = failure_writer.node_states.get_test_start_time();
                                     let duration = Utc::now() - test_start_time;
-                                    let hit_bug = check_logs_for_b1(duration);
+                                    let hit_bug = check_logs_for_b1(duration, &cluster);
                                     if !hit_bug {
                                         continue;
                                     }
@@ -113,7 +112,7 @@ pub struct Failure {
     pub consensus_properties_violated: Vec<ConsensusPropertyTypes>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
 pub enum ConsensusPropertyTypes {
     Termination,
     Validity1,
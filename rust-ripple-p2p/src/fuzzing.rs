@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use chrono::{Duration, Utc};
+use log::error;
+use rand::thread_rng;
+use crate::coverage::AbstractConsensusState;
+use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype};
+use crate::ga::fitness::ExtendedFitness;
+use crate::ga::local_search::Neighbourhood;
+use crate::LOG_FOLDER;
+use crate::node_state::MutexNodeStates;
+
+/// Parameters of the coverage-guided fuzzer as set in the configuration
+/// initial_corpus_size: The number of random schedules the corpus starts with
+/// energy: The number of mutants that are run every time a corpus entry is picked
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct FuzzingConfiguration {
+    pub initial_corpus_size: usize,
+    pub energy: usize,
+}
+
+impl Default for FuzzingConfiguration {
+    fn default() -> Self {
+        Self {
+            initial_corpus_size: 4,
+            energy: 2,
+        }
+    }
+}
+
+impl FuzzingConfiguration {
+    /// Check the parameters for values the fuzzer cannot run with, returns all problems found
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.initial_corpus_size == 0 {
+            errors.push("initial_corpus_size must be at least 1".to_string());
+        }
+        if self.energy == 0 {
+            errors.push("energy must be at least 1".to_string());
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// A schedule that reached new coverage
+/// new_coverage: The number of abstract states that were first reached by this schedule
+/// times_fuzzed: The number of times the entry has been picked for mutation
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CorpusEntry<G, F> {
+    pub genotype: G,
+    pub fitness: F,
+    pub new_coverage: usize,
+    pub times_fuzzed: usize,
+}
+
+/// The corpus of interesting schedules and the total coverage of all runs
+#[derive(Debug)]
+pub struct Corpus<G, F> {
+    pub entries: Vec<CorpusEntry<G, F>>,
+    coverage: HashSet<AbstractConsensusState>,
+}
+
+impl<G: Clone, F: Clone> Corpus<G, F> {
+    pub fn new() -> Self {
+        Self { entries: vec![], coverage: HashSet::new() }
+    }
+
+    pub fn total_coverage(&self) -> usize {
+        self.coverage.len()
+    }
+
+    /// Add the coverage of a run, returns the number of states that had not been reached before
+    pub fn add_coverage(&mut self, run_coverage: &HashSet<AbstractConsensusState>) -> usize {
+        run_coverage.iter().filter(|state| self.coverage.insert((*state).clone())).count()
+    }
+
+    /// Keep the schedule if it reached new states, returns whether it was added
+    pub fn add_if_interesting(&mut self, genotype: G, fitness: F, new_coverage: usize) -> bool {
+        if new_coverage == 0 {
+            return false;
+        }
+        self.entries.push(CorpusEntry { genotype, fitness, new_coverage, times_fuzzed: 0 });
+        true
+    }
+
+    /// Pick the entry that has been fuzzed least often, the oldest entry on ties
+    pub fn next_entry(&mut self) -> Option<&CorpusEntry<G, F>> {
+        let index = self.entries.iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| entry.times_fuzzed)
+            .map(|(index, _)| index)?;
+        self.entries[index].times_fuzzed += 1;
+        Some(&self.entries[index])
+    }
+}
+
+/// A single fuzzing run, written to fuzzing.txt
+#[derive(serde::Serialize)]
+struct FuzzingStepInfo<'a, G, F> {
+    iteration: u64,
+    fitness: &'a F,
+    new_coverage: usize,
+    total_coverage: usize,
+    corpus_size: usize,
+    individual: &'a G,
+}
+
+/// Coverage-guided fuzzing of schedules
+/// Random schedules seed the corpus, after that corpus entries are mutated and mutants that reach new abstract consensus states are added
+pub fn run_fuzzing<F, G, P, N>(
+    configuration: FuzzingConfiguration,
+    neighbourhood: N,
+    scheduler_sender: Sender<P>,
+    scheduler_receiver: Receiver<F>,
+    node_states: Arc<MutexNodeStates>,
+    search_budget: Duration,
+)
+    where F: ExtendedFitness, G: ExtendedGenotype + serde::Serialize, P: ExtendedPhenotype<G>, N: Neighbourhood<G>
+{
+    let mut fuzzing_writer = create_fuzzing_writer();
+    let rng = &mut thread_rng();
+    let mut corpus: Corpus<G, F> = Corpus::new();
+    let mut iteration = 0;
+    let start_time = Utc::now();
+    println!("Starting fuzzing with: {:?}", configuration);
+    while Utc::now() - start_time < search_budget {
+        let schedules = if (iteration as usize) < configuration.initial_corpus_size || corpus.entries.is_empty() {
+            vec![neighbourhood.random_solution(rng)]
+        } else {
+            let parent = corpus.next_entry().unwrap().genotype.clone();
+            (0..configuration.energy).map(|_| neighbourhood.mutate(parent.clone(), rng)).collect()
+        };
+        for schedule in schedules {
+            iteration += 1;
+            scheduler_sender.send(P::from_genes(&schedule)).expect("Scheduler receiver failed");
+            let fitness = scheduler_receiver.recv().expect("Scheduler sender failed");
+            let new_coverage = corpus.add_coverage(&node_states.get_coverage());
+            let step_info = FuzzingStepInfo {
+                iteration,
+                fitness: &fitness,
+                new_coverage,
+                total_coverage: corpus.total_coverage(),
+                corpus_size: corpus.entries.len() + (new_coverage > 0) as usize,
+                individual: &schedule,
+            };
+            println!("Fuzzing run {}: {} new states, {} states in total", iteration, new_coverage, corpus.total_coverage());
+            match serde_json::to_writer(&mut fuzzing_writer, &step_info) {
+                Ok(_) => {}
+                Err(err) => error!("Failed writing to fuzzing file: {}", err)
+            };
+            fuzzing_writer.write_all(b"\n").expect("Fuzzing writer failed");
+            fuzzing_writer.flush().expect("Fuzzing writer flush failed");
+            corpus.add_if_interesting(schedule, fitness, new_coverage);
+        }
+    }
+    println!("Exiting: Search budget exceeded. Corpus size: {}, states covered: {}", corpus.entries.len(), corpus.total_coverage());
+    std::process::exit(0);
+}
+
+fn create_fuzzing_writer() -> BufWriter<File> {
+    BufWriter::new(
+        File::create(
+            Path::new(format!("{}/fuzzing.txt", *LOG_FOLDER).as_str())
+        ).expect("Creating fuzzing file failed")
+    )
+}
+
+#[cfg(test)]
+mod fuzzing_tests {
+    use std::collections::HashSet;
+    use crate::coverage::AbstractConsensusState;
+    use crate::fuzzing::Corpus;
+    use crate::node_state::ConsensusPhase;
+
+    fn state(consensus_phase: ConsensusPhase, propose_seq: u32) -> AbstractConsensusState {
+        AbstractConsensusState { consensus_phase, propose_seq, bowed_out: false, round_offset: 0, received_validations: 0 }
+    }
+
+    #[test]
+    fn test_corpus() {
+        let mut corpus: Corpus<Vec<u32>, u32> = Corpus::new();
+        assert!(corpus.next_entry().is_none());
+        let run_1: HashSet<_> = [state(ConsensusPhase::Open, 0), state(ConsensusPhase::Establish, 1)].into_iter().collect();
+        let run_2: HashSet<_> = [state(ConsensusPhase::Open, 0), state(ConsensusPhase::Accepted, 1)].into_iter().collect();
+        let new_coverage = corpus.add_coverage(&run_1);
+        assert_eq!(new_coverage, 2);
+        assert!(corpus.add_if_interesting(vec![1], 0, new_coverage));
+        let new_coverage = corpus.add_coverage(&run_2);
+        assert_eq!(new_coverage, 1);
+        assert!(corpus.add_if_interesting(vec![2], 0, new_coverage));
+        let new_coverage = corpus.add_coverage(&run_1);
+        assert!(!corpus.add_if_interesting(vec![3], 0, new_coverage));
+        assert_eq!(corpus.total_coverage(), 3);
+
+        assert_eq!(corpus.next_entry().unwrap().genotype, vec![1]);
+        assert_eq!(corpus.next_entry().unwrap().genotype, vec![2]);
+        assert_eq!(corpus.next_entry().unwrap().genotype, vec![1]);
+    }
+}
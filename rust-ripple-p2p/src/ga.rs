@@ -1,9 +1,12 @@
 pub mod genetic_algorithm;
-mod mutation;
+pub mod mutation;
 pub(crate) mod fitness;
+pub mod evaluation;
 pub mod crossover;
 pub mod parameters;
 pub mod population_builder;
 pub mod encoding;
-mod selection;
-mod reinsertion;
+pub mod selection;
+pub mod reinsertion;
+pub mod local_search;
+pub mod symmetry;
@@ -1,7 +1,14 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
 use genevo::genetic::{Children, Genotype, Parents};
 use genevo::operator::{CrossoverOp, GeneticOperator};
+use genevo::operator::prelude::{MultiPointCrossBreeder, OrderOneCrossover, PartiallyMappedCrossover, UniformCrossBreeder};
 use genevo::prelude::Rng;
+use genevo::recombination::discrete::{DiscreteCrossover, MultiPointCrossover};
+use crate::ga::encoding::delay_encoding::DelayGenotype;
+use crate::ga::encoding::hybrid_encoding::HybridGenotype;
+use crate::ga::encoding::priority_encoding::PriorityGenotype;
+use crate::ga::mutation::SelfAdaptiveGenomeMutation;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct NoCrossoverOperator {}
@@ -114,10 +121,262 @@ impl SimulatedBinaryCrossover for Vec<u32> {
     }
 }
 
+/// The crossover operators for delay genotypes that can be set in the configuration
+/// SimulatedBinary: SBX, the crossover probability is applied per gene
+/// MultiPoint: genevo multi point crossover, the crossover probability is applied per pair of parents
+/// Uniform: genevo uniform crossover, the crossover probability is applied per pair of parents
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum DelayCrossoverType {
+    SimulatedBinary,
+    MultiPoint {
+        num_cut_points: usize,
+    },
+    Uniform,
+    NoCrossover,
+}
+
+/// Crossover operator for delay genotypes as set in the configuration
+/// self_adaptive: The second half of the genotypes are the standard deviations of SelfAdaptive mutation, every child keeps those of its own parent
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct DelayCrossBreeder {
+    crossover_type: DelayCrossoverType,
+    crossover_probability: f32,
+    min_value: u32,
+    max_value: u32,
+    self_adaptive: bool,
+}
+
+impl DelayCrossBreeder {
+    pub fn new(crossover_type: DelayCrossoverType, crossover_probability: f32, min_value: u32, max_value: u32) -> Self {
+        Self { crossover_type, crossover_probability, min_value, max_value, self_adaptive: false }
+    }
+
+    pub fn with_self_adaptive(mut self, self_adaptive: bool) -> Self {
+        self.self_adaptive = self_adaptive;
+        self
+    }
+
+    fn crossover_genes<G, R>(&self, parents: Parents<G>, rng: &mut R) -> Children<G>
+        where
+            G: Genotype + SimulatedBinaryCrossover + MultiPointCrossover + DiscreteCrossover,
+            R: Rng + Sized,
+    {
+        match &self.crossover_type {
+            DelayCrossoverType::SimulatedBinary => SimulatedBinaryCrossBreeder::new(self.crossover_probability, self.min_value, self.max_value)
+                .crossover(parents, rng),
+            DelayCrossoverType::MultiPoint { num_cut_points } if rng.gen::<f32>() < self.crossover_probability =>
+                MultiPointCrossBreeder::new(*num_cut_points).crossover(parents, rng),
+            DelayCrossoverType::Uniform if rng.gen::<f32>() < self.crossover_probability =>
+                UniformCrossBreeder::new().crossover(parents, rng),
+            _ => parents,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn set_crossover_probability(&mut self, value: f32) {
+        self.crossover_probability = value;
+    }
+}
+
+impl GeneticOperator for DelayCrossBreeder {
+    fn name() -> String {
+        "Delay-Cross-Breeder".to_string()
+    }
+}
+
+impl<G> CrossoverOp<G> for DelayCrossBreeder
+    where
+        G: Genotype + SimulatedBinaryCrossover + MultiPointCrossover + DiscreteCrossover + SelfAdaptiveGenomeMutation,
+{
+    fn crossover<R>(&self, parents: Parents<G>, rng: &mut R) -> Children<G>
+        where
+            R: Rng + Sized,
+    {
+        if !self.self_adaptive {
+            return self.crossover_genes(parents, rng);
+        }
+        let (genes, standard_deviations): (Vec<G>, Vec<G>) = parents.into_iter().map(G::split_standard_deviations).unzip();
+        self.crossover_genes(genes, rng).into_iter()
+            .zip(standard_deviations.into_iter().cycle())
+            .map(|(child, standard_deviations)| G::join_standard_deviations(child, standard_deviations))
+            .collect()
+    }
+}
+
+/// The crossover operators for priority genotypes that can be set in the configuration
+/// The crossover probability is applied per pair of parents
+/// PartiallyMapped: genevo PMX, keeps absolute positions of a segment
+/// OrderOne: genevo OX1, keeps a segment and the relative order of the remaining genes
+/// Cycle: every gene keeps the position it has in one of the parents
+/// EdgeRecombination: keeps the adjacencies of genes in the parents
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum PermutationCrossoverType {
+    PartiallyMapped,
+    OrderOne,
+    Cycle,
+    EdgeRecombination,
+    NoCrossover,
+}
+
+/// Crossover operator for priority genotypes as set in the configuration
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct PermutationCrossBreeder {
+    crossover_type: PermutationCrossoverType,
+    crossover_probability: f32,
+}
+
+impl PermutationCrossBreeder {
+    pub fn new(crossover_type: PermutationCrossoverType, crossover_probability: f32) -> Self {
+        Self { crossover_type, crossover_probability }
+    }
+}
+
+impl GeneticOperator for PermutationCrossBreeder {
+    fn name() -> String {
+        "Permutation-Cross-Breeder".to_string()
+    }
+}
+
+impl CrossoverOp<PriorityGenotype> for PermutationCrossBreeder {
+    fn crossover<R>(&self, parents: Parents<PriorityGenotype>, rng: &mut R) -> Children<PriorityGenotype>
+        where
+            R: Rng + Sized,
+    {
+        if rng.gen::<f32>() >= self.crossover_probability {
+            return parents;
+        }
+        match &self.crossover_type {
+            PermutationCrossoverType::PartiallyMapped => PartiallyMappedCrossover::new().crossover(parents, rng),
+            PermutationCrossoverType::OrderOne => OrderOneCrossover::new().crossover(parents, rng),
+            PermutationCrossoverType::Cycle => {
+                let (child_1, child_2) = cycle_crossover(&parents[0], &parents[1]);
+                vec![child_1, child_2]
+            }
+            PermutationCrossoverType::EdgeRecombination => vec![
+                edge_recombination(&parents[0], &parents[1], rng),
+                edge_recombination(&parents[1], &parents[0], rng),
+            ],
+            PermutationCrossoverType::NoCrossover => parents,
+        }
+    }
+}
+
+/// Crossover operator for hybrid genotypes, the delays and priorities of the parents are recombined separately
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct HybridCrossBreeder {
+    delay_crossover: DelayCrossBreeder,
+    priority_crossover: PermutationCrossBreeder,
+}
+
+impl HybridCrossBreeder {
+    pub fn new(delay_crossover: DelayCrossBreeder, priority_crossover: PermutationCrossBreeder) -> Self {
+        Self { delay_crossover, priority_crossover }
+    }
+}
+
+impl GeneticOperator for HybridCrossBreeder {
+    fn name() -> String {
+        "Hybrid-Cross-Breeder".to_string()
+    }
+}
+
+impl CrossoverOp<HybridGenotype> for HybridCrossBreeder {
+    fn crossover<R>(&self, parents: Parents<HybridGenotype>, rng: &mut R) -> Children<HybridGenotype>
+        where
+            R: Rng + Sized,
+    {
+        let (delay_parents, priority_parents): (Vec<DelayGenotype>, Vec<PriorityGenotype>) = parents.into_iter()
+            .map(|parent| (parent.delays, parent.priorities))
+            .unzip();
+        let delay_children = self.delay_crossover.crossover(delay_parents, rng);
+        let priority_children = self.priority_crossover.crossover(priority_parents, rng);
+        delay_children.into_iter()
+            .zip(priority_children)
+            .map(|(delays, priorities)| HybridGenotype::new(delays, priorities))
+            .collect()
+    }
+}
+
+/// Cycle crossover (CX), the positions are divided into cycles between the parents
+/// Child 1 takes the first, third, ... cycle from parent 1 and the other cycles from parent 2, child 2 the other way around
+pub fn cycle_crossover(parent_1: &PriorityGenotype, parent_2: &PriorityGenotype) -> (PriorityGenotype, PriorityGenotype) {
+    let genome_length = parent_1.len();
+    let mut position_1 = vec![0; genome_length];
+    for (i, gene) in parent_1.iter().enumerate() {
+        position_1[*gene] = i;
+    }
+    let mut child_1 = parent_2.clone();
+    let mut child_2 = parent_1.clone();
+    let mut visited = vec![false; genome_length];
+    let mut cycle = 0;
+    for start in 0..genome_length {
+        if visited[start] {
+            continue;
+        }
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            if cycle % 2 == 0 {
+                child_1[i] = parent_1[i];
+                child_2[i] = parent_2[i];
+            }
+            i = position_1[parent_2[i]];
+        }
+        cycle += 1;
+    }
+    (child_1, child_2)
+}
+
+/// Edge recombination (ERX), builds a child from the union of the adjacencies in both parents
+/// The child starts with the first gene of the first parent and moves to the neighbour with the fewest remaining neighbours
+pub fn edge_recombination<R>(parent_1: &PriorityGenotype, parent_2: &PriorityGenotype, rng: &mut R) -> PriorityGenotype
+    where
+        R: Rng + Sized,
+{
+    let genome_length = parent_1.len();
+    let mut adjacency: Vec<HashSet<usize>> = vec![HashSet::new(); genome_length];
+    for parent in [parent_1, parent_2] {
+        for window in parent.windows(2) {
+            adjacency[window[0]].insert(window[1]);
+            adjacency[window[1]].insert(window[0]);
+        }
+    }
+    let mut child = Vec::with_capacity(genome_length);
+    let mut remaining: Vec<bool> = vec![true; genome_length];
+    let mut current = match parent_1.first() {
+        Some(gene) => *gene,
+        None => return child,
+    };
+    loop {
+        child.push(current);
+        remaining[current] = false;
+        for neighbour in adjacency[current].clone() {
+            adjacency[neighbour].remove(&current);
+        }
+        if child.len() == genome_length {
+            return child;
+        }
+        let candidates: Vec<usize> = adjacency[current].iter().cloned().collect();
+        current = match candidates.iter().map(|gene| adjacency[*gene].len()).min() {
+            Some(fewest) => {
+                let ties: Vec<usize> = candidates.into_iter().filter(|gene| adjacency[*gene].len() == fewest).collect();
+                ties[rng.gen_range(0..ties.len())]
+            }
+            None => {
+                let unvisited: Vec<usize> = (0..genome_length).filter(|gene| remaining[*gene]).collect();
+                unvisited[rng.gen_range(0..unvisited.len())]
+            }
+        };
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
     use rand::{thread_rng};
-    use crate::ga::crossover::SimulatedBinaryCrossover;
+    use genevo::operator::CrossoverOp;
+    use crate::ga::crossover::{cycle_crossover, DelayCrossBreeder, DelayCrossoverType, edge_recombination, HybridCrossBreeder, PermutationCrossBreeder, PermutationCrossoverType, SimulatedBinaryCrossover};
+    use crate::ga::encoding::hybrid_encoding::HybridGenotype;
 
     #[test]
     fn test_sbx() {
@@ -135,4 +394,57 @@ mod tests {
         }
         dbg!(children);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_self_adaptive_crossover_keeps_standard_deviations() {
+        let p1 = vec![10, 50, 40, 30, 20, 1, 2, 3, 4, 5];
+        let p2 = vec![30, 20, 10, 40, 50, 6, 7, 8, 9, 10];
+        let crossover_types = [DelayCrossoverType::SimulatedBinary, DelayCrossoverType::MultiPoint { num_cut_points: 2 }, DelayCrossoverType::Uniform];
+        for crossover_type in crossover_types {
+            let crossover = DelayCrossBreeder::new(crossover_type, 1.0, 0, 60).with_self_adaptive(true);
+            let children = crossover.crossover(vec![p1.clone(), p2.clone()], &mut thread_rng());
+            assert_eq!(children[0][5..], p1[5..]);
+            assert_eq!(children[1][5..], p2[5..]);
+            assert!(children.iter().all(|child| child[..5].iter().all(|delay| *delay <= 60)));
+        }
+    }
+
+    #[test]
+    fn test_cycle_crossover() {
+        let p1 = vec![0, 1, 2, 3, 4, 5, 6, 7];
+        let p2 = vec![1, 2, 0, 7, 6, 5, 4, 3];
+        let (child_1, child_2) = cycle_crossover(&p1, &p2);
+        // Cycles: {0, 1, 2}, {3, 7}, {4, 6}, {5}
+        assert_eq!(child_1, vec![0, 1, 2, 7, 4, 5, 6, 3]);
+        assert_eq!(child_2, vec![1, 2, 0, 3, 6, 5, 4, 7]);
+    }
+
+    #[test]
+    fn test_edge_recombination() {
+        let p1 = vec![0, 1, 2, 3, 4, 5, 6, 7];
+        let p2 = vec![3, 7, 1, 0, 6, 2, 5, 4];
+        let mut rng = thread_rng();
+        let child = edge_recombination(&p1, &p2, &mut rng);
+        assert_eq!(child[0], 0);
+        assert_eq!(child.iter().cloned().sorted().collect_vec(), p1);
+        // Identical parents produce the same permutation
+        assert_eq!(edge_recombination(&p1, &p1, &mut rng), p1);
+    }
+
+    #[test]
+    fn test_hybrid_crossover() {
+        let p1 = HybridGenotype::new(vec![10, 50, 40, 30, 20], vec![0, 1, 2, 3, 4]);
+        let p2 = HybridGenotype::new(vec![30, 20, 10, 40, 50], vec![4, 2, 0, 3, 1]);
+        let crossover = HybridCrossBreeder::new(
+            DelayCrossBreeder::new(DelayCrossoverType::SimulatedBinary, 0.5, 0, 60),
+            PermutationCrossBreeder::new(PermutationCrossoverType::Cycle, 1.0),
+        );
+        let children = crossover.crossover(vec![p1.clone(), p2.clone()], &mut thread_rng());
+        assert_eq!(children.len(), 2);
+        for child in children {
+            assert_eq!(child.delays.len(), p1.delays.len());
+            assert!(child.delays.iter().all(|delay| *delay <= 60));
+            assert_eq!(child.priorities.iter().cloned().sorted().collect_vec(), p1.priorities);
+        }
+    }
+}
@@ -1,16 +1,48 @@
 use std::fmt::Debug;
 use std::hash::Hash;
 use genevo::genetic::{Genotype, Phenotype};
+use genevo::recombination::discrete::{DiscreteCrossover, MultiPointCrossover};
 use crate::ga::crossover::SimulatedBinaryCrossover;
+use crate::ga::encoding::delay_encoding::DelayEncodingType;
 use crate::ga::genetic_algorithm::ConsensusMessageType;
-use crate::ga::mutation::GaussianGenomeMutation;
-use crate::NUM_NODES;
+use crate::ga::mutation::{GaussianGenomeMutation, SelfAdaptiveGenomeMutation};
+use crate::ga::symmetry::Relabel;
+use crate::node_state::ConsensusPhase;
+use crate::topology::PeerGraph;
+use crate::{CONFIG, PEER_GRAPH};
 
 pub mod priority_encoding;
 pub mod delay_encoding;
+pub mod hybrid_encoding;
 
+/// The number of genes with a gene per link of the peer graph and message type
 pub(crate) fn num_genes() -> usize {
-    *NUM_NODES * (*NUM_NODES-1) * ConsensusMessageType::VALUES.len()
+    num_genes_on(&PEER_GRAPH)
+}
+
+/// The number of genes with a gene per link of the given peer graph and message type
+pub(crate) fn num_genes_on(peer_graph: &PeerGraph) -> usize {
+    peer_graph.links().len() * ConsensusMessageType::VALUES.len()
+}
+
+/// The number of genes of a delay genotype in the configured delay encoding
+pub(crate) fn num_delay_genes() -> usize {
+    CONFIG.ga.num_delay_genes()
+}
+
+/// The number of round offsets from the start of a harness run that have their own delays
+pub(crate) fn num_delay_rounds() -> usize {
+    CONFIG.ga.delay_rounds
+}
+
+/// The number of receiver consensus phases that have their own delays in every round
+pub(crate) fn num_delay_phases() -> usize {
+    if CONFIG.ga.phase_aware_delays { ConsensusPhase::VALUES.len() } else { 1 }
+}
+
+/// The configured encoding of delay genotypes
+pub(crate) fn delay_encoding() -> &'static DelayEncodingType {
+    &CONFIG.ga.delay_encoding
 }
 
 pub trait ExtendedPhenotype<G>: Phenotype<G> + Send where G: ExtendedGenotype {
@@ -19,6 +51,6 @@ pub trait ExtendedPhenotype<G>: Phenotype<G> + Send where G: ExtendedGenotype {
     fn display_genotype_by_message(&self) -> String;
 }
 
-pub trait ExtendedGenotype: Genotype + Eq + Hash + Debug + Default {}
+pub trait ExtendedGenotype: Genotype + Eq + Hash + Debug + Default + Relabel {}
 
-pub trait SuperExtendedGenotype: ExtendedGenotype + SimulatedBinaryCrossover + GaussianGenomeMutation {}
\ No newline at end of file
+pub trait SuperExtendedGenotype: ExtendedGenotype + SimulatedBinaryCrossover + MultiPointCrossover + DiscreteCrossover + GaussianGenomeMutation + SelfAdaptiveGenomeMutation {}
\ No newline at end of file
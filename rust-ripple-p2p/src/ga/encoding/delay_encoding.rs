@@ -1,13 +1,18 @@
 use std::collections::HashMap;
 use genevo::genetic::Phenotype;
-use itertools::{chain, Itertools};
-use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype, SuperExtendedGenotype};
+use itertools::Itertools;
+use crate::ga::encoding::{delay_encoding, ExtendedGenotype, ExtendedPhenotype, num_delay_phases, num_delay_rounds, SuperExtendedGenotype};
 use crate::ga::genetic_algorithm::{ConsensusMessageType};
-use crate::NUM_NODES;
+use crate::node_state::ConsensusPhase;
+use crate::topology::PeerGraph;
+use crate::{NUM_NODES, PEER_GRAPH};
 
 #[allow(unused)]
 pub const DROP_THRESHOLD: u32 = 1800;
 
+/// The delay of messages that are dropped instead of delivered, only set by schedule files
+pub const DROP_DELAY: u32 = u32::MAX;
+
 // The genotype
 pub(crate) type DelayGenotype = Vec<u32>;
 
@@ -15,70 +20,305 @@ impl ExtendedGenotype for DelayGenotype {}
 
 impl SuperExtendedGenotype for DelayGenotype {}
 
+/// The delay encodings that can be set in the configuration, every encoding is mapped to the full delay map
+/// Full: A delay per (from, to, message type) of every link in the peer graph, L*13 genes for L links, N*(N-1)*13 if all nodes are connected
+/// PerSender: A delay per (from, message type), N*13 genes
+/// PerReceiver: A delay per (to, message type), N*13 genes
+/// PerMessageType: A delay per message type, 13 genes
+/// SymmetricLinks: A delay per (edge, message type) for both directions of the edge, L/2*13 genes
+/// NodeGroups: A delay per (group of from, group of to, message type), G*G*13 genes for G groups
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum DelayEncodingType {
+    Full,
+    PerSender,
+    PerReceiver,
+    PerMessageType,
+    SymmetricLinks,
+    NodeGroups {
+        groups: Vec<Vec<usize>>,
+    },
+}
+
+impl DelayEncodingType {
+    /// The number of genes encoding the delays of a single round and phase
+    pub fn num_genes(&self, peer_graph: &PeerGraph) -> usize {
+        let num_message_types = ConsensusMessageType::VALUES.len();
+        match self {
+            Self::Full => peer_graph.links().len() * num_message_types,
+            Self::PerSender | Self::PerReceiver => peer_graph.num_nodes() * num_message_types,
+            Self::PerMessageType => num_message_types,
+            Self::SymmetricLinks => peer_graph.edges().len() * num_message_types,
+            Self::NodeGroups { groups } => groups.len() * groups.len() * num_message_types,
+        }
+    }
+
+    /// The index of the gene that holds the delay of a message, relative to the start of the round and phase
+    /// Messages are only sent between nodes connected in the peer graph
+    pub fn gene_index(&self, from: usize, to: usize, message_type_index: usize, peer_graph: &PeerGraph) -> usize {
+        let num_message_types = ConsensusMessageType::VALUES.len();
+        let index = match self {
+            Self::Full => peer_graph.link_index(from, to).expect("Nodes are not connected"),
+            Self::PerSender => from,
+            Self::PerReceiver => to,
+            Self::PerMessageType => 0,
+            Self::SymmetricLinks => peer_graph.edge_index(from, to).expect("Nodes are not connected"),
+            Self::NodeGroups { groups } => {
+                let group_of = |node: usize| groups.iter().position(|group| group.contains(&node)).unwrap();
+                group_of(from) * groups.len() + group_of(to)
+            }
+        };
+        index * num_message_types + message_type_index
+    }
+
+    /// Check that node groups divide the nodes into non-empty groups, returns all problems found
+    pub fn validate(&self, num_nodes: usize) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if let Self::NodeGroups { groups } = self {
+            if groups.iter().any(|group| group.is_empty()) {
+                errors.push("node groups must not be empty".to_string());
+            }
+            let mut nodes = groups.iter().flatten().cloned().collect_vec();
+            nodes.sort_unstable();
+            if nodes != (0..num_nodes).collect_vec() {
+                errors.push(format!("node groups must contain every node in 0..{} exactly once", num_nodes));
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
 // The phenotype from -> to -> message_type -> delay (ms)
 type DelayMap = HashMap<usize, HashMap<usize, HashMap<ConsensusMessageType, u32>>>;
 
-/// Contains the delayMap for easy use in the scheduler and delays as genotype (vec)
+/// Contains a delayMap per round offset and receiver consensus phase for easy use in the scheduler and delays as genotype (vec)
+/// The genotype holds the delays of the configured encoding per phase and the phases per round, round after round
+/// Without phase-aware delays there is a single phase that is used for every consensus phase of the receiver
+/// Messages sent in rounds after the last encoded round use the delays of the last round
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct DelayMapPhenotype {
-    pub delay_map: DelayMap,
+    pub delay_maps: Vec<Vec<DelayMap>>,
     delays: DelayGenotype
 }
 
 impl DelayMapPhenotype {
-    pub fn get_delay(&self, from: &usize, to: &usize, message_type: &ConsensusMessageType) -> u32 {
-        *self.delay_map.get(from).unwrap().get(to).unwrap().get(&message_type).unwrap()
+    /// The delay of a message sent round_offset rounds after the start of the harness run to a receiver in receiver_phase
+    /// Messages are not delayed before the first individual with delays arrives
+    pub fn get_delay(&self, from: &usize, to: &usize, message_type: &ConsensusMessageType, round_offset: u32, receiver_phase: &ConsensusPhase) -> u32 {
+        let round = match self.delay_maps.get(round_offset as usize).or_else(|| self.delay_maps.last()) {
+            Some(round) => round,
+            None => return 0,
+        };
+        let phase = if round.len() > 1 { receiver_phase.index() } else { 0 };
+        *round[phase].get(from).unwrap().get(to).unwrap().get(&message_type).unwrap()
     }
 
-    /// Display delays grouped by message and receiver node
+    /// Display delays of a round and phase grouped by message and receiver node
     #[allow(unused)]
-    pub fn message_type_delays(&self, message_type: &ConsensusMessageType) -> Vec<(usize, Vec<u32>)> {
-        self.delay_map.iter()
+    pub fn message_type_delays(&self, message_type: &ConsensusMessageType, round: usize, phase: usize) -> Vec<(usize, Vec<u32>)> {
+        self.delay_maps[round][phase].iter()
             .map(|(to, from)| (*to, from.values()
                 .map(|x| *x.get(message_type).unwrap())
                 .collect_vec()))
             .collect::<Vec<(usize, Vec<u32>)>>()
     }
-}
 
-impl Phenotype<DelayGenotype> for DelayMapPhenotype {
-    fn genes(&self) -> DelayGenotype {
-        self.delays.clone()
+    /// The layout of num_genes genes in blocks of block_size for the configured rounds and phases per round
+    /// A genotype of a single block is used in every round and phase, any other genotype has to hold every configured round and phase
+    pub fn encoded_layout(num_genes: usize, block_size: usize, rounds: usize, phases: usize) -> Result<(usize, usize), String> {
+        let (rounds, phases) = (rounds.max(1), phases.max(1));
+        if num_genes == block_size * rounds * phases {
+            Ok((rounds, phases))
+        } else if num_genes == block_size {
+            Ok((1, 1))
+        } else {
+            Err(format!(
+                "a delay genotype of {} genes is neither a single block of {} genes nor {} round(s) of {} phase(s)",
+                num_genes, block_size, rounds, phases
+            ))
+        }
     }
 
-    fn derive(&self, new_genes: DelayGenotype) -> Self {
-        DelayMapPhenotype::from_genes(&new_genes)
+    /// The phenotype of genes encoded with the encoding for the configured rounds and receiver phases
+    pub fn from_encoded_genes(genes: &DelayGenotype, encoding: &DelayEncodingType, rounds: usize, phases: usize) -> Result<Self, String> {
+        let block_size = encoding.num_genes(&PEER_GRAPH);
+        let (rounds, phases) = Self::encoded_layout(genes.len(), block_size, rounds, phases)?;
+        let delay_maps = (0..rounds)
+            .map(|round| (0..phases)
+                .map(|phase| Self::delay_map(genes, (round * phases + phase) * block_size, encoding))
+                .collect())
+            .collect();
+        Ok(Self {
+            delay_maps,
+            delays: genes.clone()
+        })
     }
-}
 
-impl ExtendedPhenotype<DelayGenotype> for DelayMapPhenotype {
-    fn from_genes(genes: &DelayGenotype) -> Self {
-        let index_factor_1 = ConsensusMessageType::VALUES.len() * (*NUM_NODES-1);
-        let index_factor_2 = ConsensusMessageType::VALUES.len();
+    fn delay_map(genes: &DelayGenotype, offset: usize, encoding: &DelayEncodingType) -> DelayMap {
         let mut from_node = HashMap::new();
         for i in 0..*NUM_NODES {
             let mut to_node = HashMap::new();
-            for (j, node) in chain(0..i, i+1..*NUM_NODES).enumerate() {
+            for &node in PEER_GRAPH.neighbours(i) {
                 let mut message_type = HashMap::new();
                 for (k, message) in ConsensusMessageType::VALUES.iter().enumerate() {
-                    message_type.insert(*message, genes[index_factor_1 * i + index_factor_2 * j + k]);
+                    message_type.insert(*message, genes[offset + encoding.gene_index(i, node, k, &PEER_GRAPH)]);
                 }
                 to_node.insert(node, message_type.clone());
             }
             from_node.insert(i, to_node.clone());
         }
-        Self {
-            delay_map: from_node,
-            delays: genes.clone()
-        }
+        from_node
+    }
+}
+
+impl Phenotype<DelayGenotype> for DelayMapPhenotype {
+    fn genes(&self) -> DelayGenotype {
+        self.delays.clone()
+    }
+
+    fn derive(&self, new_genes: DelayGenotype) -> Self {
+        DelayMapPhenotype::from_genes(&new_genes)
+    }
+}
+
+impl ExtendedPhenotype<DelayGenotype> for DelayMapPhenotype {
+    fn from_genes(genes: &DelayGenotype) -> Self {
+        Self::from_encoded_genes(genes, delay_encoding(), num_delay_rounds(), num_delay_phases())
+            .unwrap_or_else(|err| panic!("Invalid delay genotype: {}", err))
     }
 
     #[allow(unused)]
     fn display_genotype_by_message(&self) -> String {
         let mut res = String::new();
-        for message_type in ConsensusMessageType::VALUES {
-            res += format!("{:?}: {:?}\n", message_type, self.message_type_delays(&message_type)).as_str();
+        for (round, phases) in self.delay_maps.iter().enumerate() {
+            for phase in 0..phases.len() {
+                if self.delay_maps.len() > 1 {
+                    res += format!("Round {}:\n", round).as_str();
+                }
+                if phases.len() > 1 {
+                    res += format!("Receiver in {:?}:\n", ConsensusPhase::VALUES[phase]).as_str();
+                }
+                for message_type in ConsensusMessageType::VALUES {
+                    res += format!("{:?}: {:?}\n", message_type, self.message_type_delays(&message_type, round, phase)).as_str();
+                }
+            }
         }
         res
     }
 }
+
+#[cfg(test)]
+mod delay_encoding_tests {
+    use itertools::Itertools;
+    use crate::ga::encoding::delay_encoding::{DelayEncodingType, DelayMapPhenotype};
+    use crate::ga::encoding::{ExtendedPhenotype, num_genes};
+    use crate::ga::genetic_algorithm::ConsensusMessageType;
+    use crate::node_state::ConsensusPhase;
+    use crate::topology::{PeerGraph, PeerTopology};
+
+    #[test]
+    fn test_single_round_delays_apply_to_every_round() {
+        let delays = (0..num_genes() as u32).collect::<Vec<u32>>();
+        let phenotype = DelayMapPhenotype::from_genes(&delays);
+        assert_eq!(phenotype.delay_maps.len(), 1);
+        assert_eq!(phenotype.delay_maps[0].len(), 1);
+        let message_type = ConsensusMessageType::TMValidation;
+        let delay = phenotype.get_delay(&1, &0, &message_type, 0, &ConsensusPhase::Open);
+        assert_eq!(delay, phenotype.get_delay(&1, &0, &message_type, 5, &ConsensusPhase::Open));
+        assert_eq!(delay, phenotype.get_delay(&1, &0, &message_type, 0, &ConsensusPhase::Establish));
+    }
+
+    #[test]
+    fn test_default_phenotype_does_not_delay() {
+        let phenotype = DelayMapPhenotype::default();
+        assert_eq!(phenotype.get_delay(&0, &1, &ConsensusMessageType::TMValidation, 3, &ConsensusPhase::Open), 0);
+    }
+
+    #[test]
+    fn test_encoded_layout() {
+        assert_eq!(DelayMapPhenotype::encoded_layout(10, 10, 1, 1), Ok((1, 1)));
+        assert_eq!(DelayMapPhenotype::encoded_layout(30, 10, 3, 1), Ok((3, 1)));
+        assert_eq!(DelayMapPhenotype::encoded_layout(80, 10, 2, 4), Ok((2, 4)));
+        assert_eq!(DelayMapPhenotype::encoded_layout(40, 10, 1, 4), Ok((1, 4)));
+        // A single block is used for every round and phase
+        assert_eq!(DelayMapPhenotype::encoded_layout(10, 10, 3, 4), Ok((1, 1)));
+        assert_eq!(DelayMapPhenotype::encoded_layout(10, 10, 0, 0), Ok((1, 1)));
+        // The length alone does not tell rounds and phases apart
+        assert!(DelayMapPhenotype::encoded_layout(30, 10, 3, 4).is_err());
+        assert!(DelayMapPhenotype::encoded_layout(40, 10, 4, 1).is_ok());
+        assert!(DelayMapPhenotype::encoded_layout(40, 10, 3, 4).is_err());
+        assert!(DelayMapPhenotype::encoded_layout(50, 10, 3, 1).is_err());
+    }
+
+    #[test]
+    fn test_delays_of_round_offset() {
+        let delays = [vec![100; num_genes()], vec![200; num_genes()]].concat();
+        assert!(DelayMapPhenotype::from_encoded_genes(&delays, &DelayEncodingType::Full, 3, 1).is_err());
+        let phenotype = DelayMapPhenotype::from_encoded_genes(&delays, &DelayEncodingType::Full, 2, 1).unwrap();
+        assert_eq!(phenotype.delay_maps.len(), 2);
+        let message_type = ConsensusMessageType::TMProposeSet0;
+        assert_eq!(phenotype.get_delay(&0, &1, &message_type, 0, &ConsensusPhase::Open), 100);
+        assert_eq!(phenotype.get_delay(&0, &1, &message_type, 1, &ConsensusPhase::Open), 200);
+        // Rounds after the last encoded round use its delays
+        assert_eq!(phenotype.get_delay(&0, &1, &message_type, 7, &ConsensusPhase::Accepted), 200);
+        // A single block is broadcast to every configured round and phase
+        let single = DelayMapPhenotype::from_encoded_genes(&vec![300; num_genes()], &DelayEncodingType::Full, 2, ConsensusPhase::VALUES.len()).unwrap();
+        assert_eq!(single.get_delay(&0, &1, &message_type, 1, &ConsensusPhase::Establish), 300);
+    }
+
+    #[test]
+    fn test_delays_of_receiver_phase() {
+        let num_phases = ConsensusPhase::VALUES.len();
+        let delays = (0..num_phases).flat_map(|phase| vec![phase as u32 * 100; num_genes()]).collect_vec();
+        let phenotype = DelayMapPhenotype::from_encoded_genes(&delays, &DelayEncodingType::Full, 1, num_phases).unwrap();
+        assert_eq!(phenotype.delay_maps[0].len(), num_phases);
+        for (phase, receiver_phase) in ConsensusPhase::VALUES.iter().enumerate() {
+            assert_eq!(phenotype.get_delay(&2, &0, &ConsensusMessageType::TMValidation, 0, receiver_phase), phase as u32 * 100);
+        }
+    }
+
+    #[test]
+    fn test_reduced_encodings_cover_all_genes() {
+        let num_nodes = 4;
+        let peer_graph = PeerGraph::complete(num_nodes);
+        let encodings = [
+            DelayEncodingType::Full,
+            DelayEncodingType::PerSender,
+            DelayEncodingType::PerReceiver,
+            DelayEncodingType::PerMessageType,
+            DelayEncodingType::SymmetricLinks,
+            DelayEncodingType::NodeGroups { groups: vec![vec![0, 1], vec![2, 3]] },
+        ];
+        for encoding in encodings {
+            let indices = (0..num_nodes).cartesian_product(0..num_nodes)
+                .filter(|(from, to)| from != to)
+                .cartesian_product(0..ConsensusMessageType::VALUES.len())
+                .map(|((from, to), k)| encoding.gene_index(from, to, k, &peer_graph))
+                .unique()
+                .sorted()
+                .collect_vec();
+            // Every gene of the encoding is used for at least one message and no index is out of bounds
+            assert_eq!(indices, (0..encoding.num_genes(&peer_graph)).collect_vec(), "{:?}", encoding);
+        }
+        let symmetric = DelayEncodingType::SymmetricLinks;
+        assert_eq!(symmetric.gene_index(1, 3, 2, &peer_graph), symmetric.gene_index(3, 1, 2, &peer_graph));
+        let groups = DelayEncodingType::NodeGroups { groups: vec![vec![0, 1], vec![2, 3]] };
+        assert_eq!(groups.gene_index(0, 2, 0, &peer_graph), groups.gene_index(1, 3, 0, &peer_graph));
+        assert!(groups.validate(num_nodes).is_ok());
+        assert!(DelayEncodingType::NodeGroups { groups: vec![vec![0, 1], vec![1, 2]] }.validate(num_nodes).is_err());
+    }
+
+    #[test]
+    fn test_encodings_of_peer_graph_cover_all_genes() {
+        let ring = PeerTopology::Ring.peer_graph(5).unwrap();
+        for encoding in [DelayEncodingType::Full, DelayEncodingType::SymmetricLinks] {
+            let indices = ring.links().iter()
+                .cartesian_product(0..ConsensusMessageType::VALUES.len())
+                .map(|((from, to), k)| encoding.gene_index(*from, *to, k, &ring))
+                .unique()
+                .sorted()
+                .collect_vec();
+            assert_eq!(indices, (0..encoding.num_genes(&ring)).collect_vec(), "{:?}", encoding);
+        }
+        assert_eq!(DelayEncodingType::Full.num_genes(&ring), 10 * ConsensusMessageType::VALUES.len());
+    }
+}
@@ -0,0 +1,91 @@
+use genevo::genetic::{Genotype, Phenotype};
+use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype};
+use crate::ga::encoding::delay_encoding::{DelayGenotype, DelayMapPhenotype};
+use crate::ga::encoding::priority_encoding::{PriorityGenotype, PriorityMapPhenotype};
+use crate::ga::genetic_algorithm::ConsensusMessageType;
+use crate::node_state::ConsensusPhase;
+
+/// The genotype, every (from, to, message type) gene has a delay and a priority at the same index
+/// delays: value encoded, priorities: a permutation of 0..num_genes
+#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize)]
+pub struct HybridGenotype {
+    pub delays: DelayGenotype,
+    pub priorities: PriorityGenotype,
+}
+
+impl HybridGenotype {
+    pub fn new(delays: DelayGenotype, priorities: PriorityGenotype) -> Self {
+        Self { delays, priorities }
+    }
+}
+
+impl Genotype for HybridGenotype {
+    type Dna = (u32, usize);
+}
+
+impl ExtendedGenotype for HybridGenotype {}
+
+/// Contains the delay map and priority map for easy use in the scheduler
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HybridMapPhenotype {
+    delay_map: DelayMapPhenotype,
+    priority_map: PriorityMapPhenotype,
+}
+
+impl HybridMapPhenotype {
+    pub fn get_delay(&self, from: &usize, to: &usize, message_type: &ConsensusMessageType, round_offset: u32, receiver_phase: &ConsensusPhase) -> u32 {
+        self.delay_map.get_delay(from, to, message_type, round_offset, receiver_phase)
+    }
+
+    pub fn get_priority(&self, from: &usize, to: &usize, message_type: &ConsensusMessageType) -> usize {
+        self.priority_map.get_priority(from, to, message_type)
+    }
+
+    pub fn delays(&self) -> DelayGenotype {
+        self.delay_map.genes()
+    }
+}
+
+impl Phenotype<HybridGenotype> for HybridMapPhenotype {
+    fn genes(&self) -> HybridGenotype {
+        HybridGenotype::new(self.delay_map.genes(), self.priority_map.genes())
+    }
+
+    fn derive(&self, new_genes: HybridGenotype) -> Self {
+        HybridMapPhenotype::from_genes(&new_genes)
+    }
+}
+
+impl ExtendedPhenotype<HybridGenotype> for HybridMapPhenotype {
+    fn from_genes(genes: &HybridGenotype) -> Self {
+        Self {
+            delay_map: DelayMapPhenotype::from_genes(&genes.delays),
+            priority_map: PriorityMapPhenotype::from_genes(&genes.priorities),
+        }
+    }
+
+    fn display_genotype_by_message(&self) -> String {
+        format!("Delays:\n{}Priorities:\n{}", self.delay_map.display_genotype_by_message(), self.priority_map.display_genotype_by_message())
+    }
+}
+
+#[cfg(test)]
+mod hybrid_encoding_tests {
+    use genevo::genetic::Phenotype;
+    use crate::ga::encoding::{ExtendedPhenotype, num_genes};
+    use crate::ga::encoding::hybrid_encoding::{HybridGenotype, HybridMapPhenotype};
+    use crate::ga::genetic_algorithm::ConsensusMessageType;
+    use crate::node_state::ConsensusPhase;
+
+    #[test]
+    fn test_hybrid_phenotype() {
+        let delays = (0..num_genes() as u32).collect::<Vec<u32>>();
+        let priorities = (0..num_genes()).rev().collect::<Vec<usize>>();
+        let genotype = HybridGenotype::new(delays, priorities);
+        let phenotype = HybridMapPhenotype::from_genes(&genotype);
+        assert_eq!(phenotype.genes(), genotype);
+        // The first gene is the first message type from node 0 to node 1
+        assert_eq!(phenotype.get_delay(&0, &1, &ConsensusMessageType::VALUES[0], 0, &ConsensusPhase::Open), 0);
+        assert_eq!(phenotype.get_priority(&0, &1, &ConsensusMessageType::VALUES[0]), num_genes() - 1);
+    }
+}
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use genevo::genetic::Phenotype;
-use itertools::{chain, Itertools};
+use itertools::Itertools;
+use crate::ga::encoding::delay_encoding::DelayEncodingType;
 use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype};
 use crate::ga::genetic_algorithm::{ConsensusMessageType};
-use crate::NUM_NODES;
+use crate::{NUM_NODES, PEER_GRAPH};
 
 // The phenotype from -> to -> message_type -> priority
 type PriorityMap = HashMap<usize, HashMap<usize, HashMap<ConsensusMessageType, usize>>>;
@@ -50,15 +51,13 @@ impl Phenotype<PriorityGenotype> for PriorityMapPhenotype {
 
 impl ExtendedPhenotype<PriorityGenotype> for PriorityMapPhenotype {
     fn from_genes(genes: &PriorityGenotype) -> Self {
-        let index_factor_1 = ConsensusMessageType::VALUES.len() * (*NUM_NODES-1);
-        let index_factor_2 = ConsensusMessageType::VALUES.len();
         let mut from_node = HashMap::new();
         for i in 0..*NUM_NODES {
             let mut to_node = HashMap::new();
-            for (j, node) in chain(0..i, i+1..*NUM_NODES).enumerate() {
+            for &node in PEER_GRAPH.neighbours(i) {
                 let mut message_type = HashMap::new();
                 for (k, message) in ConsensusMessageType::VALUES.iter().enumerate() {
-                    message_type.insert(*message, genes[index_factor_1 * i + index_factor_2 * j + k]);
+                    message_type.insert(*message, genes[DelayEncodingType::Full.gene_index(i, node, k, &PEER_GRAPH)]);
                 }
                 to_node.insert(node, message_type.clone());
             }
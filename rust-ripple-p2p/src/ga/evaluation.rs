@@ -0,0 +1,146 @@
+use genevo::genetic::AsScalar;
+use crate::ga::fitness::ExtendedFitness;
+
+/// Policy for evaluating individuals under noise.
+/// The same schedule does not always result in the same fitness, so an individual can be run several times.
+/// repetitions: The number of harness runs for a new individual
+/// reevaluate_elites: Run an individual that survives into a new generation once more
+/// max_samples: Elites are not re-tested once they have this many samples
+/// confidence_z: If set, selection uses the lower confidence bound (mean - z * standard error) instead of the mean
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReevaluationPolicy {
+    pub repetitions: usize,
+    pub reevaluate_elites: bool,
+    pub max_samples: usize,
+    pub confidence_z: Option<f64>,
+}
+
+impl Default for ReevaluationPolicy {
+    fn default() -> Self {
+        Self {
+            repetitions: 1,
+            reevaluate_elites: false,
+            max_samples: 10,
+            confidence_z: None,
+        }
+    }
+}
+
+/// All fitness samples of one individual.
+/// pending: The number of requested harness runs for which no fitness has been received yet
+/// last_generation: The latest generation in which the individual was evaluated
+#[derive(Clone, Debug)]
+pub struct FitnessSamples<T: ExtendedFitness> {
+    pub samples: Vec<T>,
+    pub(crate) pending: usize,
+    pub(crate) last_generation: u64,
+}
+
+impl<T: ExtendedFitness> FitnessSamples<T> {
+    pub fn new() -> Self {
+        Self { samples: vec![], pending: 0, last_generation: 0 }
+    }
+
+    pub fn from_sample(sample: T) -> Self {
+        Self { samples: vec![sample], pending: 0, last_generation: 0 }
+    }
+
+    pub fn add_sample(&mut self, sample: T) {
+        self.samples.push(sample);
+        self.pending = self.pending.saturating_sub(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// All requested evaluations have finished
+    pub fn is_evaluated(&self) -> bool {
+        self.pending == 0 && !self.samples.is_empty()
+    }
+
+    /// The fitness that is reported to the GA, the average of all samples
+    pub fn fitness(&self) -> T {
+        if self.samples.is_empty() {
+            return T::zero();
+        }
+        T::average(&self.samples)
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().map(|x| x.as_scalar()).sum::<f64>() / self.samples.len() as f64
+    }
+
+    /// Unbiased sample variance of the scalar fitness values, zero for less than two samples
+    pub fn variance(&self) -> f64 {
+        let n = self.samples.len();
+        if n < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        self.samples.iter().map(|x| (x.as_scalar() - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+    }
+
+    pub fn standard_error(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        (self.variance() / self.samples.len() as f64).sqrt()
+    }
+
+    /// Pessimistic estimate of the fitness: mean - z * standard error
+    pub fn lower_confidence_bound(&self, z: f64) -> f64 {
+        self.mean() - z * self.standard_error()
+    }
+
+    pub fn summary(&self) -> FitnessSummary<T> {
+        FitnessSummary {
+            mean: self.mean(),
+            variance: self.variance(),
+            samples: self.samples.clone(),
+        }
+    }
+}
+
+/// Mean, variance and samples of an individual for writing to the GA log
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct FitnessSummary<T: ExtendedFitness> {
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: Vec<T>,
+}
+
+#[cfg(test)]
+mod evaluation_tests {
+    use crate::ga::evaluation::FitnessSamples;
+    use crate::ga::fitness::propose_seq_fitness::ProposeSeqFitness;
+
+    #[test]
+    fn test_fitness_samples_statistics() {
+        let mut samples = FitnessSamples::new();
+        samples.pending = 4;
+        for value in [2, 4, 4, 6] {
+            samples.add_sample(ProposeSeqFitness::new(value));
+        }
+        assert!(samples.is_evaluated());
+        assert_eq!(samples.fitness(), ProposeSeqFitness::new(4));
+        assert_eq!(samples.mean(), 4.0);
+        assert_eq!(samples.variance(), 8.0 / 3.0);
+        assert_eq!(samples.standard_error(), (2.0f64 / 3.0).sqrt());
+        assert_eq!(samples.lower_confidence_bound(0.0), 4.0);
+        assert!(samples.lower_confidence_bound(1.96) < 4.0);
+    }
+
+    #[test]
+    fn test_single_sample() {
+        let samples = FitnessSamples::from_sample(ProposeSeqFitness::new(7));
+        assert!(samples.is_evaluated());
+        assert_eq!(samples.variance(), 0.0);
+        assert_eq!(samples.lower_confidence_bound(1.96), 7.0);
+        assert!(!FitnessSamples::<ProposeSeqFitness>::new().is_evaluated());
+    }
+}
@@ -10,13 +10,16 @@ pub(crate) mod propose_seq_fitness;
 
 use std::collections::HashMap;
 use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
 use genevo::genetic::{AsScalar, Fitness, FitnessFunction, Genotype};
 use std::fmt::{Display};
 use std::time::{Duration as TimeDuration};
-use log::debug;
+use log::{debug, error};
 use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype};
+use crate::ga::evaluation::{FitnessSamples, ReevaluationPolicy};
+use crate::ga::symmetry::NodeSymmetry;
 use crate::node_state::MutexNodeStates;
 use crate::test_harness::TestHarness;
 
@@ -30,31 +33,101 @@ pub trait ExtendedFitness: Fitness + AsScalar + Clone + Send + Sync + Display +
     fn run_harness(test_harness: &mut TestHarness<'static>, node_states: Arc<MutexNodeStates>) -> Self;
 }
 
+/// Shared cache of all fitness samples, keyed by genotype
+pub type FitnessCache<G, T> = Arc<RwLock<HashMap<G, FitnessSamples<T>>>>;
+
 /// Fitness function communicates with scheduler handler for calculating and storing fitness of solutions.
+/// Every requested harness run adds a sample to the cache, the reported fitness is the average of the samples.
 #[derive(Clone, Debug)]
 pub struct FitnessCalculation<T, G> where T: ExtendedFitness + Clone, G: Genotype {
-    pub(crate) fitness_values: Arc<RwLock<HashMap<G, T>>>,
+    pub(crate) fitness_values: FitnessCache<G, T>,
     pub(crate) sender: Sender<G>,
+    pub(crate) policy: ReevaluationPolicy,
+    pub(crate) generation: Arc<AtomicU64>,
+    /// If set, genotypes that only differ in the numbering of the nodes share a cache entry
+    pub(crate) symmetry: Option<Arc<NodeSymmetry>>,
+}
+
+impl<T, G> FitnessCalculation<T, G>
+    where T: ExtendedFitness, G: ExtendedGenotype
+{
+    pub fn new(fitness_values: FitnessCache<G, T>, sender: Sender<G>, policy: ReevaluationPolicy) -> Self {
+        Self { fitness_values, sender, policy, generation: Arc::new(AtomicU64::new(0)), symmetry: None }
+    }
+
+    pub fn with_symmetry(mut self, symmetry: Option<NodeSymmetry>) -> Self {
+        self.symmetry = symmetry.map(Arc::new);
+        self
+    }
+
+    /// The cache key of a genotype, the canonical relabelling if the network is symmetric
+    pub fn cache_key(&self, genotype: &G) -> G {
+        match &self.symmetry {
+            Some(symmetry) => symmetry.canonical(genotype),
+            None => genotype.clone(),
+        }
+    }
+
+    /// Mark the start of a new generation, surviving elites are re-tested if the policy says so
+    pub fn next_generation(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn samples_of(&self, genotype: &G) -> Option<FitnessSamples<T>> {
+        self.fitness_values.read().unwrap().get(&self.cache_key(genotype)).cloned()
+    }
+
+    pub fn lower_confidence_bound(&self, genotype: &G, z: f64) -> Option<f64> {
+        match self.fitness_values.read().unwrap().get(&self.cache_key(genotype)) {
+            Some(samples) if !samples.samples.is_empty() => Some(samples.lower_confidence_bound(z)),
+            _ => None,
+        }
+    }
+
+    /// Determine how many harness runs are needed for this genotype and mark them as pending
+    fn request_evaluations(&self, genotype: &G) -> usize {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let mut fitness_values = self.fitness_values.write().unwrap();
+        let samples = fitness_values.entry(genotype.clone()).or_insert_with(FitnessSamples::new);
+        let requests = if samples.samples.is_empty() && samples.pending == 0 {
+            self.policy.repetitions.max(1)
+        } else if self.policy.reevaluate_elites
+            && samples.last_generation < generation
+            && samples.samples.len() + samples.pending < self.policy.max_samples {
+            1
+        } else {
+            0
+        };
+        samples.pending += requests;
+        samples.last_generation = generation;
+        requests
+    }
 }
 
 impl<T, G> FitnessFunction<G, T> for FitnessCalculation<T, G>
     where T: ExtendedFitness, G: ExtendedGenotype
 {
-    fn fitness_of(&self, delays_genotype: &G) -> T {
-        let mut sent_to_handler = false;
+    fn fitness_of(&self, genotype: &G) -> T {
+        // Equivalent genotypes are evaluated and stored under the same key
+        let delays_genotype = &self.cache_key(genotype);
+        let requests = self.request_evaluations(delays_genotype);
+        if requests > 0 {
+            println!("Requesting {} evaluation(s) for genotype: {:?}", requests, delays_genotype);
+        }
+        for _ in 0..requests {
+            self.sender.send(delays_genotype.clone()).expect("Fitness calculator receiver failed");
+        }
         loop {
             {
-                match self.fitness_values.read().unwrap().get(delays_genotype) {
-                    Some(fitness) => {
-                        println!("Fitness found: {:?} for genotype: {:?}", fitness, delays_genotype);
-                        return fitness.clone();
+                if let Some(samples) = self.fitness_values.read().unwrap().get(delays_genotype) {
+                    // Every request was made above, so nothing pending without samples means all of them failed
+                    if samples.pending == 0 && samples.samples.is_empty() {
+                        panic!("No scheduler returned a fitness for genotype: {:?}", delays_genotype);
                     }
-                    None => {
-                        if !sent_to_handler {
-                            println!("Fitness not found for genotype: {:?}", delays_genotype);
-                            self.sender.send(delays_genotype.clone()).expect("Fitness calculator receiver failed");
-                        }
-                        sent_to_handler = true;
+                    if samples.is_evaluated() {
+                        let fitness = samples.fitness();
+                        println!("Fitness found: {:?} for genotype: {:?}", fitness, delays_genotype);
+                        return fitness;
                     }
                 }
             }
@@ -82,58 +155,90 @@ pub trait SchedulerHandlerTrait {
 /// Scheduler handler is in charge of communicating new schedules to the scheduler
 /// Fitness functions send to this handler to request fitness values for untested solutions
 /// Calculated fitness values are stored in the fitness_values map and fitness functions will first check there
+/// Every cluster has its own scheduler, a request is run on whichever cluster is free and all results go into the same map
 pub struct SchedulerHandler<T, G, P>
     where T: ExtendedFitness, G: ExtendedGenotype, P: ExtendedPhenotype<G>
 {
-    scheduler_sender: Sender<P>,
-    scheduler_receiver: Receiver<T>,
+    scheduler_senders: Vec<Sender<P>>,
+    scheduler_receivers: Vec<Receiver<T>>,
     fitness_receiver: Receiver<G>,
-    fitness_values: Arc<RwLock<HashMap<G, T>>>,
+    fitness_values: FitnessCache<G, T>,
 }
 
 impl<T, G, P> SchedulerHandler<T, G, P>
     where T: ExtendedFitness, G: ExtendedGenotype, P: ExtendedPhenotype<G>
 {
     pub fn new(
-        scheduler_sender: Sender<P>,
-        scheduler_receiver: Receiver<T>,
+        scheduler_senders: Vec<Sender<P>>,
+        scheduler_receivers: Vec<Receiver<T>>,
         fitness_receiver: Receiver<G>,
-        fitness_values: Arc<RwLock<HashMap<G, T>>>,
+        fitness_values: FitnessCache<G, T>,
     ) -> Self {
-        SchedulerHandler { scheduler_sender, scheduler_receiver, fitness_receiver, fitness_values }
+        assert_eq!(scheduler_senders.len(), scheduler_receivers.len(), "Every cluster needs a scheduler sender and receiver");
+        SchedulerHandler { scheduler_senders, scheduler_receivers, fitness_receiver, fitness_values }
     }
-}
 
-impl<T, G, P> SchedulerHandlerTrait for SchedulerHandler<T, G, P>
-    where T: ExtendedFitness, G: ExtendedGenotype, P: ExtendedPhenotype<G>
-{
-    fn run(self) {
-        let mut current_individual = G::default();
-        self.fitness_values.write().unwrap().insert(current_individual.clone(), T::zero());
+    /// Run requested individuals on a single cluster until the fitness channel closes
+    fn run_cluster(
+        cluster: usize,
+        scheduler_sender: Sender<P>,
+        scheduler_receiver: Receiver<T>,
+        fitness_receiver: Arc<Mutex<Receiver<G>>>,
+        fitness_values: FitnessCache<G, T>,
+    ) {
         loop {
-            // Receive a new individual to test from a fitness function
-            match self.fitness_receiver.recv() {
+            // Receive a new individual to test from a fitness function, one request per harness run
+            let request = fitness_receiver.lock().unwrap().recv();
+            match request {
                 Ok(individual) => {
-                    debug!("Fitness function wants fitness for: {:?}", individual);
-                    if current_individual != individual && self.fitness_values.read().unwrap().contains_key(&current_individual) {
-                        current_individual = individual;
+                    debug!("Fitness function wants fitness for: {:?} on cluster {}", individual, cluster);
+                    let current_phenotype = P::from_genes(&individual);
+                    debug!("{}", current_phenotype.display_genotype_by_message());
+                    // Send the requested individual to the scheduler
+                    scheduler_sender.send(current_phenotype)
+                        .expect("Scheduler receiver failed");
+                    // Receive fitness from scheduler
+                    match scheduler_receiver.recv() {
+                        Ok(fitness) => {
+                            debug!("Received fitness of {:?} for individual: {:?} from cluster {}", fitness, individual, cluster);
+                            fitness_values.write().unwrap()
+                                .entry(individual)
+                                .or_insert_with(FitnessSamples::new)
+                                .add_sample(fitness);
+                        }
+                        Err(_) => {
+                            // The scheduler of this cluster is gone, give the request back and stop using the cluster
+                            error!("Scheduler of cluster {} failed, no fitness for individual: {:?}", cluster, individual);
+                            if let Some(samples) = fitness_values.write().unwrap().get_mut(&individual) {
+                                samples.pending = samples.pending.saturating_sub(1);
+                            }
+                            return;
+                        }
                     }
                 }
-                Err(_) => {}
-            }
-            let current_phenotype = P::from_genes(&current_individual);
-            debug!("{}", current_phenotype.display_genotype_by_message());
-            // Send the requested individual to the scheduler
-            self.scheduler_sender.send(current_phenotype)
-                .expect("Scheduler receiver failed");
-            // Receive fitness from scheduler
-            match self.scheduler_receiver.recv() {
-                Ok(fitness) => {
-                    debug!("Received fitness of {:?} for individual: {:?}", fitness, current_individual);
-                    self.fitness_values.write().unwrap().insert(current_individual.clone(), fitness);
-                }
-                Err(_) => {}
+                Err(_) => return,
             }
         }
     }
-}
\ No newline at end of file
+}
+
+impl<T, G, P> SchedulerHandlerTrait for SchedulerHandler<T, G, P>
+    where T: ExtendedFitness, G: ExtendedGenotype + 'static, P: ExtendedPhenotype<G> + 'static
+{
+    fn run(self) {
+        self.fitness_values.write().unwrap().insert(G::default(), FitnessSamples::from_sample(T::zero()));
+        let fitness_receiver = Arc::new(Mutex::new(self.fitness_receiver));
+        let cluster_threads = self.scheduler_senders.into_iter()
+            .zip(self.scheduler_receivers.into_iter())
+            .enumerate()
+            .map(|(cluster, (scheduler_sender, scheduler_receiver))| {
+                let fitness_receiver = fitness_receiver.clone();
+                let fitness_values = self.fitness_values.clone();
+                thread::spawn(move || Self::run_cluster(cluster, scheduler_sender, scheduler_receiver, fitness_receiver, fitness_values))
+            })
+            .collect::<Vec<_>>();
+        for cluster_thread in cluster_threads {
+            cluster_thread.join().expect("Cluster scheduler handler panicked");
+        }
+    }
+}
@@ -20,9 +20,7 @@ impl StateAccountFitness {
     pub fn update_server_states(node_states: Arc<MutexNodeStates>, test_harness: &TestHarness) -> Vec<ServerStateObject> {
         (0..node_states.number_of_nodes).into_iter().for_each(|i| Client::server_state(&test_harness.client_senders[i]));
         println!("Waiting on server states");
-        {
-            node_states.server_state_cvar.wait(&mut node_states.node_states.lock());
-        }
+        node_states.server_state_cvar.wait();
         println!("Getting server states");
         (0..node_states.number_of_nodes).into_iter().map(|i| node_states.get_server_state(i)).collect::<Vec<ServerStateObject>>()
     }
@@ -8,33 +8,36 @@ use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, RwLock};
 use std::thread;
 use serde_with::{serde_as, DurationSeconds};
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use genevo::ga::genetic_algorithm;
-use genevo::mutation::order::SwapOrderMutator;
 use genevo::operator::{CrossoverOp, SelectionOp};
-use genevo::operator::prelude::{MaximizeSelector, PartiallyMappedCrossover};
+use genevo::operator::prelude::MaximizeSelector;
 use genevo::prelude::{Population, SimResult, simulate, Simulation, SimulationBuilder, TimeLimit};
+use itertools::Itertools;
 use log::error;
 use crate::{CONFIG, LOG_FOLDER};
-use crate::ga::crossover::SimulatedBinaryCrossBreeder;
+use crate::ga::crossover::DelayCrossBreeder;
 use crate::ga::encoding::delay_encoding::{DelayMapPhenotype, DelayGenotype};
-use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype, num_genes, SuperExtendedGenotype};
+use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype, num_delay_genes, SuperExtendedGenotype};
+use crate::ga::evaluation::FitnessSummary;
 #[allow(unused_imports)]
 use crate::ga::fitness::state_accounting_fitness::StateAccountFitness;
-use crate::ga::fitness::{ExtendedFitness, FitnessCalculation, SchedulerHandler, SchedulerHandlerTrait};
+use crate::ga::fitness::{ExtendedFitness, FitnessCache, FitnessCalculation, SchedulerHandler, SchedulerHandlerTrait};
 #[allow(unused_imports)]
 use crate::ga::fitness::compared_fitness_functions::ComparedFitnessFunctions;
 #[allow(unused_imports)]
 use crate::ga::fitness::time_fitness::TimeFitness;
-use crate::ga::parameters::{default_mu_lambda_delays, default_mu_lambda_priorities, Parameter, PermutationParameters};
-use crate::ga::population_builder::{build_delays_population, build_priorities_population};
+use crate::ga::parameters::{delay_parameters, GaConfiguration, hybrid_parameters, HybridParameters, Parameter, PermutationParameters, priority_parameters};
+use crate::ga::population_builder::{build_delays_population, build_hybrid_population, build_priorities_population, build_self_adaptive_delays_population};
 use crate::ga::encoding::priority_encoding::{PriorityGenotype, PriorityMapPhenotype};
+use crate::ga::encoding::hybrid_encoding::{HybridGenotype, HybridMapPhenotype};
 #[allow(unused_imports)]
 use crate::ga::fitness::propose_seq_fitness::ProposeSeqFitness;
-use crate::ga::selection::MuLambdaSelector;
-use crate::ga::reinsertion::MuLambdaReinserter;
+use crate::ga::selection::{ConfiguredSelector, GaSelector};
+use crate::ga::reinsertion::GaReinserter;
+use crate::ga::symmetry::NodeSymmetry;
 use crate::message_handler::RippleMessageObject;
-use super::mutation::GaussianMutator;
+use super::mutation::{DelayMutationType, DelayMutator, HybridMutator, Lineage, MutationAdaptation, PermutationMutator, SelfAdaptiveGenomeMutation, TrackedCrossover, TrackedMutation};
 
 /// The message types that will be subject to delay
 #[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
@@ -57,6 +60,8 @@ pub enum ConsensusMessageType {
 impl ConsensusMessageType {
     pub const VALUES: [Self; 13] = [Self::TMProposeSet0, Self::TMProposeSet1, Self::TMProposeSet2, Self::TMProposeSet3, Self::TMProposeSet4, Self::TMProposeSet5, Self::TMProposeSetBowOut, Self::TMStatusChange, Self::TMValidation, Self::TMTransaction, Self::TMHaveTransactionSet, Self::TMGetLedger, Self::TMLedgerData];
     pub const RMO_MESSAGE_TYPE: [&'static str; 7] = ["ProposeSet", "StatusChange", "Validation", "Transaction", "HaveTransactionSet", "GetLedger", "LedgerData"];
+    /// The type codes in the frame headers of the messages in RMO_MESSAGE_TYPE, in the same order
+    pub const RMO_MESSAGE_CODE: [u16; 7] = [33, 34, 41, 30, 35, 31, 32];
 
     pub fn create_consensus_message_type(rmo: &RippleMessageObject) -> Option<Self> {
         match rmo {
@@ -84,57 +89,90 @@ impl ConsensusMessageType {
 }
 
 /// Run a standard mu lambda GA with delay encoding
+/// Every scheduler sender and receiver pair belongs to one cluster, individuals are evaluated in parallel across clusters
 #[allow(unused)]
-pub fn run_default_mu_lambda_delays<F: ExtendedFitness>(mu: usize, lambda: usize, scheduler_sender: Sender<DelayMapPhenotype>, scheduler_receiver: Receiver<F>) {
-    let params = default_mu_lambda_delays(mu, lambda);
-    let population = build_delays_population(params.num_genes, params.min_value, params.max_value, lambda);
+pub fn run_default_mu_lambda_delays<F: ExtendedFitness>(ga_configuration: GaConfiguration, scheduler_senders: Vec<Sender<DelayMapPhenotype>>, scheduler_receivers: Vec<Receiver<F>>) {
+    let params = delay_parameters(&ga_configuration);
+    let population = if params.mutation_type == DelayMutationType::SelfAdaptive {
+        build_self_adaptive_delays_population(params.num_genes, params.min_value, params.max_value, params.mutation_std, params.population_size)
+    } else {
+        build_delays_population(params.num_genes, params.min_value, params.max_value, params.population_size)
+    };
 
     let (fitness_sender, fitness_receiver) = channel();
-    let fitness_values: Arc<RwLock<HashMap<DelayGenotype, F>>> = Arc::new(RwLock::new(HashMap::new()));
-    let scheduler_handler = SchedulerHandler::new(scheduler_sender, scheduler_receiver, fitness_receiver, fitness_values.clone());
-    let fitness_calculation = FitnessCalculation { fitness_values: fitness_values.clone(), sender: fitness_sender };
+    let fitness_values: FitnessCache<DelayGenotype, F> = Arc::new(RwLock::new(HashMap::new()));
+    let scheduler_handler = SchedulerHandler::new(scheduler_senders, scheduler_receivers, fitness_receiver, fitness_values.clone());
+    let fitness_calculation = FitnessCalculation::new(fitness_values.clone(), fitness_sender, CONFIG.reevaluation.clone())
+        .with_symmetry(NodeSymmetry::from_config());
 
-    run_ga::<MaximizeSelector, SimulatedBinaryCrossBreeder, F, SchedulerHandler<F, DelayGenotype, DelayMapPhenotype>, DelayGenotype, DelayMapPhenotype>(scheduler_handler, fitness_calculation, params, population);
+    run_ga::<MaximizeSelector, DelayCrossBreeder, F, SchedulerHandler<F, DelayGenotype, DelayMapPhenotype>, DelayGenotype, DelayMapPhenotype>(scheduler_handler, fitness_calculation, params, population);
 }
 
 /// Run a standard mu lambda GA with priority encoding
+/// Every scheduler sender and receiver pair belongs to one cluster, individuals are evaluated in parallel across clusters
 #[allow(unused)]
-pub fn run_default_mu_lambda_priorities<F: ExtendedFitness>(mu: usize, lambda: usize, scheduler_sender: Sender<PriorityMapPhenotype>, scheduler_receiver: Receiver<F>) {
-    let params = default_mu_lambda_priorities::<F>(mu, lambda);
+pub fn run_default_mu_lambda_priorities<F: ExtendedFitness>(ga_configuration: GaConfiguration, scheduler_senders: Vec<Sender<PriorityMapPhenotype>>, scheduler_receivers: Vec<Receiver<F>>) {
+    let params = priority_parameters::<F>(&ga_configuration);
     let population = build_priorities_population(params.num_genes, params.population_size);
 
     let (fitness_sender, fitness_receiver) = channel();
-    let fitness_values: Arc<RwLock<HashMap<PriorityGenotype, F>>> = Arc::new(RwLock::new(HashMap::new()));
-    let scheduler_handler = SchedulerHandler::new(scheduler_sender, scheduler_receiver, fitness_receiver, fitness_values.clone());
-    let fitness_calculation = FitnessCalculation { fitness_values: fitness_values.clone(), sender: fitness_sender };
+    let fitness_values: FitnessCache<PriorityGenotype, F> = Arc::new(RwLock::new(HashMap::new()));
+    let scheduler_handler = SchedulerHandler::new(scheduler_senders, scheduler_receivers, fitness_receiver, fitness_values.clone());
+    let fitness_calculation = FitnessCalculation::new(fitness_values.clone(), fitness_sender, CONFIG.reevaluation.clone())
+        .with_symmetry(NodeSymmetry::from_config());
 
     run_permutation_ga::<MaximizeSelector, F, SchedulerHandler<F, PriorityGenotype, PriorityMapPhenotype>>(scheduler_handler, fitness_calculation, params, population);
 }
 
+/// Run a standard mu lambda GA with hybrid delay and priority encoding
+/// Every scheduler sender and receiver pair belongs to one cluster, individuals are evaluated in parallel across clusters
+#[allow(unused)]
+pub fn run_default_mu_lambda_hybrid<F: ExtendedFitness>(ga_configuration: GaConfiguration, scheduler_senders: Vec<Sender<HybridMapPhenotype>>, scheduler_receivers: Vec<Receiver<F>>) {
+    let params = hybrid_parameters::<F>(&ga_configuration);
+    let standard_deviation = match params.delay_mutation_type {
+        DelayMutationType::SelfAdaptive => Some(params.mutation_std),
+        _ => None,
+    };
+    let population = build_hybrid_population(params.num_genes, params.num_delay_genes, params.min_value, params.max_value, standard_deviation, params.population_size);
+
+    let (fitness_sender, fitness_receiver) = channel();
+    let fitness_values: FitnessCache<HybridGenotype, F> = Arc::new(RwLock::new(HashMap::new()));
+    let scheduler_handler = SchedulerHandler::new(scheduler_senders, scheduler_receivers, fitness_receiver, fitness_values.clone());
+    let fitness_calculation = FitnessCalculation::new(fitness_values.clone(), fitness_sender, CONFIG.reevaluation.clone())
+        .with_symmetry(NodeSymmetry::from_config());
+
+    run_hybrid_ga::<F, SchedulerHandler<F, HybridGenotype, HybridMapPhenotype>>(scheduler_handler, fitness_calculation, params, population);
+}
+
 #[allow(unused)]
 pub fn run_no_ga(number_of_tests: usize) {
-    let zero_delay = vec![0; num_genes()];
+    let zero_delay = vec![0; num_delay_genes()];
     for i in 0..number_of_tests {
 
     }
 }
 
-pub fn run_ga<S, C, T, H, G, P>(scheduler_handler: H, fitness_calculation: FitnessCalculation<T, G>, params: Parameter<MuLambdaSelector, SimulatedBinaryCrossBreeder, T, DelayGenotype>, initial_population: Population<G>)
+pub fn run_ga<S, C, T, H, G, P>(scheduler_handler: H, fitness_calculation: FitnessCalculation<T, G>, params: Parameter<ConfiguredSelector, DelayCrossBreeder, T, DelayGenotype>, initial_population: Population<G>)
     where S: SelectionOp<G, T> + Debug, C: CrossoverOp<G> + Debug + Sync, T: ExtendedFitness + serde::Serialize + 'static, H: SchedulerHandlerTrait + Send + 'static, G: SuperExtendedGenotype + serde::Serialize, P: ExtendedPhenotype<G>
 {
     println!("{:?}", initial_population);
 
     thread::spawn(move || scheduler_handler.run());
 
+    let generation_tracker = fitness_calculation.clone();
+    let mutator = DelayMutator::new(params.mutation_type.clone(), params.mutation_rate, params.mutation_std, params.min_mutation_std, params.min_value, params.max_value);
+    let lineage = Lineage::new();
     let ga = genetic_algorithm()
         .with_evaluation(fitness_calculation.clone())
-        .with_selection(params.selection_operator.clone())
-        .with_crossover(params.crossover_operator.clone())
-        .with_mutation(GaussianMutator::new(params.mutation_rate, params.mutation_std))
-        .with_reinsertion(MuLambdaReinserter::new(
-            fitness_calculation,
+        .with_selection(GaSelector::from_policy(params.selection_operator.clone(), &fitness_calculation))
+        .with_crossover(TrackedCrossover::new(params.crossover_operator.clone(), lineage.clone()))
+        .with_mutation(TrackedMutation::new(mutator.clone(), lineage.clone()))
+        .with_reinsertion(GaReinserter::new(
+            &params.reinsertion_type,
+            fitness_calculation.clone(),
             params.population_size,
-        ))
+            params.reinsertion_ratio,
+        ).with_symmetry(fitness_calculation.symmetry))
         .with_initial_population(initial_population)
         .build();
 
@@ -150,19 +188,23 @@ pub fn run_ga<S, C, T, H, G, P>(scheduler_handler: H, fitness_calculation: Fitne
     ga_writer.flush().expect("GA writer flush failed");
 
     println!("Starting GA with: {:?}", params);
+    let start_time = Utc::now();
     loop {
         let result = sim.step();
+        generation_tracker.next_generation();
         match result {
             Ok(SimResult::Intermediate(step)) => {
                 let evaluated_population = step.result.evaluated_population;
                 let best_solution = step.result.best_solution;
+                let mutation_adaptation = adapt_mutation(&mutator, &lineage, &generation_tracker, evaluated_population.individuals(), start_time, G::mean_standard_deviation);
                 let generation_info: GaStepInfo<T, G> = GaStepInfo::new(
                     step.iteration,
                     evaluated_population.average_fitness().clone(),
                     best_solution.solution.fitness,
+                    generation_tracker.samples_of(&best_solution.solution.genome).map(|samples| samples.summary()),
                     best_solution.solution.genome,
                     step.duration,
-                );
+                ).with_mutation_adaptation(mutation_adaptation);
                 println!("{}", serde_json::to_string(&generation_info).unwrap());
                 match serde_json::to_writer_pretty(&mut ga_writer, &generation_info) {
                     Ok(_) => {}
@@ -175,13 +217,15 @@ pub fn run_ga<S, C, T, H, G, P>(scheduler_handler: H, fitness_calculation: Fitne
             Ok(SimResult::Final(step, _processing_time, duration, stop_reason)) => {
                 let evaluated_population = step.result.evaluated_population;
                 let best_solution = step.result.best_solution;
+                let mutation_adaptation = adapt_mutation(&mutator, &lineage, &generation_tracker, evaluated_population.individuals(), start_time, G::mean_standard_deviation);
                 let generation_info: GaStepInfo<T, G> = GaStepInfo::new(
                     step.iteration,
                     evaluated_population.average_fitness().clone(),
                     best_solution.solution.fitness,
+                    generation_tracker.samples_of(&best_solution.solution.genome).map(|samples| samples.summary()),
                     best_solution.solution.genome,
                     duration,
-                );
+                ).with_mutation_adaptation(mutation_adaptation);
                 match serde_json::to_writer_pretty(&mut ga_writer, &generation_info) {
                     Ok(_) => {}
                     Err(err) => error!("Failed writing to ga file: {}", err)
@@ -202,22 +246,62 @@ pub fn run_ga<S, C, T, H, G, P>(scheduler_handler: H, fitness_calculation: Fitne
     std::process::exit(0);
 }
 
-pub fn run_permutation_ga<S, T, H>(scheduler_handler: H, fitness_calculation: FitnessCalculation<T, PriorityGenotype>, params: PermutationParameters<MuLambdaSelector, T, PriorityGenotype>, initial_population: Population<PriorityGenotype>)
+/// Adapt the delay mutation after a generation, offspring that are fitter than the fittest of their parents count as successes
+/// mean_standard_deviation: The mean of the standard deviations carried by an individual for self-adaptive mutation
+fn adapt_mutation<G: ExtendedGenotype, T: ExtendedFitness>(mutator: &DelayMutator, lineage: &Lineage<G>, fitness_calculation: &FitnessCalculation<T, G>, population: &[G], start_time: DateTime<Utc>, mean_standard_deviation: fn(&G) -> Option<f64>) -> MutationAdaptation {
+    let offspring = lineage.take_offspring();
+    let success_rate = offspring_success_rate(&offspring, |genome| fitness_calculation.samples_of(genome)
+        .filter(|samples| samples.is_evaluated())
+        .map(|samples| samples.fitness()));
+    let progress = (Utc::now() - start_time).num_milliseconds() as f64 / CONFIG.search_budget.num_milliseconds().max(1) as f64;
+    let standard_deviation = mutator.adapt(success_rate, progress);
+    let gene_standard_deviations = population.iter().filter_map(mean_standard_deviation).collect_vec();
+    let mean_gene_standard_deviation = match mutator.mutation_type() {
+        DelayMutationType::SelfAdaptive if !gene_standard_deviations.is_empty() =>
+            Some(gene_standard_deviations.iter().sum::<f64>() / gene_standard_deviations.len() as f64),
+        _ => None,
+    };
+    MutationAdaptation {
+        mutation_type: mutator.mutation_type().clone(),
+        standard_deviation,
+        success_rate,
+        mean_gene_standard_deviation,
+    }
+}
+
+/// The fraction of offspring that is fitter than the fittest of its parents, offspring without a fitness count as failures
+fn offspring_success_rate<G, T: Ord>(offspring: &[(G, Vec<G>)], fitness: impl Fn(&G) -> Option<T>) -> f64 {
+    if offspring.is_empty() {
+        return 0.0;
+    }
+    let successes = offspring.iter()
+        .filter(|(child, parents)| match (fitness(child), parents.iter().filter_map(&fitness).max()) {
+            (Some(child_fitness), Some(parent_fitness)) => child_fitness > parent_fitness,
+            _ => false,
+        })
+        .count();
+    successes as f64 / offspring.len() as f64
+}
+
+pub fn run_permutation_ga<S, T, H>(scheduler_handler: H, fitness_calculation: FitnessCalculation<T, PriorityGenotype>, params: PermutationParameters<ConfiguredSelector, T, PriorityGenotype>, initial_population: Population<PriorityGenotype>)
     where S: SelectionOp<PriorityGenotype, T> + Debug, T: ExtendedFitness + serde::Serialize + 'static, H: SchedulerHandlerTrait + Send + 'static
 {
     println!("{:?}", initial_population);
 
     thread::spawn(move || scheduler_handler.run());
 
+    let generation_tracker = fitness_calculation.clone();
     let ga = genetic_algorithm()
         .with_evaluation(fitness_calculation.clone())
-        .with_selection(params.selection_operator.clone())
-        .with_crossover(PartiallyMappedCrossover::new())
-        .with_mutation(SwapOrderMutator::new(params.mutation_rate))
-        .with_reinsertion(MuLambdaReinserter::new(
-            fitness_calculation,
+        .with_selection(GaSelector::from_policy(params.selection_operator.clone(), &fitness_calculation))
+        .with_crossover(params.crossover_operator.clone())
+        .with_mutation(PermutationMutator::new(params.mutation_type.clone(), params.mutation_rate))
+        .with_reinsertion(GaReinserter::new(
+            &params.reinsertion_type,
+            fitness_calculation.clone(),
             params.population_size,
-        ))
+            params.reinsertion_ratio,
+        ).with_symmetry(fitness_calculation.symmetry))
         .with_initial_population(initial_population)
         .build();
 
@@ -235,6 +319,7 @@ pub fn run_permutation_ga<S, T, H>(scheduler_handler: H, fitness_calculation: Fi
     println!("Starting GA with: {:?}", params);
     loop {
         let result = sim.step();
+        generation_tracker.next_generation();
         match result {
             Ok(SimResult::Intermediate(step)) => {
                 let evaluated_population = step.result.evaluated_population;
@@ -243,6 +328,7 @@ pub fn run_permutation_ga<S, T, H>(scheduler_handler: H, fitness_calculation: Fi
                     step.iteration,
                     evaluated_population.average_fitness().clone(),
                     best_solution.solution.fitness,
+                    generation_tracker.samples_of(&best_solution.solution.genome).map(|samples| samples.summary()),
                     best_solution.solution.genome,
                     step.duration,
                 );
@@ -264,6 +350,7 @@ pub fn run_permutation_ga<S, T, H>(scheduler_handler: H, fitness_calculation: Fi
                     step.iteration,
                     evaluated_population.average_fitness().clone(),
                     best_solution.solution.fitness,
+                    generation_tracker.samples_of(&best_solution.solution.genome).map(|samples| samples.summary()),
                     best_solution.solution.genome,
                     duration,
                 );
@@ -287,6 +374,101 @@ pub fn run_permutation_ga<S, T, H>(scheduler_handler: H, fitness_calculation: Fi
     std::process::exit(0);
 }
 
+pub fn run_hybrid_ga<T, H>(scheduler_handler: H, fitness_calculation: FitnessCalculation<T, HybridGenotype>, params: HybridParameters<ConfiguredSelector, T>, initial_population: Population<HybridGenotype>)
+    where T: ExtendedFitness + serde::Serialize + 'static, H: SchedulerHandlerTrait + Send + 'static
+{
+    println!("{:?}", initial_population);
+
+    thread::spawn(move || scheduler_handler.run());
+
+    let generation_tracker = fitness_calculation.clone();
+    let mutator = HybridMutator::new(
+        DelayMutator::new(params.delay_mutation_type.clone(), params.mutation_rate, params.mutation_std, params.min_mutation_std, params.min_value, params.max_value),
+        PermutationMutator::new(params.priority_mutation_type.clone(), params.mutation_rate),
+    );
+    let lineage = Lineage::new();
+    let ga = genetic_algorithm()
+        .with_evaluation(fitness_calculation.clone())
+        .with_selection(GaSelector::from_policy(params.selection_operator.clone(), &fitness_calculation))
+        .with_crossover(TrackedCrossover::new(params.crossover_operator.clone(), lineage.clone()))
+        .with_mutation(TrackedMutation::new(mutator.clone(), lineage.clone()))
+        .with_reinsertion(GaReinserter::new(
+            &params.reinsertion_type,
+            fitness_calculation.clone(),
+            params.population_size,
+            params.reinsertion_ratio,
+        ).with_symmetry(fitness_calculation.symmetry))
+        .with_initial_population(initial_population)
+        .build();
+
+    let mut sim = simulate(ga)
+        .until(TimeLimit::new(CONFIG.search_budget))
+        .build();
+
+    let mut ga_writer = create_ga_writer();
+    match serde_json::to_writer_pretty(&mut ga_writer, &params) {
+        Ok(_) => {}
+        Err(err) => error!("Failed writing to ga file: {}", err)
+    };
+    ga_writer.flush().expect("GA writer flush failed");
+
+    println!("Starting GA with: {:?}", params);
+    let start_time = Utc::now();
+    loop {
+        let result = sim.step();
+        generation_tracker.next_generation();
+        match result {
+            Ok(SimResult::Intermediate(step)) => {
+                let evaluated_population = step.result.evaluated_population;
+                let best_solution = step.result.best_solution;
+                let mutation_adaptation = adapt_mutation(mutator.delay_mutator(), &lineage, &generation_tracker, evaluated_population.individuals(), start_time, |individual: &HybridGenotype| individual.delays.mean_standard_deviation());
+                let generation_info: GaStepInfo<T, HybridGenotype> = GaStepInfo::new(
+                    step.iteration,
+                    evaluated_population.average_fitness().clone(),
+                    best_solution.solution.fitness,
+                    generation_tracker.samples_of(&best_solution.solution.genome).map(|samples| samples.summary()),
+                    best_solution.solution.genome,
+                    step.duration,
+                ).with_mutation_adaptation(mutation_adaptation);
+                println!("{}", serde_json::to_string(&generation_info).unwrap());
+                match serde_json::to_writer_pretty(&mut ga_writer, &generation_info) {
+                    Ok(_) => {}
+                    Err(err) => error!("Failed writing to ga file: {}", err)
+                };
+                ga_writer.flush().expect("GA writer flush failed");
+            },
+            Ok(SimResult::Final(step, _processing_time, duration, stop_reason)) => {
+                let evaluated_population = step.result.evaluated_population;
+                let best_solution = step.result.best_solution;
+                let mutation_adaptation = adapt_mutation(mutator.delay_mutator(), &lineage, &generation_tracker, evaluated_population.individuals(), start_time, |individual: &HybridGenotype| individual.delays.mean_standard_deviation());
+                let generation_info: GaStepInfo<T, HybridGenotype> = GaStepInfo::new(
+                    step.iteration,
+                    evaluated_population.average_fitness().clone(),
+                    best_solution.solution.fitness,
+                    generation_tracker.samples_of(&best_solution.solution.genome).map(|samples| samples.summary()),
+                    best_solution.solution.genome,
+                    duration,
+                ).with_mutation_adaptation(mutation_adaptation);
+                match serde_json::to_writer_pretty(&mut ga_writer, &generation_info) {
+                    Ok(_) => {}
+                    Err(err) => error!("Failed writing to ga file: {}", err)
+                };
+                ga_writer.flush().expect("GA writer flush failed");
+                println!("{}", stop_reason);
+                println!("{}", serde_json::to_string(&generation_info).unwrap());
+                print!("      ");
+                println!("{}", HybridMapPhenotype::from_genes(&generation_info.best_individual).display_genotype_by_message());
+                break;
+            },
+            Err(error) => {
+                println!("{:?}", error);
+                break;
+            },
+        }
+    }
+    std::process::exit(0);
+}
+
 pub fn create_ga_writer() -> BufWriter<File> {
     BufWriter::new(
         File::create(
@@ -297,25 +479,35 @@ pub fn create_ga_writer() -> BufWriter<File> {
 
 #[serde_as]
 #[derive(serde::Serialize)]
-struct GaStepInfo<F: ExtendedFitness, G: ExtendedGenotype> {
+pub(crate) struct GaStepInfo<F: ExtendedFitness, G: ExtendedGenotype> {
     iteration: u64,
     average_fitness: F,
     best_fitness: F,
+    best_fitness_samples: Option<FitnessSummary<F>>,
     best_individual: G,
     #[serde_as(as = "DurationSeconds<i64>")]
     duration: Duration,
+    mutation_adaptation: Option<MutationAdaptation>,
 }
 
 impl<F: ExtendedFitness, G: ExtendedGenotype> GaStepInfo<F, G> {
-    pub fn new(iteration: u64, average_fitness: F, best_fitness: F, best_individual: G, duration: Duration) -> Self {
+    pub fn new(iteration: u64, average_fitness: F, best_fitness: F, best_fitness_samples: Option<FitnessSummary<F>>, best_individual: G, duration: Duration) -> Self {
         Self {
             iteration,
             average_fitness,
             best_fitness,
+            best_fitness_samples,
             best_individual,
-            duration
+            duration,
+            mutation_adaptation: None,
         }
     }
+
+    /// Record the adapted mutation parameters of the step
+    pub fn with_mutation_adaptation(mut self, mutation_adaptation: MutationAdaptation) -> Self {
+        self.mutation_adaptation = Some(mutation_adaptation);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -329,9 +521,10 @@ mod ga_tests {
     use genevo::prelude::{GenerationLimit, SimResult, simulate, Simulation, SimulationBuilder};
     use crate::ga::encoding::delay_encoding::{DelayMapPhenotype, DelayGenotype};
     use crate::ga::encoding::num_genes;
-    use crate::ga::fitness::{FitnessCalculation, SchedulerHandlerTrait};
+    use crate::ga::evaluation::{FitnessSamples, ReevaluationPolicy};
+    use crate::ga::fitness::{FitnessCache, FitnessCalculation, SchedulerHandlerTrait};
     use crate::ga::fitness::time_fitness::TimeFitness;
-    use crate::ga::genetic_algorithm::{ConsensusMessageType, ExtendedPhenotype};
+    use crate::ga::genetic_algorithm::{ConsensusMessageType, ExtendedPhenotype, offspring_success_rate};
     use crate::ga::mutation::{NoMutation};
     use crate::ga::parameters::default_mu_lambda_delays;
     use crate::ga::population_builder::build_delays_population;
@@ -343,11 +536,20 @@ mod ga_tests {
         //let genotype: DelaysGenotype = (1..81).collect_vec();
         let genotype: DelayGenotype = vec![100u32; num_genes()];
         let phenotype = DelayMapPhenotype::from_genes(&genotype);
-        println!("{:?}", phenotype.delay_map);
-        println!("{:?}", phenotype.message_type_delays(&ConsensusMessageType::TMValidation));
+        println!("{:?}", phenotype.delay_maps);
+        println!("{:?}", phenotype.message_type_delays(&ConsensusMessageType::TMValidation, 0, 0));
         println!("{}", phenotype.display_genotype_by_message());
     }
 
+    #[test]
+    fn test_offspring_success_rate() {
+        let offspring = vec![(3, vec![1, 2]), (1, vec![2]), (5, vec![5]), (9, vec![])];
+        assert_eq!(offspring_success_rate(&offspring, |genome: &u32| Some(*genome)), 0.25);
+        // Offspring that are not evaluated do not count as successes
+        assert_eq!(offspring_success_rate(&offspring, |genome: &u32| if *genome == 3 { None } else { Some(*genome) }), 0.0);
+        assert_eq!(offspring_success_rate::<u32, u32>(&[], |genome| Some(*genome)), 0.0);
+    }
+
     #[test]
     #[ignore]
     fn test_mu_lambda() {
@@ -357,9 +559,9 @@ mod ga_tests {
         let population = build_delays_population(params.num_genes, params.min_value, params.max_value, params.population_size);
 
         let (fitness_sender, fitness_receiver) = std::sync::mpsc::channel();
-        let fitness_values: Arc<RwLock<HashMap<DelayGenotype, TimeFitness>>> = Arc::new(RwLock::new(HashMap::new()));
+        let fitness_values: FitnessCache<DelayGenotype, TimeFitness> = Arc::new(RwLock::new(HashMap::new()));
         let scheduler_handler = TestSchedulerHandler::new(fitness_receiver, fitness_values.clone());
-        let fitness_calculation = FitnessCalculation { fitness_values: fitness_values.clone(), sender: fitness_sender };
+        let fitness_calculation = FitnessCalculation::new(fitness_values.clone(), fitness_sender, ReevaluationPolicy::default());
 
         thread::spawn(move || scheduler_handler.run());
 
@@ -426,13 +628,13 @@ mod ga_tests {
 
     struct TestSchedulerHandler {
         fitness_receiver: Receiver<DelayGenotype>,
-        fitness_values: Arc<RwLock<HashMap<DelayGenotype, TimeFitness>>>
+        fitness_values: FitnessCache<DelayGenotype, TimeFitness>
     }
 
     impl TestSchedulerHandler {
         pub fn new(
             fitness_receiver: Receiver<DelayGenotype>,
-            fitness_values: Arc<RwLock<HashMap<DelayGenotype, TimeFitness>>>,
+            fitness_values: FitnessCache<DelayGenotype, TimeFitness>,
         ) -> Self {
             TestSchedulerHandler { fitness_receiver, fitness_values }
         }
@@ -445,7 +647,10 @@ mod ga_tests {
                 match self.fitness_receiver.recv() {
                     Ok(delays_genotype) => match &delays_genotype[..] {
                         x => {
-                            self.fitness_values.write().unwrap().insert(delays_genotype.clone(), TimeFitness::new(Duration::from_secs(x[0] as u64)));
+                            self.fitness_values.write().unwrap()
+                                .entry(delays_genotype.clone())
+                                .or_insert_with(FitnessSamples::new)
+                                .add_sample(TimeFitness::new(Duration::from_secs(x[0] as u64)));
                             // println!("Received {:?} from fitness calculation with fitness: {}", x, x[0]);
                             // self.fitness_values.write().unwrap().insert(delays_genotype.clone(), CurrentFitness::new(x[0]));
                         }
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use chrono::{Duration, Utc};
+use genevo::genetic::{AsScalar, FitnessFunction};
+use genevo::operator::MutationOp;
+use log::error;
+use rand::Rng;
+use rand::rngs::ThreadRng;
+use rand::thread_rng;
+use rayon::prelude::*;
+use crate::ga::encoding::delay_encoding::DelayGenotype;
+use crate::ga::encoding::priority_encoding::PriorityGenotype;
+use crate::ga::encoding::{ExtendedGenotype, ExtendedPhenotype, num_genes};
+use crate::ga::evaluation::ReevaluationPolicy;
+use crate::ga::fitness::{ExtendedFitness, FitnessCache, FitnessCalculation, SchedulerHandler, SchedulerHandlerTrait};
+use crate::ga::genetic_algorithm::{create_ga_writer, GaStepInfo};
+use crate::ga::mutation::{DelayMutationType, DelayMutator, MutationAdaptation, PermutationMutator, SelfAdaptiveGenomeMutation};
+use crate::ga::parameters::GaConfiguration;
+use crate::ga::symmetry::NodeSymmetry;
+use crate::locality::{sample_delays_genotype, sample_n_neighbors_delay_genotypes, sample_n_neighbors_priority_genotypes, sample_priority_genotype};
+
+/// The single solution search strategies
+/// HillClimbing: Move to the best of the sampled neighbours if it improves, restart randomly when stuck
+/// SimulatedAnnealing: Move to a sampled neighbour, worse neighbours are accepted with probability exp(delta / temperature)
+/// OnePlusOne: (1+1)-EA, the mutated offspring replaces the parent if it is at least as fit
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum LocalSearchStrategy {
+    HillClimbing,
+    SimulatedAnnealing,
+    OnePlusOne,
+}
+
+/// Parameters of the local search strategies as set in the configuration
+/// neighbours: The number of neighbours hill climbing samples (and evaluates in parallel) per step, at most one per gene
+/// restart_after: The number of steps without improvement after which hill climbing restarts from a random solution
+/// initial_temperature and cooling_rate: The geometric cooling schedule of simulated annealing
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LocalSearchConfiguration {
+    pub neighbours: usize,
+    pub restart_after: usize,
+    pub initial_temperature: f64,
+    pub cooling_rate: f64,
+}
+
+impl Default for LocalSearchConfiguration {
+    fn default() -> Self {
+        Self {
+            neighbours: 4,
+            restart_after: 3,
+            initial_temperature: 10.0,
+            cooling_rate: 0.95,
+        }
+    }
+}
+
+impl LocalSearchConfiguration {
+    /// Check the parameters for values the search cannot run with, returns all problems found
+    /// max_neighbours: The number of distinct neighbours of a solution of the hill climbing scheduler that is run, None if no hill climbing is run
+    pub fn validate(&self, max_neighbours: Option<usize>) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.neighbours == 0 {
+            errors.push("neighbours must be at least 1".to_string());
+        }
+        if let Some(max_neighbours) = max_neighbours {
+            if self.neighbours > max_neighbours {
+                errors.push(format!("neighbours ({}) must be at most the number of distinct neighbours of a solution ({})", self.neighbours, max_neighbours));
+            }
+        }
+        if self.initial_temperature <= 0.0 {
+            errors.push(format!("initial_temperature ({}) must be positive", self.initial_temperature));
+        }
+        if !(self.cooling_rate > 0.0 && self.cooling_rate <= 1.0) {
+            errors.push(format!("cooling_rate ({}) must be in (0, 1]", self.cooling_rate));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// The search space of a local search, creates random solutions, neighbours and mutants
+pub trait Neighbourhood<G>: Send {
+    fn random_solution(&self, rng: &mut ThreadRng) -> G;
+
+    fn neighbours(&self, n: usize, solution: &G, rng: &mut ThreadRng) -> Vec<G>;
+
+    fn mutate(&self, solution: G, rng: &mut ThreadRng) -> G;
+
+    /// Adapt the mutation after a (1+1)-EA step, returns the adapted parameters for the GA log
+    fn adapt_mutation(&self, _success: bool, _progress: f64) -> Option<MutationAdaptation> {
+        None
+    }
+}
+
+/// Neighbourhood of delay genotypes, neighbours differ by one in a single gene
+#[derive(Clone, Debug)]
+pub struct DelayNeighbourhood {
+    num_genes: usize,
+    min_delay: u32,
+    max_delay: u32,
+    mutator: DelayMutator,
+}
+
+impl DelayNeighbourhood {
+    pub fn new(ga_configuration: &GaConfiguration) -> Self {
+        Self {
+            num_genes: ga_configuration.num_delay_genes(),
+            min_delay: ga_configuration.min_delay,
+            max_delay: ga_configuration.max_delay,
+            mutator: DelayMutator::new(ga_configuration.delay_mutation.clone(), ga_configuration.mutation_rate, ga_configuration.mutation_std, ga_configuration.min_mutation_std, ga_configuration.min_delay, ga_configuration.max_delay),
+        }
+    }
+}
+
+impl Neighbourhood<DelayGenotype> for DelayNeighbourhood {
+    fn random_solution(&self, rng: &mut ThreadRng) -> DelayGenotype {
+        let delays = sample_delays_genotype(self.num_genes, self.min_delay, self.max_delay, rng);
+        match self.mutator.mutation_type() {
+            DelayMutationType::SelfAdaptive => DelayGenotype::with_standard_deviations(delays, self.mutator.current_standard_deviation()),
+            _ => delays,
+        }
+    }
+
+    fn neighbours(&self, n: usize, solution: &DelayGenotype, rng: &mut ThreadRng) -> Vec<DelayGenotype> {
+        match self.mutator.mutation_type() {
+            // Only the delays have neighbours, the standard deviations are kept
+            DelayMutationType::SelfAdaptive => {
+                let (delays, standard_deviations) = DelayGenotype::split_standard_deviations(solution.clone());
+                sample_n_neighbors_delay_genotypes(n, &delays, self.min_delay, self.max_delay, rng).into_iter()
+                    .map(|neighbour| DelayGenotype::join_standard_deviations(neighbour, standard_deviations.clone()))
+                    .collect()
+            }
+            _ => sample_n_neighbors_delay_genotypes(n, solution, self.min_delay, self.max_delay, rng),
+        }
+    }
+
+    fn mutate(&self, solution: DelayGenotype, rng: &mut ThreadRng) -> DelayGenotype {
+        self.mutator.mutate(solution, rng)
+    }
+
+    fn adapt_mutation(&self, success: bool, progress: f64) -> Option<MutationAdaptation> {
+        let success_rate = if success { 1.0 } else { 0.0 };
+        Some(MutationAdaptation {
+            mutation_type: self.mutator.mutation_type().clone(),
+            standard_deviation: self.mutator.adapt(success_rate, progress),
+            success_rate,
+            mean_gene_standard_deviation: None,
+        })
+    }
+}
+
+/// Neighbourhood of priority genotypes, neighbours have two adjacent genes swapped
+#[derive(Clone, Debug)]
+pub struct PriorityNeighbourhood {
+    num_genes: usize,
+    mutator: PermutationMutator,
+}
+
+impl PriorityNeighbourhood {
+    pub fn new(ga_configuration: &GaConfiguration) -> Self {
+        Self {
+            num_genes: num_genes(),
+            mutator: PermutationMutator::new(ga_configuration.priority_mutation.clone(), ga_configuration.mutation_rate),
+        }
+    }
+}
+
+impl Neighbourhood<PriorityGenotype> for PriorityNeighbourhood {
+    fn random_solution(&self, rng: &mut ThreadRng) -> PriorityGenotype {
+        sample_priority_genotype(self.num_genes, rng)
+    }
+
+    fn neighbours(&self, n: usize, solution: &PriorityGenotype, rng: &mut ThreadRng) -> Vec<PriorityGenotype> {
+        sample_n_neighbors_priority_genotypes(n, solution, rng)
+    }
+
+    fn mutate(&self, solution: PriorityGenotype, rng: &mut ThreadRng) -> PriorityGenotype {
+        self.mutator.mutate(solution, rng)
+    }
+}
+
+/// Whether the search moves from a solution with the current fitness to the candidate
+pub fn accept_candidate(strategy: LocalSearchStrategy, current_fitness: f64, candidate_fitness: f64, temperature: f64, rng: &mut impl Rng) -> bool {
+    match strategy {
+        LocalSearchStrategy::HillClimbing => candidate_fitness > current_fitness,
+        LocalSearchStrategy::OnePlusOne => candidate_fitness >= current_fitness,
+        LocalSearchStrategy::SimulatedAnnealing => {
+            candidate_fitness >= current_fitness
+                || rng.gen_bool(annealing_acceptance_probability(current_fitness, candidate_fitness, temperature))
+        }
+    }
+}
+
+/// Metropolis criterion: probability of accepting a candidate that is worse than the current solution
+pub fn annealing_acceptance_probability(current_fitness: f64, candidate_fitness: f64, temperature: f64) -> f64 {
+    if candidate_fitness >= current_fitness {
+        return 1.0;
+    }
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+    ((candidate_fitness - current_fitness) / temperature).exp().clamp(0.0, 1.0)
+}
+
+/// Run a local search strategy on delay or priority genotypes until the search budget is exceeded
+/// Every scheduler sender and receiver pair belongs to one cluster, the neighbours of a hill climbing step are evaluated in parallel
+/// Every step is written to the GA log in the same format as a GA generation
+pub fn run_local_search<T, G, P, N>(
+    strategy: LocalSearchStrategy,
+    configuration: LocalSearchConfiguration,
+    policy: ReevaluationPolicy,
+    neighbourhood: N,
+    scheduler_senders: Vec<Sender<P>>,
+    scheduler_receivers: Vec<Receiver<T>>,
+    search_budget: Duration,
+)
+    where T: ExtendedFitness, G: ExtendedGenotype + serde::Serialize + 'static, P: ExtendedPhenotype<G> + 'static, N: Neighbourhood<G>
+{
+    let (fitness_sender, fitness_receiver) = channel();
+    let fitness_values: FitnessCache<G, T> = Arc::new(RwLock::new(HashMap::new()));
+    let scheduler_handler = SchedulerHandler::<T, G, P>::new(scheduler_senders, scheduler_receivers, fitness_receiver, fitness_values.clone());
+    let fitness_calculation = FitnessCalculation::new(fitness_values, fitness_sender, policy)
+        .with_symmetry(NodeSymmetry::from_config());
+    thread::spawn(move || scheduler_handler.run());
+
+    let mut ga_writer = create_ga_writer();
+    match serde_json::to_writer_pretty(&mut ga_writer, &(strategy, &configuration)) {
+        Ok(_) => {}
+        Err(err) => error!("Failed writing to ga file: {}", err)
+    };
+    ga_writer.flush().expect("GA writer flush failed");
+    println!("Starting {:?} with: {:?}", strategy, configuration);
+
+    let rng = &mut thread_rng();
+    let start_time = Utc::now();
+    let mut current = neighbourhood.random_solution(rng);
+    let mut current_fitness = fitness_calculation.fitness_of(&current);
+    let mut best = current.clone();
+    let mut best_fitness = current_fitness.clone();
+    let mut temperature = configuration.initial_temperature;
+    let mut steps_without_improvement = 0;
+    let mut iteration = 0;
+    while Utc::now() - start_time < search_budget {
+        let step_start = Utc::now();
+        iteration += 1;
+        fitness_calculation.next_generation();
+        // Under noise the current solution is re-tested, as an elite would be in the GA
+        current_fitness = fitness_calculation.fitness_of(&current);
+
+        let candidates = match strategy {
+            LocalSearchStrategy::HillClimbing => neighbourhood.neighbours(configuration.neighbours, &current, rng),
+            LocalSearchStrategy::SimulatedAnnealing => neighbourhood.neighbours(1, &current, rng),
+            LocalSearchStrategy::OnePlusOne => vec![neighbourhood.mutate(current.clone(), rng)],
+        };
+        if candidates.is_empty() {
+            error!("No candidates could be created from the current solution");
+            break;
+        }
+        let candidate_fitness: Vec<T> = candidates.par_iter()
+            .map(|candidate| fitness_calculation.fitness_of(candidate))
+            .collect();
+        let (best_candidate, best_candidate_fitness) = candidates.iter()
+            .zip(candidate_fitness.iter())
+            .max_by(|a, b| a.1.cmp(b.1))
+            .unwrap();
+
+        let improved = best_candidate_fitness > &current_fitness;
+        if accept_candidate(strategy, current_fitness.as_scalar(), best_candidate_fitness.as_scalar(), temperature, rng) {
+            if improved {
+                steps_without_improvement = 0;
+            }
+            current = best_candidate.clone();
+            current_fitness = best_candidate_fitness.clone();
+        } else {
+            steps_without_improvement += 1;
+        }
+        if current_fitness > best_fitness {
+            best = current.clone();
+            best_fitness = current_fitness.clone();
+        }
+        temperature *= configuration.cooling_rate;
+        let mutation_adaptation = match strategy {
+            LocalSearchStrategy::OnePlusOne => {
+                let progress = (Utc::now() - start_time).num_milliseconds() as f64 / search_budget.num_milliseconds().max(1) as f64;
+                neighbourhood.adapt_mutation(improved, progress)
+            }
+            _ => None,
+        };
+        if strategy == LocalSearchStrategy::HillClimbing && steps_without_improvement >= configuration.restart_after {
+            println!("No improvement in {} steps, restarting from a random solution", steps_without_improvement);
+            current = neighbourhood.random_solution(rng);
+            current_fitness = fitness_calculation.fitness_of(&current);
+            steps_without_improvement = 0;
+        }
+
+        let best_fitness_samples = fitness_calculation.samples_of(&best).map(|samples| samples.summary());
+        let mut step_info: GaStepInfo<T, G> = GaStepInfo::new(
+            iteration,
+            T::average(&candidate_fitness),
+            best_fitness.clone(),
+            best_fitness_samples,
+            best.clone(),
+            Utc::now() - step_start,
+        );
+        if let Some(mutation_adaptation) = mutation_adaptation {
+            step_info = step_info.with_mutation_adaptation(mutation_adaptation);
+        }
+        println!("{}", serde_json::to_string(&step_info).unwrap());
+        match serde_json::to_writer_pretty(&mut ga_writer, &step_info) {
+            Ok(_) => {}
+            Err(err) => error!("Failed writing to ga file: {}", err)
+        };
+        ga_writer.flush().expect("GA writer flush failed");
+    }
+    println!("Exiting: Search budget exceeded.");
+    println!("{}", P::from_genes(&best).display_genotype_by_message());
+    std::process::exit(0);
+}
+
+#[cfg(test)]
+mod local_search_tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::ga::local_search::{accept_candidate, annealing_acceptance_probability, LocalSearchConfiguration, LocalSearchStrategy};
+
+    #[test]
+    fn test_acceptance() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        assert!(!accept_candidate(LocalSearchStrategy::HillClimbing, 5.0, 5.0, 1.0, &mut rng));
+        assert!(accept_candidate(LocalSearchStrategy::HillClimbing, 5.0, 6.0, 1.0, &mut rng));
+        assert!(accept_candidate(LocalSearchStrategy::OnePlusOne, 5.0, 5.0, 1.0, &mut rng));
+        assert!(!accept_candidate(LocalSearchStrategy::OnePlusOne, 5.0, 4.0, 1.0, &mut rng));
+        assert!(accept_candidate(LocalSearchStrategy::SimulatedAnnealing, 5.0, 6.0, 0.0, &mut rng));
+        assert!(!accept_candidate(LocalSearchStrategy::SimulatedAnnealing, 5.0, 4.0, 0.0, &mut rng));
+    }
+
+    #[test]
+    fn test_annealing_acceptance_probability() {
+        assert_eq!(annealing_acceptance_probability(5.0, 6.0, 1.0), 1.0);
+        assert_eq!(annealing_acceptance_probability(5.0, 4.0, 1.0), (-1.0f64).exp());
+        assert!(annealing_acceptance_probability(5.0, 4.0, 10.0) > annealing_acceptance_probability(5.0, 4.0, 1.0));
+        assert_eq!(annealing_acceptance_probability(5.0, 4.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_validate_configuration() {
+        assert!(LocalSearchConfiguration::default().validate(None).is_ok());
+        let configuration = LocalSearchConfiguration { neighbours: 0, restart_after: 3, initial_temperature: 0.0, cooling_rate: 1.5 };
+        assert_eq!(configuration.validate(None).unwrap_err().len(), 3);
+        // Hill climbing cannot sample more distinct neighbours than there are genes
+        assert!(LocalSearchConfiguration::default().validate(Some(4)).is_ok());
+        assert_eq!(LocalSearchConfiguration::default().validate(Some(3)).unwrap_err().len(), 1);
+    }
+}
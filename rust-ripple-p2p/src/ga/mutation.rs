@@ -1,10 +1,18 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::{Arc, RwLock};
 use rand_distr::{Normal, Distribution};
 use genevo::{
     random::{random_index, Rng},
-    operator::{GeneticOperator, MutationOp},
+    genetic::{Children, Parents},
+    operator::{CrossoverOp, GeneticOperator, MutationOp},
     prelude::Genotype,
 };
+use genevo::mutation::order::{InsertOrderMutator, SwapOrderMutator};
+use rand::seq::SliceRandom;
+use crate::ga::encoding::hybrid_encoding::HybridGenotype;
+use crate::ga::encoding::priority_encoding::PriorityGenotype;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct NoMutation {}
@@ -65,6 +73,399 @@ impl<G> MutationOp<G> for GaussianMutator
     }
 }
 
+/// The mutation operators for delay genotypes that can be set in the configuration
+/// Gaussian: Gaussian mutation with a fixed standard deviation
+/// OneFifthRule: Gaussian mutation, the standard deviation grows if more than a fifth of the offspring of a generation is fitter than its parents and shrinks otherwise
+/// SelfAdaptive: Every delay gene has its own standard deviation, carried in the second half of the genotype and mutated log-normally, crossover leaves it alone
+/// Annealed: Gaussian mutation, the standard deviation shrinks exponentially from mutation_std to min_mutation_std over the search budget
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum DelayMutationType {
+    Gaussian,
+    OneFifthRule,
+    SelfAdaptive,
+    Annealed,
+    NoMutation,
+}
+
+/// Factor of the 1/5th success rule, the standard deviation is divided by it on success and multiplied by it otherwise
+pub const ONE_FIFTH_RULE_FACTOR: f64 = 0.817;
+
+/// The adapted mutation parameters after a generation, written to the GA log
+/// standard_deviation: The standard deviation of the Gaussian, OneFifthRule and Annealed mutations
+/// success_rate: The fraction of offspring that is fitter than the fittest of its parents
+/// mean_gene_standard_deviation: The mean per-gene standard deviation in the population for SelfAdaptive mutation
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct MutationAdaptation {
+    pub mutation_type: DelayMutationType,
+    pub standard_deviation: f64,
+    pub success_rate: f64,
+    pub mean_gene_standard_deviation: Option<f64>,
+}
+
+/// Mutation operator for delay genotypes as set in the configuration
+/// Clones share the adapted standard deviation, so the GA loop can adapt the operator that is used by the GA
+#[derive(Clone, Debug)]
+pub struct DelayMutator {
+    mutation_type: DelayMutationType,
+    mutation_rate: f64,
+    standard_deviation: f64,
+    min_standard_deviation: f64,
+    min_delay: u32,
+    max_delay: u32,
+    adapted_standard_deviation: Arc<RwLock<f64>>,
+}
+
+impl DelayMutator {
+    pub fn new(mutation_type: DelayMutationType, mutation_rate: f64, standard_deviation: f64, min_standard_deviation: f64, min_delay: u32, max_delay: u32) -> Self {
+        Self {
+            mutation_type,
+            mutation_rate,
+            standard_deviation,
+            min_standard_deviation,
+            min_delay,
+            max_delay,
+            adapted_standard_deviation: Arc::new(RwLock::new(standard_deviation)),
+        }
+    }
+
+    pub fn mutation_type(&self) -> &DelayMutationType {
+        &self.mutation_type
+    }
+
+    /// The standard deviation used for the next mutations
+    pub fn current_standard_deviation(&self) -> f64 {
+        *self.adapted_standard_deviation.read().unwrap()
+    }
+
+    /// Adapt the standard deviation after a generation
+    /// success_rate: The fraction of successful offspring, progress: The fraction of the search budget that is used
+    pub fn adapt(&self, success_rate: f64, progress: f64) -> f64 {
+        let mut standard_deviation = self.adapted_standard_deviation.write().unwrap();
+        match self.mutation_type {
+            DelayMutationType::OneFifthRule => {
+                if success_rate > 0.2 {
+                    *standard_deviation /= ONE_FIFTH_RULE_FACTOR;
+                } else if success_rate < 0.2 {
+                    *standard_deviation *= ONE_FIFTH_RULE_FACTOR;
+                }
+                *standard_deviation = standard_deviation.max(self.min_standard_deviation);
+            }
+            DelayMutationType::Annealed => {
+                *standard_deviation = annealed_standard_deviation(self.standard_deviation, self.min_standard_deviation, progress);
+            }
+            _ => {}
+        }
+        *standard_deviation
+    }
+}
+
+/// Standard deviation that shrinks exponentially from initial to min over the progress in [0, 1]
+pub fn annealed_standard_deviation(initial_standard_deviation: f64, min_standard_deviation: f64, progress: f64) -> f64 {
+    if initial_standard_deviation <= min_standard_deviation {
+        return initial_standard_deviation;
+    }
+    initial_standard_deviation * (min_standard_deviation / initial_standard_deviation).powf(progress.clamp(0.0, 1.0))
+}
+
+impl GeneticOperator for DelayMutator {
+    fn name() -> String {
+        "Delay-Mutator".to_string()
+    }
+}
+
+impl<G> MutationOp<G> for DelayMutator
+    where
+        G: Genotype + GaussianGenomeMutation + SelfAdaptiveGenomeMutation,
+{
+    fn mutate<R>(&self, genome: G, rng: &mut R) -> G
+        where
+            R: Rng + Sized,
+    {
+        match self.mutation_type {
+            DelayMutationType::Gaussian => GaussianMutator::new(self.mutation_rate, self.standard_deviation).mutate(genome, rng),
+            DelayMutationType::OneFifthRule | DelayMutationType::Annealed =>
+                GaussianMutator::new(self.mutation_rate, self.current_standard_deviation()).mutate(genome, rng),
+            DelayMutationType::SelfAdaptive =>
+                SelfAdaptiveGenomeMutation::mutate_self_adaptive(genome, self.min_standard_deviation, self.min_delay, self.max_delay, rng),
+            DelayMutationType::NoMutation => genome,
+        }
+    }
+}
+
+/// Genotypes that carry a standard deviation per gene in their second half
+pub trait SelfAdaptiveGenomeMutation: Genotype {
+    /// Mutate the standard deviations log-normally, then mutate every gene with its own standard deviation within [min_value, max_value]
+    fn mutate_self_adaptive<R>(genome: Self, min_standard_deviation: f64, min_value: u32, max_value: u32, rng: &mut R) -> Self
+        where
+            R: Rng + Sized;
+
+    /// Append the initial standard deviation for every gene
+    fn with_standard_deviations(genome: Self, standard_deviation: f64) -> Self;
+
+    /// Split the genome into its genes and their standard deviations
+    fn split_standard_deviations(genome: Self) -> (Self, Self);
+
+    /// Inverse of split_standard_deviations
+    fn join_standard_deviations(genes: Self, standard_deviations: Self) -> Self;
+
+    fn mean_standard_deviation(&self) -> Option<f64>;
+}
+
+impl SelfAdaptiveGenomeMutation for Vec<u32> {
+    fn mutate_self_adaptive<R>(genome: Self, min_standard_deviation: f64, min_value: u32, max_value: u32, rng: &mut R) -> Self
+        where
+            R: Rng + Sized,
+    {
+        let num_genes = genome.len() / 2;
+        if num_genes == 0 {
+            return genome;
+        }
+        // Learning rates of Schwefel for the common and the per-gene factor
+        let tau_common = 1.0 / (2.0 * num_genes as f64).sqrt();
+        let tau_gene = 1.0 / (2.0 * (num_genes as f64).sqrt()).sqrt();
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let common = tau_common * normal.sample(rng);
+        let mut mutated = genome;
+        for i in 0..num_genes {
+            let standard_deviation = (mutated[num_genes + i] as f64 * (common + tau_gene * normal.sample(rng)).exp())
+                .max(min_standard_deviation);
+            mutated[num_genes + i] = standard_deviation.round() as u32;
+            mutated[i] = (mutated[i] as f64 + standard_deviation * normal.sample(rng)).round().clamp(min_value as f64, max_value as f64) as u32;
+        }
+        mutated
+    }
+
+    fn with_standard_deviations(genome: Self, standard_deviation: f64) -> Self {
+        let num_genes = genome.len();
+        let mut extended = genome;
+        extended.extend(vec![standard_deviation.round() as u32; num_genes]);
+        extended
+    }
+
+    fn split_standard_deviations(genome: Self) -> (Self, Self) {
+        let mut genes = genome;
+        let standard_deviations = genes.split_off(genes.len() / 2);
+        (genes, standard_deviations)
+    }
+
+    fn join_standard_deviations(genes: Self, standard_deviations: Self) -> Self {
+        let mut joined = genes;
+        joined.extend(standard_deviations);
+        joined
+    }
+
+    fn mean_standard_deviation(&self) -> Option<f64> {
+        let num_genes = self.len() / 2;
+        if num_genes == 0 {
+            return None;
+        }
+        Some(self[num_genes..].iter().map(|x| *x as f64).sum::<f64>() / (self.len() - num_genes) as f64)
+    }
+}
+
+/// The parents of the offspring of a generation, so the success of the mutation can be measured per offspring
+/// Clones share the records, so the GA loop reads what the operators in the GA recorded
+#[derive(Clone, Debug)]
+pub struct Lineage<G> {
+    crossover_parents: Arc<RwLock<HashMap<G, Vec<G>>>>,
+    offspring: Arc<RwLock<Vec<(G, Vec<G>)>>>,
+}
+
+impl<G: Genotype + Eq + Hash> Lineage<G> {
+    pub fn new() -> Self {
+        Self { crossover_parents: Arc::new(RwLock::new(HashMap::new())), offspring: Arc::new(RwLock::new(vec![])) }
+    }
+
+    /// The offspring since the last call with the parents they were bred from
+    pub fn take_offspring(&self) -> Vec<(G, Vec<G>)> {
+        self.crossover_parents.write().unwrap().clear();
+        std::mem::take(&mut *self.offspring.write().unwrap())
+    }
+}
+
+impl<G: Genotype + Eq + Hash> Default for Lineage<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Records the parents of every child of the crossover operator
+#[derive(Clone, Debug)]
+pub struct TrackedCrossover<C, G> {
+    crossover: C,
+    lineage: Lineage<G>,
+}
+
+impl<C, G> TrackedCrossover<C, G> {
+    pub fn new(crossover: C, lineage: Lineage<G>) -> Self {
+        Self { crossover, lineage }
+    }
+}
+
+impl<C: GeneticOperator, G: Clone> GeneticOperator for TrackedCrossover<C, G> {
+    fn name() -> String {
+        C::name()
+    }
+}
+
+impl<C, G> CrossoverOp<G> for TrackedCrossover<C, G>
+    where
+        C: CrossoverOp<G>,
+        G: Genotype + Eq + Hash,
+{
+    fn crossover<R>(&self, parents: Parents<G>, rng: &mut R) -> Children<G>
+        where
+            R: Rng + Sized,
+    {
+        let children = self.crossover.crossover(parents.clone(), rng);
+        let mut crossover_parents = self.lineage.crossover_parents.write().unwrap();
+        for child in &children {
+            crossover_parents.insert(child.clone(), parents.clone());
+        }
+        children
+    }
+}
+
+/// Records every mutated offspring with the parents of the genome it was mutated from, which is its own parent if it is not a crossover child
+#[derive(Clone, Debug)]
+pub struct TrackedMutation<M, G> {
+    mutation: M,
+    lineage: Lineage<G>,
+}
+
+impl<M, G> TrackedMutation<M, G> {
+    pub fn new(mutation: M, lineage: Lineage<G>) -> Self {
+        Self { mutation, lineage }
+    }
+}
+
+impl<M: GeneticOperator, G: Clone> GeneticOperator for TrackedMutation<M, G> {
+    fn name() -> String {
+        M::name()
+    }
+}
+
+impl<M, G> MutationOp<G> for TrackedMutation<M, G>
+    where
+        M: MutationOp<G>,
+        G: Genotype + Eq + Hash,
+{
+    fn mutate<R>(&self, genome: G, rng: &mut R) -> G
+        where
+            R: Rng + Sized,
+    {
+        let parents = self.lineage.crossover_parents.read().unwrap().get(&genome).cloned().unwrap_or_else(|| vec![genome.clone()]);
+        let mutated = self.mutation.mutate(genome, rng);
+        self.lineage.offspring.write().unwrap().push((mutated.clone(), parents));
+        mutated
+    }
+}
+
+/// The mutation operators for priority genotypes that can be set in the configuration
+/// The number of mutations per genome is mutation_rate * genome length, rounded randomly
+/// Swap: genevo swap of two genes
+/// Insertion: genevo insertion of a gene at another position
+/// Inversion: reverse a random segment
+/// Scramble: shuffle a random segment
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum PermutationMutationType {
+    Swap,
+    Insertion,
+    Inversion,
+    Scramble,
+    NoMutation,
+}
+
+/// Mutation operator for priority genotypes as set in the configuration
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct PermutationMutator {
+    mutation_type: PermutationMutationType,
+    mutation_rate: f64,
+}
+
+impl PermutationMutator {
+    pub fn new(mutation_type: PermutationMutationType, mutation_rate: f64) -> Self {
+        Self { mutation_type, mutation_rate }
+    }
+}
+
+impl GeneticOperator for PermutationMutator {
+    fn name() -> String {
+        "Permutation-Mutator".to_string()
+    }
+}
+
+impl MutationOp<PriorityGenotype> for PermutationMutator {
+    fn mutate<R>(&self, genome: PriorityGenotype, rng: &mut R) -> PriorityGenotype
+        where
+            R: Rng + Sized,
+    {
+        match self.mutation_type {
+            PermutationMutationType::Swap => SwapOrderMutator::new(self.mutation_rate).mutate(genome, rng),
+            PermutationMutationType::Insertion => InsertOrderMutator::new(self.mutation_rate).mutate(genome, rng),
+            PermutationMutationType::Inversion => mutate_segments(genome, self.mutation_rate, rng, |segment, _| segment.reverse()),
+            PermutationMutationType::Scramble => mutate_segments(genome, self.mutation_rate, rng, |segment, rng| segment.shuffle(rng)),
+            PermutationMutationType::NoMutation => genome,
+        }
+    }
+}
+
+/// Mutation operator for hybrid genotypes, the delays and priorities are mutated by their own operator
+/// Clones share the adapted standard deviation of the delay mutator
+#[derive(Clone, Debug)]
+pub struct HybridMutator {
+    delay_mutator: DelayMutator,
+    priority_mutator: PermutationMutator,
+}
+
+impl HybridMutator {
+    pub fn new(delay_mutator: DelayMutator, priority_mutator: PermutationMutator) -> Self {
+        Self { delay_mutator, priority_mutator }
+    }
+
+    pub fn delay_mutator(&self) -> &DelayMutator {
+        &self.delay_mutator
+    }
+}
+
+impl GeneticOperator for HybridMutator {
+    fn name() -> String {
+        "Hybrid-Mutator".to_string()
+    }
+}
+
+impl MutationOp<HybridGenotype> for HybridMutator {
+    fn mutate<R>(&self, genome: HybridGenotype, rng: &mut R) -> HybridGenotype
+        where
+            R: Rng + Sized,
+    {
+        HybridGenotype::new(
+            self.delay_mutator.mutate(genome.delays, rng),
+            self.priority_mutator.mutate(genome.priorities, rng),
+        )
+    }
+}
+
+/// Apply a segment mutation (mutation_rate * genome length) times to random segments of the genome
+fn mutate_segments<R, M>(genome: PriorityGenotype, mutation_rate: f64, rng: &mut R, mutate_segment: M) -> PriorityGenotype
+    where
+        R: Rng + Sized,
+        M: Fn(&mut [usize], &mut R),
+{
+    let genome_length = genome.len();
+    if genome_length < 2 {
+        return genome;
+    }
+    let num_mutations = ((genome_length as f64 * mutation_rate) + rng.gen::<f64>()).floor() as usize;
+    let mut mutated = genome;
+    for _ in 0..num_mutations {
+        let cut_points = rand::seq::index::sample(rng, genome_length, 2);
+        let (start, end) = (cut_points.index(0).min(cut_points.index(1)), cut_points.index(0).max(cut_points.index(1)));
+        mutate_segment(&mut mutated[start..=end], rng);
+    }
+    mutated
+}
+
 pub trait GaussianGenomeMutation: Genotype {
     type Dna: Clone;
 
@@ -153,10 +554,12 @@ impl_gaussian_mutation_integer!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isi
 
 #[cfg(test)]
 mod tests {
-    use genevo::operator::MutationOp;
+    use genevo::operator::{CrossoverOp, MutationOp};
+    use genevo::operator::prelude::UniformCrossBreeder;
     use genevo::prelude::Seed;
     use genevo::random::get_rng;
-    use crate::ga::mutation::GaussianMutator;
+    use itertools::Itertools;
+    use crate::ga::mutation::{annealed_standard_deviation, DelayMutationType, DelayMutator, GaussianMutator, Lineage, NoMutation, ONE_FIFTH_RULE_FACTOR, PermutationMutationType, PermutationMutator, SelfAdaptiveGenomeMutation, TrackedCrossover, TrackedMutation};
 
     #[test]
     fn gaussian_mutation_int() {
@@ -175,4 +578,76 @@ mod tests {
         let mut rng = get_rng(seed);
         assert_eq!(vec![514.6929837294464, 494.54409703529524, 500.0, 496.14762940270674], mutator.mutate(chromosome, &mut rng));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn permutation_mutations_keep_permutation() {
+        let chromosome: Vec<usize> = (0..20).collect();
+        let mut rng = get_rng(Seed::default());
+        for mutation_type in [PermutationMutationType::Swap, PermutationMutationType::Insertion, PermutationMutationType::Inversion, PermutationMutationType::Scramble] {
+            let mutator = PermutationMutator::new(mutation_type, 0.2);
+            let mutated = mutator.mutate(chromosome.clone(), &mut rng);
+            assert_eq!(mutated.iter().cloned().sorted().collect_vec(), chromosome);
+        }
+    }
+
+    #[test]
+    fn one_fifth_rule() {
+        let mutator = DelayMutator::new(DelayMutationType::OneFifthRule, 0.05, 40.0, 1.0, 0, 4000);
+        let clone = mutator.clone();
+        assert_eq!(mutator.adapt(0.5, 0.0), 40.0 / ONE_FIFTH_RULE_FACTOR);
+        assert_eq!(clone.current_standard_deviation(), 40.0 / ONE_FIFTH_RULE_FACTOR);
+        assert_eq!(mutator.adapt(0.2, 0.0), 40.0 / ONE_FIFTH_RULE_FACTOR);
+        assert!((mutator.adapt(0.0, 0.0) - 40.0).abs() < 1e-9);
+        for _ in 0..100 {
+            mutator.adapt(0.0, 0.0);
+        }
+        assert_eq!(mutator.current_standard_deviation(), 1.0);
+    }
+
+    #[test]
+    fn annealed_schedule() {
+        assert_eq!(annealed_standard_deviation(40.0, 1.0, 0.0), 40.0);
+        assert_eq!(annealed_standard_deviation(40.0, 1.0, 1.0), 1.0);
+        assert!(annealed_standard_deviation(40.0, 1.0, 0.5) < 40.0 && annealed_standard_deviation(40.0, 1.0, 0.5) > 1.0);
+        assert_eq!(annealed_standard_deviation(40.0, 1.0, 2.0), 1.0);
+        let mutator = DelayMutator::new(DelayMutationType::Annealed, 0.05, 40.0, 1.0, 0, 4000);
+        assert_eq!(mutator.adapt(0.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn tracked_operators_record_parents() {
+        let lineage = Lineage::new();
+        let crossover = TrackedCrossover::new(UniformCrossBreeder::new(), lineage.clone());
+        let mutation = TrackedMutation::new(NoMutation {}, lineage.clone());
+        let mut rng = get_rng(Seed::default());
+        let parents = vec![vec![1u32, 2, 3], vec![4, 5, 6]];
+        for child in crossover.crossover(parents.clone(), &mut rng) {
+            mutation.mutate(child, &mut rng);
+        }
+        // A genome that did not go through crossover is its own parent
+        mutation.mutate(vec![7, 8, 9], &mut rng);
+        let offspring = lineage.take_offspring();
+        assert_eq!(offspring.len(), 3);
+        assert!(offspring[..2].iter().all(|(_, child_parents)| *child_parents == parents));
+        assert_eq!(offspring[2], (vec![7, 8, 9], vec![vec![7, 8, 9]]));
+        assert!(lineage.take_offspring().is_empty());
+    }
+
+    #[test]
+    fn self_adaptive_mutation() {
+        let chromosome = <Vec<u32>>::with_standard_deviations(vec![500, 500, 500, 500], 10.0);
+        assert_eq!(chromosome, vec![500, 500, 500, 500, 10, 10, 10, 10]);
+        assert_eq!(chromosome.mean_standard_deviation(), Some(10.0));
+        let mut rng = get_rng(Seed::default());
+        let mutated = <Vec<u32>>::mutate_self_adaptive(chromosome.clone(), 2.0, 0, 4000, &mut rng);
+        assert_eq!(mutated.len(), chromosome.len());
+        assert_ne!(mutated[..4], chromosome[..4]);
+        assert!(mutated[4..].iter().all(|x| *x >= 2));
+        // Large standard deviations do not push the delays out of their bounds
+        let chromosome = <Vec<u32>>::with_standard_deviations(vec![0, 10, 490, 500], 1000.0);
+        for _ in 0..10 {
+            let mutated = <Vec<u32>>::mutate_self_adaptive(chromosome.clone(), 2.0, 0, 500, &mut rng);
+            assert!(mutated[..4].iter().all(|x| *x <= 500));
+        }
+    }
+}
@@ -2,13 +2,158 @@ use std::marker::PhantomData;
 use genevo::genetic::Genotype;
 use genevo::operator::{CrossoverOp, SelectionOp};
 use genevo::operator::prelude::{RouletteWheelSelector};
-use crate::ga::crossover::{SimulatedBinaryCrossBreeder};
-use crate::ga::encoding::delay_encoding::DelayGenotype;
+use crate::ga::crossover::{DelayCrossBreeder, DelayCrossoverType, HybridCrossBreeder, PermutationCrossBreeder, PermutationCrossoverType, SimulatedBinaryCrossBreeder};
+use crate::ga::encoding::delay_encoding::{DelayEncodingType, DelayGenotype};
+use crate::ga::encoding::hybrid_encoding::HybridGenotype;
 use crate::ga::encoding::{num_genes, SuperExtendedGenotype};
+use crate::ga::evaluation::ReevaluationPolicy;
 use crate::ga::fitness::ExtendedFitness;
 use crate::ga::genetic_algorithm::{ConsensusMessageType};
 use crate::ga::encoding::priority_encoding::{PriorityGenotype};
-use crate::ga::selection::MuLambdaSelector;
+use crate::ga::mutation::{DelayMutationType, PermutationMutationType};
+use crate::ga::reinsertion::ReinsertionType;
+use crate::ga::selection::{ConfiguredSelector, SelectionType};
+use crate::node_state::ConsensusPhase;
+use crate::PEER_GRAPH;
+use crate::topology::PeerGraph;
+
+/// GA hyperparameters and operators as set in the configuration
+/// population_size: The size of the initial population
+/// mu: The number of individuals selected as parents, lambda: the number of offspring per generation
+/// The delay_* fields are used by the delay GA, the priority_* fields by the priority GA
+/// min_delay and max_delay bound the genes of delay genotypes
+/// delay_rounds: The number of rounds from the start of a harness run that get their own delays, later rounds use the delays of the last one
+/// phase_aware_delays: Every round has delays for each consensus phase of the receiver
+/// delay_encoding: How the delays of a round and phase are encoded in the genotype
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GaConfiguration {
+    pub population_size: usize,
+    pub mu: usize,
+    pub lambda: usize,
+    pub selection: SelectionType,
+    pub reinsertion: ReinsertionType,
+    pub reinsertion_ratio: f64,
+    pub mutation_rate: f64,
+    pub mutation_std: f64,
+    pub min_mutation_std: f64,
+    pub delay_mutation: DelayMutationType,
+    pub priority_mutation: PermutationMutationType,
+    pub delay_crossover: DelayCrossoverType,
+    pub delay_crossover_probability: f32,
+    pub priority_crossover: PermutationCrossoverType,
+    pub priority_crossover_probability: f32,
+    pub min_delay: u32,
+    pub max_delay: u32,
+    pub delay_rounds: usize,
+    pub phase_aware_delays: bool,
+    pub delay_encoding: DelayEncodingType,
+}
+
+impl Default for GaConfiguration {
+    fn default() -> Self {
+        Self {
+            population_size: 4,
+            mu: 4,
+            lambda: 4,
+            selection: SelectionType::MuLambda,
+            reinsertion: ReinsertionType::MuLambda,
+            reinsertion_ratio: 0.7,
+            mutation_rate: 0.05,
+            mutation_std: 40.0,
+            min_mutation_std: 1.0,
+            delay_mutation: DelayMutationType::Gaussian,
+            priority_mutation: PermutationMutationType::Swap,
+            delay_crossover: DelayCrossoverType::SimulatedBinary,
+            delay_crossover_probability: 0.5,
+            priority_crossover: PermutationCrossoverType::PartiallyMapped,
+            priority_crossover_probability: 1.0,
+            min_delay: 0,
+            max_delay: 4000,
+            delay_rounds: 1,
+            phase_aware_delays: false,
+            delay_encoding: DelayEncodingType::Full,
+        }
+    }
+}
+
+impl GaConfiguration {
+    /// The number of genes of a delay genotype, the genes of the delay encoding per receiver phase and round
+    pub fn num_delay_genes(&self) -> usize {
+        self.num_delay_genes_on(&PEER_GRAPH)
+    }
+
+    /// The number of genes of a delay genotype on the given peer graph
+    pub fn num_delay_genes_on(&self, peer_graph: &PeerGraph) -> usize {
+        let phases = if self.phase_aware_delays { ConsensusPhase::VALUES.len() } else { 1 };
+        self.delay_encoding.num_genes(peer_graph) * phases * self.delay_rounds.max(1)
+    }
+
+    /// Check the parameters for values the GA cannot run with on the given nodes and peer graph, returns all problems found
+    pub fn validate(&self, num_nodes: usize, peer_graph: &PeerGraph, reevaluation: &ReevaluationPolicy) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.mu == 0 {
+            errors.push("mu must be at least 1".to_string());
+        }
+        if self.lambda < NUM_INDIVIDUALS_PER_PARENTS {
+            errors.push(format!("lambda must be at least {}", NUM_INDIVIDUALS_PER_PARENTS));
+        }
+        if self.population_size < self.mu {
+            errors.push(format!("population_size ({}) must be at least mu ({})", self.population_size, self.mu));
+        }
+        if !(0.0..=1.0).contains(&self.mutation_rate) {
+            errors.push(format!("mutation_rate ({}) must be in [0, 1]", self.mutation_rate));
+        }
+        if self.mutation_std <= 0.0 {
+            errors.push(format!("mutation_std ({}) must be positive", self.mutation_std));
+        }
+        if self.min_mutation_std < 1.0 || self.min_mutation_std > self.mutation_std {
+            errors.push(format!("min_mutation_std ({}) must be in [1, mutation_std]", self.min_mutation_std));
+        }
+        if !(0.0..=1.0).contains(&self.delay_crossover_probability) {
+            errors.push(format!("delay_crossover_probability ({}) must be in [0, 1]", self.delay_crossover_probability));
+        }
+        if !(0.0..=1.0).contains(&self.priority_crossover_probability) {
+            errors.push(format!("priority_crossover_probability ({}) must be in [0, 1]", self.priority_crossover_probability));
+        }
+        if !(0.0..=1.0).contains(&self.reinsertion_ratio) {
+            errors.push(format!("reinsertion_ratio ({}) must be in [0, 1]", self.reinsertion_ratio));
+        }
+        if self.min_delay > self.max_delay {
+            errors.push(format!("min_delay ({}) must not be larger than max_delay ({})", self.min_delay, self.max_delay));
+        }
+        if let Err(encoding_errors) = self.delay_encoding.validate(num_nodes) {
+            errors.extend(encoding_errors);
+        }
+        if self.delay_rounds == 0 {
+            errors.push("delay_rounds must be at least 1".to_string());
+        }
+        if let DelayCrossoverType::MultiPoint { num_cut_points } = self.delay_crossover {
+            let num_delay_genes = self.num_delay_genes_on(peer_graph);
+            if num_cut_points == 0 || num_cut_points >= num_delay_genes {
+                errors.push(format!("num_cut_points ({}) must be in [1, {})", num_cut_points, num_delay_genes));
+            }
+        }
+        if let SelectionType::Tournament { tournament_size, probability } = self.selection {
+            if tournament_size == 0 || tournament_size > self.population_size {
+                errors.push(format!("tournament_size ({}) must be in [1, population_size]", tournament_size));
+            }
+            if !(probability > 0.0 && probability <= 1.0) {
+                errors.push(format!("tournament probability ({}) must be in (0, 1]", probability));
+            }
+        }
+        if reevaluation.confidence_z.is_some() && self.selection != SelectionType::MuLambda {
+            errors.push("confidence_z can only be used with MuLambda selection".to_string());
+        }
+        if reevaluation.repetitions == 0 {
+            errors.push("repetitions must be at least 1".to_string());
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// The number of parents per crossover, SBX requires exactly two
+pub const NUM_INDIVIDUALS_PER_PARENTS: usize = 2;
 
 /// Parameters for the GA
 #[allow(unused)]
@@ -22,12 +167,15 @@ pub struct Parameter<S, C, T, G>
     num_crossover_points: usize,
     pub(crate) mutation_rate: f64,
     pub(crate) mutation_std: f64,
+    pub(crate) min_mutation_std: f64,
     pub(crate) reinsertion_ratio: f64,
     pub(crate) min_value: u32,
     pub(crate) max_value: u32,
     pub(crate) num_genes: usize,
     pub(crate) selection_operator: S,
     pub(crate) crossover_operator: C,
+    pub(crate) mutation_type: DelayMutationType,
+    pub(crate) reinsertion_type: ReinsertionType,
     stupid_type_system: PhantomData<T>,
     stupid_type_system_2: PhantomData<G>,
 }
@@ -40,12 +188,15 @@ impl<S, C, F, G> Parameter<S, C, F, G> where S: SelectionOp<G, F>, C: CrossoverO
                num_crossover_points: usize,
                mutation_rate: f64,
                mutation_std: f64,
+               min_mutation_std: f64,
                reinsertion_ratio: f64,
                min_value: u32,
                max_value: u32,
                num_genes: usize,
                selection_operator: S,
                crossover_operator: C,
+               mutation_type: DelayMutationType,
+               reinsertion_type: ReinsertionType,
     ) -> Self {
         Self {
             population_size,
@@ -54,12 +205,15 @@ impl<S, C, F, G> Parameter<S, C, F, G> where S: SelectionOp<G, F>, C: CrossoverO
             num_crossover_points,
             mutation_rate,
             mutation_std,
+            min_mutation_std,
             reinsertion_ratio,
             min_value,
             max_value,
             selection_operator,
             num_genes,
             crossover_operator,
+            mutation_type,
+            reinsertion_type,
             stupid_type_system: PhantomData,
             stupid_type_system_2: PhantomData
         }
@@ -73,12 +227,15 @@ impl<S, C, F, G> Parameter<S, C, F, G> where S: SelectionOp<G, F>, C: CrossoverO
             num_crossover_points: ConsensusMessageType::VALUES.len(),
             mutation_rate: 0.05,
             mutation_std: 50f64,
+            min_mutation_std: 1.0,
             reinsertion_ratio: 0.7,
             min_value: 0,
             max_value: 1000,
             num_genes: num_genes(),
             selection_operator: RouletteWheelSelector::new(0.7, 2),
             crossover_operator: SimulatedBinaryCrossBreeder::new(0.5, 0, 1000),
+            mutation_type: DelayMutationType::Gaussian,
+            reinsertion_type: ReinsertionType::MuLambda,
             stupid_type_system: PhantomData,
             stupid_type_system_2: PhantomData
         }
@@ -104,40 +261,83 @@ impl<S, C, F, G> Parameter<S, C, F, G> where S: SelectionOp<G, F>, C: CrossoverO
     // }
 }
 
-pub fn default_mu_lambda_delays<F: ExtendedFitness>(mu: usize, lambda: usize) -> Parameter<MuLambdaSelector, SimulatedBinaryCrossBreeder, F, DelayGenotype> {
+/// Mu lambda delay parameters with the default operators
+pub fn default_mu_lambda_delays<F: ExtendedFitness>(mu: usize, lambda: usize) -> Parameter<ConfiguredSelector, DelayCrossBreeder, F, DelayGenotype> {
+    delay_parameters(&GaConfiguration { population_size: mu, mu, lambda, ..GaConfiguration::default() })
+}
+
+/// Mu lambda priority parameters with the default operators
+#[allow(unused)]
+pub fn default_mu_lambda_priorities<F: ExtendedFitness>(mu: usize, lambda: usize) -> PermutationParameters<ConfiguredSelector, F, PriorityGenotype> {
+    priority_parameters(&GaConfiguration { population_size: mu, mu, lambda, ..GaConfiguration::default() })
+}
+
+pub fn delay_parameters<F: ExtendedFitness>(config: &GaConfiguration) -> Parameter<ConfiguredSelector, DelayCrossBreeder, F, DelayGenotype> {
     Parameter {
-        population_size: mu,
+        population_size: config.population_size,
         generation_limit: 5,
-        num_individuals_per_parents: 2,
+        num_individuals_per_parents: NUM_INDIVIDUALS_PER_PARENTS,
         num_crossover_points: ConsensusMessageType::VALUES.len(),
-        mutation_rate: 0.05,
-        mutation_std: 40f64,
-        reinsertion_ratio: 0.0,
-        min_value: 0,
-        max_value: 4000,
-        num_genes: num_genes(),
-        selection_operator: MuLambdaSelector::new(mu, lambda, 2),
-        crossover_operator: SimulatedBinaryCrossBreeder::new(0.5, 0, 4000),
+        mutation_rate: config.mutation_rate,
+        mutation_std: config.mutation_std,
+        min_mutation_std: config.min_mutation_std,
+        reinsertion_ratio: config.reinsertion_ratio,
+        min_value: config.min_delay,
+        max_value: config.max_delay,
+        num_genes: config.num_delay_genes(),
+        selection_operator: ConfiguredSelector::new(config.selection.clone(), config.mu, config.lambda, NUM_INDIVIDUALS_PER_PARENTS),
+        crossover_operator: DelayCrossBreeder::new(config.delay_crossover.clone(), config.delay_crossover_probability, config.min_delay, config.max_delay)
+            .with_self_adaptive(config.delay_mutation == DelayMutationType::SelfAdaptive),
+        mutation_type: config.delay_mutation.clone(),
+        reinsertion_type: config.reinsertion.clone(),
         stupid_type_system: PhantomData,
         stupid_type_system_2: PhantomData
     }
 }
 
-pub fn default_mu_lambda_priorities<F: ExtendedFitness>(mu: usize, lambda: usize) -> PermutationParameters<MuLambdaSelector, F, PriorityGenotype> {
+pub fn priority_parameters<F: ExtendedFitness>(config: &GaConfiguration) -> PermutationParameters<ConfiguredSelector, F, PriorityGenotype> {
     PermutationParameters {
-        population_size: mu,
+        population_size: config.population_size,
         generation_limit: 100,
-        num_individuals_per_parents: 2,
-        mutation_rate: 0.05,
+        num_individuals_per_parents: NUM_INDIVIDUALS_PER_PARENTS,
+        mutation_rate: config.mutation_rate,
         mutation_std: 0.1f64,
-        reinsertion_ratio: 1.0,
+        reinsertion_ratio: config.reinsertion_ratio,
         num_genes: num_genes(),
-        selection_operator: MuLambdaSelector::new(mu, lambda, 2),
+        selection_operator: ConfiguredSelector::new(config.selection.clone(), config.mu, config.lambda, NUM_INDIVIDUALS_PER_PARENTS),
+        crossover_operator: PermutationCrossBreeder::new(config.priority_crossover.clone(), config.priority_crossover_probability),
+        mutation_type: config.priority_mutation.clone(),
+        reinsertion_type: config.reinsertion.clone(),
         stupid_type_system: PhantomData,
         stupid_type_system_2: PhantomData
     }
 }
 
+pub fn hybrid_parameters<F: ExtendedFitness>(config: &GaConfiguration) -> HybridParameters<ConfiguredSelector, F> {
+    HybridParameters {
+        population_size: config.population_size,
+        num_individuals_per_parents: NUM_INDIVIDUALS_PER_PARENTS,
+        mutation_rate: config.mutation_rate,
+        mutation_std: config.mutation_std,
+        min_mutation_std: config.min_mutation_std,
+        reinsertion_ratio: config.reinsertion_ratio,
+        min_value: config.min_delay,
+        max_value: config.max_delay,
+        num_genes: num_genes(),
+        num_delay_genes: config.num_delay_genes(),
+        selection_operator: ConfiguredSelector::new(config.selection.clone(), config.mu, config.lambda, NUM_INDIVIDUALS_PER_PARENTS),
+        crossover_operator: HybridCrossBreeder::new(
+            DelayCrossBreeder::new(config.delay_crossover.clone(), config.delay_crossover_probability, config.min_delay, config.max_delay)
+                .with_self_adaptive(config.delay_mutation == DelayMutationType::SelfAdaptive),
+            PermutationCrossBreeder::new(config.priority_crossover.clone(), config.priority_crossover_probability),
+        ),
+        delay_mutation_type: config.delay_mutation.clone(),
+        priority_mutation_type: config.priority_mutation.clone(),
+        reinsertion_type: config.reinsertion.clone(),
+        stupid_type_system: PhantomData,
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, serde::Serialize)]
 pub struct PermutationParameters<S, T, G>
@@ -151,6 +351,33 @@ pub struct PermutationParameters<S, T, G>
     pub(crate) reinsertion_ratio: f64,
     pub(crate) num_genes: usize,
     pub(crate) selection_operator: S,
+    pub(crate) crossover_operator: PermutationCrossBreeder,
+    pub(crate) mutation_type: PermutationMutationType,
+    pub(crate) reinsertion_type: ReinsertionType,
     stupid_type_system: PhantomData<T>,
     stupid_type_system_2: PhantomData<G>,
-}
\ No newline at end of file
+}
+/// Parameters for the GA with hybrid delay and priority encoding
+/// The delay part uses the delay operators, the priority part the permutation operators
+#[allow(unused)]
+#[derive(Debug, serde::Serialize)]
+pub struct HybridParameters<S, T>
+    where S: SelectionOp<HybridGenotype, T>, T: ExtendedFitness
+{
+    pub(crate) population_size: usize,
+    num_individuals_per_parents: usize,
+    pub(crate) mutation_rate: f64,
+    pub(crate) mutation_std: f64,
+    pub(crate) min_mutation_std: f64,
+    pub(crate) reinsertion_ratio: f64,
+    pub(crate) min_value: u32,
+    pub(crate) max_value: u32,
+    pub(crate) num_genes: usize,
+    pub(crate) num_delay_genes: usize,
+    pub(crate) selection_operator: S,
+    pub(crate) crossover_operator: HybridCrossBreeder,
+    pub(crate) delay_mutation_type: DelayMutationType,
+    pub(crate) priority_mutation_type: PermutationMutationType,
+    pub(crate) reinsertion_type: ReinsertionType,
+    stupid_type_system: PhantomData<T>,
+}
@@ -2,7 +2,9 @@ use genevo::population::{build_population, GenomeBuilder, Population, ValueEncod
 use genevo::prelude::Rng;
 use rand::prelude::SliceRandom;
 use crate::ga::encoding::delay_encoding::DelayGenotype;
+use crate::ga::encoding::hybrid_encoding::HybridGenotype;
 use crate::ga::encoding::priority_encoding::{PriorityGenotype};
+use crate::ga::mutation::SelfAdaptiveGenomeMutation;
 
 pub fn build_delays_population(num_genes: usize, min_delay: u32, max_delay: u32, population_size: usize) -> Population<DelayGenotype> {
     build_population()
@@ -11,6 +13,14 @@ pub fn build_delays_population(num_genes: usize, min_delay: u32, max_delay: u32,
         .uniform_at_random()
 }
 
+/// Delay population for self-adaptive mutation, every genome carries a standard deviation per delay gene
+pub fn build_self_adaptive_delays_population(num_genes: usize, min_delay: u32, max_delay: u32, standard_deviation: f64, population_size: usize) -> Population<DelayGenotype> {
+    build_population()
+        .with_genome_builder(SelfAdaptiveGenomeBuilder::new(num_genes, min_delay, max_delay, standard_deviation))
+        .of_size(population_size)
+        .uniform_at_random()
+}
+
 pub fn build_priorities_population(num_genes: usize, population_size: usize) -> Population<PriorityGenotype> {
     build_population()
         .with_genome_builder(PermutationEncodedGenomeBuilder::new(num_genes))
@@ -18,6 +28,15 @@ pub fn build_priorities_population(num_genes: usize, population_size: usize) ->
         .uniform_at_random()
 }
 
+/// Hybrid population, delays are uniformly random and priorities are random permutations
+/// If a standard deviation is given, the delays carry a standard deviation per gene for self-adaptive mutation
+pub fn build_hybrid_population(num_genes: usize, num_delay_genes: usize, min_delay: u32, max_delay: u32, standard_deviation: Option<f64>, population_size: usize) -> Population<HybridGenotype> {
+    build_population()
+        .with_genome_builder(HybridGenomeBuilder::new(num_genes, num_delay_genes, min_delay, max_delay, standard_deviation))
+        .of_size(population_size)
+        .uniform_at_random()
+}
+
 struct PermutationEncodedGenomeBuilder {
     genome_length: usize,
 }
@@ -38,6 +57,62 @@ impl GenomeBuilder<PriorityGenotype> for PermutationEncodedGenomeBuilder {
     }
 }
 
+struct SelfAdaptiveGenomeBuilder {
+    num_genes: usize,
+    min_delay: u32,
+    max_delay: u32,
+    standard_deviation: f64,
+}
+
+impl SelfAdaptiveGenomeBuilder {
+    pub fn new(num_genes: usize, min_delay: u32, max_delay: u32, standard_deviation: f64) -> Self {
+        SelfAdaptiveGenomeBuilder {
+            num_genes,
+            min_delay,
+            max_delay,
+            standard_deviation,
+        }
+    }
+}
+
+impl GenomeBuilder<DelayGenotype> for SelfAdaptiveGenomeBuilder {
+    fn build_genome<R>(&self, _: usize, rng: &mut R) -> DelayGenotype where R: Rng + Sized {
+        let delays = (0..self.num_genes).map(|_| rng.gen_range(self.min_delay..=self.max_delay)).collect();
+        DelayGenotype::with_standard_deviations(delays, self.standard_deviation)
+    }
+}
+
+struct HybridGenomeBuilder {
+    num_genes: usize,
+    num_delay_genes: usize,
+    min_delay: u32,
+    max_delay: u32,
+    standard_deviation: Option<f64>,
+}
+
+impl HybridGenomeBuilder {
+    pub fn new(num_genes: usize, num_delay_genes: usize, min_delay: u32, max_delay: u32, standard_deviation: Option<f64>) -> Self {
+        HybridGenomeBuilder {
+            num_genes,
+            num_delay_genes,
+            min_delay,
+            max_delay,
+            standard_deviation,
+        }
+    }
+}
+
+impl GenomeBuilder<HybridGenotype> for HybridGenomeBuilder {
+    fn build_genome<R>(&self, index: usize, rng: &mut R) -> HybridGenotype where R: Rng + Sized {
+        let delays: DelayGenotype = (0..self.num_delay_genes).map(|_| rng.gen_range(self.min_delay..=self.max_delay)).collect();
+        let delays = match self.standard_deviation {
+            Some(standard_deviation) => DelayGenotype::with_standard_deviations(delays, standard_deviation),
+            None => delays,
+        };
+        HybridGenotype::new(delays, PermutationEncodedGenomeBuilder::new(self.num_genes).build_genome(index, rng))
+    }
+}
+
 #[cfg(test)]
 mod population_builder_tests {
     use genevo::population::build_population;
@@ -58,4 +133,5 @@ mod population_builder_tests {
         // All genes are within the sequence (0 ... genotype length-1)
         assert!(population.individuals().iter().map(|individual| individual.iter().all(|x| x >= &0 && x <= &9)).all(|in_range| in_range));
     }
-}
\ No newline at end of file
+}
+
@@ -1,12 +1,18 @@
+use std::collections::BTreeSet;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use genevo::algorithm::EvaluatedPopulation;
 use genevo::genetic::Offspring;
 use genevo::operator::{GeneticOperator, MultiObjective, ReinsertionOp, SingleObjective};
+use genevo::operator::prelude::ElitistReinserter;
 use genevo::prelude::*;
 use itertools::Itertools;
+use rayon::prelude::*;
+use crate::ga::symmetry::{NodeSymmetry, Relabel};
 
 /// This reinsertion operator takes the best mu individuals
 /// from the mu parents + lambda offspring population
+/// With node symmetry, offspring that are relabellings of other individuals are dropped before evaluation
 #[derive(Clone, Debug, PartialEq)]
 pub struct MuLambdaReinserter<G, F, E>
     where
@@ -20,6 +26,8 @@ pub struct MuLambdaReinserter<G, F, E>
     /// The `replace_ratio` defines the fraction of the population size that
     /// is going to be replaced by individuals from the offspring.
     mu: usize,
+    /// Equivalent schedules under node relabelling, if the network is symmetric
+    symmetry: Option<Arc<NodeSymmetry>>,
     // phantom types
     _g: PhantomData<G>,
     _f: PhantomData<F>,
@@ -36,10 +44,16 @@ impl<G, F, E> MuLambdaReinserter<G, F, E>
         MuLambdaReinserter {
             fitness_evaluator: Box::new(fitness_evaluator),
             mu,
+            symmetry: None,
             _g: PhantomData,
             _f: PhantomData,
         }
     }
+
+    pub fn with_symmetry(mut self, symmetry: Option<Arc<NodeSymmetry>>) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
 }
 
 impl<G, F, E> GeneticOperator for MuLambdaReinserter<G, F, E>
@@ -72,9 +86,9 @@ impl<G, F, E> MultiObjective for MuLambdaReinserter<G, F, E>
 
 impl<G, F, E> ReinsertionOp<G, F> for MuLambdaReinserter<G, F, E>
     where
-        G: Genotype,
-        F: Fitness,
-        E: FitnessFunction<G, F>,
+        G: Genotype + Relabel,
+        F: Fitness + Send,
+        E: FitnessFunction<G, F> + Sync,
 {
     fn combine<R>(
         &self,
@@ -94,11 +108,17 @@ impl<G, F, E> ReinsertionOp<G, F> for MuLambdaReinserter<G, F, E>
             combined_individuals.push((old_individuals[i].clone(), old_fitness_values[i].clone()));
         }
 
-        // evaluate fitness of the offspring individuals
-        while let Some(child) = offspring.pop() {
-            let fitness = self.fitness_evaluator.fitness_of(&child);
-            combined_individuals.push((child, fitness));
+        // drop offspring that are equivalent to a parent or an earlier child, they would only take up harness runs and population slots
+        if let Some(symmetry) = &self.symmetry {
+            let mut seen: BTreeSet<G> = old_individuals.iter().map(|individual| symmetry.canonical(individual)).collect();
+            offspring.retain(|child| seen.insert(symmetry.canonical(child)));
         }
+
+        // evaluate fitness of the offspring individuals, in parallel so that multiple clusters can be used
+        let offspring_fitness: Vec<F> = offspring.par_iter()
+            .map(|child| self.fitness_evaluator.fitness_of(child))
+            .collect();
+        combined_individuals.extend(offspring.drain(..).zip(offspring_fitness));
         // sort offspring from worst to best performing performing
         combined_individuals.sort_by(|x, y| y.1.cmp(&x.1));
 
@@ -106,14 +126,106 @@ impl<G, F, E> ReinsertionOp<G, F> for MuLambdaReinserter<G, F, E>
     }
 }
 
+/// The reinsertion strategies that can be set in the configuration
+/// MuLambda: Keep the best individuals of parents and offspring, see `MuLambdaReinserter`
+/// Elitist: genevo `ElitistReinserter`, replaces reinsertion_ratio of the population with the best offspring
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum ReinsertionType {
+    MuLambda,
+    Elitist {
+        offspring_has_precedence: bool,
+    },
+}
+
+/// Reinsertion operator used by the GA, decided at runtime from the configuration
+#[derive(Clone, Debug)]
+pub enum GaReinserter<G, F, E>
+    where
+        G: Genotype,
+        F: Fitness,
+        E: FitnessFunction<G, F>,
+{
+    MuLambda(MuLambdaReinserter<G, F, E>),
+    Elitist(ElitistReinserter<G, F, E>),
+}
+
+impl<G, F, E> GaReinserter<G, F, E>
+    where
+        G: Genotype,
+        F: Fitness,
+        E: FitnessFunction<G, F>,
+{
+    pub fn new(reinsertion_type: &ReinsertionType, fitness_evaluator: E, mu: usize, reinsertion_ratio: f64) -> Self {
+        match reinsertion_type {
+            ReinsertionType::MuLambda => Self::MuLambda(MuLambdaReinserter::new(fitness_evaluator, mu)),
+            ReinsertionType::Elitist { offspring_has_precedence } =>
+                Self::Elitist(ElitistReinserter::new(fitness_evaluator, *offspring_has_precedence, reinsertion_ratio)),
+        }
+    }
+
+    /// Deduplicate equivalent offspring, only applies to mu lambda reinsertion
+    pub fn with_symmetry(self, symmetry: Option<Arc<NodeSymmetry>>) -> Self {
+        match self {
+            Self::MuLambda(reinserter) => Self::MuLambda(reinserter.with_symmetry(symmetry)),
+            elitist => elitist,
+        }
+    }
+}
+
+impl<G, F, E> GeneticOperator for GaReinserter<G, F, E>
+    where
+        G: Genotype,
+        F: Fitness,
+        E: FitnessFunction<G, F>,
+{
+    fn name() -> String {
+        "Ga-Reinserter".to_string()
+    }
+}
+
+impl<G, F, E> SingleObjective for GaReinserter<G, F, E>
+    where
+        G: Genotype,
+        F: Fitness,
+        E: FitnessFunction<G, F>,
+{
+}
+
+impl<G, F, E> ReinsertionOp<G, F> for GaReinserter<G, F, E>
+    where
+        G: Genotype + Relabel,
+        F: Fitness + Send,
+        E: FitnessFunction<G, F> + Sync,
+{
+    fn combine<R>(
+        &self,
+        offspring: &mut Offspring<G>,
+        evaluated: &EvaluatedPopulation<G, F>,
+        rng: &mut R,
+    ) -> Vec<G>
+        where
+            R: Rng + Sized,
+    {
+        match self {
+            Self::MuLambda(reinserter) => reinserter.combine(offspring, evaluated, rng),
+            Self::Elitist(reinserter) => reinserter.combine(offspring, evaluated, rng),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
+    use std::sync::Arc;
     use genevo::algorithm::EvaluatedPopulation;
     use genevo::genetic::FitnessFunction;
     use genevo::operator::ReinsertionOp;
     use rand::thread_rng;
+    use itertools::Itertools;
+    use crate::ga::encoding::num_genes;
     use crate::ga::reinsertion::MuLambdaReinserter;
+    use crate::ga::symmetry::{NodeSymmetry, Relabel};
+    use crate::topology::PeerGraph;
 
     #[test]
     fn test_reinsertion() {
@@ -125,6 +237,25 @@ mod tests {
         assert_eq!(result, vec![vec![3], vec![2]]);
     }
 
+    #[test]
+    fn test_reinsertion_drops_equivalent_offspring() {
+        let mock_fitness = MockFitnessFunction{};
+        let reinserter: MuLambdaReinserter<Vec<usize>, usize, MockFitnessFunction> = MuLambdaReinserter::new(mock_fitness, 2)
+            .with_symmetry(Some(Arc::new(NodeSymmetry::new(&PeerGraph::complete(5)))));
+        // Every gene is distinct, so relabelling the nodes moves genes around
+        let genotype = |offset: usize| (0..num_genes()).map(|gene| gene + offset).collect_vec();
+        let permutation = [1, 0, 2, 3, 4];
+        let (parent, child) = (genotype(1000), genotype(2000));
+        let (relabelled_parent, relabelled_child) = (parent.relabel(&permutation), child.relabel(&permutation));
+        assert_ne!(relabelled_child, child);
+        // Both relabelled copies are fitter than the parent, they would take its slot without the symmetry
+        assert!(relabelled_child[0] > child[0] && relabelled_parent[0] > parent[0]);
+        let mut offspring = vec![child.clone(), relabelled_child, relabelled_parent];
+        let evaluated_population = EvaluatedPopulation::new(Rc::new(vec![genotype(0), parent.clone()]), vec![0, 1000], 1000, 0, 500);
+        let result = reinserter.combine(&mut offspring, &evaluated_population, &mut thread_rng());
+        assert_eq!(result, vec![child, parent]);
+    }
+
     #[derive(Clone)]
     struct MockFitnessFunction {}
 
@@ -1,12 +1,19 @@
 //! The provided `SelectionOp` implementations are:
 //! * `MuLambdaSelector`
+//! * `ConfidenceBoundSelector`
+//! * `ConfiguredSelector`, which wraps `MuLambdaSelector` and the genevo selectors as set in the configuration
+//! * `GaSelector`, which picks the configured selector or confidence bound selection depending on the re-evaluation policy
 
+use std::cmp::Ordering;
 use genevo::algorithm::EvaluatedPopulation;
-use genevo::genetic::{Fitness, Genotype, Parents};
+use genevo::genetic::{AsScalar, Fitness, Genotype, Parents};
 use genevo::operator::{GeneticOperator, MultiObjective, SelectionOp, SingleObjective};
+use genevo::operator::prelude::{MaximizeSelector, RouletteWheelSelector, TournamentSelector};
 use itertools::Itertools;
 use rand::prelude::SliceRandom;
 use rand::Rng;
+use crate::ga::encoding::ExtendedGenotype;
+use crate::ga::fitness::{ExtendedFitness, FitnessCalculation};
 
 /// The `MuLambdaSelector` selects the best performing `genetic::Genotype`s
 /// from the population.
@@ -63,25 +70,213 @@ impl<G, F> SelectionOp<G, F> for MuLambdaSelector
         let mut mating_pool: Vec<usize> = (0..fitness_values.len()).collect();
         // sort mating pool from best performing to worst performing index
         mating_pool.sort_by(|x, y| fitness_values[*y].cmp(&fitness_values[*x]));
-        let mut mating_pool = mating_pool.into_iter().take(self.mu).collect_vec();
-        mating_pool.shuffle(rng);
-
-        let pool_size = mating_pool.len();
-        let mut selected: Vec<Parents<G>> = Vec::with_capacity(self.mu);
-
-        let mut index_m = 0;
-        for _ in 0..self.lambda/2 {
-            let mut tuple = Vec::with_capacity(self.num_individuals_per_parents);
-            for _ in 0..self.num_individuals_per_parents {
-                // index into mating pool
-                index_m %= pool_size;
-                // index into individuals slice
-                let index_i = mating_pool[index_m];
-                tuple.push(individuals[index_i].clone());
-                index_m += 1;
-            }
-            selected.push(tuple);
+        create_parents(mating_pool, individuals, self.mu, self.lambda, self.num_individuals_per_parents, rng)
+    }
+}
+
+/// Pair the best mu individuals of the sorted mating pool into lambda / 2 parent tuples
+fn create_parents<G, R>(mating_pool: Vec<usize>, individuals: &[G], mu: usize, lambda: usize, num_individuals_per_parents: usize, rng: &mut R) -> Vec<Parents<G>>
+    where
+        G: Genotype,
+        R: Rng + Sized,
+{
+    let mut mating_pool = mating_pool.into_iter().take(mu).collect_vec();
+    mating_pool.shuffle(rng);
+
+    let pool_size = mating_pool.len();
+    let mut selected: Vec<Parents<G>> = Vec::with_capacity(mu);
+
+    let mut index_m = 0;
+    for _ in 0..lambda/2 {
+        let mut tuple = Vec::with_capacity(num_individuals_per_parents);
+        for _ in 0..num_individuals_per_parents {
+            // index into mating pool
+            index_m %= pool_size;
+            // index into individuals slice
+            let index_i = mating_pool[index_m];
+            tuple.push(individuals[index_i].clone());
+            index_m += 1;
+        }
+        selected.push(tuple);
+    }
+    selected
+}
+
+/// The `ConfidenceBoundSelector` selects the mu individuals with the highest
+/// lower confidence bound (mean - z * standard error) of their fitness samples.
+/// Individuals with few or widely spread samples are ranked more pessimistically than under `MuLambdaSelector`.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ConfidenceBoundSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {
+    mu: usize,
+    lambda: usize,
+    num_individuals_per_parents: usize,
+    z: f64,
+    #[serde(skip)]
+    fitness_calculation: FitnessCalculation<T, G>,
+}
+
+impl<T, G> ConfidenceBoundSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {
+    pub fn new(mu: usize, lambda: usize, num_individuals_per_parents: usize, z: f64, fitness_calculation: FitnessCalculation<T, G>) -> Self {
+        Self {
+            mu,
+            lambda,
+            num_individuals_per_parents,
+            z,
+            fitness_calculation,
+        }
+    }
+}
+
+impl<T, G> SingleObjective for ConfidenceBoundSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {}
+
+impl<T, G> GeneticOperator for ConfidenceBoundSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {
+    fn name() -> String {
+        "ConfidenceBound-Selection".to_string()
+    }
+}
+
+impl<T, G> SelectionOp<G, T> for ConfidenceBoundSelector<T, G>
+    where
+        T: ExtendedFitness,
+        G: ExtendedGenotype,
+{
+    fn select_from<R>(&self, evaluated: &EvaluatedPopulation<G, T>, rng: &mut R) -> Vec<Parents<G>>
+        where
+            R: Rng + Sized,
+    {
+        let individuals = evaluated.individuals();
+        let fitness_values = evaluated.fitness_values();
+        // Individuals without samples in the cache fall back to the reported fitness
+        let bounds = individuals.iter().zip(fitness_values.iter())
+            .map(|(individual, fitness)| self.fitness_calculation
+                .lower_confidence_bound(individual, self.z)
+                .unwrap_or_else(|| fitness.as_scalar()))
+            .collect_vec();
+
+        let mut mating_pool: Vec<usize> = (0..fitness_values.len()).collect();
+        // sort mating pool from highest to lowest confidence bound
+        mating_pool.sort_by(|x, y| bounds[*y].partial_cmp(&bounds[*x]).unwrap_or(Ordering::Equal));
+        create_parents(mating_pool, individuals, self.mu, self.lambda, self.num_individuals_per_parents, rng)
+    }
+}
+
+/// The selection strategies that can be set in the configuration
+/// MuLambda: Select the mu best individuals, see `MuLambdaSelector`
+/// Maximize: genevo `MaximizeSelector`
+/// RouletteWheel: genevo `RouletteWheelSelector`, fitness proportionate
+/// Tournament: genevo `TournamentSelector`
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum SelectionType {
+    MuLambda,
+    Maximize,
+    RouletteWheel,
+    Tournament {
+        tournament_size: usize,
+        probability: f64,
+    },
+}
+
+/// Selection operator as set in the configuration.
+/// Every strategy creates lambda / num_individuals_per_parents parent tuples, mu is only used by MuLambda.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct ConfiguredSelector {
+    selection_type: SelectionType,
+    mu: usize,
+    lambda: usize,
+    num_individuals_per_parents: usize,
+}
+
+impl ConfiguredSelector {
+    pub fn new(selection_type: SelectionType, mu: usize, lambda: usize, num_individuals_per_parents: usize) -> Self {
+        Self { selection_type, mu, lambda, num_individuals_per_parents }
+    }
+
+    /// The fraction of the population the genevo selectors turn into parent tuples, such that lambda offspring are created
+    fn selection_ratio(&self, population_size: usize) -> f64 {
+        (self.lambda / self.num_individuals_per_parents) as f64 / population_size as f64
+    }
+}
+
+impl SingleObjective for ConfiguredSelector {}
+
+impl GeneticOperator for ConfiguredSelector {
+    fn name() -> String {
+        "Configured-Selection".to_string()
+    }
+}
+
+impl<G, F> SelectionOp<G, F> for ConfiguredSelector
+    where
+        G: Genotype,
+        F: Fitness + AsScalar,
+{
+    fn select_from<R>(&self, evaluated: &EvaluatedPopulation<G, F>, rng: &mut R) -> Vec<Parents<G>>
+        where
+            R: Rng + Sized,
+    {
+        let selection_ratio = self.selection_ratio(evaluated.individuals().len());
+        match &self.selection_type {
+            SelectionType::MuLambda => MuLambdaSelector::new(self.mu, self.lambda, self.num_individuals_per_parents)
+                .select_from(evaluated, rng),
+            SelectionType::Maximize => MaximizeSelector::new(selection_ratio, self.num_individuals_per_parents)
+                .select_from(evaluated, rng),
+            SelectionType::RouletteWheel => RouletteWheelSelector::new(selection_ratio, self.num_individuals_per_parents)
+                .select_from(evaluated, rng),
+            SelectionType::Tournament { tournament_size, probability } => TournamentSelector::new(
+                selection_ratio,
+                self.num_individuals_per_parents,
+                *tournament_size,
+                *probability,
+                false,
+            ).select_from(evaluated, rng),
+        }
+    }
+}
+
+/// Selection operator used by the GA, decided at runtime from the configuration
+#[derive(Clone, Debug)]
+pub enum GaSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {
+    Configured(ConfiguredSelector),
+    ConfidenceBound(ConfidenceBoundSelector<T, G>),
+}
+
+impl<T, G> GaSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {
+    /// Use confidence bound selection if the re-evaluation policy of the fitness calculation has a z value.
+    /// Confidence bound selection replaces mu lambda selection, the configuration is validated to not combine it with other strategies.
+    pub fn from_policy(selector: ConfiguredSelector, fitness_calculation: &FitnessCalculation<T, G>) -> Self {
+        match (fitness_calculation.policy.confidence_z, &selector.selection_type) {
+            (Some(z), SelectionType::MuLambda) => Self::ConfidenceBound(ConfidenceBoundSelector::new(
+                selector.mu,
+                selector.lambda,
+                selector.num_individuals_per_parents,
+                z,
+                fitness_calculation.clone(),
+            )),
+            _ => Self::Configured(selector),
+        }
+    }
+}
+
+impl<T, G> SingleObjective for GaSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {}
+
+impl<T, G> GeneticOperator for GaSelector<T, G> where T: ExtendedFitness, G: ExtendedGenotype {
+    fn name() -> String {
+        "Ga-Selection".to_string()
+    }
+}
+
+impl<T, G> SelectionOp<G, T> for GaSelector<T, G>
+    where
+        T: ExtendedFitness,
+        G: ExtendedGenotype,
+{
+    fn select_from<R>(&self, evaluated: &EvaluatedPopulation<G, T>, rng: &mut R) -> Vec<Parents<G>>
+        where
+            R: Rng + Sized,
+    {
+        match self {
+            Self::Configured(selector) => selector.select_from(evaluated, rng),
+            Self::ConfidenceBound(selector) => selector.select_from(evaluated, rng),
         }
-        selected
     }
 }
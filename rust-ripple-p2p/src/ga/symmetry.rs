@@ -0,0 +1,153 @@
+use itertools::Itertools;
+use crate::ga::encoding::delay_encoding::{DelayEncodingType, DelayGenotype};
+use crate::ga::encoding::hybrid_encoding::HybridGenotype;
+use crate::ga::encoding::priority_encoding::PriorityGenotype;
+use crate::ga::encoding::delay_encoding;
+use crate::ga::genetic_algorithm::ConsensusMessageType;
+use crate::topology::PeerGraph;
+use crate::{CONFIG, get_unls, NUM_NODES, PEER_GRAPH};
+
+/// Above this number of nodes there are too many permutations to canonicalize every genotype
+pub const MAX_SYMMETRY_NODES: usize = 7;
+
+/// Genotypes that can be rewritten as if the validators were numbered differently
+pub trait Relabel: Clone + Ord {
+    /// The genotype where node i takes the place of node permutation[i]
+    fn relabel(&self, permutation: &[usize]) -> Self;
+}
+
+/// Move every block of genes encoded with the given encoding to the relabelled (from, to) indices
+/// The permutation has to map the links of the peer graph to links, genes that do not fill a whole block are left as is
+fn relabel_blocks<T: Copy>(genes: &[T], encoding: &DelayEncodingType, permutation: &[usize], peer_graph: &PeerGraph) -> Vec<T> {
+    let block_size = encoding.num_genes(peer_graph);
+    let mut relabelled = genes.to_vec();
+    if block_size == 0 {
+        return relabelled;
+    }
+    for block in 0..genes.len() / block_size {
+        let offset = block * block_size;
+        for &(from, to) in peer_graph.links() {
+            for k in 0..ConsensusMessageType::VALUES.len() {
+                let old_index = offset + encoding.gene_index(from, to, k, peer_graph);
+                let new_index = offset + encoding.gene_index(permutation[from], permutation[to], k, peer_graph);
+                relabelled[new_index] = genes[old_index];
+            }
+        }
+    }
+    relabelled
+}
+
+impl Relabel for DelayGenotype {
+    fn relabel(&self, permutation: &[usize]) -> Self {
+        relabel_blocks(self, delay_encoding(), permutation, &PEER_GRAPH)
+    }
+}
+
+impl Relabel for PriorityGenotype {
+    fn relabel(&self, permutation: &[usize]) -> Self {
+        relabel_blocks(self, &DelayEncodingType::Full, permutation, &PEER_GRAPH)
+    }
+}
+
+impl Relabel for HybridGenotype {
+    fn relabel(&self, permutation: &[usize]) -> Self {
+        HybridGenotype::new(self.delays.relabel(permutation), self.priorities.relabel(permutation))
+    }
+}
+
+/// Check whether every relabelling of the nodes leaves the UNL matrix unchanged,
+/// i.e. all nodes trust themselves in the same way and trust all other nodes in the same way
+pub fn is_permutation_invariant(unls: &[Vec<usize>]) -> bool {
+    let num_nodes = unls.len();
+    let trusts = |i: usize, j: usize| unls[i].contains(&j);
+    let trusts_self = num_nodes > 0 && trusts(0, 0);
+    let trusts_others = num_nodes > 1 && trusts(0, 1);
+    (0..num_nodes).cartesian_product(0..num_nodes)
+        .all(|(i, j)| trusts(i, j) == if i == j { trusts_self } else { trusts_others })
+}
+
+/// All relabellings of the nodes that lead to an equivalent schedule, the relabellings that keep the peer graph the same
+/// Genotypes in the same orbit are mapped to their smallest relabelling, which is used as cache key
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeSymmetry {
+    permutations: Vec<Vec<usize>>,
+}
+
+impl NodeSymmetry {
+    pub fn new(peer_graph: &PeerGraph) -> Self {
+        let num_nodes = peer_graph.num_nodes();
+        Self {
+            permutations: (0..num_nodes).permutations(num_nodes)
+                .filter(|permutation| peer_graph.is_automorphism(permutation))
+                .collect()
+        }
+    }
+
+    /// Only use symmetry when the UNL matrix is permutation-invariant, the delay encoding does not fix node groups
+    /// and the peer graph can be relabelled in some other way than the identity
+    pub fn from_unls(unls: &[Vec<usize>], encoding: &DelayEncodingType, peer_graph: &PeerGraph) -> Option<Self> {
+        let num_nodes = unls.len();
+        if num_nodes > MAX_SYMMETRY_NODES
+            || matches!(encoding, DelayEncodingType::NodeGroups { .. })
+            || !is_permutation_invariant(unls) {
+            return None;
+        }
+        Some(Self::new(peer_graph)).filter(|symmetry| symmetry.permutations.len() > 1)
+    }
+
+    /// Node symmetry of the configured network
+    pub fn from_config() -> Option<Self> {
+        let symmetry = Self::from_unls(&get_unls(*NUM_NODES, CONFIG.unl_type.clone()), delay_encoding(), &PEER_GRAPH);
+        if let Some(symmetry) = &symmetry {
+            println!("UNLs are permutation-invariant, genotypes are cached under {} node relabellings", symmetry.permutations.len());
+        }
+        symmetry
+    }
+
+    /// The smallest genotype among all relabellings of the genotype
+    pub fn canonical<G: Relabel>(&self, genotype: &G) -> G {
+        self.permutations.iter()
+            .map(|permutation| genotype.relabel(permutation))
+            .min()
+            .unwrap_or_else(|| genotype.clone())
+    }
+}
+
+#[cfg(test)]
+mod symmetry_tests {
+    use crate::ga::encoding::delay_encoding::DelayEncodingType;
+    use crate::ga::encoding::num_genes;
+    use crate::ga::symmetry::{is_permutation_invariant, NodeSymmetry, Relabel};
+    use crate::topology::{PeerGraph, PeerTopology};
+    use crate::{get_unls, UnlType};
+
+    #[test]
+    fn test_permutation_invariant_unls() {
+        assert!(is_permutation_invariant(&get_unls(5, UnlType::Full)));
+        assert!(!is_permutation_invariant(&get_unls(7, UnlType::Limit)));
+        assert!(!is_permutation_invariant(&get_unls(5, UnlType::Buggy)));
+        assert!(NodeSymmetry::from_unls(&get_unls(5, UnlType::Full), &DelayEncodingType::NodeGroups { groups: vec![vec![0, 1], vec![2, 3, 4]] }, &PeerGraph::complete(5)).is_none());
+    }
+
+    #[test]
+    fn test_canonical_priorities() {
+        let symmetry = NodeSymmetry::new(&PeerGraph::complete(5));
+        let priorities = (0..num_genes()).rev().collect::<Vec<usize>>();
+        let swapped = priorities.relabel(&[1, 0, 2, 3, 4]);
+        assert_ne!(priorities, swapped);
+        // Swapping back gives the original genotype
+        assert_eq!(swapped.relabel(&[1, 0, 2, 3, 4]), priorities);
+        let canonical = symmetry.canonical(&priorities);
+        assert_eq!(symmetry.canonical(&swapped), canonical);
+        assert!(canonical <= priorities);
+    }
+
+    #[test]
+    fn test_peer_graph_symmetry() {
+        // The rotations and reflections of the ring
+        let ring = PeerTopology::Ring.peer_graph(5).unwrap();
+        assert_eq!(NodeSymmetry::new(&ring).permutations.len(), 10);
+        let star = PeerTopology::Star { center: 0 }.peer_graph(4).unwrap();
+        assert!(NodeSymmetry::new(&star).permutations.iter().all(|permutation| permutation[0] == 0));
+    }
+}
@@ -10,7 +10,7 @@ use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 use serde::{Serialize, Deserialize};
 use crate::ga::encoding::delay_encoding::{DelayMapPhenotype, DelayGenotype};
-use crate::ga::encoding::{ExtendedPhenotype, num_genes};
+use crate::ga::encoding::{ExtendedPhenotype, num_delay_genes, num_genes};
 use crate::ga::encoding::priority_encoding::{PriorityGenotype, PriorityMapPhenotype};
 use crate::ga::fitness::ExtendedFitness;
 use crate::node_state::{MessageTypeDependencyEvent, MutexNodeStates};
@@ -113,11 +113,11 @@ impl<F: ExtendedFitness> DelayLocalityExperiment<F> {
         // Maximum euclidean distance is n.sqrt()*(max - min) 260.sqrt() * 4000, take half that:
         let max_distance = 260f64.sqrt() * 4000f64 / 4.0;
         println!("Starting delay locality experiment");
-        let distant_delay_genotypes: Vec<DelayGenotype> = sample_n_distant_delay_genotypes(10, num_genes(), max_distance, &mut rng);
+        let distant_delay_genotypes: Vec<DelayGenotype> = sample_n_distant_delay_genotypes(10, num_delay_genes(), max_distance, &mut rng);
         println!("Done creating genotypes");
         let mut neighbors_list: Vec<Vec<DelayGenotype>> = vec![];
         for genotype in distant_delay_genotypes.iter() {
-            let neighbors = sample_n_neighbors_delay_genotypes(10, &genotype, &mut rng);
+            let neighbors = sample_n_neighbors_delay_genotypes(10, &genotype, 0, 4000, &mut rng);
             neighbors_list.push(neighbors);
         }
         println!("Done creating neighbors");
@@ -183,8 +183,8 @@ pub fn run_locality_experiment_delays<F: ExtendedFitness>(scheduler_sender: Send
     loc_exp.run_locality_experiment_delays();
 }
 
-/// Sample n closest neighbors of delay genotype (+1 / -1 for one gene)
-pub fn sample_n_neighbors_delay_genotypes(n: usize, genotype: &DelayGenotype, rng: &mut impl Rng) -> Vec<DelayGenotype> {
+/// Sample n closest neighbors of delay genotype (+1 / -1 for one gene), the delays stay within [min_delay, max_delay]
+pub fn sample_n_neighbors_delay_genotypes(n: usize, genotype: &DelayGenotype, min_delay: u32, max_delay: u32, rng: &mut impl Rng) -> Vec<DelayGenotype> {
     // Technically we can sample 2*num_genes distinct neighbors (up and down)
     if n > genotype.len() {
         error!("Cannot sample more than num_genes distinct neighbors");
@@ -194,8 +194,9 @@ pub fn sample_n_neighbors_delay_genotypes(n: usize, genotype: &DelayGenotype, rn
     // Create n distinct gene indices where a new neighbor will be created from
     for x in rand::seq::index::sample(rng, genotype.len(), n) {
         let mut new_neighbor = genotype.clone();
-        // Either add 1 or subtract 1
-        new_neighbor[x] = new_neighbor[x] -1 + (rng.gen_bool(0.5) as u32 * 2);
+        // Either add 1 or subtract 1, a delay at a bound can only move away from it
+        let up = (rng.gen_bool(0.5) || new_neighbor[x] <= min_delay) && new_neighbor[x] < max_delay;
+        new_neighbor[x] = if up { new_neighbor[x] + 1 } else { new_neighbor[x] - 1 };
         res.push(new_neighbor);
     };
     res
@@ -396,11 +397,15 @@ mod tests {
     fn test_n_neighbors_delay_genotypes() {
         let mut rng = ChaCha8Rng::seed_from_u64(2);
         let genotype = vec![300, 300, 300, 300];
-        let neighbors = sample_n_neighbors_delay_genotypes(4, &genotype, &mut rng);
+        let neighbors = sample_n_neighbors_delay_genotypes(4, &genotype, 0, 4000, &mut rng);
         assert!(neighbors.iter().all_unique());
         for neighbor in neighbors {
             assert_eq!(delay_genotype_euclidean_distance(&neighbor, &genotype), 1f64);
         }
+        // Delays at a bound move away from it
+        let neighbors = sample_n_neighbors_delay_genotypes(3, &vec![0, 10, 10], 0, 10, &mut rng);
+        assert!(neighbors.iter().all(|neighbor| neighbor.iter().all(|delay| *delay <= 10)));
+        assert!(neighbors.contains(&vec![1, 10, 10]) && neighbors.contains(&vec![0, 9, 10]) && neighbors.contains(&vec![0, 10, 9]));
     }
 
     #[test]
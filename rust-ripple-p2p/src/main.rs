@@ -10,10 +10,23 @@ use lazy_static::lazy_static;
 use serde_with::{serde_as, DurationSeconds};
 use crate::app::SchedulerType;
 #[allow(unused_imports)]
-use crate::container_manager::{NodeKeys, start_docker_containers};
+use crate::container_manager::{CLUSTER_PORT_OFFSET, NodeKeys, start_docker_clusters, start_docker_containers};
 #[allow(unused_imports)]
 use crate::executable_manager::start_executables;
 use crate::failure_writer::ConsensusPropertyTypes;
+use crate::fuzzing::FuzzingConfiguration;
+use crate::schedule_file::ScheduleFilesConfiguration;
+use crate::systematic::SystematicConfiguration;
+use crate::pct::PctConfiguration;
+use crate::online_learning::OnlineLearningConfiguration;
+use crate::network_model::NetworkModelConfiguration;
+use crate::topology::{PeerGraph, PeerTopology};
+use crate::unl_analysis::{DEFAULT_QUORUM_THRESHOLD, UnlAnalysis};
+use crate::ga::evaluation::ReevaluationPolicy;
+use crate::ga::local_search::LocalSearchConfiguration;
+use crate::ga::parameters::GaConfiguration;
+use crate::ga::encoding::delay_encoding::DelayEncodingType;
+use crate::ga::encoding::num_genes_on;
 use crate::ga::fitness::propose_seq_fitness::ProposeSeqFitness;
 
 mod app;
@@ -34,6 +47,16 @@ mod consensus_properties;
 mod locality;
 mod scaling;
 mod failure_writer;
+mod coverage;
+mod fuzzing;
+mod schedule_file;
+mod systematic;
+mod pct;
+mod online_learning;
+mod network_model;
+mod topology;
+mod unl;
+mod unl_analysis;
 
 type AnyError = Box<dyn std::error::Error + Send + Sync>;
 type AnyResult<T> = Result<T, AnyError>;
@@ -43,6 +66,8 @@ lazy_static! {
     pub static ref CONFIG: Configuration = get_config();
     pub static ref NUM_NODES: usize = CONFIG.num_nodes;
     pub static ref LOG_FOLDER: String = get_log_path();
+    /// The peer connections between the validators, the configuration is validated before it is used
+    pub static ref PEER_GRAPH: PeerGraph = CONFIG.peer_topology.peer_graph(CONFIG.num_nodes).expect("Invalid peer topology");
 }
 
 fn main() {
@@ -53,15 +78,39 @@ fn main() {
     env_logger::Builder::new().parse_default_env().init();
     debug!("Starting with config: {:?}", config);
 
+    if let Err(errors) = config.validate() {
+        for err in errors {
+            error!("Invalid configuration: {}", err);
+        }
+        std::process::exit(1);
+    }
+
     let unls: Vec<Vec<usize>> = get_unls(config.num_nodes, config.unl_type);
     println!("Unls: {:?}", unls);
+    if let Err(err) = unl::write_unls(&unls, &LOG_FOLDER) {
+        error!("Failed writing unls to the log folder: {}", err);
+    }
+    let unl_analysis = UnlAnalysis::new(&unls, config.quorum_threshold);
+    print!("{}", unl_analysis.report());
+    for warning in unl_analysis.warnings() {
+        warn!("Unsafe UNLs: {}", warning);
+    }
+    println!("Peer connections: {:?}", PEER_GRAPH.edges());
 
     println!("Image: {}", config.rippled_version.docker_image_name());
-    let node_keys = start_docker_containers(config.num_nodes, unls, config.rippled_version.docker_image_name());
+    let num_clusters = if config.scheduler_type.supports_clusters() {
+        config.num_clusters
+    } else {
+        if config.num_clusters > 1 {
+            warn!("{:?} does not support multiple clusters, starting a single cluster", config.scheduler_type);
+        }
+        1
+    };
+    let cluster_node_keys = start_docker_clusters(num_clusters, config.num_nodes, unls, config.rippled_version.docker_image_name());
     // let node_keys = get_static_node_keys();
     // let node_keys = start_executables(config.num_nodes, unls);
 
-    let app = app::App::new(config.num_nodes as u16, node_keys);
+    let app = app::App::new(config.num_nodes as u16, cluster_node_keys);
 
     if let Err(error) = match config.fitness_function {
         FitnessFunctionType::TimeFitness => runtime.block_on(app.start::<ga::fitness::time_fitness::TimeFitness>(config.scheduler_type)),
@@ -110,6 +159,8 @@ pub fn get_log_path() -> String {
 /// Full: Clique graph UNL
 /// Limit: The minimum unl configuration for achieving overlap = 2/5 avg(UNL_u, UNL_v)
 /// Buggy: The maximum unl configuration that results in a fork
+/// Custom: The UNL of every node as configured
+/// RandomOverlap, Clustered, Nested: Generated UNLs, see unl.rs
 pub fn get_unls(num_nodes: usize, unl_type: UnlType) -> Vec<Vec<usize>> {
     match unl_type {
         UnlType::Full => {
@@ -134,6 +185,10 @@ pub fn get_unls(num_nodes: usize, unl_type: UnlType) -> Vec<Vec<usize>> {
             let g3 = vec![((num_nodes - (small_isolated_unl + overlap))..num_nodes).collect(); small_isolated_unl];
             [g1, g2, g3].concat()
         }
+        UnlType::Custom { unls } => unls,
+        UnlType::RandomOverlap { unl_size, overlap, seed } => unl::random_overlap_unls(num_nodes, unl_size, overlap, seed),
+        UnlType::Clustered { num_clusters, shared } => unl::clustered_unls(num_nodes, num_clusters, shared),
+        UnlType::Nested { min_size } => unl::nested_unls(num_nodes, min_size),
     }
 }
 
@@ -178,6 +233,42 @@ pub enum UnlType {
     Full,
     Limit,
     Buggy,
+    Custom { unls: Vec<Vec<usize>> },
+    RandomOverlap { unl_size: usize, overlap: f64, seed: u64 },
+    Clustered { num_clusters: usize, shared: usize },
+    Nested { min_size: usize },
+}
+
+impl UnlType {
+    /// Check the parameters of the generators and that every node gets a non-empty UNL, returns all problems found
+    pub fn validate(&self, num_nodes: usize) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        match self {
+            UnlType::RandomOverlap { unl_size, overlap, .. } => {
+                if *unl_size == 0 || *unl_size > num_nodes {
+                    errors.push(format!("unl_size must be in [1, {}]", num_nodes));
+                }
+                if !(0.0..=1.0).contains(overlap) {
+                    errors.push("unl overlap must be in [0, 1]".to_string());
+                }
+            }
+            UnlType::Clustered { num_clusters, shared } => {
+                if *shared > num_nodes || *num_clusters > num_nodes - shared || (*num_clusters == 0 && *shared < num_nodes) {
+                    errors.push(format!("{} clusters and {} shared nodes do not divide {} nodes", num_clusters, shared, num_nodes));
+                }
+            }
+            UnlType::Nested { min_size } => {
+                if *min_size == 0 || *min_size > num_nodes {
+                    errors.push(format!("nested unl min_size must be in [1, {}]", num_nodes));
+                }
+            }
+            UnlType::Full | UnlType::Limit | UnlType::Buggy | UnlType::Custom { .. } => {}
+        }
+        if errors.is_empty() {
+            errors.extend(unl::check_unls(&get_unls(num_nodes, self.clone()), num_nodes));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
@@ -225,6 +316,40 @@ pub struct Configuration {
     #[serde_as(as = "DurationSeconds<i64>")]
     search_budget: Duration,
     create_ripple_log_folders: bool,
+    #[serde(default)]
+    reevaluation: ReevaluationPolicy,
+    /// The number of independent validator networks used to evaluate individuals in parallel
+    #[serde(default = "default_num_clusters")]
+    num_clusters: usize,
+    #[serde(default)]
+    ga: GaConfiguration,
+    #[serde(default)]
+    local_search: LocalSearchConfiguration,
+    #[serde(default)]
+    fuzzing: FuzzingConfiguration,
+    #[serde(default)]
+    schedule_files: ScheduleFilesConfiguration,
+    #[serde(default)]
+    systematic: SystematicConfiguration,
+    #[serde(default)]
+    pct: PctConfiguration,
+    #[serde(default)]
+    online_learning: OnlineLearningConfiguration,
+    #[serde(default)]
+    network_model: NetworkModelConfiguration,
+    #[serde(default)]
+    peer_topology: PeerTopology,
+    /// The fraction of its UNL a node needs to validate a ledger, only used to analyze the UNLs before starting
+    #[serde(default = "default_quorum_threshold")]
+    quorum_threshold: f64,
+}
+
+fn default_num_clusters() -> usize {
+    1
+}
+
+fn default_quorum_threshold() -> f64 {
+    DEFAULT_QUORUM_THRESHOLD
 }
 
 impl Configuration {
@@ -239,6 +364,68 @@ impl Configuration {
         let mut reader = BufReader::new(file);
         serde_json::from_reader(&mut reader).unwrap()
     }
+
+    /// Check the configuration before starting any containers, returns all problems found
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.num_nodes > CLUSTER_PORT_OFFSET {
+            errors.push(format!("A cluster can have at most {} nodes", CLUSTER_PORT_OFFSET));
+        }
+        if self.num_clusters == 0 {
+            errors.push("At least one cluster is needed".to_string());
+        }
+        if let Err(unl_errors) = self.unl_type.validate(self.num_nodes) {
+            errors.extend(unl_errors);
+        }
+        if self.quorum_threshold <= 0.0 || self.quorum_threshold > 1.0 {
+            errors.push("quorum_threshold must be in (0, 1]".to_string());
+        }
+        // The genotype layout is built from the peer graph, so the other parts cannot be checked without it
+        let peer_graph = match self.peer_topology.peer_graph(self.num_nodes) {
+            Ok(peer_graph) => peer_graph,
+            Err(peer_topology_errors) => {
+                errors.extend(peer_topology_errors);
+                return Err(errors);
+            }
+        };
+        if let Err(ga_errors) = self.ga.validate(self.num_nodes, &peer_graph, &self.reevaluation) {
+            errors.extend(ga_errors);
+        }
+        // Hill climbing moves one delay by 1 ms or swaps two adjacent priorities
+        let max_neighbours = match self.scheduler_type {
+            SchedulerType::DelayHillClimbing => Some(self.ga.num_delay_genes_on(&peer_graph)),
+            SchedulerType::PriorityHillClimbing => Some(num_genes_on(&peer_graph).saturating_sub(1)),
+            _ => None,
+        };
+        if let Err(local_search_errors) = self.local_search.validate(max_neighbours) {
+            errors.extend(local_search_errors);
+        }
+        if let Err(fuzzing_errors) = self.fuzzing.validate() {
+            errors.extend(fuzzing_errors);
+        }
+        // The predetermined delays are written for a single round in the full layout
+        if matches!(self.scheduler_type, SchedulerType::PredeterminedDelay) && self.ga.delay_encoding != DelayEncodingType::Full {
+            errors.push("PredeterminedDelay only supports the Full delay_encoding".to_string());
+        }
+        let runs_schedule_files = matches!(self.scheduler_type, SchedulerType::ScheduleFiles);
+        if let Err(schedule_file_errors) = self.schedule_files.validate(&peer_graph, &self.ga.delay_encoding, runs_schedule_files) {
+            errors.extend(schedule_file_errors);
+        }
+        if let Err(systematic_errors) = self.systematic.validate() {
+            errors.extend(systematic_errors);
+        }
+        if let Err(pct_errors) = self.pct.validate() {
+            errors.extend(pct_errors);
+        }
+        let runs_online_learning = matches!(self.scheduler_type, SchedulerType::OnlineLearning);
+        if let Err(online_learning_errors) = self.online_learning.validate(runs_online_learning) {
+            errors.extend(online_learning_errors);
+        }
+        if let Err(network_model_errors) = self.network_model.validate(&peer_graph) {
+            errors.extend(network_model_errors);
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
 }
 
 impl Default for Configuration {
@@ -251,6 +438,18 @@ impl Default for Configuration {
             fitness_function: FitnessFunctionType::TimeFitness,
             search_budget: Duration::seconds(3600),
             create_ripple_log_folders: true,
+            reevaluation: ReevaluationPolicy::default(),
+            num_clusters: default_num_clusters(),
+            ga: GaConfiguration::default(),
+            local_search: LocalSearchConfiguration::default(),
+            fuzzing: FuzzingConfiguration::default(),
+            schedule_files: ScheduleFilesConfiguration::default(),
+            systematic: SystematicConfiguration::default(),
+            pct: PctConfiguration::default(),
+            online_learning: OnlineLearningConfiguration::default(),
+            network_model: NetworkModelConfiguration::default(),
+            peer_topology: PeerTopology::default(),
+            quorum_threshold: default_quorum_threshold(),
         }
     }
 }
@@ -350,6 +549,42 @@ mod config_tests {
         ]);
     }
 
+    #[test]
+    fn unl_custom_test() {
+        let unls = vec![vec![0, 1], vec![1, 2], vec![0, 2]];
+        assert_eq!(get_unls(3, UnlType::Custom { unls: unls.clone() }), unls);
+        assert!(UnlType::Custom { unls }.validate(3).is_ok());
+        assert_eq!(UnlType::Custom { unls: vec![vec![0], vec![]] }.validate(3).unwrap_err().len(), 2);
+        assert!(UnlType::Nested { min_size: 0 }.validate(3).is_err());
+        assert!(UnlType::Clustered { num_clusters: 3, shared: 1 }.validate(3).is_err());
+    }
+
+    #[test]
+    fn validate_configuration() {
+        assert!(Configuration::default().validate().is_ok());
+        let mut configuration = Configuration::default();
+        configuration.ga.mu = 0;
+        configuration.ga.min_delay = 5000;
+        configuration.reevaluation.confidence_z = Some(1.96);
+        configuration.ga.selection = crate::ga::selection::SelectionType::RouletteWheel;
+        assert_eq!(configuration.validate().unwrap_err().len(), 3);
+        let mut predetermined = Configuration::default();
+        predetermined.scheduler_type = SchedulerType::PredeterminedDelay;
+        predetermined.ga.delay_encoding = crate::ga::encoding::delay_encoding::DelayEncodingType::PerSender;
+        assert_eq!(predetermined.validate().unwrap_err().len(), 1);
+        // The encoding and the number of genes follow the configured nodes, not the global configuration
+        let mut three_nodes = Configuration::default();
+        three_nodes.num_nodes = 3;
+        three_nodes.ga.delay_encoding = crate::ga::encoding::delay_encoding::DelayEncodingType::NodeGroups { groups: vec![vec![0, 1], vec![2]] };
+        assert!(three_nodes.validate().is_ok());
+        three_nodes.ga.delay_encoding = crate::ga::encoding::delay_encoding::DelayEncodingType::Full;
+        three_nodes.ga.delay_crossover = crate::ga::crossover::DelayCrossoverType::MultiPoint { num_cut_points: 6 * 13 };
+        assert_eq!(three_nodes.validate().unwrap_err().len(), 1);
+        // The rest of the configuration is not checked without a peer graph
+        configuration.peer_topology = crate::topology::PeerTopology::Star { center: 5 };
+        assert_eq!(configuration.validate().unwrap_err().len(), 1);
+    }
+
     #[test]
     fn write_configuration() {
         let configuration = Configuration {
@@ -360,6 +595,7 @@ mod config_tests {
             fitness_function: crate::FitnessFunctionType::TimeFitness,
             search_budget: chrono::Duration::seconds(3600),
             create_ripple_log_folders: false,
+            ..Configuration::default()
         };
         let mut config_writer = BufWriter::new(File::create(Path::new("config_example.json")).expect("Creating config file failed"));
         serde_json::to_writer(&mut config_writer, &configuration).expect("Failed writing to config file");
@@ -384,7 +620,8 @@ mod config_tests {
                 scheduler_type: SchedulerType::RandomDelay,
                 fitness_function: FitnessFunctionType::TimeFitness,
                 search_budget: chrono::Duration::seconds(3600),
-                create_ripple_log_folders: true
+                create_ripple_log_folders: true,
+                ..Configuration::default()
             });
             configurations.push(Configuration {
                 num_nodes: 5,
@@ -393,7 +630,8 @@ mod config_tests {
                 scheduler_type: SchedulerType::RandomPriority,
                 fitness_function: FitnessFunctionType::TimeFitness,
                 search_budget: chrono::Duration::seconds(3600),
-                create_ripple_log_folders: true
+                create_ripple_log_folders: true,
+                ..Configuration::default()
             });
             for scheduler_type in &scheduler_types {
                 for fitness_function in &fitness_functions {
@@ -404,7 +642,8 @@ mod config_tests {
                         scheduler_type: scheduler_type.clone(),
                         fitness_function: fitness_function.clone(),
                         search_budget: chrono::Duration::seconds(3600),
-                        create_ripple_log_folders: true
+                        create_ripple_log_folders: true,
+                        ..Configuration::default()
                     };
                     configurations.push(config);
                 }
@@ -0,0 +1,238 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal, Pareto};
+use crate::topology::PeerGraph;
+
+/// The one-way latency of a link, sampled for every message
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum LatencyDistribution {
+    Constant { ms: f64 },
+    /// Samples below 0 are cut off at 0
+    Normal { mean_ms: f64, std_dev_ms: f64 },
+    /// At least scale_ms, the smaller the shape the heavier the tail
+    Pareto { scale_ms: f64, shape: f64 },
+}
+
+impl Default for LatencyDistribution {
+    fn default() -> Self {
+        Self::Constant { ms: 0.0 }
+    }
+}
+
+impl LatencyDistribution {
+    fn validate(&self, link: &str) -> Vec<String> {
+        let valid = match self {
+            Self::Constant { ms } => *ms >= 0.0,
+            Self::Normal { mean_ms, std_dev_ms } => *mean_ms >= 0.0 && *std_dev_ms >= 0.0,
+            Self::Pareto { scale_ms, shape } => *scale_ms > 0.0 && *shape > 0.0,
+        };
+        if valid { vec![] } else { vec![format!("network_model {} has invalid latency parameters {:?}", link, self)] }
+    }
+
+    pub fn sample_ms(&self, rng: &mut ChaCha8Rng) -> f64 {
+        let ms = match self {
+            Self::Constant { ms } => *ms,
+            Self::Normal { mean_ms, std_dev_ms } => Normal::new(*mean_ms, *std_dev_ms).expect("validated normal").sample(rng),
+            Self::Pareto { scale_ms, shape } => Pareto::new(*scale_ms, *shape).expect("validated pareto").sample(rng),
+        };
+        ms.max(0.0)
+    }
+}
+
+/// A directed link between two nodes
+/// bytes_per_second: Messages are transmitted one after the other at this rate, None for unlimited bandwidth
+/// fifo: Messages arrive in the order they were sent, as on the TCP connection between rippled peers
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LinkConfiguration {
+    pub latency: LatencyDistribution,
+    pub bytes_per_second: Option<u64>,
+    pub fifo: bool,
+}
+
+impl Default for LinkConfiguration {
+    fn default() -> Self {
+        Self {
+            latency: LatencyDistribution::default(),
+            bytes_per_second: None,
+            fifo: true,
+        }
+    }
+}
+
+impl LinkConfiguration {
+    fn validate(&self, link: &str) -> Vec<String> {
+        let mut errors = self.latency.validate(link);
+        if self.bytes_per_second == Some(0) {
+            errors.push(format!("network_model {} needs a bytes_per_second above 0", link));
+        }
+        errors
+    }
+
+    fn transmission_time(&self, bytes: usize) -> Duration {
+        match self.bytes_per_second {
+            Some(bytes_per_second) => Duration::from_secs_f64(bytes as f64 / bytes_per_second as f64),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+/// The link from one node to another, if it differs from the default link
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub struct LinkOverride {
+    pub from: usize,
+    pub to: usize,
+    #[serde(flatten)]
+    pub link: LinkConfiguration,
+}
+
+/// The network between the nodes as set in the configuration, applied by the proxy to every message the scheduler releases
+/// enabled: Without the network model messages are handed to the receiver as soon as they are released
+/// seed: Seed of the latency samples
+/// default_link: The link between nodes that are not in links
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct NetworkModelConfiguration {
+    pub enabled: bool,
+    pub seed: u64,
+    pub default_link: LinkConfiguration,
+    pub links: Vec<LinkOverride>,
+}
+
+impl NetworkModelConfiguration {
+    /// Check the links for values the model cannot run with, returns all problems found
+    pub fn validate(&self, peer_graph: &PeerGraph) -> Result<(), Vec<String>> {
+        let mut errors = self.default_link.validate("default_link");
+        let mut seen = HashSet::new();
+        for link in &self.links {
+            let name = format!("link {} -> {}", link.from, link.to);
+            let num_nodes = peer_graph.num_nodes();
+            if link.from >= num_nodes || link.to >= num_nodes {
+                errors.push(format!("network_model {} refers to a node outside 0..{}", name, num_nodes));
+            } else if link.from == link.to {
+                errors.push(format!("network_model {} connects a node to itself", name));
+            } else if peer_graph.link_index(link.from, link.to).is_none() {
+                errors.push(format!("network_model {} is not a connection of the peer_topology", name));
+            }
+            if !seen.insert((link.from, link.to)) {
+                errors.push(format!("network_model {} is configured more than once", name));
+            }
+            errors.extend(link.link.validate(&name));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// Messages in flight on a link
+/// free_at: The moment the last message is transmitted and the link can take the next one
+/// last_arrival: The moment the last message arrives, later messages on a fifo link do not arrive before it
+#[derive(Debug, Clone)]
+struct LinkState {
+    free_at: Instant,
+    last_arrival: Instant,
+}
+
+/// Decides when a message arrives at its receiver
+pub struct NetworkModel {
+    links: HashMap<(usize, usize), LinkConfiguration>,
+    default_link: LinkConfiguration,
+    states: HashMap<(usize, usize), LinkState>,
+    rng: ChaCha8Rng,
+}
+
+impl NetworkModel {
+    pub fn new(configuration: &NetworkModelConfiguration) -> Self {
+        Self {
+            links: configuration.links.iter().map(|link| ((link.from, link.to), link.link.clone())).collect(),
+            default_link: configuration.default_link.clone(),
+            states: HashMap::new(),
+            rng: ChaCha8Rng::seed_from_u64(configuration.seed),
+        }
+    }
+
+    fn link(&self, from: usize, to: usize) -> &LinkConfiguration {
+        self.links.get(&(from, to)).unwrap_or(&self.default_link)
+    }
+
+    pub fn is_fifo(&self, from: usize, to: usize) -> bool {
+        self.link(from, to).fifo
+    }
+
+    /// The moment a message of the given size sent at now arrives: it waits for the link, is transmitted and then has the latency of the link
+    pub fn arrival(&mut self, from: usize, to: usize, bytes: usize, now: Instant) -> Instant {
+        let link = self.links.get(&(from, to)).unwrap_or(&self.default_link);
+        let latency = Duration::from_secs_f64(link.latency.sample_ms(&mut self.rng) / 1000.0);
+        let transmission_time = link.transmission_time(bytes);
+        let fifo = link.fifo;
+        let state = self.states.entry((from, to)).or_insert(LinkState { free_at: now, last_arrival: now });
+        state.free_at = state.free_at.max(now) + transmission_time;
+        let mut arrival = state.free_at + latency;
+        if fifo {
+            arrival = arrival.max(state.last_arrival);
+            state.last_arrival = arrival;
+        }
+        arrival
+    }
+}
+
+#[cfg(test)]
+mod network_model_tests {
+    use std::time::{Duration, Instant};
+    use crate::network_model::{LatencyDistribution, LinkConfiguration, LinkOverride, NetworkModel, NetworkModelConfiguration};
+    use crate::topology::{PeerGraph, PeerTopology};
+
+    fn configuration(default_link: LinkConfiguration, links: Vec<LinkOverride>) -> NetworkModelConfiguration {
+        NetworkModelConfiguration { enabled: true, seed: 1, default_link, links }
+    }
+
+    #[test]
+    fn test_bandwidth_queues_messages() {
+        let link = LinkConfiguration { latency: LatencyDistribution::Constant { ms: 50.0 }, bytes_per_second: Some(1000), fifo: true };
+        let mut model = NetworkModel::new(&configuration(link, vec![]));
+        let now = Instant::now();
+        assert_eq!(model.arrival(0, 1, 100, now), now + Duration::from_millis(150));
+        // The second message waits until the first is transmitted
+        assert_eq!(model.arrival(0, 1, 100, now), now + Duration::from_millis(250));
+        // Other links are not affected
+        assert_eq!(model.arrival(1, 0, 100, now), now + Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_fifo_keeps_order() {
+        let jittery = LinkConfiguration { latency: LatencyDistribution::Normal { mean_ms: 100.0, std_dev_ms: 50.0 }, bytes_per_second: None, fifo: true };
+        let unordered = LinkConfiguration { fifo: false, ..jittery.clone() };
+        let mut model = NetworkModel::new(&configuration(jittery, vec![LinkOverride { from: 1, to: 0, link: unordered }]));
+        let now = Instant::now();
+        let fifo_arrivals = (0..100).map(|_| model.arrival(0, 1, 10, now)).collect::<Vec<_>>();
+        assert!(fifo_arrivals.windows(2).all(|pair| pair[0] <= pair[1]));
+        let arrivals = (0..100).map(|_| model.arrival(1, 0, 10, now)).collect::<Vec<_>>();
+        assert!(arrivals.windows(2).any(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn test_pareto_latency() {
+        let link = LinkConfiguration { latency: LatencyDistribution::Pareto { scale_ms: 20.0, shape: 1.5 }, bytes_per_second: None, fifo: false };
+        let mut model = NetworkModel::new(&configuration(link, vec![]));
+        let now = Instant::now();
+        assert!((0..100).all(|_| model.arrival(0, 1, 10, now) >= now + Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(NetworkModelConfiguration::default().validate(&PeerGraph::complete(3)).is_ok());
+        let invalid = LinkConfiguration { latency: LatencyDistribution::Pareto { scale_ms: 0.0, shape: 1.0 }, bytes_per_second: Some(0), fifo: true };
+        let links = vec![
+            LinkOverride { from: 0, to: 3, link: LinkConfiguration::default() },
+            LinkOverride { from: 1, to: 1, link: LinkConfiguration::default() },
+            LinkOverride { from: 0, to: 1, link: invalid },
+            LinkOverride { from: 0, to: 1, link: LinkConfiguration::default() },
+        ];
+        assert_eq!(configuration(LinkConfiguration::default(), links).validate(&PeerGraph::complete(3)).unwrap_err().len(), 5);
+        // Links between nodes that are not connected are never used
+        let star = PeerTopology::Star { center: 0 }.peer_graph(3).unwrap();
+        let links = vec![LinkOverride { from: 1, to: 2, link: LinkConfiguration::default() }];
+        assert!(configuration(LinkConfiguration::default(), links).validate(&star).is_err());
+    }
+}
@@ -2,14 +2,17 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use chrono::Utc;
 use itertools::{Itertools};
-use log::{error, trace};
-use parking_lot::{Mutex, Condvar};
+use log::trace;
+use parking_lot::{Mutex, MutexGuard, Condvar};
 use petgraph::Graph;
 use petgraph::prelude::NodeIndex;
 use crate::client::{PeerServerStateObject, ServerStateObject, Transaction, ValidatedLedger};
 use crate::collector::RippleMessage;
+use crate::coverage::{AbstractConsensusState, CoverageTracker};
 use crate::failure_writer::{ConsensusPropertyTypes, Failure};
 use crate::ga::encoding::delay_encoding::DelayGenotype;
 use crate::message_handler::ParsedValidation;
@@ -31,8 +34,6 @@ pub struct NodeState {
     pub unvalidated_transactions: Vec<Transaction>,
     pub validated_transactions: Vec<(Transaction, TransactionResultCode)>,
     pub number_of_failed_consensus_rounds: u32,
-    pub unreceived_message_sends: Vec<(RippleMessage, Option<DependencyNode>)>,
-    pub latest_message_received: Option<DependencyNode>,
     pub server_state: ServerStateObject,
     pub bowed_out: bool,
 }
@@ -52,28 +53,35 @@ impl NodeState {
             unvalidated_transactions: vec![],
             validated_transactions: vec![],
             number_of_failed_consensus_rounds: 0,
-            unreceived_message_sends: vec![],
-            latest_message_received: None,
             server_state: ServerStateObject::default(),
             bowed_out: false,
         }
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Serialize, serde::Deserialize)]
 pub enum ConsensusPhase {
     Open,
     Establish,
     Accepted,
 }
 
-/// Combine individual node states for aggregate functions
+impl ConsensusPhase {
+    pub const VALUES: [Self; 3] = [Self::Open, Self::Establish, Self::Accepted];
+
+    /// The position of the phase in VALUES
+    pub fn index(&self) -> usize {
+        match self {
+            Self::Open => 0,
+            Self::Establish => 1,
+            Self::Accepted => 2,
+        }
+    }
+}
+
+/// State of a harness run that is not tied to a single node
 #[derive(Clone, Debug)]
-pub struct NodeStates {
-    pub number_of_nodes: usize,
-    pub node_states: Vec<NodeState>,
-    pub executions: Vec<RippleMessage>,
-    pub dependency_graph: Graph<DependencyEvent, ()>,
+pub struct HarnessState {
     pub current_individual: String,
     pub current_delays: DelayGenotype,
     pub server_state_updates: Vec<bool>,
@@ -81,16 +89,12 @@ pub struct NodeStates {
     pub bow_outs: u32,
     pub harness_transactions: Vec<TransactionTimed>,
     pub test_start_time: chrono::DateTime<Utc>,
+    pub harness_start_round: u32,
 }
 
-impl NodeStates {
-    pub fn new(node_states: Vec<NodeState>) -> Self {
-        let number_of_nodes = node_states.len();
-        NodeStates {
-            number_of_nodes,
-            node_states,
-            executions: vec![],
-            dependency_graph: petgraph::Graph::new(),
+impl HarnessState {
+    fn new(number_of_nodes: usize) -> Self {
+        Self {
             current_individual: String::new(),
             current_delays: vec![],
             server_state_updates: vec![false; number_of_nodes],
@@ -98,314 +102,319 @@ impl NodeStates {
             bow_outs: 0,
             harness_transactions: vec![],
             test_start_time: Utc::now(),
+            harness_start_round: 0,
         }
     }
+}
 
-    fn set_current_round(&mut self, peer: usize, new_round: u32) {
-        self.node_states[peer].current_consensus_round = new_round;
-        self.node_states[peer].bowed_out = false;
-    }
-
-    pub fn max_current_round(&self) -> u32 {
-        self.node_states.iter().map(|x| x.current_consensus_round).max().expect("node states is empty")
-    }
-
-    pub fn min_current_round(&self) -> u32 {
-        self.node_states.iter().map(|x| x.current_consensus_round).min().expect("node states is empty")
-    }
-
-    pub fn diff_rounds(&self) -> u32 {
-        self.max_current_round() - self.min_current_round()
-    }
-
-    pub fn min_validated_ledger(&self) -> u32 {
-        self.node_states.iter().map(|state| state.last_validated_ledger.ledger_index).min().expect("node states is empty")
-    }
-
-    /// Check if nodes with equal validated ledger index have equal hash
-    pub fn check_for_fork(&self) -> bool {
-        let index_hash_map = self.node_states.iter()
-            .map(|state| (state.last_validated_ledger.ledger_index, &state.last_validated_ledger.ledger_hash))
-            .into_group_map();
-        let res = !index_hash_map.iter().all(|x| x.1.iter().all_equal());
-        if res {
-            println!("{:?}", index_hash_map);
-        }
-        res
-    }
-
-    /// Liveness is at risk if one or more nodes stop validating, while the rest continues
-    pub fn check_liveness(&self) -> bool {
-        self.max_validated_ledger() - self.min_validated_ledger() < 2
-    }
+/// The consensus messages delivered during the current harness run, in delivery order
+/// Messages are only appended, the count can be read without taking the lock
+#[derive(Debug, Default)]
+pub struct ExecutionLog {
+    executions: Mutex<Vec<RippleMessage>>,
+    count: AtomicUsize,
+}
 
-    pub fn max_validated_ledger(&self) -> u32 {
-        self.node_states.iter().map(|state| state.last_validated_ledger.ledger_index).max().expect("node states is empty")
+impl ExecutionLog {
+    pub fn append(&self, ripple_message: RippleMessage) {
+        let mut executions = self.executions.lock();
+        executions.push(ripple_message);
+        self.count.store(executions.len(), Ordering::SeqCst);
     }
 
-    pub fn validated_ledgers(&self) -> Vec<u32> {
-        self.node_states.iter().map(|state| state.last_validated_ledger.ledger_index).collect()
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
     }
 
-    pub fn total_number_of_failed_consensus_rounds(&self) -> u32 {
-        self.node_states.iter().map(|state| state.number_of_failed_consensus_rounds).sum()
+    pub fn to_vec(&self) -> Vec<RippleMessage> {
+        self.executions.lock().clone()
     }
 
-    pub fn clear_number_of_failed_consensus_rounds(&mut self) {
-        for i in 0..self.node_states.len() {
-            self.node_states[i].number_of_failed_consensus_rounds = 0;
-        }
+    /// Start a new harness run
+    pub fn clear(&self) {
+        let mut executions = self.executions.lock();
+        executions.clear();
+        self.count.store(0, Ordering::SeqCst);
     }
+}
 
-    pub fn clear_transactions(&mut self) {
-        for i in 0..self.node_states.len() {
-            self.node_states[i].unvalidated_transactions.clear();
-            self.node_states[i].validated_transactions.clear();
-        }
-    }
+/// Builds the happens-before graph of the delivered consensus messages
+/// A receive depends on the previous receive of the same node and on the send that caused it,
+/// a send depends on the latest receive of the sender at the moment it was sent
+#[derive(Clone, Debug)]
+pub struct DependencyGraphBuilder {
+    graph: Graph<DependencyEvent, ()>,
+    unreceived_message_sends: Vec<Vec<(RippleMessage, Option<DependencyNode>)>>,
+    latest_message_received: Vec<Option<DependencyNode>>,
+}
 
-    pub fn clear_dependency_graph(&mut self) {
-        self.dependency_graph = petgraph::Graph::new();
-        for i in 0..self.node_states.len() {
-            self.node_states[i].unreceived_message_sends = vec![];
-            self.node_states[i].latest_message_received = None;
+impl DependencyGraphBuilder {
+    pub fn new(number_of_nodes: usize) -> Self {
+        Self {
+            graph: Graph::new(),
+            unreceived_message_sends: vec![vec![]; number_of_nodes],
+            latest_message_received: vec![None; number_of_nodes],
         }
     }
 
     pub fn add_receive_dependency(&mut self, ripple_message: RippleMessage) {
         let dependency_event = DependencyEvent { ripple_message: ripple_message.clone() };
-        let node_index = self.dependency_graph.add_node(dependency_event.clone());
+        let node_index = self.graph.add_node(dependency_event.clone());
         let dependency_node = DependencyNode { event: dependency_event, index: node_index };
 
         // Match sender's latest message sent to this receive if possible
-        if let Some(pos) = self.node_states[ripple_message.sender_index()].unreceived_message_sends
-            .iter()
-            .position(|x| x.0 == ripple_message)
-        {
-            let (_, dependency_node) = self.node_states[ripple_message.sender_index()].unreceived_message_sends.remove(pos);
+        let unreceived_message_sends = &mut self.unreceived_message_sends[ripple_message.sender_index()];
+        if let Some(pos) = unreceived_message_sends.iter().position(|x| x.0 == ripple_message) {
+            let (_, dependency_node) = unreceived_message_sends.remove(pos);
             match dependency_node {
                 None => {}
-                Some(node) => { self.dependency_graph.add_edge(node.index, node_index, ()); }
+                Some(node) => { self.graph.add_edge(node.index, node_index, ()); }
             }
         }
 
-        match &self.node_states[ripple_message.receiver_index()].latest_message_received {
+        match &self.latest_message_received[ripple_message.receiver_index()] {
             None => {}
-            Some(receive_dependency) => { self.dependency_graph.add_edge(receive_dependency.index, node_index, ()); }
+            Some(receive_dependency) => { self.graph.add_edge(receive_dependency.index, node_index, ()); }
         }
-        self.node_states[ripple_message.receiver_index()].latest_message_received = Some(dependency_node);
+        self.latest_message_received[ripple_message.receiver_index()] = Some(dependency_node);
     }
 
     pub fn add_send_dependency(&mut self, ripple_message: RippleMessage) {
-        // Update trace graph
-        let latest_message_received = self.node_states[ripple_message.sender_index()].latest_message_received.clone();
-        self.node_states[ripple_message.sender_index()].unreceived_message_sends.push((ripple_message.clone(), latest_message_received));
-    }
-
-    pub fn set_current_individual(&mut self, individual: String) {
-        self.current_individual = individual;
+        let latest_message_received = self.latest_message_received[ripple_message.sender_index()].clone();
+        self.unreceived_message_sends[ripple_message.sender_index()].push((ripple_message, latest_message_received));
     }
 
-    pub fn get_current_individual(&self) -> String {
-        self.current_individual.clone()
+    pub fn graph(&self) -> &Graph<DependencyEvent, ()> {
+        &self.graph
     }
 
-    pub fn set_current_delays(&mut self, delays: DelayGenotype) {
-        self.current_delays = delays;
-    }
-
-    /// Returns true when the server states for all nodes have been updated
-    pub fn set_server_state(&mut self, server_state: PeerServerStateObject) -> bool {
-        self.server_state_updates[server_state.peer as usize] = true;
-        self.node_states[server_state.peer as usize].server_state = server_state.server_state_object;
-        if self.server_state_updates.iter().all(|x| *x) {
-            self.server_state_updates = vec![false; self.node_states.len()];
-            return true;
-        }
-        false
-    }
-
-    pub fn get_server_state(&self, peer: usize) -> ServerStateObject {
-        self.node_states[peer].server_state.clone()
-    }
-
-    fn set_highest_propose_seq(&mut self, propose_seq: u32, peer: usize) {
-        if propose_seq == 4294967295 && !self.node_states[peer].bowed_out {
-            self.bow_outs += 1;
-        } else if propose_seq > self.highest_propose_seq {
-            self.highest_propose_seq = propose_seq;
-        }
-    }
-
-    fn get_highest_propose_seq(&self) -> (u32, u32) {
-        (self.highest_propose_seq, self.bow_outs)
-    }
-
-    fn clear_propose_seq(&mut self) {
-        self.highest_propose_seq = 0;
-        self.bow_outs = 0;
-    }
-
-    fn set_harness_transactions(&mut self, harness_transactions: Vec<TransactionTimed>) {
-        self.harness_transactions = harness_transactions;
+    pub fn clear(&mut self) {
+        *self = Self::new(self.latest_message_received.len());
     }
+}
 
-    pub(crate) fn add_sent_validation(&mut self, validation: ParsedValidation, peer: usize) -> Option<ParsedValidation> {
-        self.node_states[peer].consensus_transaction_sets.insert(validation.ledger_sequence as usize, hex::decode(&validation.consensus_hash).unwrap());
-        self.node_states[peer].validations_sent.insert(validation.ledger_sequence as usize, validation)
-    }
+/// A condition variable with its own lock, so waiting does not hold up the state it is about
+/// Waiters are woken by the next notify after they started waiting
+#[derive(Debug, Default)]
+pub struct Notifier {
+    notifications: Mutex<u64>,
+    cvar: Condvar,
+}
 
-    pub(crate) fn add_consensus_constructed_ledger(&mut self, status_change: TMStatusChange, peer: usize) -> Option<TMStatusChange> {
-        self.node_states[peer].consensus_constructed_ledgers.insert(status_change.get_ledgerSeq() as usize, status_change)
+impl Notifier {
+    pub fn notify_all(&self) {
+        *self.notifications.lock() += 1;
+        self.cvar.notify_all();
     }
 
-    pub(crate) fn add_proposed_tx_set(&mut self, tx_set: &[u8], peer: usize) {
-        let seq = self.node_states[peer].current_consensus_round as usize;
-        if !self.node_states[peer].proposed_tx_sets.contains_key(&seq) {
-            self.node_states[peer].proposed_tx_sets.insert(seq, HashSet::new());
-        }
-        match self.node_states[peer].proposed_tx_sets.get_mut(&seq) {
-            None => error!("Just added an empty set and it disappeared"),
-            Some(set) => {
-                set.insert(tx_set.to_vec());
-            },
+    pub fn wait(&self) {
+        let mut notifications = self.notifications.lock();
+        let seen = *notifications;
+        while *notifications == seen {
+            self.cvar.wait(&mut notifications);
         }
     }
 
-    fn clear_consensus_property_data(&mut self) {
-        for i in 0..self.node_states.len() {
-            self.node_states[i].validations_sent.clear();
-            self.node_states[i].consensus_constructed_ledgers.clear();
-            self.node_states[i].consensus_transaction_sets.clear();
-            self.node_states[i].validated_ledgers.clear();
+    /// Returns false if the timeout passed without a notify
+    pub fn wait_for(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut notifications = self.notifications.lock();
+        let seen = *notifications;
+        while *notifications == seen {
+            if self.cvar.wait_until(&mut notifications, deadline).timed_out() {
+                return *notifications != seen;
+            }
         }
-        self.test_start_time = Utc::now();
+        true
     }
 }
 
-/// Wrap NodeStates in a Mutex with Condvars for convenient access in the rest of the program.
-/// This struct should always be wrapped in a Arc
-/// This struct should always be used to access and modify node_state data
+/// The state of all nodes, shared by the rest of the program. This struct should always be wrapped in a Arc
+/// Every node has its own lock, and the execution log, the dependency graph, the coverage and the harness state are locked separately,
+/// so the property checker and the collector do not hold up the scheduler.
+/// A node lock may be held while taking the coverage lock, no other locks are held at the same time.
+/// Aggregates over all nodes lock the nodes one at a time.
 #[derive(Debug)]
 pub struct MutexNodeStates {
     pub number_of_nodes: usize,
-    pub node_states: Mutex<NodeStates>,
-    pub round_cvar: Condvar,
-    pub consensus_phase_cvar: Condvar,
-    pub validated_ledger_cvar: Condvar,
-    pub transactions_cvar: Condvar,
-    pub server_state_cvar: Condvar,
+    nodes: Vec<Mutex<NodeState>>,
+    harness: Mutex<HarnessState>,
+    executions: ExecutionLog,
+    dependency_graph: Mutex<DependencyGraphBuilder>,
+    coverage: Mutex<CoverageTracker>,
+    pub round_cvar: Notifier,
+    pub consensus_phase_cvar: Notifier,
+    pub validated_ledger_cvar: Notifier,
+    pub transactions_cvar: Notifier,
+    pub server_state_cvar: Notifier,
 }
 
 impl MutexNodeStates {
-    pub fn new(node_states: NodeStates) -> Self {
+    pub fn new(node_states: Vec<NodeState>) -> Self {
+        let number_of_nodes = node_states.len();
         MutexNodeStates {
-            number_of_nodes: node_states.node_states.len(),
-            node_states: Mutex::new(node_states),
-            round_cvar: Condvar::new(),
-            consensus_phase_cvar: Condvar::new(),
-            validated_ledger_cvar: Condvar::new(),
-            transactions_cvar: Condvar::new(),
-            server_state_cvar: Condvar::new(),
+            number_of_nodes,
+            nodes: node_states.into_iter().map(Mutex::new).collect(),
+            harness: Mutex::new(HarnessState::new(number_of_nodes)),
+            executions: ExecutionLog::default(),
+            dependency_graph: Mutex::new(DependencyGraphBuilder::new(number_of_nodes)),
+            coverage: Mutex::new(CoverageTracker::new(number_of_nodes)),
+            round_cvar: Notifier::default(),
+            consensus_phase_cvar: Notifier::default(),
+            validated_ledger_cvar: Notifier::default(),
+            transactions_cvar: Notifier::default(),
+            server_state_cvar: Notifier::default(),
         }
     }
 
+    /// Lock the state of a single node
+    pub(crate) fn node(&self, peer: usize) -> MutexGuard<'_, NodeState> {
+        self.nodes[peer].lock()
+    }
+
+    /// A copy of the state of every node, taken one node at a time
+    pub fn snapshot(&self) -> Vec<NodeState> {
+        self.nodes.iter().map(|node| node.lock().clone()).collect()
+    }
+
+    fn map_nodes<T>(&self, f: impl Fn(&NodeState) -> T) -> Vec<T> {
+        self.nodes.iter().map(|node| f(&node.lock())).collect()
+    }
+
     pub fn set_current_round(&self, peer: usize, new_round: u32) {
-        self.node_states.lock().set_current_round(peer, new_round);
+        {
+            let mut node = self.node(peer);
+            node.current_consensus_round = new_round;
+            node.bowed_out = false;
+        }
+        self.coverage.lock().new_round(peer);
+        self.record_coverage(peer);
         self.round_cvar.notify_all();
     }
 
-    /// Take care not to get a deadlock here, calls to associated methods acquire locks
+    /// Add the current abstract consensus state of the node to the coverage
+    fn record_coverage(&self, peer: usize) {
+        let min_round = self.min_current_round();
+        let node = self.node(peer);
+        self.coverage.lock().record(&node, min_round);
+    }
+
     pub fn set_consensus_phase(&self, peer: usize, new_phase: ConsensusPhase) {
-        let current_phase = self.node_states.lock().node_states[peer].consensus_phase.clone();
-        if new_phase == ConsensusPhase::Open {
-            if current_phase != ConsensusPhase::Accepted {
-                self.node_states.lock().node_states[peer].number_of_failed_consensus_rounds += 1;
-                println!("Failed consensus round peer {}: establish -> open", peer);
+        {
+            let mut node = self.node(peer);
+            if new_phase == ConsensusPhase::Open {
+                if node.consensus_phase != ConsensusPhase::Accepted {
+                    node.number_of_failed_consensus_rounds += 1;
+                    println!("Failed consensus round peer {}: establish -> open", peer);
+                }
+            } else if new_phase == ConsensusPhase::Establish && node.consensus_phase != ConsensusPhase::Open {
+                node.number_of_failed_consensus_rounds += 1;
+                println!("Failed consensus round peer {}: accepted -> establish", peer);
             }
-        } else if new_phase == ConsensusPhase::Establish && current_phase != ConsensusPhase::Open {
-            self.node_states.lock().node_states[peer].number_of_failed_consensus_rounds += 1;
-            println!("Failed consensus round peer {}: accepted -> establish", peer);
+            node.consensus_phase = new_phase;
         }
-        self.node_states.lock().node_states[peer].consensus_phase = new_phase;
+        self.record_coverage(peer);
         self.consensus_phase_cvar.notify_all();
     }
 
     pub fn set_validated_ledger(&self, peer: usize, new_validated_ledger: ValidatedLedger) {
-        let mut node_states = self.node_states.lock();
-        node_states.node_states[peer].last_validated_ledger = new_validated_ledger.clone();
-        node_states.node_states[peer].validated_ledgers.insert(new_validated_ledger.ledger_index as usize, new_validated_ledger);
+        {
+            let mut node = self.node(peer);
+            node.last_validated_ledger = new_validated_ledger.clone();
+            node.validated_ledgers.insert(new_validated_ledger.ledger_index as usize, new_validated_ledger);
+        }
         self.validated_ledger_cvar.notify_all();
     }
 
     pub fn clear_transactions(&self) {
-        self.node_states.lock().clear_transactions();
-        self.node_states.lock().clear_dependency_graph();
+        for node in &self.nodes {
+            let mut node = node.lock();
+            node.unvalidated_transactions.clear();
+            node.validated_transactions.clear();
+        }
+        self.dependency_graph.lock().clear();
         self.transactions_cvar.notify_all();
     }
 
     pub fn add_unvalidated_transaction(&self, peer: usize, transaction: Transaction) {
-        self.node_states.lock().node_states[peer].unvalidated_transactions.push(transaction);
+        self.node(peer).unvalidated_transactions.push(transaction);
         self.transactions_cvar.notify_all();
     }
 
     pub fn add_validated_transaction(&self, peer: usize, transaction: Transaction, result: TransactionResultCode) {
-        let mut node_lock = self.node_states.lock();
         trace!("Added validated transaction: {:?}", &transaction.source_tag);
-        node_lock.node_states[peer].validated_transactions.push((transaction, result));
+        self.node(peer).validated_transactions.push((transaction, result));
         self.transactions_cvar.notify_all();
     }
 
     pub fn get_current_round(&self, peer: usize) -> u32 {
-        self.node_states.lock().node_states[peer].current_consensus_round
+        self.node(peer).current_consensus_round
+    }
+
+    /// Record the round of the slowest node as the first round of the harness run
+    pub fn set_harness_start_round(&self) {
+        let min_current_round = self.min_current_round();
+        self.harness.lock().harness_start_round = min_current_round;
+    }
+
+    /// The number of rounds the node has progressed since the start of the harness run
+    pub fn get_round_offset(&self, peer: usize) -> u32 {
+        let harness_start_round = self.harness.lock().harness_start_round;
+        self.get_current_round(peer).saturating_sub(harness_start_round)
     }
 
     pub fn get_consensus_phase(&self, peer: usize) -> ConsensusPhase {
-        self.node_states.lock().node_states[peer].consensus_phase.clone()
+        self.node(peer).consensus_phase.clone()
     }
 
     pub fn set_current_individual(&self, individual: String) {
-        self.node_states.lock().set_current_individual(individual);
+        self.harness.lock().current_individual = individual;
     }
 
     pub fn get_current_individual(&self) -> String {
-        self.node_states.lock().get_current_individual()
+        self.harness.lock().current_individual.clone()
     }
 
     pub fn set_current_delays(&self, delays: DelayGenotype) {
-        self.node_states.lock().set_current_delays(delays);
+        self.harness.lock().current_delays = delays;
     }
 
     pub fn get_current_delays(&self) -> DelayGenotype {
-        self.node_states.lock().current_delays.clone()
+        self.harness.lock().current_delays.clone()
+    }
+
+    pub fn get_test_start_time(&self) -> chrono::DateTime<Utc> {
+        self.harness.lock().test_start_time
     }
 
     pub fn get_number_of_failed_consensus_rounds(&self, peer: usize) -> u32 {
-        self.node_states.lock().node_states[peer].number_of_failed_consensus_rounds
+        self.node(peer).number_of_failed_consensus_rounds
     }
 
     pub fn get_total_number_of_failed_consensus_rounds(&self) -> u32 {
-        self.node_states.lock().total_number_of_failed_consensus_rounds()
+        self.map_nodes(|node| node.number_of_failed_consensus_rounds).iter().sum()
     }
 
     pub fn clear_number_of_failed_consensus_rounds(&self) {
-        self.node_states.lock().clear_number_of_failed_consensus_rounds();
+        for node in &self.nodes {
+            node.lock().number_of_failed_consensus_rounds = 0;
+        }
     }
 
     pub fn get_validated_ledger(&self, peer: usize) -> ValidatedLedger {
-        self.node_states.lock().node_states[peer].last_validated_ledger.clone()
+        self.node(peer).last_validated_ledger.clone()
     }
 
     pub fn get_max_validated_transactions(&self) -> HashSet<(Transaction, TransactionResultCode)> {
-        self.node_states.lock().node_states.iter()
-            .flat_map(|x| x.validated_transactions.clone())
+        self.map_nodes(|node| node.validated_transactions.clone()).into_iter()
+            .flatten()
             .unique()
             .collect::<HashSet<(Transaction, TransactionResultCode)>>()
     }
 
     pub fn get_min_validated_transactions(&self) -> HashSet<(Transaction, TransactionResultCode)> {
-        self.node_states.lock().node_states.iter()
-            .flat_map(|x| x.validated_transactions.clone())
+        self.map_nodes(|node| node.validated_transactions.clone()).into_iter()
+            .flatten()
             .counts()
             .into_iter()
             .filter(|(_tx, count)| *count == self.number_of_nodes)
@@ -421,115 +430,191 @@ impl MutexNodeStates {
     }
 
     pub fn get_number_min_validated_transactions(&self) -> usize {
-        self.node_states.lock().node_states.iter().map(|x| x.validated_transactions.len()).min().unwrap()
+        self.map_nodes(|node| node.validated_transactions.len()).into_iter().min().unwrap()
     }
 
     pub fn get_number_max_validated_transaction(&self) -> usize {
-        self.node_states.lock().node_states.iter().map(|x| x.validated_transactions.len()).max().unwrap()
+        self.map_nodes(|node| node.validated_transactions.len()).into_iter().max().unwrap()
     }
 
     pub fn get_min_unvalidated_transactions(&self) -> usize {
-        self.node_states.lock().node_states.iter().map(|x| x.unvalidated_transactions.len()).min().unwrap()
+        self.map_nodes(|node| node.unvalidated_transactions.len()).into_iter().min().unwrap()
     }
 
     pub fn get_unvalidated_transaction(&self, peer: usize) -> Vec<Transaction> {
-        self.node_states.lock().node_states[peer].unvalidated_transactions.clone()
+        self.node(peer).unvalidated_transactions.clone()
     }
 
     pub fn get_validated_transaction(&self, peer: usize) -> Vec<(Transaction, TransactionResultCode)> {
-        self.node_states.lock().node_states[peer].validated_transactions.clone()
+        self.node(peer).validated_transactions.clone()
+    }
+
+    pub fn validated_ledgers(&self) -> Vec<u32> {
+        self.map_nodes(|node| node.last_validated_ledger.ledger_index)
     }
 
     pub fn min_validated_ledger(&self) -> u32 {
-        self.node_states.lock().min_validated_ledger()
+        self.validated_ledgers().into_iter().min().expect("node states is empty")
     }
 
     pub fn max_validated_ledger(&self) -> u32 {
-        self.node_states.lock().max_validated_ledger()
+        self.validated_ledgers().into_iter().max().expect("node states is empty")
+    }
+
+    /// Check if nodes with equal validated ledger index have equal hash
+    pub fn check_for_fork(&self) -> bool {
+        let index_hash_map = self.map_nodes(|node| (node.last_validated_ledger.ledger_index, node.last_validated_ledger.ledger_hash.clone()))
+            .into_iter()
+            .into_group_map();
+        let res = !index_hash_map.iter().all(|x| x.1.iter().all_equal());
+        if res {
+            println!("{:?}", index_hash_map);
+        }
+        res
+    }
+
+    /// Liveness is at risk if one or more nodes stop validating, while the rest continues
+    pub fn check_liveness(&self) -> bool {
+        self.max_validated_ledger() - self.min_validated_ledger() < 2
     }
 
-    pub fn max_current_round(self) -> u32 {
-        self.node_states.lock().max_current_round()
+    pub fn max_current_round(&self) -> u32 {
+        self.map_nodes(|node| node.current_consensus_round).into_iter().max().expect("node states is empty")
     }
 
-    pub fn min_current_round(self) -> u32 {
-        self.node_states.lock().min_current_round()
+    pub fn min_current_round(&self) -> u32 {
+        self.map_nodes(|node| node.current_consensus_round).into_iter().min().expect("node states is empty")
     }
 
-    pub fn diff_rounds(self) -> u32 {
-        self.node_states.lock().diff_rounds()
+    pub fn diff_rounds(&self) -> u32 {
+        self.max_current_round() - self.min_current_round()
     }
 
     pub fn add_execution(&self, ripple_message: RippleMessage) {
-        let mut states = self.node_states.lock();
-        states.executions.push(ripple_message.clone());
-        states.add_receive_dependency(ripple_message.clone());
+        self.executions.append(ripple_message.clone());
+        let observed_peer = self.coverage.lock().observe_message(&ripple_message);
+        if let Some(peer) = observed_peer {
+            self.record_coverage(peer);
+        }
+        self.dependency_graph.lock().add_receive_dependency(ripple_message);
+    }
+
+    pub fn clear_coverage(&self) {
+        self.coverage.lock().clear_coverage();
+    }
+
+    pub fn get_coverage(&self) -> HashSet<AbstractConsensusState> {
+        self.coverage.lock().coverage().clone()
     }
 
     pub fn get_executions(&self) -> Vec<RippleMessage> {
-        self.node_states.lock().executions.clone()
+        self.executions.to_vec()
     }
 
     pub fn get_consensus_event_count(&self) -> usize {
-        self.node_states.lock().executions.len()
+        self.executions.len()
     }
 
     pub fn clear_executions(&self) {
-        self.node_states.lock().executions.clear();
+        self.executions.clear();
     }
 
     pub fn add_send_dependency(&self, ripple_message: RippleMessage) {
-        self.node_states.lock().add_send_dependency(ripple_message.clone());
+        self.dependency_graph.lock().add_send_dependency(ripple_message);
     }
 
     pub fn get_dependency_graph(&self) -> Graph<DependencyEvent, ()> {
-        self.node_states.lock().dependency_graph.clone()
+        self.dependency_graph.lock().graph().clone()
     }
 
     pub fn set_server_state(&self, server_state: PeerServerStateObject) {
-        if self.node_states.lock().set_server_state(server_state) {
+        let peer = server_state.peer as usize;
+        self.node(peer).server_state = server_state.server_state_object;
+        let all_updated = {
+            let mut harness = self.harness.lock();
+            harness.server_state_updates[peer] = true;
+            if harness.server_state_updates.iter().all(|x| *x) {
+                harness.server_state_updates = vec![false; self.number_of_nodes];
+                true
+            } else {
+                false
+            }
+        };
+        if all_updated {
             self.server_state_cvar.notify_all();
         }
     }
 
     pub fn get_server_state(&self, peer: usize) -> ServerStateObject {
-        self.node_states.lock().get_server_state(peer)
+        self.node(peer).server_state.clone()
     }
 
     pub fn set_highest_propose_seq(&self, propose_seq: u32, peer: usize) {
-        self.node_states.lock().set_highest_propose_seq(propose_seq, peer);
+        let bowed_out = self.node(peer).bowed_out;
+        let mut harness = self.harness.lock();
+        if propose_seq == 4294967295 && !bowed_out {
+            harness.bow_outs += 1;
+        } else if propose_seq > harness.highest_propose_seq {
+            harness.highest_propose_seq = propose_seq;
+        }
     }
 
     pub fn get_highest_propose_seq(&self) -> (u32, u32) {
-        self.node_states.lock().get_highest_propose_seq()
+        let harness = self.harness.lock();
+        (harness.highest_propose_seq, harness.bow_outs)
     }
 
     pub fn clear_highest_propose_seq(&self) {
-        self.node_states.lock().clear_propose_seq();
+        let mut harness = self.harness.lock();
+        harness.highest_propose_seq = 0;
+        harness.bow_outs = 0;
     }
 
     pub fn set_harness_transactions(&self, harness_transactions: Vec<TransactionTimed>) {
-        self.node_states.lock().set_harness_transactions(harness_transactions);
+        self.harness.lock().harness_transactions = harness_transactions;
+    }
+
+    pub(crate) fn add_sent_validation(&self, validation: ParsedValidation, peer: usize) -> Option<ParsedValidation> {
+        let mut node = self.node(peer);
+        node.consensus_transaction_sets.insert(validation.ledger_sequence as usize, hex::decode(&validation.consensus_hash).unwrap());
+        node.validations_sent.insert(validation.ledger_sequence as usize, validation)
+    }
+
+    pub(crate) fn add_consensus_constructed_ledger(&self, status_change: TMStatusChange, peer: usize) -> Option<TMStatusChange> {
+        self.node(peer).consensus_constructed_ledgers.insert(status_change.get_ledgerSeq() as usize, status_change)
+    }
+
+    pub(crate) fn add_proposed_tx_set(&self, tx_set: &[u8], peer: usize) {
+        let mut node = self.node(peer);
+        let seq = node.current_consensus_round as usize;
+        node.proposed_tx_sets.entry(seq).or_insert_with(HashSet::new).insert(tx_set.to_vec());
     }
 
     pub fn clear_consensus_property_data(&self) {
-        self.node_states.lock().clear_consensus_property_data()
+        for node in &self.nodes {
+            let mut node = node.lock();
+            node.validations_sent.clear();
+            node.consensus_constructed_ledgers.clear();
+            node.consensus_transaction_sets.clear();
+            node.validated_ledgers.clear();
+        }
+        self.harness.lock().test_start_time = Utc::now();
     }
 
     pub fn create_failure_data(&self, consensus_properties_violated: Vec<ConsensusPropertyTypes>, with_execution: bool, with_trace_graph: bool) -> Failure {
-        let node_states = self.node_states.lock();
+        let node_states = self.snapshot();
         Failure {
             time: Utc::now(),
-            validated_transactions: (0..self.number_of_nodes).map(|i| node_states.node_states[i].validated_transactions.clone()).collect_vec(),
-            validated_ledgers: (0..self.number_of_nodes).map(|i| node_states.node_states[i].last_validated_ledger.clone()).collect_vec(),
-            current_individual: node_states.current_individual.clone(),
+            validated_transactions: node_states.iter().map(|node| node.validated_transactions.clone()).collect_vec(),
+            validated_ledgers: node_states.iter().map(|node| node.last_validated_ledger.clone()).collect_vec(),
+            current_individual: self.get_current_individual(),
             execution: if with_execution{
-                Some(node_states.executions.clone())
+                Some(self.get_executions())
             } else {
                 None
             },
             trace_graph: if with_trace_graph {
-                Some(node_states.dependency_graph.clone())
+                Some(self.get_dependency_graph())
             } else {
                 None
             },
@@ -574,26 +659,56 @@ pub struct DependencyNode {
 
 #[cfg(test)]
 mod node_states_tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
     use crate::client::ValidatedLedger;
-    use crate::node_state::{NodeState, NodeStates};
+    use crate::collector::RippleMessage;
+    use crate::node_state::{MutexNodeStates, NodeState};
 
     #[test]
     fn test_fork_check() {
-        let mut node_states = setup(3);
-        node_states.node_states[0].last_validated_ledger = create_validated_ledger(1, "1");
-        node_states.node_states[1].last_validated_ledger = create_validated_ledger(1, "1");
-        node_states.node_states[2].last_validated_ledger = create_validated_ledger(2, "2");
+        let node_states = setup(3);
+        node_states.set_validated_ledger(0, create_validated_ledger(1, "1"));
+        node_states.set_validated_ledger(1, create_validated_ledger(1, "1"));
+        node_states.set_validated_ledger(2, create_validated_ledger(2, "2"));
         assert_eq!(node_states.check_for_fork(), false);
-        node_states.node_states[1].last_validated_ledger = create_validated_ledger(2, "1");
+        node_states.set_validated_ledger(1, create_validated_ledger(2, "1"));
         assert_eq!(node_states.check_for_fork(), true);
-        node_states.node_states[2].last_validated_ledger = create_validated_ledger(3, "3");
+        node_states.set_validated_ledger(2, create_validated_ledger(3, "3"));
         assert_eq!(node_states.check_for_fork(), false);
     }
 
-    fn setup(peers: usize) -> NodeStates {
-        let mut node_state_vec = vec![NodeState::new(0); peers];
-        for i in 0..peers { node_state_vec[i as usize].peer = i as usize }
-        NodeStates::new(node_state_vec)
+    #[test]
+    fn test_node_lock_does_not_block_other_nodes() {
+        let node_states = Arc::new(setup(2));
+        let node_states_clone = node_states.clone();
+        let node = node_states.node(0);
+        let other = thread::spawn(move || {
+            node_states_clone.set_validated_ledger(1, create_validated_ledger(2, "2"));
+            let mut send = RippleMessage::default();
+            send.from_node = "Ripple2".to_string();
+            node_states_clone.add_send_dependency(send);
+            node_states_clone.get_consensus_event_count()
+        });
+        assert_eq!(other.join().unwrap(), 0);
+        drop(node);
+        assert_eq!(node_states.validated_ledgers(), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_notifier_wakes_waiter() {
+        let node_states = Arc::new(setup(2));
+        let node_states_clone = node_states.clone();
+        let waiter = thread::spawn(move || node_states_clone.validated_ledger_cvar.wait_for(Duration::from_secs(5)));
+        thread::sleep(Duration::from_millis(100));
+        node_states.set_validated_ledger(0, create_validated_ledger(1, "1"));
+        assert!(waiter.join().unwrap());
+        assert!(!node_states.validated_ledger_cvar.wait_for(Duration::from_millis(10)));
+    }
+
+    fn setup(peers: usize) -> MutexNodeStates {
+        MutexNodeStates::new((0..peers).map(NodeState::new).collect())
     }
 
     fn create_validated_ledger(ledger_index: u32, ledger_hash: &str) -> ValidatedLedger {
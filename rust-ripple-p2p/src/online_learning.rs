@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use chrono::{Duration, Utc};
+use genevo::genetic::AsScalar;
+use log::error;
+use parking_lot::Mutex;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::coverage::MAX_COVERED_ROUND_OFFSET;
+use crate::ga::fitness::ExtendedFitness;
+use crate::ga::genetic_algorithm::ConsensusMessageType;
+use crate::LOG_FOLDER;
+use crate::node_state::ConsensusPhase;
+
+/// Messages in flight to a node above this value are counted as this value
+pub const MAX_OBSERVED_IN_FLIGHT: u32 = 3;
+
+/// Parameters of the online learning scheduler as set in the configuration
+/// delays_ms: The delays the policy chooses from for every message
+/// learning_rate: The step size of the value updates
+/// exploration_rate: The probability of a random delay, multiplied by exploration_decay after every run
+/// policy_file: A policy exported by an earlier run to continue learning from
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OnlineLearningConfiguration {
+    pub delays_ms: Vec<u32>,
+    pub learning_rate: f64,
+    pub exploration_rate: f64,
+    pub exploration_decay: f64,
+    pub policy_file: Option<String>,
+}
+
+impl Default for OnlineLearningConfiguration {
+    fn default() -> Self {
+        Self {
+            delays_ms: vec![0, 250, 500, 1000, 2000],
+            learning_rate: 0.1,
+            exploration_rate: 0.2,
+            exploration_decay: 0.99,
+            policy_file: None,
+        }
+    }
+}
+
+impl OnlineLearningConfiguration {
+    /// Check the parameters and, if the scheduler is used, the policy file, returns all problems found
+    pub fn validate(&self, used: bool) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.delays_ms.is_empty() {
+            errors.push("online learning needs at least one delay".to_string());
+        }
+        if !(self.learning_rate > 0.0 && self.learning_rate <= 1.0) {
+            errors.push(format!("online learning learning_rate must be in (0, 1], got {}", self.learning_rate));
+        }
+        if !(0.0..=1.0).contains(&self.exploration_rate) {
+            errors.push(format!("online learning exploration_rate must be in [0, 1], got {}", self.exploration_rate));
+        }
+        if !(self.exploration_decay > 0.0 && self.exploration_decay <= 1.0) {
+            errors.push(format!("online learning exploration_decay must be in (0, 1], got {}", self.exploration_decay));
+        }
+        if used {
+            if let Err(err) = self.load_table() {
+                errors.push(err);
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// The table of the policy file, or an empty table
+    fn load_table(&self) -> Result<QTable, String> {
+        let path = match &self.policy_file {
+            Some(path) => path,
+            None => return Ok(QTable::default()),
+        };
+        let file = File::open(path).map_err(|err| format!("Failed opening policy file {}: {}", path, err))?;
+        let entries: Vec<PolicyEntry> = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| format!("Failed reading policy file {}: {}", path, err))?;
+        if let Some(entry) = entries.iter().find(|entry| entry.values.len() != self.delays_ms.len()) {
+            return Err(format!("Policy file {} has {} delays for {:?}, the configuration has {}", path, entry.values.len(), entry.state, self.delays_ms.len()));
+        }
+        Ok(QTable::from_entries(entries))
+    }
+}
+
+/// What the scheduler observes when a consensus message arrives
+/// round_offset: The number of rounds the receiver is ahead of the slowest node
+/// in_flight: The number of delayed messages to the receiver that have not been delivered yet
+#[derive(Clone, Debug, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct PolicyState {
+    pub message_type: String,
+    pub receiver_phase: ConsensusPhase,
+    pub round_offset: u32,
+    pub in_flight: u32,
+}
+
+impl PolicyState {
+    pub fn new(message_type: ConsensusMessageType, receiver_phase: ConsensusPhase, round_offset: u32, in_flight: usize) -> Self {
+        Self {
+            message_type: format!("{:?}", message_type),
+            receiver_phase,
+            round_offset: round_offset.min(MAX_COVERED_ROUND_OFFSET),
+            in_flight: (in_flight as u32).min(MAX_OBSERVED_IN_FLIGHT),
+        }
+    }
+}
+
+/// The estimated reward and number of updates of every delay in a state, as exported to policy.json
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PolicyEntry {
+    pub state: PolicyState,
+    pub values: Vec<f64>,
+    pub visits: Vec<usize>,
+}
+
+/// Estimated reward of every delay in every observed state
+#[derive(Clone, Debug, Default)]
+pub struct QTable {
+    entries: HashMap<PolicyState, (Vec<f64>, Vec<usize>)>,
+}
+
+impl QTable {
+    pub fn from_entries(entries: Vec<PolicyEntry>) -> Self {
+        Self { entries: entries.into_iter().map(|entry| (entry.state, (entry.values, entry.visits))).collect() }
+    }
+
+    pub fn to_entries(&self) -> Vec<PolicyEntry> {
+        let mut entries = self.entries.iter()
+            .map(|(state, (values, visits))| PolicyEntry { state: state.clone(), values: values.clone(), visits: visits.clone() })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| (entry.state.message_type.clone(), format!("{:?}", entry.state.receiver_phase), entry.state.round_offset, entry.state.in_flight));
+        entries
+    }
+
+    fn entry(&mut self, state: &PolicyState, num_actions: usize) -> &mut (Vec<f64>, Vec<usize>) {
+        self.entries.entry(state.clone()).or_insert_with(|| (vec![0.0; num_actions], vec![0; num_actions]))
+    }
+}
+
+/// The individual of the online learning scheduler, a contextual bandit that picks a delay for every message
+/// Clones share the table and the decisions of the current run, so the policy carries over between harness runs
+#[derive(Clone, Debug, Default)]
+pub struct OnlinePolicy {
+    pub delays_ms: Vec<u32>,
+    pub learning_rate: f64,
+    pub exploration_rate: f64,
+    table: Arc<Mutex<QTable>>,
+    decisions: Arc<Mutex<Vec<(PolicyState, usize)>>>,
+}
+
+impl OnlinePolicy {
+    pub fn new(configuration: &OnlineLearningConfiguration) -> Result<Self, String> {
+        Ok(Self {
+            delays_ms: configuration.delays_ms.clone(),
+            learning_rate: configuration.learning_rate,
+            exploration_rate: configuration.exploration_rate,
+            table: Arc::new(Mutex::new(configuration.load_table()?)),
+            decisions: Arc::new(Mutex::new(vec![])),
+        })
+    }
+
+    /// Pick the delay of a message and remember the decision for the update at the end of the run
+    /// Delays that have never been tried in the state are tried first, otherwise the best delay is picked except with the exploration rate
+    pub fn choose(&self, state: PolicyState, rng: &mut impl Rng) -> Option<u32> {
+        if self.delays_ms.is_empty() {
+            return None;
+        }
+        let action = {
+            let mut table = self.table.lock();
+            let (values, visits) = table.entry(&state, self.delays_ms.len());
+            let untried = (0..visits.len()).filter(|action| visits[*action] == 0).collect::<Vec<_>>();
+            if let Some(action) = untried.choose(rng) {
+                *action
+            } else if rng.gen::<f64>() < self.exploration_rate {
+                rng.gen_range(0..self.delays_ms.len())
+            } else {
+                (0..values.len()).rev().max_by(|x, y| values[*x].total_cmp(&values[*y])).unwrap()
+            }
+        };
+        self.decisions.lock().push((state, action));
+        Some(self.delays_ms[action])
+    }
+
+    /// Move the value of every delay chosen in the run towards the reward of the run, returns the number of decisions
+    pub fn end_run(&mut self, reward: f64, exploration_decay: f64) -> usize {
+        let decisions = std::mem::take(&mut *self.decisions.lock());
+        let mut table = self.table.lock();
+        for (state, action) in decisions.iter().collect::<HashSet<_>>() {
+            let (values, visits) = table.entry(state, self.delays_ms.len());
+            visits[*action] += 1;
+            values[*action] += self.learning_rate * (reward - values[*action]);
+        }
+        self.exploration_rate *= exploration_decay;
+        decisions.len()
+    }
+
+    /// Forget the decisions of a run that is not rewarded
+    pub fn clear_decisions(&self) {
+        self.decisions.lock().clear();
+    }
+
+    pub fn entries(&self) -> Vec<PolicyEntry> {
+        self.table.lock().to_entries()
+    }
+
+    /// Write the table to a file that can be inspected or used as policy_file
+    pub fn export(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(Path::new(path))?);
+        serde_json::to_writer_pretty(&mut writer, &self.entries())?;
+        writer.flush()
+    }
+}
+
+/// A harness run, written to online_learning.txt
+#[derive(serde::Serialize)]
+struct OnlineRunInfo<'a, F> {
+    run: usize,
+    fitness: &'a F,
+    reward: f64,
+    decisions: usize,
+    exploration_rate: f64,
+    observed_states: usize,
+}
+
+/// Learn a policy over harness runs until the search budget is exceeded, the policy is exported to policy.json after every run
+pub fn run_online_learning<F: ExtendedFitness>(
+    configuration: OnlineLearningConfiguration,
+    scheduler_sender: Sender<OnlinePolicy>,
+    scheduler_receiver: Receiver<F>,
+    search_budget: Duration,
+) {
+    // The policy file has been checked when validating the configuration
+    let mut policy = OnlinePolicy::new(&configuration).expect("Loading policy failed");
+    let mut online_writer = create_online_writer();
+    let policy_path = format!("{}/policy.json", *LOG_FOLDER);
+    let start_time = Utc::now();
+    let mut run = 0;
+    while Utc::now() - start_time < search_budget {
+        // Messages delayed between runs are not part of the episode
+        policy.clear_decisions();
+        scheduler_sender.send(policy.clone()).expect("Scheduler receiver failed");
+        let fitness = scheduler_receiver.recv().expect("Scheduler sender failed");
+        let reward = fitness.as_scalar();
+        let exploration_rate = policy.exploration_rate;
+        let decisions = policy.end_run(reward, configuration.exploration_decay);
+        let run_info = OnlineRunInfo { run, fitness: &fitness, reward, decisions, exploration_rate, observed_states: policy.entries().len() };
+        match serde_json::to_writer(&mut online_writer, &run_info) {
+            Ok(_) => {}
+            Err(err) => error!("Failed writing to online learning file: {}", err)
+        };
+        online_writer.write_all(b"\n").expect("Online learning writer failed");
+        online_writer.flush().expect("Online learning writer flush failed");
+        if let Err(err) = policy.export(&policy_path) {
+            error!("Failed exporting policy to {}: {}", policy_path, err);
+        }
+        run += 1;
+    }
+    println!("Exiting: Search budget exceeded.");
+    std::process::exit(0);
+}
+
+fn create_online_writer() -> BufWriter<File> {
+    BufWriter::new(
+        File::create(
+            Path::new(format!("{}/online_learning.txt", *LOG_FOLDER).as_str())
+        ).expect("Creating online learning file failed")
+    )
+}
+
+#[cfg(test)]
+mod online_learning_tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::ga::genetic_algorithm::ConsensusMessageType;
+    use crate::node_state::ConsensusPhase;
+    use crate::online_learning::{OnlineLearningConfiguration, OnlinePolicy, PolicyState, QTable};
+
+    fn state() -> PolicyState {
+        PolicyState::new(ConsensusMessageType::TMValidation, ConsensusPhase::Establish, 7, 1)
+    }
+
+    #[test]
+    fn test_state_is_bucketed() {
+        let state = state();
+        assert_eq!(state.message_type, "TMValidation");
+        assert_eq!(state.round_offset, 3);
+        assert_eq!(PolicyState::new(ConsensusMessageType::TMValidation, ConsensusPhase::Open, 0, 10).in_flight, 3);
+    }
+
+    #[test]
+    fn test_policy_learns_best_delay() {
+        let configuration = OnlineLearningConfiguration { delays_ms: vec![0, 1000], exploration_rate: 0.0, learning_rate: 1.0, ..OnlineLearningConfiguration::default() };
+        let mut policy = OnlinePolicy::new(&configuration).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        // Both delays are tried before any of them is exploited
+        let first = policy.choose(state(), &mut rng).unwrap();
+        policy.end_run(if first == 1000 { 5.0 } else { 1.0 }, 1.0);
+        let second = policy.choose(state(), &mut rng).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(policy.end_run(if second == 1000 { 5.0 } else { 1.0 }, 1.0), 1);
+        assert_eq!(policy.choose(state(), &mut rng), Some(1000));
+        let entries = policy.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].visits, vec![1, 1]);
+        assert_eq!(entries[0].values, vec![1.0, 5.0]);
+        // The exported table is read back unchanged
+        assert_eq!(QTable::from_entries(entries.clone()).to_entries(), entries);
+    }
+
+    #[test]
+    fn test_validate_configuration() {
+        assert!(OnlineLearningConfiguration::default().validate(true).is_ok());
+        let configuration = OnlineLearningConfiguration {
+            delays_ms: vec![],
+            learning_rate: 0.0,
+            policy_file: Some("does_not_exist.json".to_string()),
+            ..OnlineLearningConfiguration::default()
+        };
+        assert_eq!(configuration.validate(false).unwrap_err().len(), 2);
+        assert_eq!(configuration.validate(true).unwrap_err().len(), 3);
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use chrono::{Duration, Utc};
+use log::error;
+use rand::Rng;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use crate::ga::fitness::ExtendedFitness;
+use crate::LOG_FOLDER;
+use crate::locality::sample_priority_genotype;
+
+/// Parameters of probabilistic concurrency testing as set in the configuration
+/// depth: The depth d of the bugs to find, every run has d-1 priority change points
+/// expected_messages: The expected number k of consensus messages delivered in a harness run, change points are picked among them
+/// seed: Seed of the random priorities and change points, the same seed gives the same sequence of schedules
+/// step_ms: The time between two deliveries, messages that arrive in between compete on priority
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PctConfiguration {
+    pub depth: usize,
+    pub expected_messages: usize,
+    pub seed: u64,
+    pub step_ms: u64,
+}
+
+impl Default for PctConfiguration {
+    fn default() -> Self {
+        Self {
+            depth: 3,
+            expected_messages: 500,
+            seed: 0,
+            step_ms: 10,
+        }
+    }
+}
+
+impl PctConfiguration {
+    /// Check the parameters for values PCT cannot run with, returns all problems found
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.depth == 0 {
+            errors.push("pct depth must be at least 1".to_string());
+        }
+        if self.depth > self.expected_messages + 1 {
+            errors.push(format!("pct needs at least {} expected_messages for {} change points", self.depth - 1, self.depth - 1));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// The lower bound 1 / (n * k^(d-1)) on the probability that a run finds a given bug of depth d
+    pub fn probability_bound(&self, num_nodes: usize) -> f64 {
+        1.0 / (num_nodes as f64 * (self.expected_messages as f64).powi(self.depth as i32 - 1))
+    }
+}
+
+/// The individual of the PCT scheduler
+/// node_priorities: The initial priority of every node, a permutation of d..d+n
+/// change_points: At the delivery change_points[i] the node about to receive gets priority d-1-i
+/// delivered: The number of messages delivered by priority in the last run, written by the scheduler
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct PctSchedule {
+    pub node_priorities: Vec<usize>,
+    pub change_points: Vec<usize>,
+    pub step_ms: u64,
+    #[serde(skip)]
+    pub delivered: Arc<AtomicUsize>,
+}
+
+impl PctSchedule {
+    pub fn sample(num_nodes: usize, configuration: &PctConfiguration, rng: &mut impl Rng) -> Self {
+        let depth = configuration.depth;
+        let node_priorities = sample_priority_genotype(num_nodes, rng).into_iter()
+            .map(|priority| priority + depth)
+            .collect();
+        let change_points = sample(rng, configuration.expected_messages, depth - 1).into_iter()
+            .map(|change_point| change_point + 1)
+            .collect();
+        Self { node_priorities, change_points, step_ms: configuration.step_ms, delivered: Arc::new(AtomicUsize::new(0)) }
+    }
+}
+
+/// The state of a PCT schedule during a harness run
+#[derive(Debug, Default)]
+pub struct PctRun {
+    priorities: Vec<usize>,
+    change_points: Vec<usize>,
+    steps: usize,
+}
+
+impl PctRun {
+    pub fn new(schedule: &PctSchedule) -> Self {
+        Self { priorities: schedule.node_priorities.clone(), change_points: schedule.change_points.clone(), steps: 0 }
+    }
+
+    fn priority(&self, node: usize) -> usize {
+        self.priorities.get(node).copied().unwrap_or(0)
+    }
+
+    /// The pending message with the highest priority receiver, the oldest one if a node has several
+    fn highest(&self, receivers: &[usize]) -> Option<usize> {
+        (0..receivers.len()).rev().max_by_key(|i| self.priority(receivers[*i]))
+    }
+
+    /// The index of the message to deliver among the receivers of the pending messages in arrival order
+    /// At a change point the node with the highest priority is lowered before choosing
+    pub fn next(&mut self, receivers: &[usize]) -> Option<usize> {
+        let index = self.highest(receivers)?;
+        self.steps += 1;
+        if let Some(change) = self.change_points.iter().position(|change_point| *change_point == self.steps) {
+            let lowered = self.change_points.len() - change;
+            if let Some(priority) = self.priorities.get_mut(receivers[index]) {
+                *priority = lowered;
+            }
+            return self.highest(receivers);
+        }
+        Some(index)
+    }
+}
+
+/// A harness run, written to pct.txt
+#[derive(serde::Serialize)]
+struct PctRunInfo<'a, F> {
+    run: usize,
+    schedule: &'a PctSchedule,
+    delivered: usize,
+    fitness: &'a F,
+}
+
+/// Run seeded PCT schedules until the search budget is exceeded
+pub fn run_pct<F: ExtendedFitness>(
+    configuration: PctConfiguration,
+    num_nodes: usize,
+    scheduler_sender: Sender<PctSchedule>,
+    scheduler_receiver: Receiver<F>,
+    search_budget: Duration,
+) {
+    let mut pct_writer = create_pct_writer();
+    let mut rng = ChaCha8Rng::seed_from_u64(configuration.seed);
+    println!("Starting PCT with {:?}, a run finds a depth {} bug with probability at least {:e}",
+             configuration, configuration.depth, configuration.probability_bound(num_nodes));
+    let start_time = Utc::now();
+    let mut run = 0;
+    while Utc::now() - start_time < search_budget {
+        let schedule = PctSchedule::sample(num_nodes, &configuration, &mut rng);
+        scheduler_sender.send(schedule.clone()).expect("Scheduler receiver failed");
+        let fitness = scheduler_receiver.recv().expect("Scheduler sender failed");
+        let delivered = schedule.delivered.load(Ordering::SeqCst);
+        if delivered < configuration.expected_messages {
+            println!("Run {} delivered {} of the {} expected messages, change points after the last delivery are missed", run, delivered, configuration.expected_messages);
+        }
+        match serde_json::to_writer(&mut pct_writer, &PctRunInfo { run, schedule: &schedule, delivered, fitness: &fitness }) {
+            Ok(_) => {}
+            Err(err) => error!("Failed writing to pct file: {}", err)
+        };
+        pct_writer.write_all(b"\n").expect("PCT writer failed");
+        pct_writer.flush().expect("PCT writer flush failed");
+        run += 1;
+    }
+    println!("Exiting: Search budget exceeded.");
+    std::process::exit(0);
+}
+
+fn create_pct_writer() -> BufWriter<File> {
+    BufWriter::new(
+        File::create(
+            Path::new(format!("{}/pct.txt", *LOG_FOLDER).as_str())
+        ).expect("Creating pct file failed")
+    )
+}
+
+#[cfg(test)]
+mod pct_tests {
+    use itertools::Itertools;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::pct::{PctConfiguration, PctRun, PctSchedule};
+
+    #[test]
+    fn test_sample_schedule() {
+        let configuration = PctConfiguration { depth: 4, expected_messages: 50, ..PctConfiguration::default() };
+        let schedule = PctSchedule::sample(5, &configuration, &mut ChaCha8Rng::seed_from_u64(1));
+        assert_eq!(schedule.node_priorities.iter().sorted().cloned().collect_vec(), (4..9).collect_vec());
+        assert_eq!(schedule.change_points.len(), 3);
+        assert!(schedule.change_points.iter().all(|change_point| (1..=50).contains(change_point)));
+        assert_eq!(schedule.change_points.iter().unique().count(), 3);
+        // The same seed gives the same schedule
+        let again = PctSchedule::sample(5, &configuration, &mut ChaCha8Rng::seed_from_u64(1));
+        assert_eq!((again.node_priorities, again.change_points), (schedule.node_priorities, schedule.change_points));
+    }
+
+    #[test]
+    fn test_change_points_lower_priority() {
+        let schedule = PctSchedule { node_priorities: vec![3, 4, 2], change_points: vec![2], ..PctSchedule::default() };
+        let mut run = PctRun::new(&schedule);
+        // Node 1 has the highest priority, the oldest of its messages goes first
+        assert_eq!(run.next(&[0, 1, 2, 1]), Some(1));
+        // At the second delivery node 1 drops below every other node
+        assert_eq!(run.next(&[0, 2, 1]), Some(0));
+        assert_eq!(run.next(&[2, 1]), Some(0));
+        assert_eq!(run.next(&[1]), Some(0));
+        assert_eq!(run.next(&[]), None);
+    }
+
+    #[test]
+    fn test_probability_bound() {
+        let configuration = PctConfiguration { depth: 3, expected_messages: 10, ..PctConfiguration::default() };
+        assert!((configuration.probability_bound(4) - 1.0 / 400.0).abs() < 1e-12);
+    }
+}
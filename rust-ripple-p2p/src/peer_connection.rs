@@ -1,5 +1,6 @@
 use log::*;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use bytes::{Buf, BytesMut};
 use openssl::ssl::{Ssl, SslContext, SslMethod};
 use secp256k1::{Message as CryptoMessage, Secp256k1, SecretKey};
@@ -10,7 +11,7 @@ use tokio::net::TcpStream;
 use tokio::task::JoinHandle;
 use tokio_openssl::SslStream;
 use byteorder::{BigEndian, ByteOrder};
-use crate::scheduler::Event;
+use crate::scheduler::{Event, Frame};
 
 /// A peer connection between two peers
 pub struct PeerConnection {
@@ -159,8 +160,8 @@ impl PeerConnection {
                          peer2: usize,
                          sender1: tokio::sync::mpsc::Sender<Event>,
                          sender2: tokio::sync::mpsc::Sender<Event>,
-                         receiver1: tokio::sync::mpsc::Receiver<Vec<u8>>,
-                         receiver2: tokio::sync::mpsc::Receiver<Vec<u8>>
+                         receiver1: tokio::sync::mpsc::Receiver<Frame>,
+                         receiver2: tokio::sync::mpsc::Receiver<Frame>
     ) -> (JoinHandle<()>, JoinHandle<()>) {
         trace!("Thread {:?} has started", self.name);
         // Connect to the two validators using each other's identity
@@ -198,13 +199,13 @@ impl PeerConnection {
         from: usize,
         to: usize,
         sender: tokio::sync::mpsc::Sender<Event>,
-        mut receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+        mut receiver: tokio::sync::mpsc::Receiver<Frame>,
     ) {
         let (mut ssl_reader, mut ssl_writer) = tokio::io::split(ssl_stream);
         tokio::spawn(async move {
             loop {
                 match receiver.recv().await {
-                    Some(message) => match ssl_writer.write_all(message.as_slice()).await {
+                    Some(message) => match ssl_writer.write_all(&message).await {
                         Ok(_) => {}
                         Err(err) => error!("Failed to write to ssl stream from {}, to {}, with err: {}", from, to, err)
                     }
@@ -252,7 +253,7 @@ impl PeerConnection {
             }
 
             // Send received message to scheduler
-            let message = bytes[0..(6+payload_size)].to_vec();
+            let message = Arc::new(bytes[0..(6+payload_size)].to_vec());
             let event = Event { from, to, message};
             match sender.send(event).await {
                 Ok(_) => {}
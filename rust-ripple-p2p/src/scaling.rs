@@ -4,7 +4,7 @@ use std::io::Write;
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 
-use crate::{ga::{encoding::{delay_encoding::{DelayMapPhenotype, DelayGenotype}, ExtendedPhenotype, num_genes}}, node_state::MutexNodeStates, locality::sample_delays_genotype};
+use crate::{ga::{encoding::{delay_encoding::{DelayMapPhenotype, DelayGenotype}, ExtendedPhenotype, num_delay_genes, num_genes}}, node_state::MutexNodeStates, locality::sample_delays_genotype};
 use crate::ga::encoding::priority_encoding::{PriorityGenotype, PriorityMapPhenotype};
 use crate::ga::fitness::ExtendedFitness;
 use crate::locality::sample_priority_genotype;
@@ -37,7 +37,7 @@ impl<F: ExtendedFitness> ScalingExperiment<F> {
 		let mut genotypes = vec![];
 		let num_schedules = 500;
 		for _ in 0..num_schedules {
-			genotypes.push(sample_delays_genotype(num_genes(), 0, 4000, &mut rng));
+			genotypes.push(sample_delays_genotype(num_delay_genes(), 0, 4000, &mut rng));
 		}
 		for i in 0..num_schedules {
 			self.execute_schedule(&genotypes[i]);
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{Receiver, Sender};
+use itertools::Itertools;
+use log::error;
+use crate::ga::encoding::delay_encoding::{DelayEncodingType, DelayGenotype, DROP_DELAY};
+use crate::ga::encoding::priority_encoding::PriorityGenotype;
+use crate::ga::fitness::ExtendedFitness;
+use crate::ga::genetic_algorithm::ConsensusMessageType;
+use crate::LOG_FOLDER;
+use crate::topology::PeerGraph;
+
+/// Schedule files to run with the ScheduleFiles scheduler as set in the configuration
+/// files: Paths of the schedule files, all files are either delay or priority files
+/// repetitions: The number of times every file is run
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScheduleFilesConfiguration {
+    pub files: Vec<String>,
+    pub repetitions: usize,
+}
+
+impl Default for ScheduleFilesConfiguration {
+    fn default() -> Self {
+        Self {
+            files: vec![],
+            repetitions: 1,
+        }
+    }
+}
+
+impl ScheduleFilesConfiguration {
+    /// Check the parameters, the files are only read and compiled if they are going to be run, returns all problems found
+    pub fn validate(&self, peer_graph: &PeerGraph, delay_encoding: &DelayEncodingType, used: bool) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.repetitions == 0 {
+            errors.push("schedule file repetitions must be at least 1".to_string());
+        }
+        if used {
+            match self.load(peer_graph.num_nodes()) {
+                Ok((ScheduleKind::Delay, schedule_files)) => {
+                    for schedule_file in schedule_files {
+                        if let Err(delay_errors) = schedule_file.delays(peer_graph, delay_encoding) {
+                            errors.extend(delay_errors);
+                        }
+                    }
+                }
+                Ok((ScheduleKind::Priority, _)) => {}
+                Err(load_errors) => errors.extend(load_errors),
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Read and parse all files, they must all be of the same kind
+    pub fn load(&self, num_nodes: usize) -> Result<(ScheduleKind, Vec<ScheduleFile>), Vec<String>> {
+        if self.files.is_empty() {
+            return Err(vec!["the ScheduleFiles scheduler needs at least one schedule file".to_string()]);
+        }
+        let mut errors = vec![];
+        let mut schedule_files = vec![];
+        for path in &self.files {
+            match ScheduleFile::read(path, num_nodes) {
+                Ok(schedule_file) => schedule_files.push(schedule_file),
+                Err(file_errors) => errors.extend(file_errors),
+            }
+        }
+        let kinds = schedule_files.iter()
+            .map(|schedule_file| schedule_file.kind())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| { errors.push(err); vec![] });
+        if !kinds.iter().all_equal() {
+            errors.push("schedule files must either all contain delays or all contain priorities".to_string());
+        }
+        if errors.is_empty() {
+            Ok((kinds.first().cloned().unwrap_or(ScheduleKind::Delay), schedule_files))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Whether a schedule file is run with the delay or the priority scheduler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleKind {
+    Delay,
+    Priority,
+}
+
+/// What happens to the messages matched by a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    Delay(u32),
+    Drop,
+    Priority(usize),
+}
+
+impl RuleAction {
+    fn kind(&self) -> ScheduleKind {
+        match self {
+            RuleAction::Delay(_) | RuleAction::Drop => ScheduleKind::Delay,
+            RuleAction::Priority(_) => ScheduleKind::Priority,
+        }
+    }
+}
+
+/// A single line of a schedule file, None matches every node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleRule {
+    message_types: Vec<ConsensusMessageType>,
+    from: Option<usize>,
+    to: Option<usize>,
+    action: RuleAction,
+}
+
+impl ScheduleRule {
+    fn matches(&self, from: usize, to: usize, message_type: &ConsensusMessageType) -> bool {
+        self.from.map_or(true, |node| node == from)
+            && self.to.map_or(true, |node| node == to)
+            && self.message_types.contains(message_type)
+    }
+
+    /// The line of a rule for a single message type on a single link, parse reads it back
+    pub fn line(message_type: &ConsensusMessageType, from: usize, to: usize, action: RuleAction) -> String {
+        let action = match action {
+            RuleAction::Delay(ms) => format!("{}ms", ms),
+            RuleAction::Drop => "drop".to_string(),
+            RuleAction::Priority(priority) => priority.to_string(),
+        };
+        format!("{} from {} to {} = {}", &format!("{:?}", message_type)[2..], from, to, action)
+    }
+
+    /// Parse "<message type> from <node> to <node> = <action>" or "<message type> <node> -> <node> = <action>"
+    fn parse(line: &str, num_nodes: usize) -> Result<Self, String> {
+        let (selector, action) = line.split_once('=').ok_or("expected '=' followed by a delay, drop or priority")?;
+        let selector = selector.replace("->", " -> ");
+        let tokens = selector.split_whitespace().collect_vec();
+        let (message_type, from, to) = match tokens.as_slice() {
+            [message_type, "from", from, "to", to] | [message_type, from, "->", to] => (*message_type, *from, *to),
+            _ => return Err("expected '<message type> from <node> to <node>' or '<message type> <node> -> <node>'".to_string()),
+        };
+        Ok(Self {
+            message_types: Self::parse_message_types(message_type)?,
+            from: Self::parse_node(from, num_nodes)?,
+            to: Self::parse_node(to, num_nodes)?,
+            action: Self::parse_action(action.trim())?,
+        })
+    }
+
+    /// Message types are named without the TM prefix, ProposeSet matches every proposeSeq and * matches every type
+    fn parse_message_types(token: &str) -> Result<Vec<ConsensusMessageType>, String> {
+        let name = token.strip_prefix("TM").unwrap_or(token);
+        let message_types = ConsensusMessageType::VALUES.iter()
+            .filter(|message_type| {
+                let type_name = format!("{:?}", message_type);
+                name == "*" || type_name[2..] == *name || (name == "ProposeSet" && type_name.starts_with("TMProposeSet"))
+            })
+            .cloned()
+            .collect_vec();
+        if message_types.is_empty() {
+            Err(format!("unknown message type {}", token))
+        } else {
+            Ok(message_types)
+        }
+    }
+
+    fn parse_node(token: &str, num_nodes: usize) -> Result<Option<usize>, String> {
+        if token == "*" {
+            return Ok(None);
+        }
+        match token.parse::<usize>() {
+            Ok(node) if node < num_nodes => Ok(Some(node)),
+            _ => Err(format!("{} is not * or a node in 0..{}", token, num_nodes)),
+        }
+    }
+
+    /// "<n>ms" delays, "drop" drops and a plain number is a priority
+    fn parse_action(token: &str) -> Result<RuleAction, String> {
+        if token == "drop" {
+            return Ok(RuleAction::Drop);
+        }
+        let invalid = || format!("{} is not a delay in ms, drop or a priority", token);
+        match token.strip_suffix("ms") {
+            Some(ms) => ms.trim().parse::<u32>().ok().filter(|ms| *ms != DROP_DELAY).map(RuleAction::Delay).ok_or_else(invalid),
+            None => token.parse::<usize>().map(RuleAction::Priority).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// A human-readable schedule, compiled into a delay or priority genotype
+/// Every line is a rule, later rules override earlier rules and messages without a rule get 0ms or priority 0
+/// Lines starting with # are comments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleFile {
+    pub name: String,
+    rules: Vec<ScheduleRule>,
+}
+
+impl ScheduleFile {
+    pub fn read(path: &str, num_nodes: usize) -> Result<Self, Vec<String>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::parse(path, &contents, num_nodes),
+            Err(err) => Err(vec![format!("failed reading schedule file {}: {}", path, err)]),
+        }
+    }
+
+    /// Parse all rules, returns every line that could not be parsed
+    pub fn parse(name: &str, contents: &str, num_nodes: usize) -> Result<Self, Vec<String>> {
+        let mut rules = vec![];
+        let mut errors = vec![];
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            match ScheduleRule::parse(line, num_nodes) {
+                Ok(rule) => rules.push(rule),
+                Err(err) => errors.push(format!("{}:{}: {}", name, number + 1, err)),
+            }
+        }
+        if errors.is_empty() { Ok(Self { name: name.to_string(), rules }) } else { Err(errors) }
+    }
+
+    /// Delay files only contain delays and drops, priority files only priorities
+    pub fn kind(&self) -> Result<ScheduleKind, String> {
+        let kinds = self.rules.iter().map(|rule| rule.action.kind()).unique().collect_vec();
+        match kinds.as_slice() {
+            [] => Ok(ScheduleKind::Delay),
+            [kind] => Ok(*kind),
+            _ => Err(format!("{} mixes delays and priorities", self.name)),
+        }
+    }
+
+    /// The action of the last rule that matches, for every (from, to, message type) of a link in the peer graph
+    /// Rules for nodes that are not connected match no message
+    fn full_actions(&self, peer_graph: &PeerGraph) -> HashMap<(usize, usize, usize), RuleAction> {
+        let mut actions = HashMap::new();
+        for &(from, to) in peer_graph.links() {
+            for (k, message_type) in ConsensusMessageType::VALUES.iter().enumerate() {
+                if let Some(rule) = self.rules.iter().rev().find(|rule| rule.matches(from, to, message_type)) {
+                    actions.insert((from, to, k), rule.action);
+                }
+            }
+        }
+        actions
+    }
+
+    /// Compile the rules into a delay genotype of a single round in the given encoding
+    /// Rules that give different delays to messages sharing a gene in a reduced encoding are an error
+    pub fn delays(&self, peer_graph: &PeerGraph, encoding: &DelayEncodingType) -> Result<DelayGenotype, Vec<String>> {
+        let actions = self.full_actions(peer_graph);
+        let mut genes: Vec<Option<u32>> = vec![None; encoding.num_genes(peer_graph)];
+        let mut errors = vec![];
+        for &(from, to) in peer_graph.links() {
+            for (k, message_type) in ConsensusMessageType::VALUES.iter().enumerate() {
+                let delay = match actions.get(&(from, to, k)) {
+                    Some(RuleAction::Delay(ms)) => *ms,
+                    Some(RuleAction::Drop) => DROP_DELAY,
+                    _ => 0,
+                };
+                let index = encoding.gene_index(from, to, k, peer_graph);
+                match genes[index] {
+                    Some(gene) if gene != delay => errors.push(format!(
+                        "{}: {:?} {} -> {} cannot get its own delay in the {:?} delay encoding", self.name, message_type, from, to, encoding
+                    )),
+                    _ => genes[index] = Some(delay),
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(genes.into_iter().map(|gene| gene.unwrap_or(0)).collect())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Compile the rules into a priority genotype, a higher priority is delivered first
+    pub fn priorities(&self, peer_graph: &PeerGraph) -> PriorityGenotype {
+        let actions = self.full_actions(peer_graph);
+        let mut priorities = vec![0usize; DelayEncodingType::Full.num_genes(peer_graph)];
+        for ((from, to, k), action) in actions {
+            if let RuleAction::Priority(priority) = action {
+                priorities[DelayEncodingType::Full.gene_index(from, to, k, peer_graph)] = priority;
+            }
+        }
+        priorities
+    }
+}
+
+/// The outcome of a single run of a schedule file, written to schedule_files.txt
+#[derive(serde::Serialize)]
+struct ScheduleFileRunInfo<'a, F> {
+    file: &'a str,
+    repetition: usize,
+    fitness: &'a F,
+}
+
+/// Run every compiled schedule the configured number of times and exit
+pub fn run_schedule_files<F: ExtendedFitness, P>(schedules: Vec<(String, P)>, repetitions: usize, scheduler_sender: Sender<P>, scheduler_receiver: Receiver<F>)
+    where P: Clone
+{
+    let mut schedule_files_writer = create_schedule_files_writer();
+    for (name, schedule) in schedules {
+        for repetition in 0..repetitions {
+            println!("Running schedule file {} ({}/{})", name, repetition + 1, repetitions);
+            scheduler_sender.send(schedule.clone()).expect("Scheduler receiver failed");
+            let fitness = scheduler_receiver.recv().expect("Scheduler sender failed");
+            let run_info = ScheduleFileRunInfo { file: &name, repetition, fitness: &fitness };
+            match serde_json::to_writer(&mut schedule_files_writer, &run_info) {
+                Ok(_) => {}
+                Err(err) => error!("Failed writing to schedule files file: {}", err)
+            };
+            schedule_files_writer.write_all(b"\n").expect("Schedule files writer failed");
+            schedule_files_writer.flush().expect("Schedule files writer flush failed");
+        }
+    }
+    println!("Finished running schedule files. exiting...");
+    std::process::exit(0);
+}
+
+fn create_schedule_files_writer() -> BufWriter<File> {
+    BufWriter::new(
+        File::create(
+            Path::new(format!("{}/schedule_files.txt", *LOG_FOLDER).as_str())
+        ).expect("Creating schedule files file failed")
+    )
+}
+
+#[cfg(test)]
+mod schedule_file_tests {
+    use crate::ga::encoding::delay_encoding::{DelayEncodingType, DROP_DELAY};
+    use crate::ga::genetic_algorithm::ConsensusMessageType;
+    use crate::schedule_file::{RuleAction, ScheduleFile, ScheduleKind, ScheduleRule};
+    use crate::topology::{PeerGraph, PeerTopology};
+
+    const DELAYS: &str = "
+        # The proposal bug schedule
+        ProposeSet1 from 0 to * = 1200ms
+        Validation * -> 4 = drop
+        * 1->2 = 10ms
+    ";
+
+    fn gene(from: usize, to: usize, message_type: ConsensusMessageType) -> usize {
+        let k = ConsensusMessageType::VALUES.iter().position(|value| *value == message_type).unwrap();
+        DelayEncodingType::Full.gene_index(from, to, k, &PeerGraph::complete(5))
+    }
+
+    #[test]
+    fn test_compile_delays() {
+        let schedule_file = ScheduleFile::parse("delays", DELAYS, 5).unwrap();
+        assert_eq!(schedule_file.kind(), Ok(ScheduleKind::Delay));
+        let delays = schedule_file.delays(&PeerGraph::complete(5), &DelayEncodingType::Full).unwrap();
+        assert_eq!(delays[gene(0, 3, ConsensusMessageType::TMProposeSet1)], 1200);
+        assert_eq!(delays[gene(1, 3, ConsensusMessageType::TMProposeSet1)], 0);
+        assert_eq!(delays[gene(2, 4, ConsensusMessageType::TMValidation)], DROP_DELAY);
+        assert_eq!(delays[gene(0, 4, ConsensusMessageType::TMProposeSet1)], 1200);
+        // The last matching rule wins
+        assert_eq!(delays[gene(1, 2, ConsensusMessageType::TMValidation)], 10);
+        // Per-link rules do not fit in a per message type encoding
+        assert!(schedule_file.delays(&PeerGraph::complete(5), &DelayEncodingType::PerMessageType).is_err());
+    }
+
+    #[test]
+    fn test_compile_delays_on_peer_graph() {
+        let ring = PeerTopology::Ring.peer_graph(5).unwrap();
+        let schedule_file = ScheduleFile::parse("delays", DELAYS, 5).unwrap();
+        let delays = schedule_file.delays(&ring, &DelayEncodingType::Full).unwrap();
+        // Only the links of the ring have genes, rules for other pairs match no message
+        assert_eq!(delays.len(), 10 * ConsensusMessageType::VALUES.len());
+        let k = ConsensusMessageType::VALUES.iter().position(|value| *value == ConsensusMessageType::TMProposeSet1).unwrap();
+        assert_eq!(delays[DelayEncodingType::Full.gene_index(0, 4, k, &ring)], 1200);
+        assert_eq!(delays.iter().filter(|delay| **delay == 1200).count(), 2);
+    }
+
+    #[test]
+    fn test_compile_priorities() {
+        let schedule_file = ScheduleFile::parse("priorities", "* * -> * = 20\nProposeSet from 3 to 1 = 1", 5).unwrap();
+        assert_eq!(schedule_file.kind(), Ok(ScheduleKind::Priority));
+        let priorities = schedule_file.priorities(&PeerGraph::complete(5));
+        assert_eq!(priorities[gene(3, 1, ConsensusMessageType::TMProposeSetBowOut)], 1);
+        assert_eq!(priorities[gene(1, 3, ConsensusMessageType::TMProposeSet0)], 20);
+    }
+
+    #[test]
+    fn test_rule_lines() {
+        let line = ScheduleRule::line(&ConsensusMessageType::TMProposeSet1, 0, 2, RuleAction::Delay(500));
+        assert_eq!(line, "ProposeSet1 from 0 to 2 = 500ms");
+        let dropped = ScheduleRule::line(&ConsensusMessageType::TMValidation, 1, 3, RuleAction::Drop);
+        let schedule_file = ScheduleFile::parse("lines", &format!("{}\n{}", line, dropped), 5).unwrap();
+        let delays = schedule_file.delays(&PeerGraph::complete(5), &DelayEncodingType::Full).unwrap();
+        assert_eq!(delays[gene(0, 2, ConsensusMessageType::TMProposeSet1)], 500);
+        assert_eq!(delays[gene(1, 3, ConsensusMessageType::TMValidation)], DROP_DELAY);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let errors = ScheduleFile::parse("invalid", "Validation from 0 to 5 = 10ms\nProposal * -> * = drop\n* * -> * 10ms", 5).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("invalid:1:"));
+        let mixed = ScheduleFile::parse("mixed", "* * -> * = 10ms\n* * -> * = 2", 5).unwrap();
+        assert!(mixed.kind().is_err());
+    }
+}
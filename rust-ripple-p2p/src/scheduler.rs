@@ -1,5 +1,12 @@
 pub mod delay_scheduler;
 pub mod priority_scheduler;
+pub mod hybrid_scheduler;
+pub mod interactive_scheduler;
+pub mod systematic_scheduler;
+pub mod pct_scheduler;
+pub mod online_scheduler;
+pub mod dispatcher;
+pub mod pipeline;
 
 use std::cmp::Ordering;
 use log::{debug, error, trace};
@@ -8,8 +15,9 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::sync::{Arc, RwLock};
 use chrono::{DateTime, MAX_DATETIME, Utc};
+use futures::future::{BoxFuture, FutureExt};
 use tokio::sync::mpsc::{Sender as TokioSender, Receiver as TokioReceiver};
-use std::sync::mpsc::{Sender as STDSender, Receiver as STDReceiver, channel, Sender};
+use std::sync::mpsc::{Sender as STDSender, Receiver as STDReceiver, Sender};
 use std::thread;
 use std::time::Duration;
 use parking_lot::{Mutex, Condvar};
@@ -23,10 +31,15 @@ use crate::ga::fitness::ExtendedFitness;
 use crate::ga::genetic_algorithm::{ConsensusMessageType};
 use crate::message_handler::{parse_protocol_message, ParsedValidation, RippleMessageObject, rmo_to_bytes};
 use crate::node_state::{MutexNodeStates};
-use crate::NodeKeys;
+use crate::scheduler::pipeline::{deliver_events, DELIVERY_CAPACITY, DeliveryRecorder, LinkDelivery, metered_channel, MeteredReceiver, MeteredSender, NODE_STATE_CAPACITY, PipelineMetrics, PROPERTY_CHECK_CAPACITY, REPORT_INTERVAL, route_events, SCHEDULER_CAPACITY};
+use crate::{CONFIG, NodeKeys};
+use crate::network_model::NetworkModel;
 use crate::test_harness::TestHarness;
 
 pub type P2PConnections = HashMap<usize, HashMap<usize, PeerChannel>>;
+/// A peer message as read from the wire: 4 byte size, 2 byte type and the payload
+/// Shared, so it can be forwarded to the receiving peer without copying
+pub type Frame = Arc<Vec<u8>>;
 
 pub trait Scheduler: Sized {
     type IndividualPhenotype: Default + Send + 'static;
@@ -47,7 +60,7 @@ pub trait Scheduler: Sized {
              client_receiver: STDReceiver<(Transaction, String)>,
              account_receiver: STDReceiver<AccountInfo>,
              balance_receiver: STDReceiver<u32>,
-    )
+    ) where Self: Send + 'static
     {
         let latest_validated_ledger_clone = self.get_state().latest_validated_ledger.clone();
         let latest_validated_ledger_clone_2 = self.get_state().latest_validated_ledger.clone();
@@ -59,96 +72,112 @@ pub trait Scheduler: Sized {
         let node_states_clone_3 = self.get_state().node_states.clone();
         let failure_sender_clone = self.get_state().failure_sender.clone();
         let failure_sender_clone_2 = self.get_state().failure_sender.clone();
-        let (round_update_sender, round_update_receiver) = std::sync::mpsc::channel();
-        let (ripple_message_sender, ripple_message_receiver) = std::sync::mpsc::channel();
-        let (consensus_property_checker_sender, consensus_property_checker_receiver) = std::sync::mpsc::channel();
 
         thread::spawn(move || Self::update_current_round(node_states_clone, current_round_clone));
         thread::spawn(move || Self::update_latest_validated_ledger(node_states_clone_3, latest_validated_ledger_clone, failure_sender_clone));
         thread::spawn(move || Self::harness_controller(ga_sender, client_senders, failure_sender_clone_2, client_receiver, account_receiver, balance_receiver,latest_validated_ledger_clone_2, current_round_clone_2, run_clone, node_states_clone_2));
-        Self::check_message_for_round_update(round_update_receiver, self.get_state().node_states.clone());
-        Self::update_send_dependency(ripple_message_receiver, self.get_state().node_states.clone());
+        self.run_pipeline(receiver, ga_receiver, p2p_connections);
+    }
 
-        let (event_schedule_sender, event_schedule_receiver) = channel();
-        let run_clone = self.get_state().run.clone();
-        let node_states_clone = self.get_state().node_states.clone();
+    /// Route, schedule, deliver and check the consensus properties of the messages on tokio tasks connected by bounded queues
+    /// Only consensus messages go through the scheduler, the other frames are passed on without decoding them
+    /// A full queue holds back the stage before it up to the peer readers, the depths are written to pipeline_metrics.txt
+    fn run_pipeline(
+        self,
+        peer_receiver: TokioReceiver<Event>,
+        ga_receiver: STDReceiver<Self::IndividualPhenotype>,
+        p2p_connections: P2PConnections,
+    ) where Self: Send + 'static {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("scheduler-pipeline")
+            .enable_all()
+            .build()
+            .expect("Building pipeline runtime failed");
+        let metrics = Arc::new(PipelineMetrics::new());
+        let (scheduler_sender, scheduler_receiver) = metered_channel("scheduler", SCHEDULER_CAPACITY, &metrics);
+        let (round_update_sender, round_update_receiver) = metered_channel("round_updates", NODE_STATE_CAPACITY, &metrics);
+        let (send_dependency_sender, send_dependency_receiver) = metered_channel("send_dependencies", NODE_STATE_CAPACITY, &metrics);
+        let (delivery_sender, delivery_receiver) = metered_channel("deliveries", DELIVERY_CAPACITY, &metrics);
+        let (pass_through_sender, pass_through_receiver) = metered_channel("pass_through", DELIVERY_CAPACITY, &metrics);
+        let (property_check_sender, mut property_check_receiver) = metered_channel("property_checks", PROPERTY_CHECK_CAPACITY, &metrics);
+        let run = self.get_state().run.clone();
+        let node_states = self.get_state().node_states.clone();
         let current_individual = Arc::new(Mutex::new(Self::IndividualPhenotype::default()));
         let current_individual_2 = current_individual.clone();
-        thread::spawn(move || Self::schedule_controller(receiver, run_clone, current_individual, round_update_sender, event_schedule_sender, ripple_message_sender));
-        thread::spawn(move || Self::listen_to_ga(current_individual_2, ga_receiver, node_states_clone));
-        Self::listen_to_scheduler(event_schedule_receiver, consensus_property_checker_sender, p2p_connections);
-        self.consensus_property_checker(consensus_property_checker_receiver);
+        let node_states_2 = node_states.clone();
+        runtime.spawn(route_events(peer_receiver, scheduler_sender, pass_through_sender));
+        runtime.spawn(Self::check_message_for_round_update(round_update_receiver, node_states.clone()));
+        runtime.spawn(Self::update_send_dependency(send_dependency_receiver, node_states.clone()));
+        runtime.spawn(Self::schedule_controller(scheduler_receiver, run, current_individual, node_states, round_update_sender, delivery_sender, send_dependency_sender));
+        // The GA side is synchronous, its individuals are awaited on a blocking thread of the runtime
+        runtime.spawn_blocking(move || Self::listen_to_ga(current_individual_2, ga_receiver, node_states_2));
+        let network_model = if CONFIG.network_model.enabled { Some(NetworkModel::new(&CONFIG.network_model)) } else { None };
+        let links = LinkDelivery::new(p2p_connections, network_model, DeliveryRecorder::new(property_check_sender, metrics.clone()));
+        runtime.spawn(deliver_events(delivery_receiver, pass_through_receiver, links));
+        runtime.spawn(metrics.report(REPORT_INTERVAL));
+        runtime.block_on(async move {
+            while let Some(delivered) = property_check_receiver.recv().await {
+                // Frames that were passed on are only decoded here, after they reached the receiving peer
+                self.check_consensus_properties(delivered.into_ripple_message());
+            }
+            error!("Consensus property sender failed");
+        });
     }
 
+    /// Decide when the consensus messages of the peers are released to the delivery queue, runs as a task of the pipeline
+    /// Helper threads of a scheduler release messages with blocking sends, so a full delivery queue also holds them back
     fn schedule_controller(
-        receiver: TokioReceiver<Event>,
+        receiver: MeteredReceiver<Event>,
         run: Arc<(RwLock<bool>, Condvar)>,
         current_individual: Arc<Mutex<Self::IndividualPhenotype>>,
-        round_update_sender: STDSender<RMOEvent>,
-        event_schedule_sender: STDSender<RMOEvent>,
-        send_dependency_sender: STDSender<RippleMessage>,
-    );
+        node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>;
 
     fn listen_to_ga(current_individual: Arc<Mutex<Self::IndividualPhenotype>>, ga_receiver: STDReceiver<Self::IndividualPhenotype>, node_states: Arc<MutexNodeStates>);
 
     fn get_state(&self) -> &SchedulerState;
 
-    /// Execute event and report to collector
-    fn listen_to_scheduler(event_schedule_receiver: STDReceiver<RMOEvent>, consensus_property_sender: Sender<Box<RippleMessage>>, p2p_connections: P2PConnections) {
-        thread::spawn(move || {
-            loop {
-                match event_schedule_receiver.recv() {
-                    Ok(event) => {
-                        let collector_message = RippleMessage::new(format!("Ripple{}", event.from + 1), format!("Ripple{}", event.to + 1),
-                                                                   Utc::now().signed_duration_since(event.time_in), Utc::now(), event.message.clone());
-                        consensus_property_sender.send(collector_message).expect("Consensus property sender failed");
-                        p2p_connections.get(&event.to).unwrap().get(&event.from).unwrap().send(event);
-                    },
-                    Err(_) => panic!("Scheduler sender failed")
-                }
-            }
-        });
-    }
-
-    fn consensus_property_checker(self, receiver: STDReceiver<Box<RippleMessage>>) {
+    /// Report a delivered message to the collector and check the properties it could violate
+    fn check_consensus_properties(&self, collector_message: Box<RippleMessage>) {
         let (ref run_lock, ref _run_cvar) = &*self.get_state().run;
-        loop {
-            let collector_message = receiver.recv().expect("consensus property receiver failed");
-            self.get_state().collector_sender.send(collector_message.clone()).expect("collector sender failed");
-            if *run_lock.read().unwrap() {
-                if Self::is_consensus_rmo(&collector_message.message) {
-                    self.get_state().node_states.add_execution(collector_message.as_ref().clone());
-                    if Self::is_own_message(&collector_message.message, &self.get_state().node_keys[collector_message.sender_index()].validation_public_key) {
-                        let mut consensus_property_violations = vec![];
-                        match &collector_message.message {
-                            RippleMessageObject::TMStatusChange(status_change) => {
-                                consensus_property_violations.append(
-                                    &mut ConsensusProperties::check_proposal_integrity_property(
-                                        &self.get_state().node_states,
-                                        &status_change,
-                                        collector_message.sender_index()
-                                    ));
-                            }
-                            RippleMessageObject::TMValidation(validation) => {
-                                let parsed_validation = ParsedValidation::new(validation);
-                                consensus_property_violations.append(
-                                    &mut ConsensusProperties::check_validation_integrity_property(
-                                        &self.get_state().node_states,
-                                        parsed_validation,
-                                        collector_message.sender_index()
-                                    ));
-                            }
-                            RippleMessageObject::TMProposeSet(proposal) => {
-                                self.get_state().node_states.node_states.lock().add_proposed_tx_set(proposal.get_currentTxHash(), collector_message.sender_index());
-                            }
-                            _ => {}
+        self.get_state().collector_sender.send(collector_message.clone()).expect("collector sender failed");
+        if *run_lock.read().unwrap() {
+            if Self::is_consensus_rmo(&collector_message.message) {
+                self.get_state().node_states.add_execution(collector_message.as_ref().clone());
+                if Self::is_own_message(&collector_message.message, &self.get_state().node_keys[collector_message.sender_index()].validation_public_key) {
+                    let mut consensus_property_violations = vec![];
+                    match &collector_message.message {
+                        RippleMessageObject::TMStatusChange(status_change) => {
+                            consensus_property_violations.append(
+                                &mut ConsensusProperties::check_proposal_integrity_property(
+                                    &self.get_state().node_states,
+                                    &status_change,
+                                    collector_message.sender_index()
+                                ));
                         }
-                        if !consensus_property_violations.is_empty() {
-                            match self.get_state().failure_sender.send(consensus_property_violations) {
-                                Ok(_) => {}
-                                Err(_) => error!("Failure channel failed")
-                            };
+                        RippleMessageObject::TMValidation(validation) => {
+                            let parsed_validation = ParsedValidation::new(validation);
+                            consensus_property_violations.append(
+                                &mut ConsensusProperties::check_validation_integrity_property(
+                                    &self.get_state().node_states,
+                                    parsed_validation,
+                                    collector_message.sender_index()
+                                ));
                         }
+                        RippleMessageObject::TMProposeSet(proposal) => {
+                            self.get_state().node_states.add_proposed_tx_set(proposal.get_currentTxHash(), collector_message.sender_index());
+                        }
+                        _ => {}
+                    }
+                    if !consensus_property_violations.is_empty() {
+                        match self.get_state().failure_sender.send(consensus_property_violations) {
+                            Ok(_) => {}
+                            Err(_) => error!("Failure channel failed")
+                        };
                     }
                 }
             }
@@ -171,10 +200,9 @@ pub trait Scheduler: Sized {
     /// Update round number based on ledgerAccept message.
     /// The node has accepted the new ledger and is building/validating that ledger
     /// We consider the node to have moved on to the next round
-    fn check_message_for_round_update(message_listener: STDReceiver<RMOEvent>, node_states: Arc<MutexNodeStates>) {
-        thread::spawn(move || {
-            loop {
-                let rmo_event = message_listener.recv().expect("round_update_receiver failed");
+    fn check_message_for_round_update(mut message_listener: MeteredReceiver<RMOEvent>, node_states: Arc<MutexNodeStates>) -> BoxFuture<'static, ()> {
+        async move {
+            while let Some(rmo_event) = message_listener.recv().await {
                 match rmo_event.message {
                     crate::message_handler::RippleMessageObject::TMStatusChange(ref status_change) => {
                         if status_change.has_newEvent() {
@@ -190,24 +218,24 @@ pub trait Scheduler: Sized {
                     _ => {}
                 }
             }
-        });
+            error!("round_update_sender failed");
+        }.boxed()
     }
 
-    fn update_send_dependency(ripple_message_receiver: STDReceiver<RippleMessage>, node_states: Arc<MutexNodeStates>) {
-        thread::spawn(move || {
-            loop {
-                let ripple_message = ripple_message_receiver.recv().expect("ripple_message_receiver failed");
+    fn update_send_dependency(mut ripple_message_receiver: MeteredReceiver<RippleMessage>, node_states: Arc<MutexNodeStates>) -> BoxFuture<'static, ()> {
+        async move {
+            while let Some(ripple_message) = ripple_message_receiver.recv().await {
                 node_states.add_send_dependency(ripple_message);
             }
-        });
+            error!("ripple_message_sender failed");
+        }.boxed()
     }
 
     /// Update the current round if a message is received with a higher ledger sequence number
     fn update_current_round(node_states: Arc<MutexNodeStates>, current_round: Arc<(Mutex<u32>, Condvar)>) {
         loop {
-            let mut node_states_mutex = node_states.node_states.lock();
-            node_states.round_cvar.wait(&mut node_states_mutex);
-            let round = node_states_mutex.max_current_round();
+            node_states.round_cvar.wait();
+            let round = node_states.max_current_round();
             let (ref lock, ref cvar) = &*current_round;
             let mut locked_round = lock.lock();
             if round > *locked_round {
@@ -226,10 +254,9 @@ pub trait Scheduler: Sized {
     ) {
         let mut liveness = true;
         loop {
-            let mut node_states_mutex = node_states.node_states.lock();
             // Liveness check!
             let now = Utc::now();
-            node_states.validated_ledger_cvar.wait_for(&mut node_states_mutex, Duration::from_secs(65));
+            node_states.validated_ledger_cvar.wait_for(Duration::from_secs(65));
             if Utc::now() - chrono::Duration::seconds(65) >= now {
                 error!("Bounded liveness bug");
                 match failure_sender.send(vec![ConsensusPropertyTypes::Termination]) {
@@ -240,7 +267,7 @@ pub trait Scheduler: Sized {
             } else if !liveness {
                 liveness = true;
             }
-            let validated_ledger_index = node_states_mutex.min_validated_ledger();
+            let validated_ledger_index = node_states.min_validated_ledger();
             let (ref lock, ref cvar) = &*latest_validated_ledger;
             let mut locked_ledger_index = lock.lock();
             if validated_ledger_index > *locked_ledger_index {
@@ -248,7 +275,7 @@ pub trait Scheduler: Sized {
                 *locked_ledger_index = validated_ledger_index;
                 cvar.notify_all();
             }
-            println!("Validated ledgers: {:?}, fork: {}, liveness: {}", node_states_mutex.validated_ledgers(), node_states_mutex.check_for_fork(), liveness);
+            println!("Validated ledgers: {:?}, fork: {}, liveness: {}", node_states.validated_ledgers(), node_states.check_for_fork(), liveness);
         }
     }
 
@@ -292,6 +319,8 @@ pub trait Scheduler: Sized {
                 if *round_number > first_round {
                     drop(round_number);
                     test_harness.setup_balances(&node_states);
+                    node_states.clear_coverage();
+                    node_states.set_harness_start_round();
                     {
                         *run_lock.write().unwrap() = true;
                     }
@@ -380,12 +409,13 @@ impl SchedulerState {
 }
 
 /// Struct for sending from a peer to another peer
+#[derive(Clone)]
 pub struct PeerChannel {
-    sender: TokioSender<Vec<u8>>,
+    sender: TokioSender<Frame>,
 }
 
 impl PeerChannel {
-    pub fn new(sender: TokioSender<Vec<u8>>) -> Self {
+    pub fn new(sender: TokioSender<Frame>) -> Self {
         PeerChannel { sender }
     }
 
@@ -395,23 +425,50 @@ impl PeerChannel {
             Err(_err) => error!("Failed to send message to peer {}", _err)
         }
     }
+
+    /// Send from a tokio task, waits while the channel to the peer is full
+    pub async fn send_async(&self, message: RMOEvent) {
+        self.send_frame_async(Event::from(message).message).await
+    }
+
+    /// Send a frame as it was received, without decoding it
+    pub async fn send_frame_async(&self, frame: Frame) {
+        match self.sender.send(frame).await {
+            Ok(_) => { }
+            Err(_err) => error!("Failed to send message to peer {}", _err)
+        }
+    }
 }
 
 /// Event is a message event, where the 'message' is sent from peer 'from' and received by 'to'
 pub struct Event {
     pub from: usize,
     pub to: usize,
-    pub message: Vec<u8>
+    pub message: Frame
 }
 
 impl Event {
+    /// Reuses the frame the message was parsed from, only messages built by the proxy are serialized
     pub fn from(event: RMOEvent) -> Self {
         Self {
             from: event.from,
             to: event.to,
-            message: rmo_to_bytes(event.message)
+            message: match event.raw {
+                Some(frame) => frame,
+                None => Arc::new(rmo_to_bytes(event.message)),
+            }
         }
     }
+
+    /// The type code in the frame header
+    pub fn message_type(&self) -> u16 {
+        BigEndian::read_u16(&self.message[4..6])
+    }
+
+    /// Whether the scheduler needs the decoded message, read from the header without parsing the payload
+    pub fn is_consensus(&self) -> bool {
+        ConsensusMessageType::RMO_MESSAGE_CODE.contains(&self.message_type())
+    }
 }
 
 #[derive(Clone, PartialEq, Debug, Hash)]
@@ -420,6 +477,8 @@ pub struct RMOEvent {
     pub to: usize,
     pub message: RippleMessageObject,
     pub time_in: DateTime<Utc>,
+    /// The frame the message was parsed from, None if the message was built by the proxy
+    pub raw: Option<Frame>,
 }
 
 impl RMOEvent {
@@ -427,8 +486,9 @@ impl RMOEvent {
         Self {
             from: event.from,
             to: event.to,
-            message: parse_protocol_message(BigEndian::read_u16(&event.message[4..6]), &event.message[6..]),
+            message: parse_protocol_message(event.message_type(), &event.message[6..]),
             time_in: Utc::now(),
+            raw: Some(event.message.clone()),
         }
     }
 }
@@ -457,17 +517,18 @@ impl Ord for RMOEvent {
 
 impl Default for RMOEvent {
     fn default() -> Self {
-        Self { from: 0, to: 0, message: RippleMessageObject::default(), time_in: MAX_DATETIME }
+        Self { from: 0, to: 0, message: RippleMessageObject::default(), time_in: MAX_DATETIME, raw: None }
     }
 }
 
 #[cfg(test)]
 mod scheduler_tests {
+    use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
     use chrono::{TimeZone, Utc};
     use crate::ga::encoding::delay_encoding::DROP_THRESHOLD;
-    use crate::message_handler::RippleMessageObject;
+    use crate::message_handler::{RippleMessageObject, write_message};
     use crate::protos::ripple::{TMTransaction as PBTransaction, TransactionStatus};
     use crate::scheduler::{Event, RMOEvent};
     use crate::scheduler::delay_scheduler::ScheduledEvent;
@@ -477,21 +538,32 @@ mod scheduler_tests {
         let mut transaction = PBTransaction::new();
         transaction.set_rawTransaction(vec![]);
         transaction.set_status(TransactionStatus::tsCOMMITED);
-        let rmo_event = RMOEvent { from: 0, to: 1, message: RippleMessageObject::TMTransaction(transaction), time_in: Utc.timestamp(1431648000, 0) };
+        let rmo_event = RMOEvent { from: 0, to: 1, message: RippleMessageObject::TMTransaction(transaction), time_in: Utc.timestamp(1431648000, 0), raw: None };
         let event = Event::from(rmo_event.clone());
         let transformed_event = RMOEvent::from(&event);
         assert_eq!(rmo_event.message, transformed_event.message);
     }
 
+    #[test]
+    fn test_event_reuses_frame() {
+        let mut transaction = PBTransaction::new();
+        transaction.set_rawTransaction(vec![1, 2, 3]);
+        transaction.set_status(TransactionStatus::tsNEW);
+        let event = Event { from: 0, to: 1, message: Arc::new(write_message(30, transaction)) };
+        assert!(event.is_consensus());
+        let forwarded = Event::from(RMOEvent::from(&event));
+        assert!(Arc::ptr_eq(&event.message, &forwarded.message));
+    }
+
     #[test]
     fn test_drop_threshold() {
-        let rmo_event = RMOEvent { from: 0, to: 1, message: RippleMessageObject::TMTransaction(PBTransaction::new()), time_in: Utc.timestamp(1431648000, 0) };
+        let rmo_event = RMOEvent { from: 0, to: 1, message: RippleMessageObject::TMTransaction(PBTransaction::new()), time_in: Utc.timestamp(1431648000, 0), raw: None };
         let (sender, receiver) = std::sync::mpsc::channel();
         ScheduledEvent::schedule_execution(rmo_event, DROP_THRESHOLD as u64 + 1, sender.clone());
         thread::sleep(Duration::from_millis(DROP_THRESHOLD as u64 + 500));
         let result = receiver.try_recv();
         assert!(result.is_err());
-        let rmo_event = RMOEvent { from: 0, to: 1, message: RippleMessageObject::TMTransaction(PBTransaction::default()), time_in: Utc.timestamp(1431648000, 0) };
+        let rmo_event = RMOEvent { from: 0, to: 1, message: RippleMessageObject::TMTransaction(PBTransaction::default()), time_in: Utc.timestamp(1431648000, 0), raw: None };
         ScheduledEvent::schedule_execution(rmo_event, 100, sender);
         thread::sleep(Duration::from_millis(1000));
         let result = receiver.try_recv();
@@ -1,20 +1,20 @@
 use std::sync::{Arc, RwLock};
 use std::sync::mpsc::{Sender as STDSender, Receiver as STDReceiver};
-use tokio::sync::mpsc::{Receiver as TokioReceiver};
-use std::thread;
+use futures::future::{BoxFuture, FutureExt};
 use genevo::genetic::Phenotype;
 use log::{debug, error, trace};
 use parking_lot::{Condvar, Mutex};
-use spin_sleep::SpinSleeper;
 use crate::collector::RippleMessage;
 use crate::failure_writer::ConsensusPropertyTypes;
 use crate::ga::genetic_algorithm::{ConsensusMessageType};
 #[allow(unused_imports)]
-use crate::ga::encoding::delay_encoding::{DelayMapPhenotype, DROP_THRESHOLD};
+use crate::ga::encoding::delay_encoding::{DelayMapPhenotype, DROP_DELAY, DROP_THRESHOLD};
 use crate::ga::encoding::ExtendedPhenotype;
 use crate::node_state::MutexNodeStates;
 use crate::NodeKeys;
 use crate::scheduler::{Event, RMOEvent, Scheduler, SchedulerState};
+use crate::scheduler::dispatcher::{DELAYED_EVENTS, DelayedSender};
+use crate::scheduler::pipeline::{MeteredReceiver, MeteredSender};
 
 /// Scheduler module responsible for scheduling execution of events (message receivals in peers)
 /// p2p_connections: Contains the senders for sending from a peer to another peer
@@ -43,43 +43,50 @@ impl Scheduler for DelayScheduler {
     /// If the network is not stable, immediately relay messages
     /// Else schedule messages with a certain delay
     fn schedule_controller(
-        mut receiver: TokioReceiver<Event>,
+        mut receiver: MeteredReceiver<Event>,
         run: Arc<(RwLock<bool>, Condvar)>,
         current_delays: Arc<Mutex<Self::IndividualPhenotype>>,
-        round_update_sender: STDSender<RMOEvent>,
-        event_schedule_sender: STDSender<RMOEvent>,
-        send_dependency_sender: STDSender<RippleMessage>,
-    )
+        node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>
     {
-        let (run_lock, _run_cvar) = &*run;
-        loop {
-            match receiver.blocking_recv() {
-                Some(event) => {
-                    let rmo_event = RMOEvent::from(&event);
-                    if Self::is_consensus_rmo(&rmo_event.message) {
-                        round_update_sender.send(rmo_event.clone()).expect("Round update sender failed");
-                        // If the network is ready to apply the test case, determine delay of message, else delay = 0
-                        if *run_lock.read().unwrap() {
-                            send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).expect("send dependency sender failed");
-                            let consensus_message_type_option = ConsensusMessageType::create_consensus_message_type(&rmo_event.message);
-                            if let Some(consensus_message_type) = consensus_message_type_option {
-                                let ms = current_delays.lock().get_delay(&rmo_event.from, &rmo_event.to, &consensus_message_type) as u64;
-                                if ms > 0 {
-                                    ScheduledEvent::schedule_execution(
-                                        rmo_event,
-                                        ms,
-                                        event_schedule_sender.clone()
-                                    );
-                                    continue;
-                                }
+        async move {
+            let (run_lock, _run_cvar) = &*run;
+            while let Some(event) = receiver.recv().await {
+                let rmo_event = RMOEvent::from(&event);
+                if Self::is_consensus_rmo(&rmo_event.message) {
+                    round_update_sender.send(rmo_event.clone()).await.expect("Round update sender failed");
+                    // If the network is ready to apply the test case, determine delay of message, else delay = 0
+                    let running = *run_lock.read().unwrap();
+                    if running {
+                        send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).await.expect("send dependency sender failed");
+                        let consensus_message_type_option = ConsensusMessageType::create_consensus_message_type(&rmo_event.message);
+                        if let Some(consensus_message_type) = consensus_message_type_option {
+                            // Delays are looked up for the round the sender is in and the phase the receiver is in
+                            let round_offset = node_states.get_round_offset(rmo_event.from);
+                            let receiver_phase = node_states.get_consensus_phase(rmo_event.to);
+                            let ms = current_delays.lock().get_delay(&rmo_event.from, &rmo_event.to, &consensus_message_type, round_offset, &receiver_phase) as u64;
+                            if ms == DROP_DELAY as u64 {
+                                trace!("Dropping message: {} -> {}: {:?}", rmo_event.from, rmo_event.to, rmo_event.message);
+                                continue;
+                            }
+                            if ms > 0 {
+                                ScheduledEvent::schedule_execution(
+                                    rmo_event,
+                                    ms,
+                                    event_schedule_sender.clone()
+                                );
+                                continue;
                             }
                         }
                     }
-                    event_schedule_sender.send(rmo_event).expect("Event schedule sender failed");
-                },
-                None => error!("Peer senders failed")
+                }
+                event_schedule_sender.send(rmo_event).await.expect("Event schedule sender failed");
             }
-        }
+            error!("Peer senders failed");
+        }.boxed()
     }
 
     /// Listen to the genetic algorithm for new individuals to test
@@ -105,27 +112,12 @@ impl Scheduler for DelayScheduler {
 
 
 /// ScheduledEvent is a struct with functionality for scheduling the sending of a message after a certain duration
+/// All delayed messages share a single dispatcher thread
 pub struct ScheduledEvent {}
 
 impl ScheduledEvent {
-    pub(crate) fn schedule_execution(event: RMOEvent, duration: u64, sender: STDSender<RMOEvent>) {
-        // if duration.as_millis() > DROP_THRESHOLD as u128 {
-        //     return
-        // } else {
-            thread::spawn(move || {
-                let sleeper = SpinSleeper::default();
-                trace!("Sleeping for {} ms for message: {} -> {}: {:?}", duration, event.from, event.to, event.message);
-                let ns = match duration.checked_mul(1000 * 1000) {
-                    None => {
-                        error!("The delay ms to ns caused a u64 overflow, sleeping max ns");
-                        u64::MAX
-                    }
-                    Some(ns) => ns
-                };
-                sleeper.sleep_ns(ns);
-                trace!("Sending event to executor: {} -> {}: {:?}", event.from, event.to, event.message);
-                sender.send(event).expect("Scheduler receiver failed");
-            });
-        // }
+    pub(crate) fn schedule_execution(event: RMOEvent, duration: u64, sender: impl DelayedSender<RMOEvent>) {
+        trace!("Delaying for {} ms message: {} -> {}: {:?}", duration, event.from, event.to, event.message);
+        DELAYED_EVENTS.schedule(event, duration, sender);
     }
-}
\ No newline at end of file
+}
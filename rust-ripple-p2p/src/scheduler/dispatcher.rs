@@ -0,0 +1,177 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::mpsc::{Receiver as STDReceiver, RecvTimeoutError, Sender as STDSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use log::{error, trace};
+use parking_lot::Mutex;
+use spin_sleep::SpinSleeper;
+use crate::scheduler::RMOEvent;
+
+/// The last part of a wait is spun instead of slept, to deliver close to the deadline
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+lazy_static! {
+    /// The dispatcher of all delayed messages
+    pub static ref DELAYED_EVENTS: DelayedDispatcher<RMOEvent> = DelayedDispatcher::new("delayed-events");
+}
+
+/// Where an item goes once its deadline passed
+pub trait DelayedSender<T>: Send + 'static {
+    /// Returns false if the receiver hung up
+    fn send_delayed(&self, item: T) -> bool;
+}
+
+impl<T: Send + 'static> DelayedSender<T> for STDSender<T> {
+    fn send_delayed(&self, item: T) -> bool {
+        self.send(item).is_ok()
+    }
+}
+
+/// An item that has to be sent at the deadline, seq orders items with equal deadlines in the order they were scheduled
+struct Timer<T> {
+    deadline: Instant,
+    seq: u64,
+    item: T,
+    sender: Box<dyn DelayedSender<T>>,
+}
+
+impl<T> PartialEq for Timer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline && self.seq == other.seq
+    }
+}
+
+impl<T> Eq for Timer<T> {}
+
+impl<T> PartialOrd for Timer<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Reversed, so the max-heap pops the earliest deadline first
+impl<T> Ord for Timer<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.deadline.cmp(&self.deadline).then(other.seq.cmp(&self.seq))
+    }
+}
+
+/// Sends items after a delay from a single thread that keeps the pending items in a heap ordered by deadline
+/// Items with equal deadlines are sent in the order they were scheduled
+/// A sender that waits for space in a bounded queue holds back the items after it
+pub struct DelayedDispatcher<T> {
+    timer_sender: Mutex<STDSender<(Instant, T, Box<dyn DelayedSender<T>>)>>,
+}
+
+impl<T: Send + 'static> DelayedDispatcher<T> {
+    pub fn new(name: &str) -> Self {
+        let (timer_sender, timer_receiver) = std::sync::mpsc::channel();
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || Self::dispatch(timer_receiver))
+            .expect("Spawning dispatcher failed");
+        Self { timer_sender: Mutex::new(timer_sender) }
+    }
+
+    /// Send the item to the sender after ms milliseconds
+    pub fn schedule(&self, item: T, ms: u64, sender: impl DelayedSender<T>) {
+        match Instant::now().checked_add(Duration::from_millis(ms)) {
+            Some(deadline) => self.timer_sender.lock().send((deadline, item, Box::new(sender))).expect("Dispatcher failed"),
+            None => error!("A delay of {} ms does not fit in an instant, the item is never sent", ms),
+        }
+    }
+
+    fn dispatch(timer_receiver: STDReceiver<(Instant, T, Box<dyn DelayedSender<T>>)>) {
+        let sleeper = SpinSleeper::default();
+        let mut timers: BinaryHeap<Timer<T>> = BinaryHeap::new();
+        let mut seq = 0u64;
+        let mut disconnected = false;
+        loop {
+            let now = Instant::now();
+            while timers.peek().map_or(false, |timer| timer.deadline <= now) {
+                let timer = timers.pop().unwrap();
+                if !timer.sender.send_delayed(timer.item) {
+                    trace!("Receiver of a delayed item hung up");
+                }
+            }
+            let received = match timers.peek() {
+                Some(timer) => {
+                    let remaining = timer.deadline.saturating_duration_since(Instant::now());
+                    if remaining <= SPIN_THRESHOLD || disconnected {
+                        sleeper.sleep(remaining);
+                        continue;
+                    }
+                    timer_receiver.recv_timeout(remaining - SPIN_THRESHOLD)
+                }
+                None if disconnected => return,
+                None => timer_receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok((deadline, item, sender)) => {
+                    timers.push(Timer { deadline, seq, item, sender });
+                    seq += 1;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => disconnected = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod dispatcher_tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+    use spin_sleep::SpinSleeper;
+    use crate::scheduler::dispatcher::DelayedDispatcher;
+
+    #[test]
+    fn test_dispatch_order() {
+        let dispatcher = DelayedDispatcher::new("test-dispatcher");
+        let (sender, receiver) = std::sync::mpsc::channel();
+        dispatcher.schedule("late", 60, sender.clone());
+        dispatcher.schedule("early", 20, sender.clone());
+        dispatcher.schedule("now", 0, sender);
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), vec!["now", "early", "late"]);
+    }
+
+    /// Difference between the deadline and the moment an item arrives, for items with random delays up to 500 ms
+    fn jitter(schedule: impl Fn(Instant, u64, std::sync::mpsc::Sender<Instant>)) -> Vec<Duration> {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for _ in 0..2000 {
+            let ms = rng.gen_range(0..500);
+            schedule(Instant::now() + Duration::from_millis(ms), ms, sender.clone());
+        }
+        drop(sender);
+        let mut jitter = receiver.iter().map(|deadline| Instant::now().saturating_duration_since(deadline)).collect::<Vec<_>>();
+        jitter.sort();
+        jitter
+    }
+
+    fn print_jitter(name: &str, jitter: &[Duration]) {
+        let mean = jitter.iter().sum::<Duration>() / jitter.len() as u32;
+        println!("{}: mean {:?}, median {:?}, p99 {:?}, max {:?}", name, mean, jitter[jitter.len() / 2], jitter[jitter.len() * 99 / 100], jitter[jitter.len() - 1]);
+    }
+
+    /// Compares the thread per delayed message approach with the dispatcher
+    /// Run with: cargo test --release benchmark_dispatch_jitter -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark_dispatch_jitter() {
+        let thread_per_message = jitter(|deadline, ms, sender| {
+            thread::spawn(move || {
+                SpinSleeper::default().sleep(Duration::from_millis(ms));
+                sender.send(deadline).unwrap();
+            });
+        });
+        print_jitter("thread per message", &thread_per_message);
+        let dispatcher = DelayedDispatcher::new("benchmark-dispatcher");
+        let dispatched = jitter(|deadline, ms, sender| dispatcher.schedule(deadline, ms, sender));
+        print_jitter("dispatcher", &dispatched);
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::{Arc, RwLock};
+use std::sync::mpsc::{Receiver as STDReceiver, Sender as STDSender};
+use std::thread;
+use log::{debug, error, trace};
+use parking_lot::{Condvar, Mutex};
+use futures::future::{BoxFuture, FutureExt};
+use crate::collector::RippleMessage;
+use crate::failure_writer::ConsensusPropertyTypes;
+use crate::ga::encoding::ExtendedPhenotype;
+use crate::ga::encoding::delay_encoding::DROP_DELAY;
+use crate::ga::encoding::hybrid_encoding::HybridMapPhenotype;
+use crate::ga::genetic_algorithm::ConsensusMessageType;
+use crate::node_state::MutexNodeStates;
+use crate::NodeKeys;
+use crate::scheduler::{Event, RMOEvent, Scheduler, SchedulerState};
+use crate::scheduler::delay_scheduler::ScheduledEvent;
+use crate::scheduler::pipeline::{MeteredReceiver, MeteredSender};
+use crate::scheduler::priority_scheduler::{OrderedRMOEvent, PriorityScheduler};
+
+/// Scheduler that first delays messages and then lets them compete in a priority inbox
+/// The delay and priority of a message are determined by the same (from, to, message type) gene
+pub struct HybridScheduler {
+    state: SchedulerState,
+}
+
+impl HybridScheduler {
+    /// Move delayed messages to the inbox with the priority of the current individual
+    fn delay_controller(
+        delayed_rx: STDReceiver<RMOEvent>,
+        current_individual: Arc<Mutex<HybridMapPhenotype>>,
+        inbox_tx: STDSender<OrderedRMOEvent>,
+    ) {
+        while let Ok(rmo_event) = delayed_rx.recv() {
+            let priority = Self::priority_of(&rmo_event, &current_individual.lock());
+            inbox_tx.send(OrderedRMOEvent::new(rmo_event, priority)).expect("Inbox sender failed");
+        }
+        error!("Delayed event senders failed");
+    }
+
+    fn priority_of(rmo_event: &RMOEvent, individual: &HybridMapPhenotype) -> usize {
+        match ConsensusMessageType::create_consensus_message_type(&rmo_event.message) {
+            Some(consensus_message_type) => individual.get_priority(&rmo_event.from, &rmo_event.to, &consensus_message_type),
+            None => 0usize,
+        }
+    }
+}
+
+impl Scheduler for HybridScheduler {
+    type IndividualPhenotype = HybridMapPhenotype;
+
+    fn new(
+        collector_sender: STDSender<Box<RippleMessage>>,
+        node_states: Arc<MutexNodeStates>,
+        node_keys: Vec<NodeKeys>,
+        failure_sender: STDSender<Vec<ConsensusPropertyTypes>>,
+    ) -> Self {
+        Self {
+            state: SchedulerState::new(collector_sender, node_states, node_keys, failure_sender)
+        }
+    }
+
+    /// Wait for new messages delivered by peers
+    /// If the network is not stable, immediately relay messages
+    /// Else delay messages and collect them in the inbox afterwards, the inbox is scheduled based on priority
+    fn schedule_controller(
+        mut receiver: MeteredReceiver<Event>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        current_individual: Arc<Mutex<Self::IndividualPhenotype>>,
+        node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>
+    {
+        async move {
+            let (run_lock, _run_cvar) = &*run;
+            let (inbox_tx, inbox_rx) = std::sync::mpsc::channel();
+            let (delayed_tx, delayed_rx) = std::sync::mpsc::channel();
+            let event_schedule_sender_2 = event_schedule_sender.clone();
+            let run_2 = run.clone();
+            let current_individual_2 = current_individual.clone();
+            thread::spawn(move || PriorityScheduler::inbox_controller(inbox_rx, run_2, event_schedule_sender_2));
+            thread::spawn(move || Self::delay_controller(delayed_rx, current_individual_2, inbox_tx));
+            while let Some(event) = receiver.recv().await {
+                let rmo_event = RMOEvent::from(&event);
+                if Self::is_consensus_rmo(&rmo_event.message) {
+                    round_update_sender.send(rmo_event.clone()).await.expect("Round update sender failed");
+                    // If the network is ready to apply the test case, delay the message before it enters the inbox, else immediately relay
+                    let running = *run_lock.read().unwrap();
+                    if running {
+                        send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).await.expect("send dependency sender failed");
+                        let ms = match ConsensusMessageType::create_consensus_message_type(&rmo_event.message) {
+                            Some(consensus_message_type) => {
+                                let round_offset = node_states.get_round_offset(rmo_event.from);
+                                let receiver_phase = node_states.get_consensus_phase(rmo_event.to);
+                                current_individual.lock().get_delay(&rmo_event.from, &rmo_event.to, &consensus_message_type, round_offset, &receiver_phase) as u64
+                            }
+                            None => 0,
+                        };
+                        if ms == DROP_DELAY as u64 {
+                            trace!("Dropping message: {} -> {}: {:?}", rmo_event.from, rmo_event.to, rmo_event.message);
+                            continue;
+                        }
+                        if ms > 0 {
+                            ScheduledEvent::schedule_execution(rmo_event, ms, delayed_tx.clone());
+                        } else {
+                            delayed_tx.send(rmo_event).expect("Delayed event sender failed");
+                        }
+                        continue;
+                    }
+                }
+                event_schedule_sender.send(rmo_event).await.expect("Event schedule sender failed");
+            }
+            error!("Peer senders failed");
+        }.boxed()
+    }
+
+    /// Listen to the genetic algorithm for new individuals to test
+    fn listen_to_ga(current_individual: Arc<Mutex<Self::IndividualPhenotype>>, ga_receiver: STDReceiver<Self::IndividualPhenotype>, node_states: Arc<MutexNodeStates>) {
+        loop {
+            match ga_receiver.recv() {
+                Ok(new_individual) => {
+                    node_states.set_current_delays(new_individual.delays());
+                    node_states.set_current_individual(current_individual.lock().display_genotype_by_message());
+                    *current_individual.lock() = new_individual;
+                    debug!("New delays and priorities received");
+                },
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn get_state(&self) -> &SchedulerState {
+        &self.state
+    }
+}
@@ -0,0 +1,452 @@
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::sync::mpsc::{Receiver as STDReceiver, RecvTimeoutError, Sender as STDSender};
+use std::thread;
+use std::time::Duration as TimeDuration;
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use log::error;
+use parking_lot::{Condvar, Mutex};
+use futures::future::{BoxFuture, FutureExt};
+use crate::collector::RippleMessage;
+use crate::failure_writer::ConsensusPropertyTypes;
+use crate::ga::encoding::{ExtendedPhenotype, num_genes};
+use crate::ga::encoding::delay_encoding::DROP_DELAY;
+use crate::ga::encoding::priority_encoding::PriorityMapPhenotype;
+use crate::ga::fitness::ExtendedFitness;
+use crate::ga::genetic_algorithm::ConsensusMessageType;
+use crate::LOG_FOLDER;
+use crate::node_state::MutexNodeStates;
+use crate::NodeKeys;
+use crate::schedule_file::{RuleAction, ScheduleRule};
+use crate::scheduler::{Event, RMOEvent, Scheduler, SchedulerState};
+use crate::scheduler::delay_scheduler::ScheduledEvent;
+use crate::scheduler::pipeline::{MeteredReceiver, MeteredSender};
+use crate::scheduler::priority_scheduler::{OrderedRMOEvent, PriorityInbox, PriorityScheduler};
+
+/// The number of characters of a decoded message shown when listing the inbox
+const SUMMARY_LENGTH: usize = 120;
+
+/// Scheduler that lets a human release, delay or drop the messages in the priority inbox during a harness run
+/// Outside of harness runs messages are relayed immediately, like the other schedulers
+pub struct InteractiveScheduler {
+    state: SchedulerState,
+}
+
+/// Commands typed at the interactive prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InteractiveCommand {
+    List,
+    Show(usize),
+    Release(usize),
+    ReleaseNext,
+    ReleaseAll,
+    Delay(usize, u64),
+    Drop(usize),
+    StepRound,
+    StepLedger,
+    Save(Option<String>),
+    Help,
+}
+
+impl InteractiveCommand {
+    pub const HELP: &'static str = "Commands:
+  list                  Show the pending messages, highest priority first
+  show <id>             Show the decoded message
+  release <id>          Deliver the message now
+  next                  Deliver the pending message with the highest priority
+  release all           Deliver all pending messages
+  delay <id> <ms>       Deliver the message after ms milliseconds
+  drop <id>             Never deliver the message
+  step round            Deliver messages by priority until every node is in the next round
+  step ledger           Deliver messages by priority until every node validated the next ledger
+  save [path]           Write the decisions of the current harness run as a schedule file, by default to interactive_schedule.txt in the log folder
+  help                  Show this help";
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let tokens = line.split_whitespace().collect_vec();
+        let id = |token: &str| token.parse::<usize>().map_err(|_| format!("{} is not a message id", token));
+        match tokens.as_slice() {
+            ["list"] | ["ls"] => Ok(Self::List),
+            ["show", message] => Ok(Self::Show(id(message)?)),
+            ["release", "all"] => Ok(Self::ReleaseAll),
+            ["release", message] => Ok(Self::Release(id(message)?)),
+            ["next"] => Ok(Self::ReleaseNext),
+            ["delay", message, ms] => Ok(Self::Delay(id(message)?, ms.parse().map_err(|_| format!("{} is not a delay in ms", ms))?)),
+            ["drop", message] => Ok(Self::Drop(id(message)?)),
+            ["step", "round"] => Ok(Self::StepRound),
+            ["step", "ledger"] => Ok(Self::StepLedger),
+            ["save"] => Ok(Self::Save(None)),
+            ["save", path] => Ok(Self::Save(Some(path.to_string()))),
+            ["help"] => Ok(Self::Help),
+            _ => Err(format!("Unknown command: {}, type help for the commands", line.trim())),
+        }
+    }
+}
+
+/// What was done with a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleAction {
+    Release,
+    Delay { ms: u64 },
+    Drop,
+}
+
+/// A single decision of the current harness run, saved as a delay rule for its message type on its link
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleDecision {
+    ms_since_start: i64,
+    from: usize,
+    to: usize,
+    message_type: Option<ConsensusMessageType>,
+    /// The time the message was held in the inbox plus the delay it was given, or a drop
+    action: RuleAction,
+    message: String,
+}
+
+impl ScheduleDecision {
+    fn new(rmo_event: &RMOEvent, action: &ScheduleAction, run_start: DateTime<Utc>) -> Self {
+        let now = Utc::now();
+        let held_ms = (now - rmo_event.time_in).num_milliseconds().max(0) as u64;
+        let delay = |ms: u64| RuleAction::Delay(ms.min(DROP_DELAY as u64 - 1) as u32);
+        Self {
+            ms_since_start: (now - run_start).num_milliseconds(),
+            from: rmo_event.from,
+            to: rmo_event.to,
+            message_type: ConsensusMessageType::create_consensus_message_type(&rmo_event.message),
+            action: match action {
+                ScheduleAction::Release => delay(held_ms),
+                ScheduleAction::Delay { ms } => delay(held_ms + ms),
+                ScheduleAction::Drop => RuleAction::Drop,
+            },
+            message: rmo_event.message.to_string().split_whitespace().join(" ").chars().take(SUMMARY_LENGTH).collect(),
+        }
+    }
+
+    /// A comment with the time of the decision and the message, followed by its rule
+    fn lines(&self) -> String {
+        let comment = format!("# {}ms: {} -> {} {}", self.ms_since_start, self.from, self.to, self.message);
+        match &self.message_type {
+            Some(message_type) => format!("{}\n{}\n", comment, ScheduleRule::line(message_type, self.from, self.to, self.action)),
+            None => format!("{}\n# Not a consensus message type, it cannot be replayed\n", comment),
+        }
+    }
+}
+
+/// The decisions as a delay schedule file for the ScheduleFiles scheduler
+/// Later rules override earlier rules, so replaying gives every message of a type on a link the last decision for it
+pub fn schedule_file_contents(decisions: &[ScheduleDecision]) -> String {
+    let header = format!("# Interactive schedule of {} decision(s), delays are the time a message was held\n", decisions.len());
+    header + &decisions.iter().map(ScheduleDecision::lines).join("")
+}
+
+/// Automatically deliver messages by priority until every node reached the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepTarget {
+    Round(u32),
+    Ledger(u32),
+}
+
+impl StepTarget {
+    fn is_reached(&self, node_states: &MutexNodeStates) -> bool {
+        match self {
+            StepTarget::Round(round) => node_states.min_current_round() >= *round,
+            StepTarget::Ledger(ledger) => node_states.min_validated_ledger() >= *ledger,
+        }
+    }
+}
+
+/// State of the interactive inbox, owned by the inbox controller thread
+struct InteractiveController {
+    inbox: PriorityInbox,
+    event_schedule_sender: MeteredSender<RMOEvent>,
+    node_states: Arc<MutexNodeStates>,
+    decisions: Vec<ScheduleDecision>,
+    run_start: DateTime<Utc>,
+    step_target: Option<StepTarget>,
+}
+
+impl InteractiveController {
+    fn decide(&mut self, rmo_event: RMOEvent, action: ScheduleAction) {
+        self.decisions.push(ScheduleDecision::new(&rmo_event, &action, self.run_start));
+        match action {
+            ScheduleAction::Release => self.event_schedule_sender.blocking_send(rmo_event).expect("Event scheduler failed"),
+            ScheduleAction::Delay { ms } => ScheduledEvent::schedule_execution(rmo_event, ms, self.event_schedule_sender.clone()),
+            ScheduleAction::Drop => {}
+        }
+    }
+
+    fn with_event(&mut self, id: usize, action: ScheduleAction) {
+        match self.inbox.take(id) {
+            Some(rmo_event) => self.decide(rmo_event, action),
+            None => println!("No pending message with id {}", id),
+        }
+    }
+
+    fn handle(&mut self, command: InteractiveCommand) {
+        match command {
+            InteractiveCommand::List => {
+                println!("{} pending message(s)", self.inbox.len());
+                for (id, rmo_event, priority) in self.inbox.pending() {
+                    let message = rmo_event.message.to_string().chars().take(SUMMARY_LENGTH).collect::<String>();
+                    println!("{:>5} [{}] {} -> {} {}", id, priority, rmo_event.from, rmo_event.to, message);
+                }
+            }
+            InteractiveCommand::Show(id) => match self.inbox.get(id) {
+                Some(rmo_event) => println!("{} -> {} received {}: {}", rmo_event.from, rmo_event.to, rmo_event.time_in, rmo_event.message),
+                None => println!("No pending message with id {}", id),
+            },
+            InteractiveCommand::Release(id) => self.with_event(id, ScheduleAction::Release),
+            InteractiveCommand::ReleaseNext => match self.inbox.pop_highest() {
+                Some((_, rmo_event)) => self.decide(rmo_event, ScheduleAction::Release),
+                None => println!("The inbox is empty"),
+            },
+            InteractiveCommand::ReleaseAll => {
+                for rmo_event in self.inbox.drain() {
+                    self.decide(rmo_event, ScheduleAction::Release);
+                }
+            }
+            InteractiveCommand::Delay(id, ms) => self.with_event(id, ScheduleAction::Delay { ms }),
+            InteractiveCommand::Drop(id) => self.with_event(id, ScheduleAction::Drop),
+            InteractiveCommand::StepRound => self.step_target = Some(StepTarget::Round(self.node_states.min_current_round() + 1)),
+            InteractiveCommand::StepLedger => self.step_target = Some(StepTarget::Ledger(self.node_states.min_validated_ledger() + 1)),
+            InteractiveCommand::Save(path) => {
+                let path = path.unwrap_or_else(|| format!("{}/interactive_schedule.txt", *LOG_FOLDER));
+                match self.save(&path) {
+                    Ok(_) => println!("Saved {} decision(s) to {}", self.decisions.len(), path),
+                    Err(err) => println!("Failed saving schedule to {}: {}", path, err),
+                }
+            }
+            InteractiveCommand::Help => println!("{}", InteractiveCommand::HELP),
+        }
+    }
+
+    /// Write the decisions of the current run as a schedule file that can be replayed with the ScheduleFiles scheduler
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(Path::new(path))?);
+        writer.write_all(schedule_file_contents(&self.decisions).as_bytes())?;
+        writer.flush()
+    }
+
+    /// Deliver the next message by priority if a step is in progress
+    fn step(&mut self) {
+        if let Some(target) = self.step_target {
+            if target.is_reached(&self.node_states) {
+                println!("Reached {:?}, {} message(s) pending", target, self.inbox.len());
+                self.step_target = None;
+            } else if let Some((_, rmo_event)) = self.inbox.pop_highest() {
+                self.decide(rmo_event, ScheduleAction::Release);
+            }
+        }
+    }
+}
+
+impl InteractiveScheduler {
+    /// Hold messages in the inbox during harness runs and act on the commands typed at the prompt
+    /// Outside of harness runs the inbox is emptied immediately
+    fn interactive_inbox_controller(
+        inbox_rx: STDReceiver<OrderedRMOEvent>,
+        command_rx: STDReceiver<InteractiveCommand>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        node_states: Arc<MutexNodeStates>,
+    ) {
+        let (run_lock, _run_cvar) = &*run;
+        let mut controller = InteractiveController {
+            inbox: PriorityInbox::new(),
+            event_schedule_sender,
+            node_states,
+            decisions: vec![],
+            run_start: Utc::now(),
+            step_target: None,
+        };
+        let mut was_running = false;
+        loop {
+            while let Ok(ordered_event) = inbox_rx.try_recv() {
+                controller.inbox.push(ordered_event.rmo_event, ordered_event.priority);
+            }
+            let running = *run_lock.read().unwrap();
+            if running && !was_running {
+                println!("Harness run started, messages are held until released. Type help for the commands");
+                controller.decisions.clear();
+                controller.run_start = Utc::now();
+            } else if !running && was_running {
+                println!("Harness run finished, delivering {} pending message(s)", controller.inbox.len());
+                controller.step_target = None;
+            }
+            was_running = running;
+            if running {
+                controller.step();
+            } else {
+                for rmo_event in controller.inbox.drain() {
+                    controller.event_schedule_sender.blocking_send(rmo_event).expect("Event scheduler failed");
+                }
+            }
+            match command_rx.recv_timeout(TimeDuration::from_millis(10)) {
+                Ok(command) => controller.handle(command),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    // Without a prompt nobody can release messages, fall back to stepping through every round
+                    if controller.step_target.is_none() {
+                        controller.step_target = Some(StepTarget::Round(u32::MAX));
+                    }
+                    thread::sleep(TimeDuration::from_millis(10));
+                }
+            }
+        }
+    }
+
+    /// Read commands from stdin, one per line
+    fn prompt(command_tx: STDSender<InteractiveCommand>) {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) if line.trim().is_empty() => {}
+                Ok(line) => match InteractiveCommand::parse(&line) {
+                    Ok(command) => if command_tx.send(command).is_err() { return },
+                    Err(err) => println!("{}", err),
+                },
+                Err(err) => {
+                    error!("Failed reading command: {}", err);
+                    return;
+                }
+            }
+        }
+        error!("Stdin closed, messages are delivered by priority from now on");
+    }
+}
+
+impl Scheduler for InteractiveScheduler {
+    type IndividualPhenotype = PriorityMapPhenotype;
+
+    fn new(
+        collector_sender: STDSender<Box<RippleMessage>>,
+        node_states: Arc<MutexNodeStates>,
+        node_keys: Vec<NodeKeys>,
+        failure_sender: STDSender<Vec<ConsensusPropertyTypes>>,
+    ) -> Self {
+        Self {
+            state: SchedulerState::new(collector_sender, node_states, node_keys, failure_sender)
+        }
+    }
+
+    /// Wait for new messages delivered by peers
+    /// If the network is not stable, immediately relay messages
+    /// Else collect messages in the interactive inbox with the priority of the current individual
+    fn schedule_controller(
+        mut receiver: MeteredReceiver<Event>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        current_individual: Arc<Mutex<Self::IndividualPhenotype>>,
+        node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>
+    {
+        async move {
+            let (run_lock, _run_cvar) = &*run;
+            let (inbox_tx, inbox_rx) = std::sync::mpsc::channel();
+            let (command_tx, command_rx) = std::sync::mpsc::channel();
+            let event_schedule_sender_2 = event_schedule_sender.clone();
+            let run_2 = run.clone();
+            thread::spawn(move || Self::prompt(command_tx));
+            thread::spawn(move || Self::interactive_inbox_controller(inbox_rx, command_rx, run_2, event_schedule_sender_2, node_states));
+            while let Some(event) = receiver.recv().await {
+                let rmo_event = RMOEvent::from(&event);
+                if Self::is_consensus_rmo(&rmo_event.message) {
+                    round_update_sender.send(rmo_event.clone()).await.expect("Round update sender failed");
+                    let running = *run_lock.read().unwrap();
+                    if running {
+                        send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).await.expect("send dependency sender failed");
+                        let priority = match ConsensusMessageType::create_consensus_message_type(&rmo_event.message) {
+                            Some(consensus_message_type) => current_individual.lock().get_priority(&rmo_event.from, &rmo_event.to, &consensus_message_type),
+                            None => 0usize,
+                        };
+                        inbox_tx.send(OrderedRMOEvent::new(rmo_event, priority)).expect("Inbox sender failed");
+                        continue;
+                    }
+                }
+                event_schedule_sender.send(rmo_event).await.expect("Event scheduler failed");
+            }
+            error!("Peer senders failed");
+        }.boxed()
+    }
+
+    fn listen_to_ga(current_individual: Arc<Mutex<Self::IndividualPhenotype>>, ga_receiver: STDReceiver<Self::IndividualPhenotype>, node_states: Arc<MutexNodeStates>) {
+        PriorityScheduler::listen_to_ga(current_individual, ga_receiver, node_states)
+    }
+
+    fn get_state(&self) -> &SchedulerState {
+        &self.state
+    }
+}
+
+/// Run harnesses with equal priorities for all messages until the search budget is exceeded, a human schedules the messages
+pub fn run_interactive<F: ExtendedFitness>(scheduler_sender: STDSender<PriorityMapPhenotype>, scheduler_receiver: STDReceiver<F>, search_budget: Duration) {
+    let start_time = Utc::now();
+    let priorities = vec![0usize; num_genes()];
+    while Utc::now() - start_time < search_budget {
+        scheduler_sender.send(PriorityMapPhenotype::from_genes(&priorities)).expect("Scheduler receiver failed");
+        let fitness = scheduler_receiver.recv().expect("Scheduler sender failed");
+        println!("Harness run finished with fitness {}", fitness);
+    }
+    println!("Exiting: Search budget exceeded.");
+    std::process::exit(0);
+}
+
+#[cfg(test)]
+mod interactive_scheduler_tests {
+    use chrono::Utc;
+    use crate::ga::encoding::delay_encoding::{DelayEncodingType, DROP_DELAY};
+    use crate::ga::genetic_algorithm::ConsensusMessageType;
+    use crate::message_handler::RippleMessageObject;
+    use crate::protos::ripple::{TMStatusChange, TMValidation};
+    use crate::schedule_file::{ScheduleFile, ScheduleKind};
+    use crate::scheduler::interactive_scheduler::{InteractiveCommand, schedule_file_contents, ScheduleAction, ScheduleDecision};
+    use crate::scheduler::RMOEvent;
+    use crate::topology::PeerGraph;
+
+    #[test]
+    fn test_saved_schedule_replays() {
+        let run_start = Utc::now();
+        let mut validation = RMOEvent::default();
+        validation.message = RippleMessageObject::TMValidation(TMValidation::new());
+        validation.from = 1;
+        validation.to = 3;
+        validation.time_in = Utc::now();
+        let mut status_change = validation.clone();
+        status_change.message = RippleMessageObject::TMStatusChange(TMStatusChange::new());
+        let dropped = RMOEvent { from: 2, to: 0, ..validation.clone() };
+        let decisions = vec![
+            ScheduleDecision::new(&validation, &ScheduleAction::Release, run_start),
+            ScheduleDecision::new(&status_change, &ScheduleAction::Delay { ms: 100_000 }, run_start),
+            ScheduleDecision::new(&dropped, &ScheduleAction::Drop, run_start),
+            // The last decision for a message type on a link is replayed
+            ScheduleDecision::new(&validation, &ScheduleAction::Delay { ms: 100_000 }, run_start),
+        ];
+        let schedule_file = ScheduleFile::parse("interactive", &schedule_file_contents(&decisions), 5).unwrap();
+        assert_eq!(schedule_file.kind(), Ok(ScheduleKind::Delay));
+        let peer_graph = PeerGraph::complete(5);
+        let delays = schedule_file.delays(&peer_graph, &DelayEncodingType::Full).unwrap();
+        let gene = |from: usize, to: usize, message_type: ConsensusMessageType| {
+            let k = ConsensusMessageType::VALUES.iter().position(|value| *value == message_type).unwrap();
+            delays[DelayEncodingType::Full.gene_index(from, to, k, &peer_graph)]
+        };
+        assert!((100_000..101_000).contains(&gene(1, 3, ConsensusMessageType::TMValidation)));
+        assert!((100_000..101_000).contains(&gene(1, 3, ConsensusMessageType::TMStatusChange)));
+        assert_eq!(gene(2, 0, ConsensusMessageType::TMValidation), DROP_DELAY);
+        assert_eq!(gene(0, 2, ConsensusMessageType::TMValidation), 0);
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(InteractiveCommand::parse("delay 3 500"), Ok(InteractiveCommand::Delay(3, 500)));
+        assert_eq!(InteractiveCommand::parse("release all"), Ok(InteractiveCommand::ReleaseAll));
+        assert_eq!(InteractiveCommand::parse(" release  4 "), Ok(InteractiveCommand::Release(4)));
+        assert_eq!(InteractiveCommand::parse("save"), Ok(InteractiveCommand::Save(None)));
+        assert!(InteractiveCommand::parse("drop all").is_err());
+        assert!(InteractiveCommand::parse("step").is_err());
+    }
+}
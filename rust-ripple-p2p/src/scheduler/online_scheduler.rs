@@ -0,0 +1,120 @@
+use std::sync::{Arc, RwLock};
+use std::sync::mpsc::{Receiver as STDReceiver, Sender as STDSender};
+use std::time::{Duration, Instant};
+use log::{debug, error};
+use parking_lot::{Condvar, Mutex};
+use futures::future::{BoxFuture, FutureExt};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use crate::collector::RippleMessage;
+use crate::failure_writer::ConsensusPropertyTypes;
+use crate::ga::genetic_algorithm::ConsensusMessageType;
+use crate::node_state::MutexNodeStates;
+use crate::NodeKeys;
+use crate::online_learning::{OnlinePolicy, PolicyState};
+use crate::scheduler::{Event, RMOEvent, Scheduler, SchedulerState};
+use crate::scheduler::delay_scheduler::ScheduledEvent;
+use crate::scheduler::pipeline::{MeteredReceiver, MeteredSender};
+
+/// Scheduler that asks a learned policy for the delay of every consensus message during a harness run
+pub struct OnlineScheduler {
+    state: SchedulerState,
+}
+
+/// The delivery times of the delayed messages to every node
+#[derive(Debug, Default)]
+struct InFlight {
+    deadlines: Vec<Vec<Instant>>,
+}
+
+impl InFlight {
+    fn add(&mut self, to: usize, ms: u64) {
+        if self.deadlines.len() <= to {
+            self.deadlines.resize(to + 1, vec![]);
+        }
+        self.deadlines[to].push(Instant::now() + Duration::from_millis(ms));
+    }
+
+    fn count(&mut self, to: usize) -> usize {
+        let now = Instant::now();
+        match self.deadlines.get_mut(to) {
+            Some(deadlines) => {
+                deadlines.retain(|deadline| *deadline > now);
+                deadlines.len()
+            }
+            None => 0,
+        }
+    }
+}
+
+impl Scheduler for OnlineScheduler {
+    type IndividualPhenotype = OnlinePolicy;
+
+    fn new(
+        collector_sender: STDSender<Box<RippleMessage>>,
+        node_states: Arc<MutexNodeStates>,
+        node_keys: Vec<NodeKeys>,
+        failure_sender: STDSender<Vec<ConsensusPropertyTypes>>,
+    ) -> Self {
+        Self {
+            state: SchedulerState::new(collector_sender, node_states, node_keys, failure_sender)
+        }
+    }
+
+    fn schedule_controller(
+        mut receiver: MeteredReceiver<Event>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        current_individual: Arc<Mutex<Self::IndividualPhenotype>>,
+        node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>
+    {
+        async move {
+            let (run_lock, _run_cvar) = &*run;
+            let mut rng = ChaCha8Rng::from_entropy();
+            let mut in_flight = InFlight::default();
+            while let Some(event) = receiver.recv().await {
+                let rmo_event = RMOEvent::from(&event);
+                if Self::is_consensus_rmo(&rmo_event.message) {
+                    round_update_sender.send(rmo_event.clone()).await.expect("Round update sender failed");
+                    let running = *run_lock.read().unwrap();
+                    if running {
+                        send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).await.expect("send dependency sender failed");
+                        if let Some(consensus_message_type) = ConsensusMessageType::create_consensus_message_type(&rmo_event.message) {
+                            let round_offset = node_states.get_current_round(rmo_event.to).saturating_sub(node_states.min_current_round());
+                            let state = PolicyState::new(consensus_message_type, node_states.get_consensus_phase(rmo_event.to), round_offset, in_flight.count(rmo_event.to));
+                            let policy = current_individual.lock().clone();
+                            let ms = policy.choose(state, &mut rng).unwrap_or(0) as u64;
+                            if ms > 0 {
+                                in_flight.add(rmo_event.to, ms);
+                                ScheduledEvent::schedule_execution(rmo_event, ms, event_schedule_sender.clone());
+                                continue;
+                            }
+                        }
+                    }
+                }
+                event_schedule_sender.send(rmo_event).await.expect("Event schedule sender failed");
+            }
+            error!("Peer senders failed");
+        }.boxed()
+    }
+
+    fn listen_to_ga(current_individual: Arc<Mutex<Self::IndividualPhenotype>>, ga_receiver: STDReceiver<Self::IndividualPhenotype>, node_states: Arc<MutexNodeStates>) {
+        loop {
+            match ga_receiver.recv() {
+                Ok(policy) => {
+                    node_states.set_current_individual(format!("online policy with exploration rate {}", policy.exploration_rate));
+                    *current_individual.lock() = policy;
+                    debug!("New policy received");
+                },
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn get_state(&self) -> &SchedulerState {
+        &self.state
+    }
+}
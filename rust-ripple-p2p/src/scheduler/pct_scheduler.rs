@@ -0,0 +1,127 @@
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{Receiver as STDReceiver, Sender as STDSender};
+use std::thread;
+use std::time::Duration;
+use itertools::Itertools;
+use log::{debug, error};
+use parking_lot::{Condvar, Mutex};
+use futures::future::{BoxFuture, FutureExt};
+use crate::collector::RippleMessage;
+use crate::failure_writer::ConsensusPropertyTypes;
+use crate::node_state::MutexNodeStates;
+use crate::NodeKeys;
+use crate::pct::{PctRun, PctSchedule};
+use crate::scheduler::{Event, RMOEvent, Scheduler, SchedulerState};
+use crate::scheduler::pipeline::{MeteredReceiver, MeteredSender};
+
+/// Probabilistic concurrency testing: consensus messages are delivered by the priority of their receiver,
+/// which changes at the change points of the schedule
+pub struct PctScheduler {
+    state: SchedulerState,
+}
+
+impl PctScheduler {
+    /// Deliver the pending message chosen by the PCT schedule every step_ms during a harness run
+    fn pct_controller(
+        inbox_rx: STDReceiver<RMOEvent>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        current_individual: Arc<Mutex<PctSchedule>>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+    ) {
+        let (run_lock, _run_cvar) = &*run;
+        let mut pending: Vec<RMOEvent> = vec![];
+        let mut schedule = PctSchedule::default();
+        let mut pct_run = PctRun::default();
+        let mut was_running = false;
+        loop {
+            while let Ok(rmo_event) = inbox_rx.try_recv() {
+                pending.push(rmo_event);
+            }
+            let running = *run_lock.read().unwrap();
+            if running && !was_running {
+                schedule = current_individual.lock().clone();
+                schedule.delivered.store(0, Ordering::SeqCst);
+                pct_run = PctRun::new(&schedule);
+            }
+            was_running = running;
+            if running {
+                let receivers = pending.iter().map(|rmo_event| rmo_event.to).collect_vec();
+                if let Some(index) = pct_run.next(&receivers) {
+                    schedule.delivered.fetch_add(1, Ordering::SeqCst);
+                    event_schedule_sender.blocking_send(pending.remove(index)).expect("Event scheduler failed");
+                }
+            } else {
+                for rmo_event in pending.drain(..) {
+                    event_schedule_sender.blocking_send(rmo_event).expect("Event scheduler failed");
+                }
+            }
+            thread::sleep(Duration::from_millis(schedule.step_ms.max(1)));
+        }
+    }
+}
+
+impl Scheduler for PctScheduler {
+    type IndividualPhenotype = PctSchedule;
+
+    fn new(
+        collector_sender: STDSender<Box<RippleMessage>>,
+        node_states: Arc<MutexNodeStates>,
+        node_keys: Vec<NodeKeys>,
+        failure_sender: STDSender<Vec<ConsensusPropertyTypes>>,
+    ) -> Self {
+        Self {
+            state: SchedulerState::new(collector_sender, node_states, node_keys, failure_sender)
+        }
+    }
+
+    fn schedule_controller(
+        mut receiver: MeteredReceiver<Event>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        current_individual: Arc<Mutex<Self::IndividualPhenotype>>,
+        _node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>
+    {
+        async move {
+            let (run_lock, _run_cvar) = &*run;
+            let (inbox_tx, inbox_rx) = std::sync::mpsc::channel();
+            let event_schedule_sender_2 = event_schedule_sender.clone();
+            let run_2 = run.clone();
+            thread::spawn(move || Self::pct_controller(inbox_rx, run_2, current_individual, event_schedule_sender_2));
+            while let Some(event) = receiver.recv().await {
+                let rmo_event = RMOEvent::from(&event);
+                if Self::is_consensus_rmo(&rmo_event.message) {
+                    round_update_sender.send(rmo_event.clone()).await.expect("Round update sender failed");
+                    let running = *run_lock.read().unwrap();
+                    if running {
+                        send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).await.expect("send dependency sender failed");
+                        inbox_tx.send(rmo_event).expect("Inbox sender failed");
+                        continue;
+                    }
+                }
+                event_schedule_sender.send(rmo_event).await.expect("Event scheduler failed");
+            }
+            error!("Peer senders failed");
+        }.boxed()
+    }
+
+    fn listen_to_ga(current_individual: Arc<Mutex<Self::IndividualPhenotype>>, ga_receiver: STDReceiver<Self::IndividualPhenotype>, node_states: Arc<MutexNodeStates>) {
+        loop {
+            match ga_receiver.recv() {
+                Ok(schedule) => {
+                    node_states.set_current_individual(format!("node priorities: {:?}, change points: {:?}", schedule.node_priorities, schedule.change_points));
+                    *current_individual.lock() = schedule;
+                    debug!("New PCT schedule received");
+                },
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn get_state(&self) -> &SchedulerState {
+        &self.state
+    }
+}
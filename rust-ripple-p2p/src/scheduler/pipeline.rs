@@ -0,0 +1,524 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use byteorder::{BigEndian, ByteOrder};
+use chrono::{DateTime, Utc};
+use log::error;
+use parking_lot::Mutex;
+use tokio::sync::mpsc::{Receiver as TokioReceiver, Sender as TokioSender};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use crate::collector::RippleMessage;
+use crate::LOG_FOLDER;
+use crate::message_handler::{parse_protocol_message, RippleMessageObject};
+use crate::network_model::NetworkModel;
+use crate::scheduler::{Event, Frame, P2PConnections, PeerChannel, RMOEvent};
+use crate::scheduler::dispatcher::DelayedSender;
+
+/// Capacity of the queue between the peers and the schedule controller
+pub const SCHEDULER_CAPACITY: usize = 1024;
+/// Capacity of the queue between the schedulers and the delivery to peers
+pub const DELIVERY_CAPACITY: usize = 1024;
+/// Capacity of the queue between the delivery to peers and the consensus property checker
+pub const PROPERTY_CHECK_CAPACITY: usize = 1024;
+/// Capacity of the queues between the schedule controller and the round and send dependency updates of the node states
+pub const NODE_STATE_CAPACITY: usize = 1024;
+/// Capacity of the queue of frames waiting for the network model on a single fifo link
+pub const LINK_CAPACITY: usize = 256;
+/// The time between two samples written to pipeline_metrics.txt
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Identifies the pipelines of different clusters in pipeline_metrics.txt
+static NEXT_PIPELINE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Counters of a bounded queue
+/// full_sends: The number of sends that had to wait for space, i.e. the times backpressure was applied
+#[derive(Debug, Default)]
+pub struct QueueMetrics {
+    pub name: String,
+    pub capacity: usize,
+    depth: AtomicUsize,
+    max_depth: AtomicUsize,
+    sent: AtomicU64,
+    full_sends: AtomicU64,
+}
+
+impl QueueMetrics {
+    fn pushed(&self) {
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_depth.fetch_max(depth, Ordering::SeqCst);
+        self.sent.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn popped(&self) {
+        self.depth.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn unpushed(&self) {
+        self.popped();
+        self.sent.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// The current counters, the maximum depth starts over from the current depth
+    fn sample(&self) -> QueueSample {
+        let depth = self.depth.load(Ordering::SeqCst);
+        QueueSample {
+            name: self.name.clone(),
+            capacity: self.capacity,
+            depth,
+            max_depth: self.max_depth.swap(depth, Ordering::SeqCst),
+            sent: self.sent.load(Ordering::SeqCst),
+            full_sends: self.full_sends.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// A bounded tokio channel that keeps track of its depth
+pub fn metered_channel<T>(name: &str, capacity: usize, metrics: &PipelineMetrics) -> (MeteredSender<T>, MeteredReceiver<T>) {
+    let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+    let queue = Arc::new(QueueMetrics { name: name.to_string(), capacity, ..QueueMetrics::default() });
+    metrics.queues.lock().push(queue.clone());
+    (MeteredSender { sender, queue: queue.clone() }, MeteredReceiver { receiver, queue })
+}
+
+pub struct MeteredSender<T> {
+    sender: TokioSender<T>,
+    queue: Arc<QueueMetrics>,
+}
+
+impl<T> Clone for MeteredSender<T> {
+    fn clone(&self) -> Self {
+        Self { sender: self.sender.clone(), queue: self.queue.clone() }
+    }
+}
+
+impl<T> MeteredSender<T> {
+    /// Send the item, waiting for space if the queue is full. Returns the item if the receiver hung up
+    pub async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        // Counted before sending, so the receiver never sees a negative depth
+        self.queue.pushed();
+        let sent = match self.sender.try_send(item) {
+            Ok(_) => Ok(()),
+            Err(TrySendError::Full(item)) => {
+                self.queue.full_sends.fetch_add(1, Ordering::SeqCst);
+                self.sender.send(item).await
+            }
+            Err(TrySendError::Closed(item)) => Err(SendError(item)),
+        };
+        if sent.is_err() {
+            self.queue.unpushed();
+        }
+        sent
+    }
+
+    /// Send from a thread outside the runtime, blocking the thread while the queue is full
+    pub fn blocking_send(&self, item: T) -> Result<(), SendError<T>> {
+        self.queue.pushed();
+        let sent = match self.sender.try_send(item) {
+            Ok(_) => Ok(()),
+            Err(TrySendError::Full(item)) => {
+                self.queue.full_sends.fetch_add(1, Ordering::SeqCst);
+                self.sender.blocking_send(item)
+            }
+            Err(TrySendError::Closed(item)) => Err(SendError(item)),
+        };
+        if sent.is_err() {
+            self.queue.unpushed();
+        }
+        sent
+    }
+}
+
+/// Delayed messages wait for space in the queue on the dispatcher thread
+impl<T: Send + 'static> DelayedSender<T> for MeteredSender<T> {
+    fn send_delayed(&self, item: T) -> bool {
+        self.blocking_send(item).is_ok()
+    }
+}
+
+pub struct MeteredReceiver<T> {
+    receiver: TokioReceiver<T>,
+    queue: Arc<QueueMetrics>,
+}
+
+impl<T> MeteredReceiver<T> {
+    pub async fn recv(&mut self) -> Option<T> {
+        let item = self.receiver.recv().await;
+        if item.is_some() {
+            self.queue.popped();
+        }
+        item
+    }
+}
+
+/// Sample of a queue as written to pipeline_metrics.txt
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct QueueSample {
+    pub name: String,
+    pub capacity: usize,
+    pub depth: usize,
+    pub max_depth: usize,
+    pub sent: u64,
+    pub full_sends: u64,
+}
+
+/// A line of pipeline_metrics.txt
+/// proxy latency: The time between the proxy receiving a message and handing it to the receiving peer or the network model, over the last interval
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PipelineSample {
+    pub pipeline: usize,
+    pub time: DateTime<Utc>,
+    pub queues: Vec<QueueSample>,
+    pub delivered: u64,
+    pub mean_proxy_latency_ms: f64,
+    pub max_proxy_latency_ms: f64,
+}
+
+/// Queue depths and proxy latency of a scheduler pipeline
+#[derive(Debug)]
+pub struct PipelineMetrics {
+    pub id: usize,
+    queues: Mutex<Vec<Arc<QueueMetrics>>>,
+    delivered: AtomicU64,
+    latency_count: AtomicU64,
+    latency_total_us: AtomicU64,
+    latency_max_us: AtomicU64,
+}
+
+impl PipelineMetrics {
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_PIPELINE_ID.fetch_add(1, Ordering::SeqCst),
+            queues: Mutex::new(vec![]),
+            delivered: AtomicU64::new(0),
+            latency_count: AtomicU64::new(0),
+            latency_total_us: AtomicU64::new(0),
+            latency_max_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Record the proxy latency of a delivered message, delayed messages include their delay
+    pub fn delivered(&self, latency: chrono::Duration) {
+        let us = latency.num_microseconds().unwrap_or(i64::MAX).max(0) as u64;
+        self.delivered.fetch_add(1, Ordering::SeqCst);
+        self.latency_count.fetch_add(1, Ordering::SeqCst);
+        self.latency_total_us.fetch_add(us, Ordering::SeqCst);
+        self.latency_max_us.fetch_max(us, Ordering::SeqCst);
+    }
+
+    /// The counters of all queues, the latencies start over
+    pub fn sample(&self) -> PipelineSample {
+        let count = self.latency_count.swap(0, Ordering::SeqCst);
+        let total_us = self.latency_total_us.swap(0, Ordering::SeqCst);
+        let max_us = self.latency_max_us.swap(0, Ordering::SeqCst);
+        PipelineSample {
+            pipeline: self.id,
+            time: Utc::now(),
+            queues: self.queues.lock().iter().map(|queue| queue.sample()).collect(),
+            delivered: self.delivered.load(Ordering::SeqCst),
+            mean_proxy_latency_ms: if count == 0 { 0.0 } else { total_us as f64 / count as f64 / 1000.0 },
+            max_proxy_latency_ms: max_us as f64 / 1000.0,
+        }
+    }
+
+    /// Append a sample to pipeline_metrics.txt every interval
+    pub async fn report(self: Arc<Self>, interval: Duration) {
+        let path = format!("{}/pipeline_metrics.txt", *LOG_FOLDER);
+        let mut file = match OpenOptions::new().create(true).append(true).open(Path::new(&path)) {
+            Ok(file) => file,
+            Err(err) => {
+                error!("Failed opening {}: {}", path, err);
+                return;
+            }
+        };
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let mut line = serde_json::to_vec(&self.sample()).expect("Serializing pipeline sample failed");
+            line.push(b'\n');
+            if let Err(err) = file.write_all(&line) {
+                error!("Failed writing to {}: {}", path, err);
+            }
+        }
+    }
+}
+
+/// Send the consensus messages of the peers to the schedule controller and pass the other frames on undecoded
+pub async fn route_events(
+    mut peer_receiver: TokioReceiver<Event>,
+    scheduler_sender: MeteredSender<Event>,
+    pass_through_sender: MeteredSender<(Event, DateTime<Utc>)>,
+) {
+    while let Some(event) = peer_receiver.recv().await {
+        let routed = if event.is_consensus() {
+            scheduler_sender.send(event).await.is_ok()
+        } else {
+            pass_through_sender.send((event, Utc::now())).await.is_ok()
+        };
+        if !routed {
+            error!("Scheduler or delivery hung up");
+            return;
+        }
+    }
+    error!("Peer senders failed");
+}
+
+/// A delivered message on its way to the consensus property checker
+pub enum Delivered {
+    /// A message the scheduler decoded
+    Decoded(Box<RippleMessage>),
+    /// A frame that was passed on, decoded when it is checked
+    Raw { from: usize, to: usize, delay: chrono::Duration, timestamp: DateTime<Utc>, frame: Frame },
+}
+
+impl Delivered {
+    pub fn into_ripple_message(self) -> Box<RippleMessage> {
+        match self {
+            Delivered::Decoded(message) => message,
+            Delivered::Raw { from, to, delay, timestamp, frame } => {
+                let message = parse_protocol_message(BigEndian::read_u16(&frame[4..6]), &frame[6..]);
+                RippleMessage::new(format!("Ripple{}", from + 1), format!("Ripple{}", to + 1), delay, timestamp, message)
+            }
+        }
+    }
+}
+
+/// A frame on its way to the receiving peer
+/// message: The message the scheduler decoded, None for frames that were passed on
+pub struct Handoff {
+    pub from: usize,
+    pub to: usize,
+    pub time_in: DateTime<Utc>,
+    pub frame: Frame,
+    pub message: Option<RippleMessageObject>,
+}
+
+impl Handoff {
+    /// The delivery as the property checker sees it, when the peer got the frame at time now
+    fn into_delivered(self, now: DateTime<Utc>) -> Delivered {
+        let Handoff { from, to, time_in, frame, message } = self;
+        match message {
+            Some(message) => Delivered::Decoded(RippleMessage::new(format!("Ripple{}", from + 1), format!("Ripple{}", to + 1), now - time_in, now, message)),
+            None => Delivered::Raw { from, to, delay: now - time_in, timestamp: now, frame },
+        }
+    }
+}
+
+/// Records a frame as delivered once it is handed to the peer, so the property checker only sees frames the peer has
+#[derive(Clone)]
+pub struct DeliveryRecorder {
+    property_check_sender: MeteredSender<Delivered>,
+    metrics: Arc<PipelineMetrics>,
+}
+
+impl DeliveryRecorder {
+    pub fn new(property_check_sender: MeteredSender<Delivered>, metrics: Arc<PipelineMetrics>) -> Self {
+        Self { property_check_sender, metrics }
+    }
+
+    /// Returns false if the property checker hung up
+    async fn hand_off(&self, peer_channel: &PeerChannel, handoff: Handoff) -> bool {
+        peer_channel.send_frame_async(handoff.frame.clone()).await;
+        let now = Utc::now();
+        self.metrics.delivered(now - handoff.time_in);
+        if self.property_check_sender.send(handoff.into_delivered(now)).await.is_err() {
+            error!("Consensus property checker hung up");
+            return false;
+        }
+        true
+    }
+}
+
+/// Hands frames to the receiving peers, after the delay of the network model if there is one
+/// Frames on a fifo link wait in the bounded queue of the link, a link that falls behind holds up the delivery loop once its queue is full
+pub struct LinkDelivery {
+    p2p_connections: P2PConnections,
+    network_model: Option<NetworkModel>,
+    recorder: DeliveryRecorder,
+    link_senders: HashMap<(usize, usize), MeteredSender<(Instant, Handoff)>>,
+}
+
+impl LinkDelivery {
+    pub fn new(p2p_connections: P2PConnections, network_model: Option<NetworkModel>, recorder: DeliveryRecorder) -> Self {
+        Self { p2p_connections, network_model, recorder, link_senders: HashMap::new() }
+    }
+
+    fn peer_channel(&self, from: usize, to: usize) -> &PeerChannel {
+        self.p2p_connections.get(&to).unwrap().get(&from).unwrap()
+    }
+
+    /// Returns false if the property checker hung up, frames that wait for the network model report that themselves
+    pub async fn deliver(&mut self, handoff: Handoff) -> bool {
+        let (from, to) = (handoff.from, handoff.to);
+        let (arrival, fifo) = match &mut self.network_model {
+            None => return self.recorder.hand_off(self.peer_channel(from, to), handoff).await,
+            Some(network_model) => (network_model.arrival(from, to, handoff.frame.len(), Instant::now()), network_model.is_fifo(from, to)),
+        };
+        if fifo {
+            if !self.link_senders.contains_key(&(from, to)) {
+                let name = format!("link_{}_{}", from, to);
+                let (link_sender, link_receiver) = metered_channel(&name, LINK_CAPACITY, &self.recorder.metrics);
+                tokio::spawn(deliver_on_link(link_receiver, self.peer_channel(from, to).clone(), self.recorder.clone()));
+                self.link_senders.insert((from, to), link_sender);
+            }
+            if self.link_senders[&(from, to)].send((arrival, handoff)).await.is_err() {
+                error!("Link {} -> {} hung up", from, to);
+            }
+        } else {
+            let peer_channel = self.peer_channel(from, to).clone();
+            let recorder = self.recorder.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep_until(arrival.into()).await;
+                recorder.hand_off(&peer_channel, handoff).await;
+            });
+        }
+        true
+    }
+}
+
+/// Frames on a fifo link arrive in the order they were sent, so the link only has to wait for the first frame in its queue
+async fn deliver_on_link(mut link_receiver: MeteredReceiver<(Instant, Handoff)>, peer_channel: PeerChannel, recorder: DeliveryRecorder) {
+    while let Some((arrival, handoff)) = link_receiver.recv().await {
+        tokio::time::sleep_until(arrival.into()).await;
+        if !recorder.hand_off(&peer_channel, handoff).await {
+            return;
+        }
+    }
+}
+
+/// Deliver events to the receiving peers, which pass them on to the consensus property checker once the peer has them
+/// Waits when the peer channel or the property checker queue is full, instead of letting messages pile up
+pub async fn deliver_events(
+    mut delivery_receiver: MeteredReceiver<RMOEvent>,
+    mut pass_through_receiver: MeteredReceiver<(Event, DateTime<Utc>)>,
+    mut links: LinkDelivery,
+) {
+    loop {
+        let handoff = tokio::select! {
+            Some(event) = delivery_receiver.recv() => {
+                let (from, to, time_in, message) = (event.from, event.to, event.time_in, event.message.clone());
+                Handoff { from, to, time_in, frame: Event::from(event).message, message: Some(message) }
+            }
+            Some((event, time_in)) = pass_through_receiver.recv() => {
+                Handoff { from: event.from, to: event.to, time_in, frame: event.message, message: None }
+            }
+            else => break,
+        };
+        if !links.deliver(handoff).await {
+            return;
+        }
+    }
+    error!("Delivery sender failed");
+}
+
+#[cfg(test)]
+mod pipeline_tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use chrono::Utc;
+    use crate::message_handler::{parse_protocol_message, rmo_to_bytes, write_message};
+    use crate::protos::ripple::{TMPing, TMPing_pingType, TMTransaction, TransactionStatus};
+    use crate::scheduler::{Event, Frame, PeerChannel};
+    use crate::scheduler::pipeline::{deliver_on_link, Delivered, DeliveryRecorder, Handoff, metered_channel, PipelineMetrics, route_events};
+
+    fn ping_frame() -> Frame {
+        let mut ping = TMPing::new();
+        ping.set_field_type(TMPing_pingType::ptPING);
+        Arc::new(write_message(3, ping))
+    }
+
+    fn transaction_frame() -> Frame {
+        let mut transaction = TMTransaction::new();
+        transaction.set_rawTransaction(vec![7; 200]);
+        transaction.set_status(TransactionStatus::tsNEW);
+        Arc::new(write_message(30, transaction))
+    }
+
+    #[tokio::test]
+    async fn test_route_events() {
+        let metrics = PipelineMetrics::new();
+        let (peer_sender, peer_receiver) = tokio::sync::mpsc::channel(4);
+        let (scheduler_sender, mut scheduler_receiver) = metered_channel("scheduler", 4, &metrics);
+        let (pass_through_sender, mut pass_through_receiver) = metered_channel("pass_through", 4, &metrics);
+        tokio::spawn(route_events(peer_receiver, scheduler_sender, pass_through_sender));
+        let ping = ping_frame();
+        peer_sender.send(Event { from: 0, to: 1, message: ping.clone() }).await.unwrap();
+        peer_sender.send(Event { from: 1, to: 0, message: transaction_frame() }).await.unwrap();
+        assert_eq!(scheduler_receiver.recv().await.unwrap().message_type(), 30);
+        // The ping is passed on without copying the frame
+        let (passed, _) = pass_through_receiver.recv().await.unwrap();
+        assert!(Arc::ptr_eq(&passed.message, &ping));
+    }
+
+    /// Compares decoding and re-serializing every message with routing on the header and decoding only consensus messages
+    /// Run with: cargo test --release benchmark_frame_routing -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark_frame_routing() {
+        // Roughly the mix on a quiet network, most traffic is not scheduled
+        let frames = (0..100_000).map(|i| if i % 4 == 0 { transaction_frame() } else { ping_frame() }).collect::<Vec<_>>();
+        let start = Instant::now();
+        let mut bytes = 0;
+        for frame in &frames {
+            let message = parse_protocol_message(u16::from_be_bytes([frame[4], frame[5]]), &frame[6..]);
+            bytes += rmo_to_bytes(message).len();
+        }
+        let full = start.elapsed();
+        let start = Instant::now();
+        let mut forwarded = 0;
+        for frame in &frames {
+            let event = Event { from: 0, to: 1, message: frame.clone() };
+            if event.is_consensus() {
+                let _message = parse_protocol_message(event.message_type(), &event.message[6..]);
+            }
+            forwarded += event.message.len();
+        }
+        let routed = start.elapsed();
+        assert_eq!(bytes, forwarded);
+        println!("decode and re-serialize: {:.0} msg/s", frames.len() as f64 / full.as_secs_f64());
+        println!("route on header: {:.0} msg/s", frames.len() as f64 / routed.as_secs_f64());
+    }
+
+    #[tokio::test]
+    async fn test_queue_metrics() {
+        let metrics = PipelineMetrics::new();
+        let (sender, mut receiver) = metered_channel::<usize>("test", 2, &metrics);
+        assert!(sender.send(1).await.is_ok());
+        assert!(sender.send(2).await.is_ok());
+        let sample = metrics.sample();
+        assert_eq!((sample.queues[0].depth, sample.queues[0].max_depth, sample.queues[0].sent), (2, 2, 2));
+        // The third send waits until there is space
+        let waiting_sender = sender.clone();
+        let waiting = tokio::spawn(async move { waiting_sender.send(3).await });
+        tokio::task::yield_now().await;
+        assert_eq!(receiver.recv().await, Some(1));
+        assert!(waiting.await.unwrap().is_ok());
+        assert_eq!(receiver.recv().await, Some(2));
+        // A waiting send counts towards the depth
+        let sample = metrics.sample();
+        assert_eq!((sample.queues[0].depth, sample.queues[0].max_depth, sample.queues[0].full_sends), (1, 3, 1));
+        // The maximum depth starts over after a sample
+        assert_eq!(metrics.sample().queues[0].max_depth, 1);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_recorded_at_hand_off() {
+        let metrics = Arc::new(PipelineMetrics::new());
+        let (property_check_sender, mut property_check_receiver) = metered_channel("property_checks", 4, &metrics);
+        let (peer_sender, mut peer_receiver) = tokio::sync::mpsc::channel(4);
+        let (link_sender, link_receiver) = metered_channel("link_0_1", 4, &metrics);
+        tokio::spawn(deliver_on_link(link_receiver, PeerChannel::new(peer_sender), DeliveryRecorder::new(property_check_sender, metrics.clone())));
+        let time_in = Utc::now();
+        let handoff = Handoff { from: 0, to: 1, time_in, frame: ping_frame(), message: None };
+        link_sender.send((Instant::now() + Duration::from_millis(50), handoff)).await.unwrap();
+        // The property checker does not see the frame while it is still on the link
+        assert!(tokio::time::timeout(Duration::from_millis(10), property_check_receiver.recv()).await.is_err());
+        let delivered = property_check_receiver.recv().await.unwrap();
+        assert!(peer_receiver.try_recv().is_ok());
+        assert!(matches!(delivered, Delivered::Raw { delay, .. } if delay >= chrono::Duration::milliseconds(50)));
+        assert_eq!(metrics.sample().delivered, 1);
+    }
+}
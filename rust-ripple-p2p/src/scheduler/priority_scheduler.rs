@@ -1,12 +1,11 @@
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, RwLock};
 use std::sync::mpsc::{Receiver as STDReceiver, Sender as STDSender};
 use std::thread;
-use hashbrown::hash_map::DefaultHashBuilder;
 use log::{debug, error, trace};
 use parking_lot::{Condvar, Mutex};
-use priority_queue::priority_queue::PriorityQueue;
-use tokio::sync::mpsc::Receiver as TokioReceiver;
+use futures::future::{BoxFuture, FutureExt};
 use spin_sleep::SpinSleeper;
 use crate::collector::RippleMessage;
 use crate::failure_writer::ConsensusPropertyTypes;
@@ -16,25 +15,83 @@ use crate::ga::encoding::priority_encoding::{PriorityMapPhenotype};
 use crate::node_state::MutexNodeStates;
 use crate::NodeKeys;
 use crate::scheduler::{Event, RMOEvent, Scheduler, SchedulerState};
+use crate::scheduler::pipeline::{MeteredReceiver, MeteredSender};
 
 pub struct PriorityScheduler {
     state: SchedulerState,
 }
 
+/// The messages held back during a harness run, released highest priority first and oldest first on ties
+/// Every message gets an id, so it can also be taken out of order
+#[derive(Debug, Default)]
+pub struct PriorityInbox {
+    events: BTreeMap<(Reverse<usize>, usize), RMOEvent>,
+    priorities: HashMap<usize, usize>,
+    next_id: usize,
+}
+
+impl PriorityInbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, rmo_event: RMOEvent, priority: usize) -> usize {
+        let id = self.next_id;
+        self.events.insert((Reverse(priority), id), rmo_event);
+        self.priorities.insert(id, priority);
+        self.next_id += 1;
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn take(&mut self, id: usize) -> Option<RMOEvent> {
+        let priority = self.priorities.remove(&id)?;
+        self.events.remove(&(Reverse(priority), id))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&RMOEvent> {
+        let priority = self.priorities.get(&id)?;
+        self.events.get(&(Reverse(*priority), id))
+    }
+
+    /// Pending messages with their id and priority in release order
+    pub fn pending(&self) -> Vec<(usize, &RMOEvent, usize)> {
+        self.events.iter().map(|((Reverse(priority), id), rmo_event)| (*id, rmo_event, *priority)).collect()
+    }
+
+    /// Remove the message that is released next
+    pub fn pop_highest(&mut self) -> Option<(usize, RMOEvent)> {
+        let (_, id) = *self.events.keys().next()?;
+        self.take(id).map(|rmo_event| (id, rmo_event))
+    }
+
+    pub fn drain(&mut self) -> Vec<RMOEvent> {
+        self.priorities.clear();
+        std::mem::take(&mut self.events).into_values().collect()
+    }
+}
+
 impl PriorityScheduler {
     /// Execute events every 1 / rate seconds based on size of the inbox.
     /// Do we have a target size of the inbox? ~30 (10% of the different types of events maybe?)
     /// How to determine base rate -> Compare capability to delay scheduling
     /// If inbox reaches 150% of desired capacity, increase rate (decrease t) by 10%? t / 1.1
     /// If inbox reaches 50% of desired capacity, decrease rate (increase t) by 10%? t * 1.1
-    fn inbox_controller(
+    pub(crate) fn inbox_controller(
         inbox_rx: STDReceiver<OrderedRMOEvent>,
         run: Arc<(RwLock<bool>, Condvar)>,
-        event_schedule_sender: STDSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
     ) {
         let (run_lock, _run_cvar) = &*run;
         let sleeper = SpinSleeper::default();
-        let mut inbox = PriorityQueue::<RMOEvent, usize, DefaultHashBuilder>::with_default_hasher();
+        let mut inbox = PriorityInbox::new();
         let mut rate = 0.5 * num_genes() as f64; // Rate at which events are executed from the queue. Base rate of num_genes / second -> too low?
         let target_inbox_size = 0.2 * num_genes() as f64; // Target inbox size of x% of the events mapped -> higher?
         let sensitivity_ratio = 1.01; // Change rate by s at a time
@@ -55,14 +112,13 @@ impl PriorityScheduler {
                     rate = (rate / sensitivity_ratio).max(num_genes() as f64 / 6f64);
                 }
                 // Execute event with highest priority
-                if inbox_size > 0 {
-                    let rmo_event = inbox.pop().unwrap().0;
-                    event_schedule_sender.send(rmo_event).expect("Event scheduler failed");
+                if let Some((_, rmo_event)) = inbox.pop_highest() {
+                    event_schedule_sender.blocking_send(rmo_event).expect("Event scheduler failed");
                 }
             } else {
-                while let Some((event, _)) = inbox.pop() {
+                for event in inbox.drain() {
                     trace!("Emptying inbox");
-                    event_schedule_sender.send(event).expect("Event scheduler failed");
+                    event_schedule_sender.blocking_send(event).expect("Event scheduler failed");
                 }
             }
             // We sleep for 1 / rate seconds
@@ -90,45 +146,45 @@ impl Scheduler for PriorityScheduler {
     /// If the network is not stable, immediately relay messages
     /// Else collect messages in inbox and schedule based on priority
     fn schedule_controller(
-        mut receiver: TokioReceiver<Event>,
+        mut receiver: MeteredReceiver<Event>,
         run: Arc<(RwLock<bool>, Condvar)>,
         current_individual: Arc<Mutex<Self::IndividualPhenotype>>,
-        round_update_sender: STDSender<RMOEvent>,
-        event_schedule_sender: STDSender<RMOEvent>,
-        send_dependency_sender: STDSender<RippleMessage>,
-    )
+        _node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>
     {
-        let (run_lock, _run_cvar) = &*run;
-        let (inbox_tx, inbox_rx) = std::sync::mpsc::channel();
-        let event_schedule_sender_2 = event_schedule_sender.clone();
-        let run_2 = run.clone();
-        thread::spawn(move || Self::inbox_controller(inbox_rx, run_2, event_schedule_sender_2));
-        loop {
-            match receiver.blocking_recv() {
-                Some(event) => {
-                    let rmo_event = RMOEvent::from(&event);
-                    // If the network is ready to apply the test case, collect messages in inbox, else immediately relay
-                    if Self::is_consensus_rmo(&rmo_event.message) {
-                        round_update_sender.send(rmo_event.clone()).expect("Round update sender failed");
-                        if *run_lock.read().unwrap() {
-                            send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).expect("send dependency sender failed");
-                            let consensus_message_type_option = ConsensusMessageType::create_consensus_message_type(&rmo_event.message);
-                            let priority = if let Some(consensus_message_type) = consensus_message_type_option {
-                                current_individual.lock().get_priority(&rmo_event.from, &rmo_event.to, &consensus_message_type)
-                            } else {
-                                0usize
-                            };
-                            inbox_tx.send(OrderedRMOEvent::new(rmo_event, priority)).expect("Inbox sender failed");
+        async move {
+            let (run_lock, _run_cvar) = &*run;
+            let (inbox_tx, inbox_rx) = std::sync::mpsc::channel();
+            let event_schedule_sender_2 = event_schedule_sender.clone();
+            let run_2 = run.clone();
+            thread::spawn(move || Self::inbox_controller(inbox_rx, run_2, event_schedule_sender_2));
+            while let Some(event) = receiver.recv().await {
+                let rmo_event = RMOEvent::from(&event);
+                // If the network is ready to apply the test case, collect messages in inbox, else immediately relay
+                if Self::is_consensus_rmo(&rmo_event.message) {
+                    round_update_sender.send(rmo_event.clone()).await.expect("Round update sender failed");
+                    let running = *run_lock.read().unwrap();
+                    if running {
+                        send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).await.expect("send dependency sender failed");
+                        let consensus_message_type_option = ConsensusMessageType::create_consensus_message_type(&rmo_event.message);
+                        let priority = if let Some(consensus_message_type) = consensus_message_type_option {
+                            current_individual.lock().get_priority(&rmo_event.from, &rmo_event.to, &consensus_message_type)
                         } else {
-                            event_schedule_sender.send(rmo_event).expect("Event scheduler failed");
-                        }
+                            0usize
+                        };
+                        inbox_tx.send(OrderedRMOEvent::new(rmo_event, priority)).expect("Inbox sender failed");
                     } else {
-                        event_schedule_sender.send(rmo_event).expect("Event scheduler failed");
+                        event_schedule_sender.send(rmo_event).await.expect("Event scheduler failed");
                     }
-                },
-                None => error!("Peer senders failed")
+                } else {
+                    event_schedule_sender.send(rmo_event).await.expect("Event scheduler failed");
+                }
             }
-        }
+            error!("Peer senders failed");
+        }.boxed()
     }
 
     fn listen_to_ga(current_individual: Arc<Mutex<Self::IndividualPhenotype>>, ga_receiver: STDReceiver<Self::IndividualPhenotype>, node_states: Arc<MutexNodeStates>) {
@@ -151,8 +207,8 @@ impl Scheduler for PriorityScheduler {
 
 #[derive(Debug, Clone, Hash)]
 pub struct OrderedRMOEvent {
-    rmo_event: RMOEvent,
-    priority: usize,
+    pub(crate) rmo_event: RMOEvent,
+    pub(crate) priority: usize,
 }
 
 impl OrderedRMOEvent {
@@ -187,32 +243,54 @@ impl Ord for OrderedRMOEvent {
 #[cfg(test)]
 mod priority_scheduler_tests {
     use std::sync::{Arc, RwLock};
-    use std::sync::mpsc::channel;
     use std::thread;
     use std::time::Duration;
     use parking_lot::{Condvar};
     use crate::message_handler::RippleMessageObject;
     use crate::protos::ripple::{TMStatusChange, TMValidation};
-    use crate::scheduler::priority_scheduler::{OrderedRMOEvent, PriorityScheduler};
+    use crate::scheduler::pipeline::{metered_channel, PipelineMetrics};
+    use crate::scheduler::priority_scheduler::{OrderedRMOEvent, PriorityInbox, PriorityScheduler};
     use crate::scheduler::RMOEvent;
 
     #[test]
-    fn test_inbox_controller() {
+    fn test_priority_inbox_order() {
+        let mut inbox = PriorityInbox::new();
+        let mut status_change = RMOEvent::default();
+        status_change.message = RippleMessageObject::TMStatusChange(TMStatusChange::new());
+        let mut validation = RMOEvent::default();
+        validation.message = RippleMessageObject::TMValidation(TMValidation::new());
+        assert_eq!(inbox.push(validation.clone(), 1), 0);
+        assert_eq!(inbox.push(status_change.clone(), 2), 1);
+        // Equal messages are both kept
+        assert_eq!(inbox.push(validation.clone(), 2), 2);
+        assert_eq!(inbox.push(validation.clone(), 0), 3);
+        assert_eq!(inbox.pending().iter().map(|(id, _, _)| *id).collect::<Vec<_>>(), vec![1, 2, 0, 3]);
+        assert_eq!(inbox.take(2), Some(validation.clone()));
+        assert_eq!(inbox.take(2), None);
+        assert_eq!(inbox.get(0), Some(&validation));
+        assert_eq!(inbox.pop_highest(), Some((1, status_change)));
+        assert_eq!(inbox.drain(), vec![validation.clone(), validation]);
+        assert!(inbox.is_empty());
+        assert_eq!(inbox.get(0), None);
+    }
+
+    #[tokio::test]
+    async fn test_inbox_controller() {
         let (inbox_tx, inbox_rx) = std::sync::mpsc::channel();
         let run = Arc::new((RwLock::new(false), Condvar::new()));
-        let (event_schedule_sender, event_scheduler_receiver) = channel();
+        let (event_schedule_sender, mut event_scheduler_receiver) = metered_channel("deliveries", 4, &PipelineMetrics::new());
         thread::spawn(move || PriorityScheduler::inbox_controller(inbox_rx, run, event_schedule_sender,));
-        thread::sleep(Duration::from_millis(100));
+        tokio::time::sleep(Duration::from_millis(100)).await;
         let mut rmo_event_size = RMOEvent::default();
         rmo_event_size.message = RippleMessageObject::TMStatusChange(TMStatusChange::new());
         let mut rmo_event_time = RMOEvent::default();
         rmo_event_time.message = RippleMessageObject::TMValidation(TMValidation::new());
         inbox_tx.send(OrderedRMOEvent::new(rmo_event_size.clone(), 2)).unwrap();
         inbox_tx.send(OrderedRMOEvent::new(rmo_event_time.clone(), 1)).unwrap();
-        thread::sleep(Duration::from_millis(100));
-        let res = event_scheduler_receiver.recv();
-        assert_eq!(res, Ok(rmo_event_size));
-        let res = event_scheduler_receiver.recv();
-        assert_eq!(res, Ok(rmo_event_time));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let res = event_scheduler_receiver.recv().await;
+        assert_eq!(res, Some(rmo_event_size));
+        let res = event_scheduler_receiver.recv().await;
+        assert_eq!(res, Some(rmo_event_time));
     }
 }
\ No newline at end of file
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::mpsc::{Receiver as STDReceiver, RecvTimeoutError, Sender as STDSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{debug, error};
+use parking_lot::{Condvar, Mutex};
+use futures::future::{BoxFuture, FutureExt};
+use crate::collector::RippleMessage;
+use crate::failure_writer::ConsensusPropertyTypes;
+use crate::node_state::MutexNodeStates;
+use crate::NodeKeys;
+use crate::scheduler::{Event, RMOEvent, Scheduler, SchedulerState};
+use crate::scheduler::pipeline::{MeteredReceiver, MeteredSender};
+use crate::systematic::{DeliverySchedule, DeliveryStep, MessageKey};
+
+/// Without new messages the next message is delivered after the quiescence time,
+/// with a steady stream of messages it is delivered after this many times the quiescence time
+const MAX_QUIESCENCE_WAITS: u32 = 10;
+
+/// Scheduler that delivers the consensus messages of a harness run one at a time in the order of a delivery schedule
+/// Outside of harness runs, and after max_depth deliveries, messages are relayed immediately
+pub struct SystematicScheduler {
+    state: SchedulerState,
+}
+
+/// A held message with the number of controlled deliveries before it arrived
+struct PendingMessage {
+    key: MessageKey,
+    appeared_at: usize,
+    rmo_event: RMOEvent,
+}
+
+/// The messages held back during a harness run and the deliveries made so far
+#[derive(Default)]
+struct DeliveryController {
+    pending: Vec<PendingMessage>,
+    occurrences: HashMap<(usize, usize, String), usize>,
+    schedule: DeliverySchedule,
+    deliveries: usize,
+}
+
+impl DeliveryController {
+    fn start_run(&mut self, schedule: DeliverySchedule) {
+        schedule.trace.lock().clear();
+        self.schedule = schedule;
+        self.occurrences.clear();
+        self.deliveries = 0;
+    }
+
+    fn is_controlling(&self) -> bool {
+        self.deliveries < self.schedule.max_depth
+    }
+
+    fn hold(&mut self, rmo_event: RMOEvent) {
+        let key = MessageKey::next(&rmo_event, &mut self.occurrences);
+        self.pending.push(PendingMessage { key, appeared_at: self.deliveries, rmo_event });
+    }
+
+    /// Take the next message of the prefix if it is pending, otherwise the smallest pending message
+    fn next(&mut self) -> Option<RMOEvent> {
+        let index = self.schedule.prefix.get(self.deliveries)
+            .and_then(|key| self.pending.iter().position(|message| &message.key == key))
+            .or_else(|| (0..self.pending.len()).min_by(|i, j| self.pending[*i].key.cmp(&self.pending[*j].key)))?;
+        let mut enabled = self.pending.iter().map(|message| message.key.clone()).collect::<Vec<_>>();
+        enabled.sort();
+        let message = self.pending.remove(index);
+        self.schedule.trace.lock().push(DeliveryStep { enabled, chosen: message.key, appeared_at: message.appeared_at });
+        self.deliveries += 1;
+        Some(message.rmo_event)
+    }
+
+    /// Release the pending messages in the order they arrived
+    fn drain(&mut self) -> Vec<RMOEvent> {
+        self.pending.drain(..).map(|message| message.rmo_event).collect()
+    }
+}
+
+impl SystematicScheduler {
+    fn delivery_controller(
+        inbox_rx: STDReceiver<RMOEvent>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        current_individual: Arc<Mutex<DeliverySchedule>>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+    ) {
+        let (run_lock, _run_cvar) = &*run;
+        let mut controller = DeliveryController::default();
+        let mut was_running = false;
+        let mut waiting_since = Instant::now();
+        loop {
+            let quiescence = Duration::from_millis(controller.schedule.quiescence_ms.max(1));
+            let received = inbox_rx.recv_timeout(quiescence);
+            let running = *run_lock.read().unwrap();
+            if running && !was_running {
+                controller.start_run(current_individual.lock().clone());
+            }
+            was_running = running;
+            match received {
+                Ok(rmo_event) => {
+                    if running && controller.is_controlling() {
+                        if controller.pending.is_empty() {
+                            waiting_since = Instant::now();
+                        }
+                        controller.hold(rmo_event);
+                        if waiting_since.elapsed() < quiescence * MAX_QUIESCENCE_WAITS {
+                            continue;
+                        }
+                    } else {
+                        event_schedule_sender.blocking_send(rmo_event).expect("Event scheduler failed");
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    error!("Systematic inbox sender failed");
+                    return;
+                }
+            }
+            if running && controller.is_controlling() {
+                if let Some(rmo_event) = controller.next() {
+                    debug!("Delivering {} -> {} after {} deliveries", rmo_event.from, rmo_event.to, controller.deliveries - 1);
+                    event_schedule_sender.blocking_send(rmo_event).expect("Event scheduler failed");
+                    waiting_since = Instant::now();
+                }
+            } else {
+                for rmo_event in controller.drain() {
+                    event_schedule_sender.blocking_send(rmo_event).expect("Event scheduler failed");
+                }
+            }
+        }
+    }
+}
+
+impl Scheduler for SystematicScheduler {
+    type IndividualPhenotype = DeliverySchedule;
+
+    fn new(
+        collector_sender: STDSender<Box<RippleMessage>>,
+        node_states: Arc<MutexNodeStates>,
+        node_keys: Vec<NodeKeys>,
+        failure_sender: STDSender<Vec<ConsensusPropertyTypes>>,
+    ) -> Self {
+        Self {
+            state: SchedulerState::new(collector_sender, node_states, node_keys, failure_sender)
+        }
+    }
+
+    fn schedule_controller(
+        mut receiver: MeteredReceiver<Event>,
+        run: Arc<(RwLock<bool>, Condvar)>,
+        current_individual: Arc<Mutex<Self::IndividualPhenotype>>,
+        _node_states: Arc<MutexNodeStates>,
+        round_update_sender: MeteredSender<RMOEvent>,
+        event_schedule_sender: MeteredSender<RMOEvent>,
+        send_dependency_sender: MeteredSender<RippleMessage>,
+    ) -> BoxFuture<'static, ()>
+    {
+        async move {
+            let (run_lock, _run_cvar) = &*run;
+            let (inbox_tx, inbox_rx) = std::sync::mpsc::channel();
+            let event_schedule_sender_2 = event_schedule_sender.clone();
+            let run_2 = run.clone();
+            thread::spawn(move || Self::delivery_controller(inbox_rx, run_2, current_individual, event_schedule_sender_2));
+            while let Some(event) = receiver.recv().await {
+                let rmo_event = RMOEvent::from(&event);
+                if Self::is_consensus_rmo(&rmo_event.message) {
+                    round_update_sender.send(rmo_event.clone()).await.expect("Round update sender failed");
+                    let running = *run_lock.read().unwrap();
+                    if running {
+                        send_dependency_sender.send(RippleMessage::from_rmo_event(rmo_event.clone())).await.expect("send dependency sender failed");
+                        inbox_tx.send(rmo_event).expect("Inbox sender failed");
+                        continue;
+                    }
+                }
+                event_schedule_sender.send(rmo_event).await.expect("Event scheduler failed");
+            }
+            error!("Peer senders failed");
+        }.boxed()
+    }
+
+    fn listen_to_ga(current_individual: Arc<Mutex<Self::IndividualPhenotype>>, ga_receiver: STDReceiver<Self::IndividualPhenotype>, node_states: Arc<MutexNodeStates>) {
+        loop {
+            match ga_receiver.recv() {
+                Ok(schedule) => {
+                    node_states.set_current_individual(format!("{:?}", schedule.prefix));
+                    *current_individual.lock() = schedule;
+                    debug!("New delivery schedule received");
+                },
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn get_state(&self) -> &SchedulerState {
+        &self.state
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use log::error;
+use parking_lot::Mutex;
+use crate::failure_writer::ConsensusPropertyTypes;
+use crate::ga::fitness::ExtendedFitness;
+use crate::LOG_FOLDER;
+use crate::scheduler::RMOEvent;
+
+/// Parameters of the systematic exploration as set in the configuration
+/// max_depth: The number of consensus messages per execution whose delivery order is controlled, later messages are delivered as they come
+/// max_executions: The maximum number of executions, exploration stops earlier if every ordering up to max_depth has been covered
+/// quiescence_ms: The time without new messages before the next message is delivered
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SystematicConfiguration {
+    pub max_depth: usize,
+    pub max_executions: usize,
+    pub quiescence_ms: u64,
+}
+
+impl Default for SystematicConfiguration {
+    fn default() -> Self {
+        Self {
+            max_depth: 10,
+            max_executions: 100,
+            quiescence_ms: 200,
+        }
+    }
+}
+
+impl SystematicConfiguration {
+    /// Check the parameters for values the exploration cannot run with, returns all problems found
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.max_depth == 0 {
+            errors.push("systematic max_depth must be at least 1".to_string());
+        }
+        if self.max_executions == 0 {
+            errors.push("systematic max_executions must be at least 1".to_string());
+        }
+        if self.quiescence_ms == 0 {
+            errors.push("systematic quiescence_ms must be at least 1".to_string());
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// Identifies a message across executions, the n-th message of a type from a sender to a receiver in the execution
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+pub struct MessageKey {
+    pub from: usize,
+    pub to: usize,
+    pub message_type: String,
+    pub occurrence: usize,
+}
+
+impl MessageKey {
+    /// The key of the next message, occurrences counts the messages seen per sender, receiver and type
+    pub fn next(rmo_event: &RMOEvent, occurrences: &mut HashMap<(usize, usize, String), usize>) -> Self {
+        let message_type = rmo_event.message.message_type().to_string();
+        let occurrence = occurrences.entry((rmo_event.from, rmo_event.to, message_type.clone())).or_insert(0);
+        *occurrence += 1;
+        Self { from: rmo_event.from, to: rmo_event.to, message_type, occurrence: *occurrence - 1 }
+    }
+
+    /// Deliveries to the same receiver do not commute, deliveries to different receivers do
+    pub fn is_dependent(&self, other: &Self) -> bool {
+        self.to == other.to
+    }
+}
+
+/// A controlled delivery
+/// enabled: The messages that were pending, chosen: The delivered message
+/// appeared_at: The number of deliveries before the chosen message was sent
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct DeliveryStep {
+    pub enabled: Vec<MessageKey>,
+    pub chosen: MessageKey,
+    pub appeared_at: usize,
+}
+
+/// The individual of the systematic scheduler, deliver the prefix first and then the smallest pending message
+/// The scheduler writes the deliveries it controlled to the trace
+#[derive(Clone, Debug, Default)]
+pub struct DeliverySchedule {
+    pub prefix: Vec<MessageKey>,
+    pub max_depth: usize,
+    pub quiescence_ms: u64,
+    pub trace: Arc<Mutex<Vec<DeliveryStep>>>,
+}
+
+impl DeliverySchedule {
+    pub fn new(prefix: Vec<MessageKey>, max_depth: usize, quiescence_ms: u64) -> Self {
+        Self { prefix, max_depth, quiescence_ms, trace: Arc::new(Mutex::new(vec![])) }
+    }
+}
+
+/// A state on the current exploration path
+/// backtrack: Messages that still have to be delivered first from this state, done: Messages that have been
+#[derive(Clone, Debug)]
+struct ExplorationNode {
+    enabled: BTreeSet<MessageKey>,
+    chosen: MessageKey,
+    backtrack: BTreeSet<MessageKey>,
+    done: BTreeSet<MessageKey>,
+}
+
+impl ExplorationNode {
+    fn new(step: &DeliveryStep) -> Self {
+        let chosen_set: BTreeSet<MessageKey> = [step.chosen.clone()].into_iter().collect();
+        Self {
+            enabled: step.enabled.iter().cloned().collect(),
+            chosen: step.chosen.clone(),
+            backtrack: chosen_set.clone(),
+            done: chosen_set,
+        }
+    }
+}
+
+/// Stateless depth-first exploration of delivery orders with dynamic partial-order reduction
+/// Only orderings of dependent deliveries (same receiver) that are not causally related are reversed
+#[derive(Debug, Default)]
+pub struct Explorer {
+    nodes: Vec<ExplorationNode>,
+}
+
+impl Explorer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the trace of an execution to the exploration path and add backtrack points for its races
+    /// Returns whether the execution followed the requested prefix
+    pub fn add_execution(&mut self, prefix: &[MessageKey], trace: &[DeliveryStep]) -> bool {
+        let followed_prefix = trace.len() >= prefix.len() && trace.iter().zip(prefix).all(|(step, key)| &step.chosen == key);
+        for (index, step) in trace.iter().enumerate() {
+            match self.nodes.get_mut(index) {
+                Some(node) if node.chosen == step.chosen => node.enabled.extend(step.enabled.iter().cloned()),
+                Some(node) => {
+                    // The system did not follow the prefix, continue from what it did instead
+                    node.chosen = step.chosen.clone();
+                    node.done.insert(step.chosen.clone());
+                    node.backtrack.insert(step.chosen.clone());
+                    node.enabled.extend(step.enabled.iter().cloned());
+                    self.nodes.truncate(index + 1);
+                }
+                None => self.nodes.push(ExplorationNode::new(step)),
+            }
+        }
+        self.nodes.truncate(trace.len());
+        self.add_backtrack_points(trace);
+        followed_prefix
+    }
+
+    /// For every delivery, reverse the last earlier dependent delivery it is not causally after
+    fn add_backtrack_points(&mut self, trace: &[DeliveryStep]) {
+        let causal_pasts = Self::causal_pasts(trace);
+        for (j, step) in trace.iter().enumerate() {
+            let race = (0..j).rev()
+                .find(|i| trace[*i].chosen.is_dependent(&step.chosen) && !causal_pasts[j].contains(i));
+            if let Some(i) = race {
+                let node = &mut self.nodes[i];
+                if node.enabled.contains(&step.chosen) {
+                    node.backtrack.insert(step.chosen.clone());
+                } else {
+                    let enabled = node.enabled.clone();
+                    node.backtrack.extend(enabled);
+                }
+            }
+        }
+    }
+
+    /// The deliveries every delivery causally depends on, i.e. the deliveries to its sender before it was sent and their causal pasts
+    fn causal_pasts(trace: &[DeliveryStep]) -> Vec<HashSet<usize>> {
+        let mut causal_pasts: Vec<HashSet<usize>> = vec![];
+        for (j, step) in trace.iter().enumerate() {
+            let mut causal_past = HashSet::new();
+            for k in 0..step.appeared_at.min(j) {
+                if trace[k].chosen.to == step.chosen.from {
+                    causal_past.insert(k);
+                    causal_past.extend(causal_pasts[k].iter().cloned());
+                }
+            }
+            causal_pasts.push(causal_past);
+        }
+        causal_pasts
+    }
+
+    /// The prefix of the next execution, None if every backtrack point has been explored
+    pub fn next_prefix(&mut self) -> Option<Vec<MessageKey>> {
+        while let Some(node) = self.nodes.last_mut() {
+            if let Some(key) = node.backtrack.difference(&node.done).next().cloned() {
+                node.done.insert(key.clone());
+                node.chosen = key;
+                return Some(self.nodes.iter().map(|node| node.chosen.clone()).collect());
+            }
+            self.nodes.pop();
+        }
+        None
+    }
+}
+
+/// Pass violations on to the failure writer and keep a copy for the report of the current execution
+pub fn forward_violations(
+    violation_receiver: Receiver<Vec<ConsensusPropertyTypes>>,
+    failure_sender: Sender<Vec<ConsensusPropertyTypes>>,
+    report_sender: Sender<Vec<ConsensusPropertyTypes>>,
+) {
+    for violations in violation_receiver {
+        report_sender.send(violations.clone()).ok();
+        if failure_sender.send(violations).is_err() {
+            error!("Failure writer hung up");
+        }
+    }
+}
+
+/// A single execution, written to systematic.txt
+#[derive(serde::Serialize)]
+struct ExecutionReport<'a, F> {
+    execution: usize,
+    prefix: &'a [MessageKey],
+    followed_prefix: bool,
+    deliveries: Vec<&'a MessageKey>,
+    fitness: &'a F,
+    violations: &'a [ConsensusPropertyTypes],
+}
+
+/// The outcome of the exploration, written as the last line of systematic.txt
+#[derive(serde::Serialize)]
+struct ExplorationSummary {
+    executions: usize,
+    complete: bool,
+    executions_with_violations: usize,
+}
+
+/// Explore delivery orders until every backtrack point has been covered or the execution bound is reached
+/// Every execution is a harness run, the network stabilizes and the harness starts in a new round between executions
+pub fn run_systematic<F: ExtendedFitness>(
+    configuration: SystematicConfiguration,
+    scheduler_sender: Sender<DeliverySchedule>,
+    scheduler_receiver: Receiver<F>,
+    violation_receiver: Receiver<Vec<ConsensusPropertyTypes>>,
+) {
+    let mut systematic_writer = create_systematic_writer();
+    let mut explorer = Explorer::new();
+    let mut prefix = Some(vec![]);
+    let mut executions = 0;
+    let mut executions_with_violations = 0;
+    println!("Starting systematic exploration with: {:?}", configuration);
+    while let Some(current_prefix) = prefix {
+        if executions == configuration.max_executions {
+            break;
+        }
+        let schedule = DeliverySchedule::new(current_prefix.clone(), configuration.max_depth, configuration.quiescence_ms);
+        let trace = schedule.trace.clone();
+        // Violations reported between executions do not belong to any ordering
+        while violation_receiver.try_recv().is_ok() {}
+        scheduler_sender.send(schedule).expect("Scheduler receiver failed");
+        let fitness = scheduler_receiver.recv().expect("Scheduler sender failed");
+        let violations = violation_receiver.try_iter().flatten().collect::<Vec<_>>();
+        let trace = trace.lock().clone();
+        let followed_prefix = explorer.add_execution(&current_prefix, &trace);
+        if !violations.is_empty() {
+            executions_with_violations += 1;
+            println!("Execution {} violated {:?}", executions, violations);
+        }
+        let report = ExecutionReport {
+            execution: executions,
+            prefix: &current_prefix,
+            followed_prefix,
+            deliveries: trace.iter().map(|step| &step.chosen).collect(),
+            fitness: &fitness,
+            violations: &violations,
+        };
+        write_line(&mut systematic_writer, &report);
+        executions += 1;
+        prefix = explorer.next_prefix();
+    }
+    let summary = ExplorationSummary { executions, complete: prefix.is_none(), executions_with_violations };
+    write_line(&mut systematic_writer, &summary);
+    println!("Finished systematic exploration after {} executions, complete: {}, executions with violations: {}", executions, summary.complete, executions_with_violations);
+    std::process::exit(0);
+}
+
+fn write_line<T: serde::Serialize>(systematic_writer: &mut BufWriter<File>, line: &T) {
+    match serde_json::to_writer(&mut *systematic_writer, line) {
+        Ok(_) => {}
+        Err(err) => error!("Failed writing to systematic file: {}", err)
+    };
+    systematic_writer.write_all(b"\n").expect("Systematic writer failed");
+    systematic_writer.flush().expect("Systematic writer flush failed");
+}
+
+fn create_systematic_writer() -> BufWriter<File> {
+    BufWriter::new(
+        File::create(
+            Path::new(format!("{}/systematic.txt", *LOG_FOLDER).as_str())
+        ).expect("Creating systematic file failed")
+    )
+}
+
+#[cfg(test)]
+mod systematic_tests {
+    use crate::systematic::{DeliveryStep, Explorer, MessageKey};
+
+    fn key(from: usize, to: usize) -> MessageKey {
+        MessageKey { from, to, message_type: "Validation".to_string(), occurrence: 0 }
+    }
+
+    fn step(enabled: &[MessageKey], chosen: &MessageKey, appeared_at: usize) -> DeliveryStep {
+        DeliveryStep { enabled: enabled.to_vec(), chosen: chosen.clone(), appeared_at }
+    }
+
+    #[test]
+    fn test_independent_deliveries_are_not_reordered() {
+        let (a, b) = (key(0, 1), key(0, 2));
+        let mut explorer = Explorer::new();
+        assert!(explorer.add_execution(&[], &[step(&[a.clone(), b.clone()], &a, 0), step(&[b.clone()], &b, 0)]));
+        assert_eq!(explorer.next_prefix(), None);
+    }
+
+    #[test]
+    fn test_racing_deliveries_are_reversed() {
+        let (a, b) = (key(0, 2), key(1, 2));
+        let mut explorer = Explorer::new();
+        explorer.add_execution(&[], &[step(&[a.clone(), b.clone()], &a, 0), step(&[b.clone()], &b, 0)]);
+        let prefix = explorer.next_prefix().unwrap();
+        assert_eq!(prefix, vec![b.clone()]);
+        assert!(explorer.add_execution(&prefix, &[step(&[a.clone(), b.clone()], &b, 0), step(&[a.clone()], &a, 0)]));
+        assert_eq!(explorer.next_prefix(), None);
+    }
+
+    #[test]
+    fn test_causally_related_deliveries_are_not_reversed() {
+        // Node 2 answers the request of node 0, the answer is sent after the request is delivered
+        let (request, answer) = (key(0, 2), key(2, 0));
+        let reply = key(1, 0);
+        let mut explorer = Explorer::new();
+        explorer.add_execution(&[], &[
+            step(&[request.clone(), reply.clone()], &request, 0),
+            step(&[reply.clone(), answer.clone()], &reply, 0),
+            step(&[answer.clone()], &answer, 1),
+        ]);
+        // The reply and the answer race at node 0, the request and the answer are causally related
+        assert_eq!(explorer.next_prefix(), Some(vec![request.clone(), answer.clone()]));
+    }
+}
@@ -180,7 +180,7 @@ impl TestHarness<'static> {
         }
         debug!("Waiting for balances to be set up");
         while node_states.get_number_min_validated_transactions() < self.starting_balances.len() {
-            node_states.transactions_cvar.wait(&mut node_states.node_states.lock());
+            node_states.transactions_cvar.wait();
         }
         debug!("Done setting up balances")
     }
@@ -229,7 +229,7 @@ impl TestHarness<'static> {
         while test_result == InProgress &&
             node_states.get_consensus_event_count() < MAX_EVENTS_TEST
         {
-            node_states.transactions_cvar.wait_for(&mut node_states.node_states.lock(), Duration::from_millis(1000));
+            node_states.transactions_cvar.wait_for(Duration::from_millis(1000));
             min_validated_transactions = node_states.get_min_validated_transactions_idx();
             test_result = TransactionResult::check_transaction_results(&self.transaction_results, &min_validated_transactions, &unfunded_payment_idxs);
         }
@@ -470,7 +470,7 @@ mod harness_tests {
     use websocket::{Message, OwnedMessage};
     use crate::client::{Client, Transaction};
     use crate::container_manager::AccountKeys;
-    use crate::node_state::{MutexNodeStates, NodeState, NodeStates};
+    use crate::node_state::{MutexNodeStates, NodeState};
     use crate::test_harness::{Account, TestHarness, TransactionResult, TransactionResultCode, TransactionTimed};
     use crate::test_harness::TestResult::{Failed, InProgress, Success};
 
@@ -518,7 +518,7 @@ mod harness_tests {
     #[test]
     fn test_transaction_scheduler() {
         let (mut actual_harness, mut expected_harness, receivers) = parse_harness();
-        let node_states = Arc::new(MutexNodeStates::new(NodeStates::new(vec![NodeState::new(0), NodeState::new(1)])));
+        let node_states = Arc::new(MutexNodeStates::new(vec![NodeState::new(0), NodeState::new(1)]));
         assert_eq!(actual_harness.transactions, expected_harness.transactions);
         thread::spawn(move || actual_harness.schedule_transactions(node_states.clone()));
         thread::sleep(Duration::from_millis(200));
@@ -605,7 +605,7 @@ mod harness_tests {
     #[ignore]
     fn test_setup_balance() {
         let (mut actual_harness, _expected_harness, receivers) = parse_harness();
-        let node_states = Arc::new(MutexNodeStates::new(NodeStates::new(vec![NodeState::new(0), NodeState::new(1)])));
+        let node_states = Arc::new(MutexNodeStates::new(vec![NodeState::new(0), NodeState::new(1)]));
         let node_states_clone = node_states.clone();
         let (balance_sender, balance_receiver) = channel();
         actual_harness.balance_receiver = balance_receiver;
@@ -0,0 +1,240 @@
+use std::collections::BTreeSet;
+use itertools::Itertools;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// The number of times a random regular graph is paired up from scratch before giving up
+const MAX_REGULAR_ATTEMPTS: usize = 1000;
+
+/// The peer connections the proxy opens between the validators, independent of the UNLs
+/// Full: Every pair of validators is connected
+/// Ring: Every validator is connected to the next one, the last one to the first
+/// Star: Every validator is connected to the center only
+/// RandomRegular: Every validator is connected to degree random other validators, the same seed gives the same graph
+/// Edges: The given undirected edges
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub enum PeerTopology {
+    Full,
+    Ring,
+    Star { center: usize },
+    RandomRegular { degree: usize, seed: u64 },
+    Edges { edges: Vec<(usize, usize)> },
+}
+
+impl Default for PeerTopology {
+    fn default() -> Self {
+        Self::Full
+    }
+}
+
+impl PeerTopology {
+    /// Build the graph of the topology, the graph has to be connected so every message can be relayed to every validator
+    pub fn peer_graph(&self, num_nodes: usize) -> Result<PeerGraph, Vec<String>> {
+        let mut errors = vec![];
+        let edges = match self {
+            Self::Full => (0..num_nodes).tuple_combinations().collect_vec(),
+            Self::Ring => (0..num_nodes).map(|node| (node, (node + 1) % num_nodes)).filter(|(a, b)| a != b).collect_vec(),
+            Self::Star { center } => {
+                if *center >= num_nodes {
+                    errors.push(format!("peer_topology star center {} is not in 0..{}", center, num_nodes));
+                }
+                (0..num_nodes).filter(|node| node != center).map(|node| (*center, node)).collect_vec()
+            }
+            Self::RandomRegular { degree, seed } => {
+                if *degree >= num_nodes.max(1) || (num_nodes * degree) % 2 != 0 {
+                    errors.push(format!("peer_topology has no {}-regular graph on {} nodes", degree, num_nodes));
+                    vec![]
+                } else {
+                    random_regular_edges(num_nodes, *degree, *seed).unwrap_or_else(|| {
+                        errors.push(format!("peer_topology could not pair up a {}-regular graph on {} nodes, try another seed", degree, num_nodes));
+                        vec![]
+                    })
+                }
+            }
+            Self::Edges { edges } => {
+                let mut seen = BTreeSet::new();
+                for (a, b) in edges {
+                    if *a >= num_nodes || *b >= num_nodes {
+                        errors.push(format!("peer_topology edge {} - {} refers to a node outside 0..{}", a, b, num_nodes));
+                    }
+                    if a == b {
+                        errors.push(format!("peer_topology edge {} - {} connects a node to itself", a, b));
+                    }
+                    if !seen.insert((*a.min(b), *a.max(b))) {
+                        errors.push(format!("peer_topology edge {} - {} is configured more than once", a, b));
+                    }
+                }
+                edges.clone()
+            }
+        };
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let graph = PeerGraph::new(num_nodes, edges);
+        if !graph.is_connected() {
+            return Err(vec![format!("peer_topology {:?} does not connect every validator", self)]);
+        }
+        Ok(graph)
+    }
+}
+
+/// Pair up degree stubs per node at random, restarting when the remaining stubs can only form self loops or double edges
+fn random_regular_edges(num_nodes: usize, degree: usize, seed: u64) -> Option<Vec<(usize, usize)>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    for _ in 0..MAX_REGULAR_ATTEMPTS {
+        let mut stubs = (0..num_nodes).flat_map(|node| std::iter::repeat(node).take(degree)).collect_vec();
+        let mut edges = BTreeSet::new();
+        while !stubs.is_empty() {
+            let candidates = (0..stubs.len()).tuple_combinations()
+                .filter(|(i, j): &(usize, usize)| stubs[*i] != stubs[*j] && !edges.contains(&(stubs[*i].min(stubs[*j]), stubs[*i].max(stubs[*j]))))
+                .collect_vec();
+            if candidates.is_empty() {
+                break;
+            }
+            let (i, j) = candidates[rng.gen_range(0..candidates.len())];
+            let (a, b) = (stubs[i], stubs[j]);
+            edges.insert((a.min(b), a.max(b)));
+            stubs.remove(j);
+            stubs.remove(i);
+        }
+        if stubs.is_empty() {
+            return Some(edges.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// The undirected graph of peer connections
+/// Messages are only sent over links, the directed edges, and only links have genes in the genotype
+/// edges and links are sorted, which fixes their position in the genotype
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerGraph {
+    num_nodes: usize,
+    edges: Vec<(usize, usize)>,
+    links: Vec<(usize, usize)>,
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl PeerGraph {
+    /// The graph of the edges, in either direction
+    pub fn new(num_nodes: usize, edges: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        let edges = edges.into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let links = edges.iter()
+            .flat_map(|(a, b)| [(*a, *b), (*b, *a)])
+            .sorted()
+            .collect_vec();
+        let mut neighbours = vec![vec![]; num_nodes];
+        for (from, to) in &links {
+            neighbours[*from].push(*to);
+        }
+        Self { num_nodes, edges, links, neighbours }
+    }
+
+    /// The graph where every pair of nodes is connected
+    pub fn complete(num_nodes: usize) -> Self {
+        Self::new(num_nodes, (0..num_nodes).tuple_combinations())
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// The undirected edges (a, b) with a < b
+    pub fn edges(&self) -> &[(usize, usize)] {
+        &self.edges
+    }
+
+    /// Both directions (from, to) of every edge
+    pub fn links(&self) -> &[(usize, usize)] {
+        &self.links
+    }
+
+    /// The nodes connected to the node, in ascending order
+    pub fn neighbours(&self, node: usize) -> &[usize] {
+        &self.neighbours[node]
+    }
+
+    /// The position of the link in links, None if the nodes are not connected
+    pub fn link_index(&self, from: usize, to: usize) -> Option<usize> {
+        self.links.binary_search(&(from, to)).ok()
+    }
+
+    /// The position of the edge between the nodes in edges, None if the nodes are not connected
+    pub fn edge_index(&self, a: usize, b: usize) -> Option<usize> {
+        self.edges.binary_search(&(a.min(b), a.max(b))).ok()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        if self.num_nodes == 0 {
+            return true;
+        }
+        let mut visited = vec![false; self.num_nodes];
+        let mut stack = vec![0];
+        visited[0] = true;
+        while let Some(node) = stack.pop() {
+            for neighbour in &self.neighbours[node] {
+                if !visited[*neighbour] {
+                    visited[*neighbour] = true;
+                    stack.push(*neighbour);
+                }
+            }
+        }
+        visited.into_iter().all(|visited| visited)
+    }
+
+    /// Whether relabelling node i as permutation[i] maps every edge to an edge
+    pub fn is_automorphism(&self, permutation: &[usize]) -> bool {
+        self.edges.iter().all(|(a, b)| self.edge_index(permutation[*a], permutation[*b]).is_some())
+    }
+}
+
+#[cfg(test)]
+mod topology_tests {
+    use itertools::Itertools;
+    use crate::topology::{PeerGraph, PeerTopology};
+
+    #[test]
+    fn test_complete_graph_layout() {
+        // The layout of the complete graph is the layout of the genotype without a topology
+        let graph = PeerTopology::Full.peer_graph(5).unwrap();
+        assert_eq!(graph, PeerGraph::complete(5));
+        assert_eq!(graph.links().len(), 20);
+        for (from, to) in (0..5).cartesian_product(0..5).filter(|(from, to)| from != to) {
+            assert_eq!(graph.link_index(from, to), Some(from * 4 + if to < from { to } else { to - 1 }));
+        }
+        assert_eq!(graph.edge_index(3, 1), Some(5));
+    }
+
+    #[test]
+    fn test_topologies() {
+        let ring = PeerTopology::Ring.peer_graph(5).unwrap();
+        assert_eq!(ring.edges(), &[(0, 1), (0, 4), (1, 2), (2, 3), (3, 4)]);
+        assert_eq!(ring.neighbours(0), &[1, 4]);
+        assert_eq!(ring.link_index(0, 2), None);
+        assert!(ring.is_automorphism(&[1, 2, 3, 4, 0]));
+        assert!(!ring.is_automorphism(&[1, 0, 2, 3, 4]));
+        let star = PeerTopology::Star { center: 2 }.peer_graph(4).unwrap();
+        assert_eq!(star.edges(), &[(0, 2), (1, 2), (2, 3)]);
+        let regular = PeerTopology::RandomRegular { degree: 3, seed: 7 }.peer_graph(8).unwrap();
+        assert!((0..8).all(|node| regular.neighbours(node).len() == 3));
+        assert_eq!(regular, PeerTopology::RandomRegular { degree: 3, seed: 7 }.peer_graph(8).unwrap());
+        let edges = PeerTopology::Edges { edges: vec![(1, 0), (1, 2)] }.peer_graph(3).unwrap();
+        assert_eq!(edges.links(), &[(0, 1), (1, 0), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn test_invalid_topologies() {
+        assert!(PeerTopology::Star { center: 5 }.peer_graph(5).is_err());
+        assert!(PeerTopology::RandomRegular { degree: 3, seed: 0 }.peer_graph(5).is_err());
+        assert!(PeerTopology::RandomRegular { degree: 5, seed: 0 }.peer_graph(5).is_err());
+        let invalid = PeerTopology::Edges { edges: vec![(0, 1), (1, 0), (2, 2), (0, 5)] };
+        assert_eq!(invalid.peer_graph(5).unwrap_err().len(), 3);
+        // Node 2 is not connected
+        assert!(PeerTopology::Edges { edges: vec![(0, 1)] }.peer_graph(3).is_err());
+    }
+}
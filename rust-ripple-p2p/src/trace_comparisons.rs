@@ -5,7 +5,6 @@ use std::sync::Arc;
 use std::sync::mpsc::{Sender, Receiver};
 use std::thread;
 use chrono::{Duration, Utc};
-use itertools::chain;
 use log::debug;
 use petgraph::Graph;
 use rand::distributions::Uniform;
@@ -13,13 +12,13 @@ use rand::{Rng, thread_rng};
 use rand_chacha::ChaCha8Rng;
 use rand_chacha::rand_core::SeedableRng;
 use crate::ga::encoding::delay_encoding::{DelayMapPhenotype, DelayGenotype};
-use crate::ga::encoding::{ExtendedPhenotype, num_genes};
+use crate::ga::encoding::{ExtendedPhenotype, num_delay_genes, num_genes};
 use crate::ga::encoding::priority_encoding::{PriorityGenotype, PriorityMapPhenotype};
 use crate::ga::fitness::ExtendedFitness;
 use crate::ga::genetic_algorithm::{ConsensusMessageType};
 use crate::locality::{sample_delays_genotype, sample_priority_genotype};
 use crate::node_state::{DependencyEvent, MessageTypeDependencyEvent, MutexNodeStates};
-use crate::NUM_NODES;
+use crate::PEER_GRAPH;
 
 mod compare;
 mod compare_fitness;
@@ -52,11 +51,11 @@ impl<T> DelayTraceGraphSchedulerHandler<T>
 
     /// Write trace graphs to file after running a number of test harnesses with certain delays
     pub fn delay_trace_graph_creation(&mut self, node_states: Arc<MutexNodeStates>) {
-        let zero_delays = vec![0u32; num_genes()];
-        let one_delays = vec![1000u32; num_genes()];
+        let zero_delays = vec![0u32; num_delay_genes()];
+        let one_delays = vec![1000u32; num_delay_genes()];
         let range = Uniform::from(0..1000);
-        let random_delays_1: Vec<u32> = rand::thread_rng().sample_iter(&range).take(num_genes()).collect();
-        let random_delays_2: Vec<u32> = rand::thread_rng().sample_iter(&range).take(num_genes()).collect();
+        let random_delays_1: Vec<u32> = rand::thread_rng().sample_iter(&range).take(num_delay_genes()).collect();
+        let random_delays_2: Vec<u32> = rand::thread_rng().sample_iter(&range).take(num_delay_genes()).collect();
         let delays = vec![zero_delays, one_delays, random_delays_1, random_delays_2];
 
         // Allow five test harnesses to pass to mitigate any startup difficulties in the network
@@ -194,11 +193,11 @@ impl<F: ExtendedFitness> FitnessComparisonSchedulerHandler<F> {
         let number_of_tests = 100;
         let number_of_tests_per_chromosome = 5;
         for _ in 0..number_of_tests {
-            delays.push(rand::thread_rng().sample_iter(&range).take(num_genes()).collect());
+            delays.push(rand::thread_rng().sample_iter(&range).take(num_delay_genes()).collect());
         }
 
         // Allow five test harnesses to pass to mitigate any startup difficulties in the network
-        let zero_delays = vec![0u32; num_genes()];
+        let zero_delays = vec![0u32; num_delay_genes()];
         for _ in 0..5 {
             self.scheduler_sender.send(DelayMapPhenotype::from_genes(&zero_delays)).expect("Scheduler receiver failed");
             self.scheduler_receiver.recv().expect("Scheduler sender failed");
@@ -249,7 +248,7 @@ impl<F: ExtendedFitness> NoDelaySchedulerHandler<F> {
     }
 
     pub fn run(&mut self, search_budget: Duration) {
-        let delays: Vec<u32> = vec![0; num_genes()];
+        let delays: Vec<u32> = vec![0; num_delay_genes()];
         let start_time = Utc::now();
         while Utc::now() - start_time < search_budget {
             self.scheduler_sender.send(DelayMapPhenotype::from_genes(&delays)).expect("Scheduler receiver failed");
@@ -293,8 +292,7 @@ impl<F: ExtendedFitness> PreDeterminedDelaySchedulerHandler<F> {
 
     #[allow(unused)]
     fn create_liveness_bug_delays() -> DelayGenotype {
-        let index_factor_1 = ConsensusMessageType::VALUES.len() * (*NUM_NODES-1);
-        let index_factor_2 = ConsensusMessageType::VALUES.len();
+        let index_factor = ConsensusMessageType::VALUES.len();
         let mut delays = vec![0u32; num_genes()];
         let ledger_data_index = 12;
         let get_ledger_index = 11;
@@ -302,12 +300,10 @@ impl<F: ExtendedFitness> PreDeterminedDelaySchedulerHandler<F> {
         let ledger_data_delay = 3000;
         let get_ledger_delay = 3000;
         let transaction_delay = 0;
-        for i in 0..*NUM_NODES {
-            for (j, _) in chain(0..i, i+1..*NUM_NODES).enumerate() {
-                delays[index_factor_1 * i + index_factor_2 * j + ledger_data_index] = ledger_data_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + get_ledger_index] = get_ledger_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + transaction_index] = transaction_delay;
-            }
+        for link in 0..PEER_GRAPH.links().len() {
+            delays[index_factor * link + ledger_data_index] = ledger_data_delay;
+            delays[index_factor * link + get_ledger_index] = get_ledger_delay;
+            delays[index_factor * link + transaction_index] = transaction_delay;
         }
         println!("{}", DelayMapPhenotype::from_genes(&delays).display_genotype_by_message());
         delays
@@ -315,8 +311,7 @@ impl<F: ExtendedFitness> PreDeterminedDelaySchedulerHandler<F> {
 
     #[allow(unused)]
     fn create_proposal_bug_delays() -> DelayGenotype {
-        let index_factor_1 = ConsensusMessageType::VALUES.len() * (*NUM_NODES-1);
-        let index_factor_2 = ConsensusMessageType::VALUES.len();
+        let index_factor = ConsensusMessageType::VALUES.len();
         let mut delays = vec![0u32; num_genes()];
         let propose_set_0_index = 0;
         let propose_set_1_index = 1;
@@ -333,17 +328,15 @@ impl<F: ExtendedFitness> PreDeterminedDelaySchedulerHandler<F> {
         let propose_set_4_delay = 0;
         let propose_set_5_delay = 0;
         let propose_set_bowout_delay = 0;
-        for i in [1, 2, 3, 4] {
-            for (j, _) in chain(0..i, i+1..*NUM_NODES).enumerate() {
-                delays[index_factor_1 * i + index_factor_2 * j + propose_set_0_index] = propose_set_0_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + propose_set_1_index] = propose_set_1_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + propose_set_2_index] = propose_set_2_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + propose_set_3_index] = propose_set_3_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + propose_set_4_index] = propose_set_4_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + propose_set_5_index] = propose_set_5_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + propose_set_bowout_index] = propose_set_bowout_delay;
-                delays[index_factor_1 * i + index_factor_2 * j + ledger_data_index] = 3000;
-            }
+        for (link, _) in PEER_GRAPH.links().iter().enumerate().filter(|(_, (from, _))| [1, 2, 3, 4].contains(from)) {
+            delays[index_factor * link + propose_set_0_index] = propose_set_0_delay;
+            delays[index_factor * link + propose_set_1_index] = propose_set_1_delay;
+            delays[index_factor * link + propose_set_2_index] = propose_set_2_delay;
+            delays[index_factor * link + propose_set_3_index] = propose_set_3_delay;
+            delays[index_factor * link + propose_set_4_index] = propose_set_4_delay;
+            delays[index_factor * link + propose_set_5_index] = propose_set_5_delay;
+            delays[index_factor * link + propose_set_bowout_index] = propose_set_bowout_delay;
+            delays[index_factor * link + ledger_data_index] = 3000;
         }
         println!("{}", DelayMapPhenotype::from_genes(&delays).display_genotype_by_message());
         delays
@@ -382,8 +375,7 @@ impl<F: ExtendedFitness> PreDeterminedPrioritySchedulerHandler<F> {
     }
 
     fn create_priorities() -> PriorityGenotype {
-        let index_factor_1 = ConsensusMessageType::VALUES.len() * (*NUM_NODES-1);
-        let index_factor_2 = ConsensusMessageType::VALUES.len();
+        let index_factor = ConsensusMessageType::VALUES.len();
         let mut priorities = vec![20usize; num_genes()];
         let ledger_data_index = 12;
         let get_ledger_index = 11;
@@ -391,12 +383,10 @@ impl<F: ExtendedFitness> PreDeterminedPrioritySchedulerHandler<F> {
         let ledger_data_priority = 1;
         let get_ledger_priority = 2;
         let transaction_priority = 3;
-        for i in 0..*NUM_NODES {
-            for (j, _) in chain(0..i, i+1..*NUM_NODES).enumerate() {
-                priorities[index_factor_1 * i + index_factor_2 * j + ledger_data_index] = ledger_data_priority;
-                priorities[index_factor_1 * i + index_factor_2 * j + get_ledger_index] = get_ledger_priority;
-                priorities[index_factor_1 * i + index_factor_2 * j + transaction_index] = transaction_priority;
-            }
+        for link in 0..PEER_GRAPH.links().len() {
+            priorities[index_factor * link + ledger_data_index] = ledger_data_priority;
+            priorities[index_factor * link + get_ledger_index] = get_ledger_priority;
+            priorities[index_factor * link + transaction_index] = transaction_priority;
         }
         priorities
     }
@@ -455,7 +445,7 @@ pub fn run_random_priorities<F: ExtendedFitness>(scheduler_sender: Sender<Priori
 pub fn run_random_delays<F: ExtendedFitness>(scheduler_sender: Sender<DelayMapPhenotype>, scheduler_receiver: Receiver<F>, search_budget: Duration) {
     let start_time = Utc::now();
     while Utc::now() - start_time < search_budget {
-        let delays = sample_delays_genotype(num_genes(), 0, 4000, &mut thread_rng());
+        let delays = sample_delays_genotype(num_delay_genes(), 0, 4000, &mut thread_rng());
         debug!("Running random delays: {:?}", delays);
         scheduler_sender.send(DelayMapPhenotype::from_genes(&delays)).expect("Scheduler receiver failed");
         scheduler_receiver.recv().expect("Scheduler sender failed");
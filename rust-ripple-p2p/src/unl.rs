@@ -0,0 +1,116 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use itertools::Itertools;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// UNLs of unl_size nodes that all contain the same random core of ceil(overlap * unl_size) nodes,
+/// the rest of every UNL is picked at random, so every pair of UNLs overlaps in at least the target fraction
+pub fn random_overlap_unls(num_nodes: usize, unl_size: usize, overlap: f64, seed: u64) -> Vec<Vec<usize>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let core_size = ((overlap * unl_size as f64).ceil() as usize).min(unl_size);
+    let core = sample(&mut rng, num_nodes, core_size).into_iter().collect::<BTreeSet<usize>>();
+    let rest = (0..num_nodes).filter(|node| !core.contains(node)).collect_vec();
+    (0..num_nodes)
+        .map(|_| {
+            let extra = sample(&mut rng, rest.len(), unl_size - core_size).into_iter().map(|index| rest[index]);
+            core.iter().cloned().chain(extra).sorted().collect()
+        })
+        .collect()
+}
+
+/// The first shared nodes trust every node, the other nodes are split into num_clusters clusters of consecutive nodes
+/// that trust their own cluster and the shared nodes
+pub fn clustered_unls(num_nodes: usize, num_clusters: usize, shared: usize) -> Vec<Vec<usize>> {
+    let clustered = num_nodes - shared;
+    let mut unls = vec![(0..num_nodes).collect(); shared];
+    let mut start = shared;
+    for cluster in 0..num_clusters {
+        // Earlier clusters get one node more when the nodes cannot be split evenly
+        let size = clustered / num_clusters + usize::from(cluster < clustered % num_clusters);
+        let unl = (0..shared).chain(start..start + size).collect_vec();
+        unls.extend(vec![unl; size]);
+        start += size;
+    }
+    unls
+}
+
+/// Node i trusts the first max(min_size, i + 1) nodes, so the UNL of every node contains the UNLs of the nodes before it
+pub fn nested_unls(num_nodes: usize, min_size: usize) -> Vec<Vec<usize>> {
+    (0..num_nodes).map(|node| (0..min_size.max(node + 1)).collect()).collect()
+}
+
+/// Check that there is a non-empty UNL of existing nodes for every node, returns all problems found
+pub fn check_unls(unls: &[Vec<usize>], num_nodes: usize) -> Vec<String> {
+    let mut errors = vec![];
+    if unls.len() != num_nodes {
+        errors.push(format!("there are {} UNLs for {} nodes", unls.len(), num_nodes));
+    }
+    for (node, unl) in unls.iter().enumerate() {
+        if unl.is_empty() {
+            errors.push(format!("the UNL of node {} is empty", node));
+        }
+        if let Some(trusted) = unl.iter().find(|trusted| **trusted >= num_nodes) {
+            errors.push(format!("the UNL of node {} contains node {} outside 0..{}", node, trusted, num_nodes));
+        }
+        if unl.iter().unique().count() != unl.len() {
+            errors.push(format!("the UNL of node {} contains a node more than once", node));
+        }
+    }
+    errors
+}
+
+/// Write the UNLs the nodes are started with to unls.json in the log folder
+pub fn write_unls(unls: &[Vec<usize>], log_folder: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(Path::new(format!("{}/unls.json", log_folder).as_str()))?);
+    serde_json::to_writer(&mut writer, unls)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod unl_tests {
+    use itertools::Itertools;
+    use crate::unl::{check_unls, clustered_unls, nested_unls, random_overlap_unls};
+
+    #[test]
+    fn test_random_overlap_unls() {
+        let unls = random_overlap_unls(10, 6, 0.5, 3);
+        assert!(check_unls(&unls, 10).is_empty());
+        assert!(unls.iter().all(|unl| unl.len() == 6));
+        for (a, b) in unls.iter().tuple_combinations() {
+            assert!(a.iter().filter(|node| b.contains(node)).count() >= 3);
+        }
+        assert_eq!(unls, random_overlap_unls(10, 6, 0.5, 3));
+    }
+
+    #[test]
+    fn test_clustered_unls() {
+        assert_eq!(clustered_unls(7, 2, 2), vec![
+            vec![0, 1, 2, 3, 4, 5, 6],
+            vec![0, 1, 2, 3, 4, 5, 6],
+            vec![0, 1, 2, 3, 4],
+            vec![0, 1, 2, 3, 4],
+            vec![0, 1, 2, 3, 4],
+            vec![0, 1, 5, 6],
+            vec![0, 1, 5, 6],
+        ]);
+    }
+
+    #[test]
+    fn test_nested_unls() {
+        let unls = nested_unls(5, 3);
+        assert_eq!(unls[0], vec![0, 1, 2]);
+        assert_eq!(unls[4], vec![0, 1, 2, 3, 4]);
+        assert!(unls.windows(2).all(|pair| pair[0].iter().all(|node| pair[1].contains(node))));
+    }
+
+    #[test]
+    fn test_check_unls() {
+        assert!(check_unls(&nested_unls(3, 1), 3).is_empty());
+        assert_eq!(check_unls(&[vec![0, 1], vec![], vec![1, 1, 3]], 3).len(), 3);
+        assert_eq!(check_unls(&[vec![0]], 2).len(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use itertools::Itertools;
+
+/// The fraction of its UNL rippled needs to validate a ledger
+pub const DEFAULT_QUORUM_THRESHOLD: f64 = 0.8;
+
+/// The largest number of Byzantine nodes t for which slack > t still holds, None if it does not hold without them
+fn max_faults(slack: f64) -> Option<usize> {
+    if slack <= 0.0 {
+        None
+    } else {
+        Some(slack.ceil() as usize - 1)
+    }
+}
+
+/// The UNLs of two nodes checked against the overlap conditions of Chase and MacBrough, Analysis of the XRP Ledger Consensus Protocol (2018)
+/// overlap: The number of nodes in both UNLs
+/// overlap_fraction: The overlap relative to the largest of the two UNLs
+/// quorum_intersection: The number of nodes any quorum of the one node shares with any quorum of the other, q_i + q_j - n_union.
+/// It is only above 0 if O > (n_i - q_i) + (n_j - q_j), the >40% overlap condition with an 80% quorum, otherwise the nodes can fully validate conflicting ledgers
+/// validation_faults: The number of Byzantine nodes for which the nodes cannot fully validate conflicting ledgers, None if they can without Byzantine nodes
+/// consensus_faults: The number of Byzantine nodes t for which the protocol as a whole is fork safe, O > n_union / 2 + n - q + t for both nodes.
+/// With an 80% quorum and 20% Byzantine nodes this is roughly the >90% overlap condition. None if it does not hold without Byzantine nodes
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairAnalysis {
+    pub nodes: (usize, usize),
+    pub overlap: usize,
+    pub overlap_fraction: f64,
+    pub quorum_intersection: usize,
+    pub validation_faults: Option<usize>,
+    pub consensus_faults: Option<usize>,
+}
+
+/// Safety and liveness of a UNL matrix for a quorum threshold
+/// quorums: The number of UNL nodes every node needs to validate a ledger, it keeps making progress as long as at most unl size - quorum of them are unresponsive
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnlAnalysis {
+    pub quorum_threshold: f64,
+    pub quorums: Vec<usize>,
+    pub pairs: Vec<PairAnalysis>,
+}
+
+impl UnlAnalysis {
+    pub fn new(unls: &[Vec<usize>], quorum_threshold: f64) -> Self {
+        // The epsilon keeps thresholds like 0.8 * 5 from rounding up to the next node
+        let quorums = unls.iter().map(|unl| (quorum_threshold * unl.len() as f64 - 1e-9).ceil() as usize).collect_vec();
+        let pairs = (0..unls.len()).tuple_combinations()
+            .map(|(i, j)| {
+                let (n_i, n_j) = (unls[i].len(), unls[j].len());
+                let overlap = unls[i].iter().filter(|node| unls[j].contains(node)).count();
+                let union = n_i + n_j - overlap;
+                let quorum_intersection = (quorums[i] + quorums[j]).saturating_sub(union);
+                let consensus_bound = (n_i - quorums[i]).max(n_j - quorums[j]) as f64 + union as f64 / 2.0;
+                PairAnalysis {
+                    nodes: (i, j),
+                    overlap,
+                    overlap_fraction: overlap as f64 / n_i.max(n_j).max(1) as f64,
+                    quorum_intersection,
+                    // Conflicting ledgers are only both fully validated if every node in the intersection signs both
+                    validation_faults: quorum_intersection.checked_sub(1),
+                    consensus_faults: max_faults(overlap as f64 - consensus_bound).map(|faults| faults.min(overlap)),
+                }
+            })
+            .collect();
+        Self { quorum_threshold, quorums, pairs }
+    }
+
+    /// The pairs of nodes whose quorums do not intersect, they can fully validate conflicting ledgers even if every node is honest
+    pub fn fork_pairs(&self) -> Vec<(usize, usize)> {
+        self.pairs.iter().filter(|pair| pair.quorum_intersection == 0).map(|pair| pair.nodes).collect()
+    }
+
+    /// The pairs of nodes for which the protocol as a whole is not known to be fork safe
+    pub fn unsafe_consensus_pairs(&self) -> Vec<(usize, usize)> {
+        self.pairs.iter().filter(|pair| pair.consensus_faults.is_none()).map(|pair| pair.nodes).collect()
+    }
+
+    /// Problems with the UNLs that are worth a warning before starting a run
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        let fork_pairs = self.fork_pairs();
+        if !fork_pairs.is_empty() {
+            warnings.push(format!("Nodes {:?} have quorums that do not intersect and can fully validate conflicting ledgers without Byzantine nodes", fork_pairs));
+        }
+        let unsafe_pairs = self.unsafe_consensus_pairs();
+        if !unsafe_pairs.is_empty() {
+            warnings.push(format!("Nodes {:?} are not known to be fork safe without Byzantine nodes (overlap condition O > n_union / 2 + n - q)", unsafe_pairs));
+        }
+        warnings
+    }
+
+    /// A line per node and per pair of nodes
+    pub fn report(&self) -> String {
+        let mut report = format!("UNL analysis with a quorum of {}%\n", self.quorum_threshold * 100.0);
+        for (node, quorum) in self.quorums.iter().enumerate() {
+            report += format!("Node {}: quorum {}\n", node, quorum).as_str();
+        }
+        let faults = |faults: Option<usize>| faults.map_or("can fork".to_string(), |faults| format!("tolerates {} Byzantine", faults));
+        for pair in &self.pairs {
+            report += format!(
+                "Nodes {} and {}: overlap {} ({:.0}%), quorum intersection {}, validation {}, consensus {}\n",
+                pair.nodes.0, pair.nodes.1, pair.overlap, pair.overlap_fraction * 100.0, pair.quorum_intersection,
+                faults(pair.validation_faults), faults(pair.consensus_faults),
+            ).as_str();
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod unl_analysis_tests {
+    use crate::unl_analysis::{DEFAULT_QUORUM_THRESHOLD, UnlAnalysis};
+    use crate::{get_unls, UnlType};
+
+    #[test]
+    fn test_full_unls_are_safe() {
+        let analysis = UnlAnalysis::new(&get_unls(5, UnlType::Full), DEFAULT_QUORUM_THRESHOLD);
+        assert_eq!(analysis.quorums, vec![4; 5]);
+        assert!(analysis.warnings().is_empty());
+        // Quorums of 4 out of 5 share 3 nodes, and 5 > 5 / 2 + 5 - 4 + t for t = 1
+        assert!(analysis.pairs.iter().all(|pair| pair.validation_faults == Some(2) && pair.consensus_faults == Some(1)));
+    }
+
+    #[test]
+    fn test_buggy_unls_can_fork() {
+        let analysis = UnlAnalysis::new(&get_unls(5, UnlType::Buggy), DEFAULT_QUORUM_THRESHOLD);
+        assert!(analysis.fork_pairs().contains(&(0, 3)));
+        assert!(!analysis.fork_pairs().contains(&(0, 1)));
+        assert!(!analysis.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_limit_unls_meet_the_validation_bound() {
+        let analysis = UnlAnalysis::new(&get_unls(7, UnlType::Limit), DEFAULT_QUORUM_THRESHOLD);
+        assert!(analysis.fork_pairs().is_empty());
+        assert!(!analysis.unsafe_consensus_pairs().is_empty());
+    }
+}